@@ -1,141 +1,275 @@
 // temporary until we figure things out
 #![allow(dead_code)]
 
-use std::{f64::consts::PI, time::Duration};
-
-use chrono::Timelike;
-use gtk4::{glib, prelude::*};
-use relm4::{
-    ComponentSender,
-    component::{ComponentParts, SimpleComponent},
-    gtk::DrawingArea,
+use std::{
+    cell::{Cell, RefCell},
+    f64::consts::PI,
+    rc::Rc,
 };
 
-use crate::settings;
+use gtk4::{
+    cairo::{Context, FontSlant, FontWeight, LineCap},
+    glib,
+    prelude::*,
+};
+use relm4::prelude::*;
+
+use crate::{
+    clock::NOW,
+    settings::{self, AnalogClockConfig, CONFIG, SecondHand},
+};
 
 #[derive(Debug)]
 pub struct AnalogClock {
-    radius: f64,
+    drawing_area: gtk::DrawingArea,
+    config: Rc<RefCell<AnalogClockConfig>>,
+    /// Whether a tick callback is redrawing every frame for a sweeping second
+    /// hand.
+    sweeping: Rc<Cell<bool>>,
 }
 
 #[derive(Debug)]
-pub enum AnalogClockMsg {}
+pub enum AnalogClockMsg {
+    /// Another second has passed.
+    Tick,
+    ConfigChanged(AnalogClockConfig),
+}
 
-#[relm4::component(pub)]
 impl SimpleComponent for AnalogClock {
     type Init = f64;
     type Input = AnalogClockMsg;
     type Output = ();
+    type Root = gtk::DrawingArea;
+    type Widgets = ();
 
-    view! {
-        #[root]
-        #[name(drawing_area)]
-        DrawingArea {
-            set_width_request: (model.radius * 2.0) as i32,
-            set_height_request: (model.radius * 2.0) as i32,
-
-            set_draw_func: move |_, cr, width, height| {
-                let center_x = width as f64 / 2.0;
-                let center_y = height as f64 / 2.0;
-                let radius = (width.min(height) as f64 / 2.0) * 0.9;
-
-                // clear background
-                cr.set_source_rgba(0.0, 0.0, 0.0, 0.0);
-                let _ = cr.paint();
-
-                // get current time
-                let now = chrono::Local::now();
-                let hours = now.hour() % 12;
-                let minutes = now.minute();
-                let seconds = now.second();
-                let subseconds = now.nanosecond() as f64 / 1_000_000_000.0;
-
-                // draw hour markers (optional, subtle)
-                cr.set_source_rgba(1.0, 1.0, 1.0, 0.3);
-                cr.set_line_width(1.0);
-                for i in 0..12 {
-                    let angle = (i as f64) * PI / 6.0 - PI / 2.0;
-                    let inner_radius = radius * 0.85;
-                    let outer_radius = radius * 0.95;
-
-                    let x1 = center_x + inner_radius * angle.cos();
-                    let y1 = center_y + inner_radius * angle.sin();
-                    let x2 = center_x + outer_radius * angle.cos();
-                    let y2 = center_y + outer_radius * angle.sin();
-
-                    cr.move_to(x1, y1);
-                    cr.line_to(x2, y2);
-                    let _ = cr.stroke();
-                }
-
-                // draw hour hand
-                let hour_angle = (hours as f64 + minutes as f64 / 60.0) * PI / 6.0 - PI / 2.0;
-                cr.set_source_rgba(1.0, 1.0, 1.0, 0.9);
-                cr.set_line_width(4.0);
-                cr.set_line_cap(gtk4::cairo::LineCap::Round);
-                cr.move_to(center_x, center_y);
-                cr.line_to(
-                    center_x + radius * 0.5 * hour_angle.cos(),
-                    center_y + radius * 0.5 * hour_angle.sin(),
-                );
-                let _ = cr.stroke();
-
-                // draw minute hand
-                let minute_angle = (minutes as f64 + seconds as f64 / 60.0) * PI / 30.0 - PI / 2.0;
-                cr.set_source_rgba(1.0, 1.0, 1.0, 0.75);
-                cr.set_line_width(3.0);
-                cr.set_line_cap(gtk4::cairo::LineCap::Round);
-                cr.move_to(center_x, center_y);
-                cr.line_to(
-                    center_x + radius * 0.75 * minute_angle.cos(),
-                    center_y + radius * 0.75 * minute_angle.sin(),
-                );
-                let _ = cr.stroke();
-
-                // draw second hand
-                let second_angle = (seconds as f64 + subseconds) * PI / 30.0 - PI / 2.0;
-                cr.set_source_rgba(1.0, 1.0, 1.0, 0.5);
-                cr.set_line_width(1.0);
-                cr.set_line_cap(gtk4::cairo::LineCap::Round);
-                cr.move_to(center_x, center_y);
-                cr.line_to(
-                    center_x + radius * 0.9 * second_angle.cos(),
-                    center_y + radius * 0.9 * second_angle.sin(),
-                );
-                let _ = cr.stroke();
-
-                // Draw center dot
-                cr.set_source_rgba(1.0, 1.0, 1.0, 0.9);
-                cr.arc(center_x, center_y, 3.0, 0.0, 2.0 * PI);
-                let _ = cr.fill();
-            },
-        }
+    fn init_root() -> Self::Root {
+        gtk::DrawingArea::new()
     }
 
     fn init(
         _radius: Self::Init,
         root: Self::Root,
-        _sender: ComponentSender<Self>,
+        sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         // Use configuration instead of hardcoded radius
         let config = settings::get_config();
         let radius = config.tiles.analog_clock_radius;
+        root.set_width_request((radius * 2.0) as i32);
+        root.set_height_request((radius * 2.0) as i32);
 
-        let model = AnalogClock { radius };
+        warn_unknown_timezones(&config.analog_clock);
+        let model = AnalogClock {
+            drawing_area: root.clone(),
+            config: Rc::new(RefCell::new(config.analog_clock)),
+            sweeping: Rc::default(),
+        };
 
-        let widgets = view_output!();
+        let clock_config = model.config.clone();
+        root.set_draw_func(move |_, cr, width, height| {
+            draw(cr, width, height, &clock_config.borrow());
+        });
+
+        // a ticking second hand moves with the shared clock
+        NOW.subscribe(sender.input_sender(), |_| AnalogClockMsg::Tick);
+        CONFIG.subscribe(sender.input_sender(), |config| {
+            AnalogClockMsg::ConfigChanged(config.analog_clock.clone())
+        });
+
+        model.start_sweeping();
+
+        ComponentParts { model, widgets: () }
+    }
 
-        // Start the timer for updates - directly update the drawing area
-        let drawing_area_clone = widgets.drawing_area.clone();
-        glib::timeout_add_local(Duration::from_millis(100), move || {
-            drawing_area_clone.queue_draw();
+    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>) {
+        match message {
+            AnalogClockMsg::Tick => {
+                // a sweeping hand is already redrawn every frame
+                if !self.sweeping.get() {
+                    self.drawing_area.queue_draw();
+                }
+            }
+            AnalogClockMsg::ConfigChanged(config) => {
+                warn_unknown_timezones(&config);
+                *self.config.borrow_mut() = config;
+                self.start_sweeping();
+                self.drawing_area.queue_draw();
+            }
+        }
+    }
+}
+
+impl AnalogClock {
+    /// Redraws every frame while the second hand is set to sweep. The callback
+    /// removes itself once it's set to tick instead.
+    fn start_sweeping(&self) {
+        if self.sweeping.get() || self.config.borrow().second_hand != SecondHand::Sweep {
+            return;
+        }
+
+        self.sweeping.set(true);
+        let config = self.config.clone();
+        let sweeping = self.sweeping.clone();
+        self.drawing_area.add_tick_callback(move |area, _| {
+            if config.borrow().second_hand != SecondHand::Sweep {
+                sweeping.set(false);
+                return glib::ControlFlow::Break;
+            }
+
+            area.queue_draw();
             glib::ControlFlow::Continue
         });
+    }
+}
+
+fn warn_unknown_timezones(config: &AnalogClockConfig) {
+    for id in [&config.timezone, &config.secondary_timezone]
+        .into_iter()
+        .flatten()
+    {
+        if glib::TimeZone::from_identifier(Some(id)).is_none() {
+            log::warn!("unknown timezone '{id}' for the analog clock; using local time");
+        }
+    }
+}
+
+/// The time in `timezone`, or local time if it's unset or unknown.
+fn now_in(timezone: Option<&str>) -> Option<glib::DateTime> {
+    let zone = timezone
+        .and_then(|id| glib::TimeZone::from_identifier(Some(id)))
+        .unwrap_or_else(glib::TimeZone::local);
+    glib::DateTime::now(&zone).ok()
+}
+
+fn draw(cr: &Context, width: i32, height: i32, config: &AnalogClockConfig) {
+    let center = (width as f64 / 2.0, height as f64 / 2.0);
+    let radius = (width.min(height) as f64 / 2.0) * 0.9;
+
+    // clear background
+    cr.set_source_rgba(0.0, 0.0, 0.0, 0.0);
+    let _ = cr.paint();
+
+    let Some(time) = now_in(config.timezone.as_deref()) else {
+        return;
+    };
+
+    // draw hour markers (optional, subtle)
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.3);
+    cr.set_line_width(1.0);
+    for i in 0..12 {
+        let angle = (i as f64) * PI / 6.0 - PI / 2.0;
+        let inner_radius = radius * 0.85;
+        let outer_radius = radius * 0.95;
+
+        cr.move_to(
+            center.0 + inner_radius * angle.cos(),
+            center.1 + inner_radius * angle.sin(),
+        );
+        cr.line_to(
+            center.0 + outer_radius * angle.cos(),
+            center.1 + outer_radius * angle.sin(),
+        );
+        let _ = cr.stroke();
+    }
+
+    if config.numerals {
+        cr.set_source_rgba(1.0, 1.0, 1.0, 0.6);
+        cr.select_font_face("sans-serif", FontSlant::Normal, FontWeight::Bold);
+        cr.set_font_size(radius * 0.18);
+        for hour in 1..=12 {
+            let angle = hour as f64 * PI / 6.0 - PI / 2.0;
+            show_centered_text(
+                cr,
+                (
+                    center.0 + radius * 0.7 * angle.cos(),
+                    center.1 + radius * 0.7 * angle.sin(),
+                ),
+                &hour.to_string(),
+            );
+        }
+    }
 
-        ComponentParts { model, widgets }
+    // the secondary timezone goes on a small dial in the lower half, clear of
+    // the hands most of the time
+    if let Some(zone) = &config.secondary_timezone
+        && let Some(secondary) = now_in(Some(zone))
+    {
+        draw_subdial(
+            cr,
+            (center.0, center.1 + radius * 0.4),
+            radius * 0.2,
+            &secondary,
+        );
     }
 
-    fn update(&mut self, _message: Self::Input, _sender: ComponentSender<Self>) {
-        // No messages to handle - drawing updates happen via timer
+    let hours = (time.hour() % 12) as f64;
+    let minutes = time.minute() as f64;
+    let seconds = match config.second_hand {
+        SecondHand::Sweep => time.seconds(),
+        SecondHand::Tick => time.second() as f64,
+    };
+
+    let hour_angle = (hours + minutes / 60.0) * PI / 6.0 - PI / 2.0;
+    draw_hand(cr, center, hour_angle, radius * 0.5, 4.0, 0.9);
+
+    let minute_angle = (minutes + seconds / 60.0) * PI / 30.0 - PI / 2.0;
+    draw_hand(cr, center, minute_angle, radius * 0.75, 3.0, 0.75);
+
+    let second_angle = seconds * PI / 30.0 - PI / 2.0;
+    draw_hand(cr, center, second_angle, radius * 0.9, 1.0, 0.5);
+
+    // Draw center dot
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.9);
+    cr.arc(center.0, center.1, 3.0, 0.0, 2.0 * PI);
+    let _ = cr.fill();
+}
+
+/// Draws a small dial with hour and minute hands, labeled with the timezone's
+/// abbreviation.
+fn draw_subdial(cr: &Context, center: (f64, f64), radius: f64, time: &glib::DateTime) {
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.3);
+    cr.set_line_width(1.0);
+    cr.new_sub_path();
+    cr.arc(center.0, center.1, radius, 0.0, 2.0 * PI);
+    let _ = cr.stroke();
+
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.5);
+    cr.select_font_face("sans-serif", FontSlant::Normal, FontWeight::Normal);
+    cr.set_font_size(radius * 0.5);
+    show_centered_text(
+        cr,
+        (center.0, center.1 + radius * 0.5),
+        &time.timezone_abbreviation(),
+    );
+
+    let hours = (time.hour() % 12) as f64;
+    let minutes = time.minute() as f64;
+    let hour_angle = (hours + minutes / 60.0) * PI / 6.0 - PI / 2.0;
+    draw_hand(cr, center, hour_angle, radius * 0.55, 2.0, 0.75);
+    draw_hand(
+        cr,
+        center,
+        minutes * PI / 30.0 - PI / 2.0,
+        radius * 0.85,
+        1.5,
+        0.6,
+    );
+}
+
+fn draw_hand(cr: &Context, from: (f64, f64), angle: f64, length: f64, width: f64, alpha: f64) {
+    cr.set_source_rgba(1.0, 1.0, 1.0, alpha);
+    cr.set_line_width(width);
+    cr.set_line_cap(LineCap::Round);
+    cr.move_to(from.0, from.1);
+    cr.line_to(from.0 + length * angle.cos(), from.1 + length * angle.sin());
+    let _ = cr.stroke();
+}
+
+fn show_centered_text(cr: &Context, center: (f64, f64), text: &str) {
+    if let Ok(extents) = cr.text_extents(text) {
+        cr.move_to(
+            center.0 - extents.width() / 2.0 - extents.x_bearing(),
+            center.1 - extents.height() / 2.0 - extents.y_bearing(),
+        );
+        let _ = cr.show_text(text);
     }
 }
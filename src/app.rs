@@ -1,32 +1,83 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
 use gdk4::Display;
 use gtk4::prelude::*;
 use relm4::prelude::*;
-use tokio::sync::Mutex;
+use tokio::signal::unix::{SignalKind, signal};
 
+#[cfg(feature = "bluetooth")]
+use crate::bluetooth::{PAIRING_PROMPT, run_bluetooth_service};
+#[cfg(feature = "niri")]
+use crate::niri;
 use crate::{
-    battery::start_battery_service,
-    bluetooth::run_bluetooth_service,
-    brightness::start_brightness_watcher,
+    appearance::run_appearance_watcher,
+    battery::{self, start_battery_service},
+    brightness::{run_brightness_control, run_brightness_schedule, start_brightness_watcher},
+    clock::run_clock,
+    commands::{self, AppRequest, run_app_requests},
+    compositor,
+    control::{ControlRequest, run_control_interface},
+    monitors::{
+        MonitorInfo, connected_monitors, focused_output, generate_monitor_report, primary_monitor,
+    },
     mpris::run_mpris_service,
     network::run_network_service,
-    niri,
-    notifications::run_notifications_service,
+    notifications::{self, run_notifications_service},
     pulseaudio::run_pulseaudio_loop,
+    services::Services,
+    settings,
     sleep_monitor::run_sleep_monitor,
+    style::{StyleChange, apply_styles, reads_stylesheet_from_source, watch_styles},
+    systemd,
+    tray_backend::{ActivateResponse, TrayHandler, TrayHostEvent, TrayUpdate, run_tray_host},
     weather::start_weather_polling,
     widgets::{
-        bar::{Bar, BarInit, BarMsg, BarOutput},
-        tray_item::{TrayClient, TrayEvent, TrayItemOutput},
+        bar::{Bar, BarInit, BarMenu, BarMsg, BarOutput},
+        identify_overlay,
+        tray_item::{TrayEvent, TrayItemOutput},
     },
 };
 
+/// How long to wait for services to wrap up (e.g. the notifications service
+/// giving up its bus name) when quitting.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Set once the shell has been asked to quit, so `main` can tell a clean exit
+/// from the app stopping on its own.
+static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn quit_requested() -> bool {
+    QUIT_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Resolves once the process is sent SIGTERM or SIGINT.
+async fn wait_for_termination() {
+    let (Ok(mut terminate), Ok(mut interrupt)) = (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+    ) else {
+        log::error!("couldn't listen for termination signals");
+        return std::future::pending().await;
+    };
+
+    tokio::select! {
+        _ = terminate.recv() => log::info!("received SIGTERM"),
+        _ = interrupt.recv() => log::info!("received SIGINT"),
+    }
+}
+
 pub(crate) struct CadenzaShellModel {
     bars: HashMap<String, AsyncController<Bar>>,
-    tray_client: Option<Arc<Mutex<TrayClient>>>,
+    services: Services,
+    tray_handler: TrayHandler,
 
-    _display: Display,
+    display: Display,
 }
 
 #[derive(Debug)]
@@ -39,13 +90,36 @@ pub(crate) enum CadenzaShellMsg {
     /// the items_changed signal, which may fire later or not at all depending
     /// on the compositor.
     MonitorInvalidated(String),
-    HandleTrayItemOutput(TrayItemOutput),
+    /// Output from a tray item on the bar with the given connector.
+    HandleTrayItemOutput(String, TrayItemOutput),
     ToggleNotificationCenter,
+    /// Toggles a menu on the bar with the given connector, closing menus on
+    /// every other bar so only one is open at a time.
+    ToggleBarMenu(String, BarMenu, gtk::Widget),
+    /// A device is pairing and needs a code shown or answered, which happens
+    /// in the bluetooth menu.
+    #[cfg(feature = "bluetooth")]
+    ShowPairingPrompt,
+    /// The bars for the monitors connected at startup have been created.
+    Started,
+    /// Closes every bar, releases the notifications name and saves the battery
+    /// profile, then quits.
+    Quit,
 }
 
 #[derive(Debug)]
 pub(crate) enum CadenzaShellCommandOutput {
-    TrayEvent(TrayEvent),
+    Tray(TrayHostEvent),
+    Control(ControlRequest),
+    /// The settings file or a stylesheet in the source tree was edited.
+    StylesChanged(StyleChange),
+    /// The desktop switched between light and dark.
+    ColorSchemeChanged,
+    /// Something an executed command needs the app to do.
+    App(AppRequest),
+    /// Everything that needed tearing down for [`CadenzaShellMsg::Quit`] is
+    /// done.
+    ReadyToQuit,
 }
 
 impl AsyncComponent for CadenzaShellModel {
@@ -66,11 +140,9 @@ impl AsyncComponent for CadenzaShellModel {
         _root: Self::Root,
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
-        let tray_client = TrayClient::new()
-            .await
-            .inspect_err(|e| log::error!("couldn't setup tray client: {}", e))
-            .ok()
-            .map(|c| Arc::new(Mutex::new(c)));
+        // the tray client is connected in the background, and reconnected if
+        // it stops
+        let services = Services::new(None, compositor::connect());
 
         // start sleep monitor (must be first so other services can subscribe)
         sender.command(|_, shutdown| shutdown.register(run_sleep_monitor()).drop_on_shutdown());
@@ -91,12 +163,21 @@ impl AsyncComponent for CadenzaShellModel {
         });
 
         // start bluetooth watching
+        #[cfg(feature = "bluetooth")]
         sender.command(|_, shutdown| {
             shutdown
                 .register(run_bluetooth_service())
                 .drop_on_shutdown()
         });
 
+        // pairing can be started from the device, with no menu open to answer
+        #[cfg(feature = "bluetooth")]
+        PAIRING_PROMPT.subscribe_optional(sender.input_sender(), |prompt| {
+            prompt
+                .is_some()
+                .then_some(CadenzaShellMsg::ShowPairingPrompt)
+        });
+
         // start brightness watching
         sender.command(|_, shutdown| {
             shutdown
@@ -104,6 +185,20 @@ impl AsyncComponent for CadenzaShellModel {
                 .drop_on_shutdown()
         });
 
+        // start writing brightness changes from the bar
+        sender.command(|_, shutdown| {
+            shutdown
+                .register(run_brightness_control())
+                .drop_on_shutdown()
+        });
+
+        // start idle dimming and the day/night brightness schedule, if enabled
+        sender.command(|_, shutdown| {
+            shutdown
+                .register(run_brightness_schedule())
+                .drop_on_shutdown()
+        });
+
         // start network service
         sender.command(|_, shutdown| shutdown.register(run_network_service()).drop_on_shutdown());
 
@@ -114,50 +209,99 @@ impl AsyncComponent for CadenzaShellModel {
                 .drop_on_shutdown()
         });
 
+        // tick every clock from one timer
+        sender.command(|_, shutdown| shutdown.register(run_clock()).drop_on_shutdown());
+
         // start mpris service
         sender.command(|_, shutdown| shutdown.register(run_mpris_service()).drop_on_shutdown());
 
         // start niri event watching
+        #[cfg(feature = "niri")]
         sender.command(|_, shutdown| {
             shutdown
                 .register(niri::start_event_listener())
                 .drop_on_shutdown()
         });
 
+        #[cfg(feature = "hyprland")]
+        log::warn!("hyprland support was enabled at build time but isn't implemented yet");
+
+        #[cfg(not(any(feature = "hyprland", feature = "niri")))]
+        log::warn!("built without a compositor backend; workspaces won't be shown");
+
         // start pulseaudio service
         sender.command(|_, shutdown| shutdown.register(run_pulseaudio_loop()).drop_on_shutdown());
 
-        if let Some(ref tray_client) = tray_client {
-            let tray_client = Arc::clone(tray_client);
-            sender.command(|out, shutdown| {
-                shutdown
-                    .register(async move {
-                        // subscribe to tray events
-                        let mut rx = tray_client.lock().await.subscribe();
-                        loop {
-                            match rx.recv().await {
-                                Ok(event) => {
-                                    out.send(CadenzaShellCommandOutput::TrayEvent(event))
-                                        .unwrap_or_else(|_| {
-                                            log::error!(
-                                                "unable to send tray event as command output",
-                                            )
-                                        });
-                                }
-                                Err(e) => log::error!("error receiving tray event: {}", e),
-                            }
-                        }
-                    })
-                    .drop_on_shutdown()
-            });
-        }
+        // accept commands from `cadenza-shell <command>`
+        sender.command(|out, shutdown| {
+            shutdown
+                .register(run_control_interface(move |request| {
+                    out.send(CadenzaShellCommandOutput::Control(request))
+                        .unwrap_or_else(|_| log::error!("unable to forward control request"));
+                }))
+                .drop_on_shutdown()
+        });
+
+        // restyle as the settings or stylesheet are edited
+        sender.command(|out, shutdown| {
+            shutdown
+                .register(watch_styles(move |change| {
+                    out.send(CadenzaShellCommandOutput::StylesChanged(change))
+                        .unwrap_or_else(|_| log::error!("unable to forward style change"));
+                }))
+                .drop_on_shutdown()
+        });
+
+        // restyle when the desktop switches between light and dark
+        sender.command(|out, shutdown| {
+            shutdown
+                .register(run_appearance_watcher(move || {
+                    out.send(CadenzaShellCommandOutput::ColorSchemeChanged)
+                        .unwrap_or_else(|_| log::error!("unable to forward color scheme change"));
+                }))
+                .drop_on_shutdown()
+        });
+
+        // carry out commands that need the app's components
+        sender.command(|out, shutdown| {
+            shutdown
+                .register(run_app_requests(move |request| {
+                    out.send(CadenzaShellCommandOutput::App(request))
+                        .unwrap_or_else(|_| log::error!("unable to forward app request"));
+                }))
+                .drop_on_shutdown()
+        });
+
+        // quit cleanly when a service manager or terminal asks us to stop,
+        // so systemd sees a successful exit instead of a crash
+        sender.command(|out, shutdown| {
+            shutdown
+                .register(async move {
+                    wait_for_termination().await;
+                    out.send(CadenzaShellCommandOutput::Control(ControlRequest::Quit))
+                        .unwrap_or_else(|_| log::error!("unable to forward termination signal"));
+                })
+                .drop_on_shutdown()
+        });
+
+        sender.command(|out, shutdown| {
+            shutdown
+                .register(run_tray_host(move |event| {
+                    out.send(CadenzaShellCommandOutput::Tray(event))
+                        .unwrap_or_else(|_| {
+                            log::error!("unable to send tray event as command output")
+                        });
+                }))
+                .drop_on_shutdown()
+        });
         let display = Display::default().expect("could not get default display");
 
         let model = CadenzaShellModel {
             bars: HashMap::new(),
-            tray_client,
+            services,
+            tray_handler: TrayHandler::default(),
 
-            _display: display.clone(),
+            display: display.clone(),
         };
 
         // set up monitor detection
@@ -185,6 +329,9 @@ impl AsyncComponent for CadenzaShellModel {
             }
         }
 
+        // handled after the bars above are created
+        sender.input(CadenzaShellMsg::Started);
+
         // monitor for display changes (hotplug support)
         let sender_clone = sender.clone();
         monitors.connect_items_changed(move |monitors, position, removed, added| {
@@ -272,26 +419,52 @@ impl AsyncComponent for CadenzaShellModel {
                     self.bars.remove(&connector_str);
                 }
 
+                // read fresh each time, so settings changes apply when a
+                // monitor is plugged back in
+                let monitor_bar = settings::get_config().monitor_bar(&connector_str);
+                if !monitor_bar.enabled {
+                    log::info!("bar is turned off for monitor: {}", connector_str);
+                    return;
+                }
+
                 // get the current system tray items
-                let tray_items = if let Some(ref c) = self.tray_client {
-                    Some(c.lock().await.items())
-                } else {
-                    None
-                };
+                let tray_items = self.services.tray.as_ref().map(|c| c.items());
 
                 log::info!("creating bar for monitor: {}", connector_str);
 
+                let bar_connector = connector_str.clone();
                 let bar = Bar::builder()
                     .launch(BarInit {
                         monitor,
                         tray_items,
+                        services: self.services.clone(),
+                        monitor_bar,
                     })
-                    .forward(sender.input_sender(), |output| match output {
+                    .forward(sender.input_sender(), move |output| match output {
                         BarOutput::ToggleNotificationCenter => {
                             CadenzaShellMsg::ToggleNotificationCenter
                         }
+                        BarOutput::ToggleNetworkMenu(anchor) => CadenzaShellMsg::ToggleBarMenu(
+                            bar_connector.clone(),
+                            BarMenu::Network,
+                            anchor,
+                        ),
+                        #[cfg(feature = "bluetooth")]
+                        BarOutput::ToggleBluetoothMenu(anchor) => CadenzaShellMsg::ToggleBarMenu(
+                            bar_connector.clone(),
+                            BarMenu::Bluetooth,
+                            anchor,
+                        ),
+                        BarOutput::ToggleVolumeMenu(anchor) => CadenzaShellMsg::ToggleBarMenu(
+                            bar_connector.clone(),
+                            BarMenu::Volume,
+                            anchor,
+                        ),
                         BarOutput::TrayItemOutput(tray_item_output) => {
-                            CadenzaShellMsg::HandleTrayItemOutput(tray_item_output)
+                            CadenzaShellMsg::HandleTrayItemOutput(
+                                bar_connector.clone(),
+                                tray_item_output,
+                            )
                         }
                         BarOutput::MonitorInvalidated(connector) => {
                             CadenzaShellMsg::MonitorInvalidated(connector)
@@ -311,17 +484,68 @@ impl AsyncComponent for CadenzaShellModel {
                 );
                 self.bars.remove(&connector);
             }
-            CadenzaShellMsg::HandleTrayItemOutput(tray_item_output) => match tray_item_output {
+            CadenzaShellMsg::HandleTrayItemOutput(connector, output) => match output {
                 TrayItemOutput::Activate(activate_request) => {
-                    if let Some(client) = &self.tray_client {
-                        client
-                            .lock()
-                            .await
-                            .activate(activate_request)
-                            .await
-                            .unwrap_or_else(|e| {
-                                log::error!("error sending activate request to tray client: {}", e)
+                    let Some(tray) = &self.services.tray else {
+                        return;
+                    };
+
+                    match self
+                        .tray_handler
+                        .activate(tray.as_ref(), activate_request)
+                        .await
+                    {
+                        ActivateResponse::Activated => {}
+                        ActivateResponse::ShowMenu(address) => {
+                            if let Some(bar) = self.bars.get(&connector) {
+                                bar.emit(BarMsg::ShowTrayMenu(address));
+                            }
+                        }
+                        ActivateResponse::Removed { address, name } => {
+                            for bar in self.bars.values() {
+                                bar.emit(BarMsg::TrayEvent(TrayEvent::Remove(address.clone())));
+                            }
+
+                            let name = name.unwrap_or_else(|| "A tray app".to_string());
+                            relm4::spawn(async move {
+                                notifications::send_notification(
+                                    "System tray",
+                                    &format!("{name} isn't running anymore"),
+                                    "Its icon was removed from the tray.",
+                                    1,
+                                    &[],
+                                    -1,
+                                )
+                                .await;
+                            });
+                        }
+                        ActivateResponse::Unresponsive => {
+                            relm4::spawn(async {
+                                notifications::send_notification(
+                                    "System tray",
+                                    "Tray app didn't respond",
+                                    "The app may be busy. Try again in a moment.",
+                                    1,
+                                    &[],
+                                    -1,
+                                )
+                                .await;
                             });
+                        }
+                    }
+                }
+                TrayItemOutput::Scroll {
+                    address,
+                    delta,
+                    orientation,
+                } => {
+                    let Some(tray) = &self.services.tray else {
+                        return;
+                    };
+
+                    // plenty of items ignore scrolling, so failures are quiet
+                    if let Err(e) = tray.scroll(address.clone(), delta, orientation).await {
+                        log::debug!("couldn't scroll tray item {address}: {e}");
                     }
                 }
             },
@@ -331,21 +555,176 @@ impl AsyncComponent for CadenzaShellModel {
                     bar.emit(BarMsg::ToggleNotificationCenter);
                 }
             }
+            CadenzaShellMsg::ToggleBarMenu(connector, menu, anchor) => {
+                for (other, bar) in &self.bars {
+                    if *other != connector {
+                        bar.emit(BarMsg::CloseMenus);
+                    }
+                }
+
+                match self.bars.get(&connector) {
+                    Some(bar) => bar.emit(BarMsg::ToggleMenu(menu, anchor)),
+                    None => log::warn!("no bar for {connector} to open a menu on"),
+                }
+            }
+            #[cfg(feature = "bluetooth")]
+            CadenzaShellMsg::ShowPairingPrompt => match self.active_bar() {
+                Some(bar) => bar.emit(BarMsg::ShowTileMenu(BarMenu::Bluetooth)),
+                None => log::warn!("no bar to show the pairing prompt on"),
+            },
+            CadenzaShellMsg::Started => systemd::notify_ready(),
+            CadenzaShellMsg::Quit => {
+                log::info!("quitting");
+                systemd::notify_stopping();
+
+                // dropping the bars closes their layer-shell windows (see
+                // Bar::shutdown)
+                self.bars.clear();
+
+                sender.oneshot_command(async {
+                    let (notifications_stopped, profile_saved) = tokio::join!(
+                        tokio::time::timeout(SHUTDOWN_TIMEOUT, notifications::shutdown()),
+                        tokio::time::timeout(SHUTDOWN_TIMEOUT, battery::save_profile()),
+                    );
+                    if notifications_stopped.is_err() {
+                        log::warn!("notifications service didn't stop in time");
+                    }
+                    if profile_saved.is_err() {
+                        log::warn!("battery service didn't save in time");
+                    }
+                    CadenzaShellCommandOutput::ReadyToQuit
+                });
+            }
         }
     }
 
     async fn update_cmd(
         &mut self,
         message: Self::CommandOutput,
-        _sender: AsyncComponentSender<Self>,
+        sender: AsyncComponentSender<Self>,
         _root: &Self::Root,
     ) {
         match message {
-            Self::CommandOutput::TrayEvent(event) => {
+            Self::CommandOutput::Tray(event) => {
+                let Some(update) = self.tray_handler.host_event(event, &mut self.services.tray)
+                else {
+                    return;
+                };
                 for bar in self.bars.values() {
-                    bar.emit(BarMsg::TrayEvent(event.clone()));
+                    bar.emit(match &update {
+                        TrayUpdate::Reset(items) => BarMsg::ResetTray(items.clone()),
+                        TrayUpdate::Event(event) => BarMsg::TrayEvent(event.clone()),
+                    });
+                }
+            }
+            Self::CommandOutput::Control(ControlRequest::IdentifyDisplays) => {
+                for (monitor, info) in connected_monitors(&self.display) {
+                    identify_overlay::show(&monitor, &info);
                 }
             }
+            Self::CommandOutput::Control(ControlRequest::ShowPowerMenu) => {
+                match self.active_bar() {
+                    Some(bar) => bar.emit(BarMsg::ShowPowerMenu),
+                    None => log::warn!("no bar to show the power menu on"),
+                }
+            }
+            Self::CommandOutput::Control(ControlRequest::MonitorReport(reply)) => {
+                let monitors: Vec<MonitorInfo> = connected_monitors(&self.display)
+                    .into_iter()
+                    .map(|(_, info)| info)
+                    .collect();
+                let bars: Vec<&str> = self.bars.keys().map(String::as_str).collect();
+
+                let _ = reply.send(generate_monitor_report(&monitors, &bars));
+            }
+            Self::CommandOutput::Control(ControlRequest::Reload(reply)) => {
+                let _ = reply.send(reload());
+            }
+            Self::CommandOutput::Control(ControlRequest::Run(name, reply)) => {
+                let executor = self.services.commands.clone();
+                relm4::spawn(async move {
+                    let _ = reply.send(commands::run_named(&executor, &name).await);
+                });
+            }
+            Self::CommandOutput::Control(ControlRequest::Quit) => {
+                sender.input(CadenzaShellMsg::Quit);
+            }
+            Self::CommandOutput::StylesChanged(StyleChange::Stylesheet) => match apply_styles() {
+                Ok(()) => log::info!("reloaded styles"),
+                Err(e) => log::error!("couldn't compile styles (keeping the previous ones): {e}"),
+            },
+            Self::CommandOutput::ColorSchemeChanged => {
+                if let Err(e) = apply_styles() {
+                    log::error!("couldn't restyle for the color scheme: {e}");
+                }
+            }
+            Self::CommandOutput::StylesChanged(StyleChange::Settings) => {
+                // reload() logs whatever went wrong
+                let _ = reload();
+            }
+            Self::CommandOutput::App(AppRequest::ToggleNotificationCenter) => {
+                sender.input(CadenzaShellMsg::ToggleNotificationCenter);
+            }
+            Self::CommandOutput::App(AppRequest::ToggleMenu(menu)) => match self.active_bar() {
+                Some(bar) => bar.emit(BarMsg::ToggleTileMenu(menu)),
+                None => log::warn!("no bar to open a menu on"),
+            },
+            Self::CommandOutput::ReadyToQuit => {
+                QUIT_REQUESTED.store(true, Ordering::SeqCst);
+                // stopping the app drops the root component, which cancels
+                // every service registered with drop_on_shutdown
+                relm4::main_application().quit();
+            }
+        }
+    }
+}
+
+impl CadenzaShellModel {
+    /// The bar on the focused monitor, falling back to the primary monitor's
+    /// bar, then any bar at all.
+    fn active_bar(&self) -> Option<&AsyncController<Bar>> {
+        let primary = primary_monitor(&self.display)
+            .and_then(|monitor| monitor.connector())
+            .map(String::from);
+
+        [focused_output(), primary]
+            .into_iter()
+            .flatten()
+            .find_map(|connector| self.bars.get(&connector))
+            .or_else(|| self.bars.values().next())
+    }
+}
+
+/// Re-reads settings and recompiles styles, applying whichever succeeded.
+/// Returns a summary for the caller, or every error that occurred.
+fn reload() -> Result<String, String> {
+    let mut errors = Vec::new();
+
+    // components pick up new settings through their CONFIG subscriptions
+    if let Err(e) = settings::reload_config() {
+        errors.push(format!("couldn't reload settings: {e}"));
+    }
+
+    if let Err(e) = apply_styles() {
+        errors.push(format!(
+            "couldn't compile styles (keeping the previous ones): {e}"
+        ));
+    }
+
+    if errors.is_empty() {
+        // the built-in stylesheet can't have changed, only the theme
+        // variables compiled into it
+        let summary = if reads_stylesheet_from_source() {
+            "reloaded settings and styles"
+        } else {
+            "reloaded settings and theme variables (the stylesheet is built in)"
+        };
+        log::info!("{summary}");
+        Ok(summary.to_string())
+    } else {
+        for e in &errors {
+            log::error!("{e}");
         }
+        Err(errors.join("\n"))
     }
 }
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use futures_lite::StreamExt;
+use relm4::SharedState;
+use zbus::{proxy, zvariant::Value};
+
+/// Whether the desktop prefers dark styles, according to the XDG settings
+/// portal. `None` if there's no preference or no portal to ask.
+pub static PREFERS_DARK: SharedState<Option<bool>> = SharedState::new();
+
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+
+/// How long startup waits on the portal before styling without it. The portal
+/// is usually quick, and waiting avoids flashing the wrong scheme.
+const READ_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[proxy(
+    interface = "org.freedesktop.portal.Settings",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait PortalSettings {
+    fn read_one(&self, namespace: &str, key: &str) -> zbus::Result<zbus::zvariant::OwnedValue>;
+
+    #[zbus(signal)]
+    fn setting_changed(&self, namespace: &str, key: &str, value: Value<'_>) -> zbus::Result<()>;
+}
+
+/// Reads the portal's `color-scheme`: 1 prefers dark, 2 prefers light and
+/// anything else is no preference.
+fn prefers_dark(value: &Value<'_>) -> Option<bool> {
+    match value {
+        // older portals wrap the value in another variant
+        Value::Value(inner) => prefers_dark(inner),
+        Value::U32(1) => Some(true),
+        Value::U32(2) => Some(false),
+        _ => None,
+    }
+}
+
+async fn read_prefers_dark() -> zbus::Result<Option<bool>> {
+    let connection = zbus::Connection::session().await?;
+    let proxy = PortalSettingsProxy::new(&connection).await?;
+    let value = proxy
+        .read_one(APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY)
+        .await?;
+    Ok(prefers_dark(&value))
+}
+
+/// Reads the desktop's color scheme into [`PREFERS_DARK`], giving up after a
+/// moment. Meant to be awaited before styles are first applied.
+pub async fn read_color_scheme() {
+    match tokio::time::timeout(READ_TIMEOUT, read_prefers_dark()).await {
+        Ok(Ok(prefers_dark)) => *PREFERS_DARK.write() = prefers_dark,
+        Ok(Err(e)) => log::warn!("couldn't read the color scheme from the portal: {e}"),
+        Err(_) => log::warn!("the settings portal didn't give a color scheme in time"),
+    }
+}
+
+/// Keeps [`PREFERS_DARK`] up to date, calling `on_change` whenever the
+/// desktop's color scheme changes.
+pub async fn run_appearance_watcher(on_change: impl Fn()) {
+    let connection = match zbus::Connection::session().await {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("couldn't connect to session D-Bus for the color scheme: {e}");
+            return;
+        }
+    };
+
+    let proxy = match PortalSettingsProxy::new(&connection).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("couldn't create settings portal proxy: {e}");
+            return;
+        }
+    };
+
+    let mut stream = match proxy.receive_setting_changed().await {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("couldn't subscribe to SettingChanged signal: {e}");
+            return;
+        }
+    };
+
+    while let Some(signal) = stream.next().await {
+        let Ok(args) = signal.args() else {
+            continue;
+        };
+        if args.namespace != APPEARANCE_NAMESPACE || args.key != COLOR_SCHEME_KEY {
+            continue;
+        }
+
+        let prefers_dark = prefers_dark(&args.value);
+        if *PREFERS_DARK.read() != prefers_dark {
+            log::info!("color scheme changed (prefers dark: {prefers_dark:?})");
+            *PREFERS_DARK.write() = prefers_dark;
+            on_change();
+        }
+    }
+
+    log::warn!("stopped receiving color scheme changes");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_scheme_values() {
+        assert_eq!(prefers_dark(&Value::U32(1)), Some(true));
+        assert_eq!(prefers_dark(&Value::U32(2)), Some(false));
+        assert_eq!(prefers_dark(&Value::U32(0)), None);
+        assert_eq!(prefers_dark(&Value::from("dark")), None);
+
+        let wrapped = Value::Value(Box::new(Value::U32(1)));
+        assert_eq!(prefers_dark(&wrapped), Some(true));
+    }
+}
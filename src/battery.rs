@@ -1,6 +1,7 @@
-use std::time::Duration;
+use std::{sync::OnceLock, time::Duration};
 
 use relm4::SharedState;
+use tokio::sync::{mpsc, oneshot};
 
 mod alerts;
 mod discharging;
@@ -10,10 +11,26 @@ mod watcher;
 
 use chrono::TimeDelta;
 use serde::{Deserialize, Serialize};
-pub use watcher::start_battery_service;
+pub use sysfs::detect_battery_path;
+pub use watcher::{explain_estimate, start_battery_service};
 
 pub static BATTERY_STATE: SharedState<Option<BatteryState>> = SharedState::new();
 
+/// Asks the battery service to save its discharge profile, replying once done.
+static SAVE_TX: OnceLock<mpsc::UnboundedSender<oneshot::Sender<()>>> = OnceLock::new();
+
+/// Saves the learned discharge profile now, so nothing since the last
+/// periodic save is lost when the shell exits. Returns once it's saved, or
+/// immediately if the battery service isn't running.
+pub async fn save_profile() {
+    if let Some(tx) = SAVE_TX.get() {
+        let (done_tx, done_rx) = oneshot::channel();
+        if tx.send(done_tx).is_ok() {
+            let _ = done_rx.await;
+        }
+    }
+}
+
 /// For a moving average over 10 readings.
 const STATISTICS_ALPHA: f64 = 1.0 / 10.0;
 
@@ -27,7 +44,67 @@ const READ_INTERVAL_SECONDS: u32 = 10;
 pub struct BatteryState {
     pub percentage: f32,
     pub status: ChargingStatus,
-    pub discharging_time_remaining: Duration,
+    /// `None` while the discharge model is still warming up.
+    pub discharging_time_remaining: Option<Duration>,
+    /// How far to trust `discharging_time_remaining`, from 0 to 1.
+    pub confidence: f32,
+    pub health: BatteryHealth,
+}
+
+/// How far a time-remaining estimate can be trusted, in broad strokes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EstimateConfidence {
+    /// The model is still learning, so the estimate isn't worth showing.
+    Learning,
+    Medium,
+    High,
+}
+
+impl EstimateConfidence {
+    /// Buckets a confidence from 0 to 1.
+    pub fn from_score(score: f32) -> Self {
+        if score < 0.4 {
+            Self::Learning
+        } else if score < 0.75 {
+            Self::Medium
+        } else {
+            Self::High
+        }
+    }
+}
+
+/// Long-term wear information for the battery. Either value may be absent if
+/// the driver doesn't report it.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct BatteryHealth {
+    /// Number of charge cycles the battery has been through.
+    pub cycle_count: Option<u32>,
+
+    /// Current full capacity relative to the design capacity, from 0 to 1.
+    pub health: Option<f32>,
+}
+
+impl BatteryHealth {
+    /// Full capacity as a rounded percentage of the design capacity.
+    pub fn health_pct(&self) -> Option<u32> {
+        self.health.map(|h| (h * 100.0).round() as u32)
+    }
+
+    /// A short human-readable wear summary, or `None` if nothing is known.
+    pub fn summary(&self) -> Option<String> {
+        let parts: Vec<String> = [
+            self.health_pct().map(|pct| format!("Health {pct}%")),
+            self.cycle_count.map(|c| match c {
+                1 => "1 cycle".to_string(),
+                c => format!("{c} cycles"),
+            }),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        (!parts.is_empty()).then(|| parts.join(" · "))
+    }
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
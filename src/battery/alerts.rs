@@ -1,25 +1,70 @@
-use crate::sound;
+use std::{fs, io, path::PathBuf, time::Duration};
+
+use anyhow::{Context, Result};
+use tokio::{sync::broadcast::error::RecvError, task::AbortHandle};
+
+use crate::{
+    battery::BatteryHealth,
+    notifications::{self, NotificationEvent},
+    settings::{self, CriticalBatteryAction},
+    sleep_monitor::Login1ManagerProxy,
+    sound,
+    utils::state::get_state_directory,
+};
 
 // SoC at which a battery's charge is considered low.
 pub const BATTERY_THRESHOLD_LOW: f32 = 0.2;
 
-// SoC at which a battery's charge is considered critically low.
-pub const BATTERY_THRESHOLD_CRITICAL: f32 = 0.1;
-
 /// XDG sound event id for battery warning (20%).
 const SOUND_BATTERY_LOW: &str = "battery-low";
 
-/// XDG sound event id for battery critical (10%).
+/// XDG sound event id for battery critical.
 const SOUND_BATTERY_CRITICAL: &str = "battery-critical";
 
+/// Action key for cancelling a pending critical battery action.
+const ACTION_CANCEL: &str = "cancel";
+
+/// How many readings in a row must show the battery worn past
+/// `battery.health_warning` before warning about it, so one bogus reading
+/// from the driver can't set it off.
+const HEALTH_LOW_READINGS: u32 = 6;
+
+/// How far above the level it was warned at a battery's health has to read,
+/// `HEALTH_LOW_READINGS` times in a row, before it's taken to be a new
+/// battery, so readings wobbling around the threshold don't warn again.
+const HEALTH_RECOVERY_MARGIN: f32 = 0.1;
+
 /// Tracks which low-battery alerts have already fired in the current
 /// discharging session, so each is only triggered once.
 pub(super) struct AlertState {
     /// Whether the "battery low" (20%) alert has fired this session.
     warn_triggered: bool,
 
-    /// Whether the "battery critical" (10%) alert has fired this session.
+    /// Whether the "battery critical" alert has fired this session.
     critical_triggered: bool,
+
+    /// The suspend/hibernate countdown, if one is running.
+    countdown: Option<Countdown>,
+
+    /// How many readings in a row have shown the battery's health below the
+    /// warning threshold.
+    health_low_readings: u32,
+
+    /// How many readings in a row have shown the battery's health well above
+    /// the level it was warned at.
+    health_recovered_readings: u32,
+
+    /// The threshold the worn battery warning last fired for, if it has.
+    /// Unlike the charge alerts, this is kept across runs, so the warning only
+    /// fires again once the battery wears past a lower threshold, or recovers
+    /// (say, it was replaced) and wears out again.
+    health_warned_at: Option<f32>,
+}
+
+/// A pending critical battery action and the notification announcing it.
+struct Countdown {
+    notification_id: u32,
+    task: AbortHandle,
 }
 
 impl AlertState {
@@ -27,29 +72,63 @@ impl AlertState {
         Self {
             warn_triggered: false,
             critical_triggered: false,
+            countdown: None,
+            health_low_readings: 0,
+            health_recovered_readings: 0,
+            health_warned_at: None,
+        }
+    }
+
+    /// Like [`AlertState::new`], but remembers which worn battery warning
+    /// was last given, even by an earlier run.
+    pub fn load() -> Self {
+        let health_warned_at = read_health_warning()
+            .inspect_err(|e| log::warn!("couldn't read the last battery health warning: {e}"))
+            .unwrap_or_default();
+
+        Self {
+            health_warned_at,
+            ..Self::new()
         }
     }
 
-    /// Reset alert flags at the start of a new discharging session.
+    /// Reset alert flags at the start of a new discharging session, cancelling
+    /// any pending critical action (e.g. because a charger was connected).
     pub fn reset(&mut self) {
         self.warn_triggered = false;
         self.critical_triggered = false;
+
+        if let Some(countdown) = self.countdown.take() {
+            log::info!("charger connected, cancelling critical battery action");
+            countdown.task.abort();
+            notifications::dismiss(countdown.notification_id);
+        }
     }
 
     /// Fire any pending low-battery alerts based on the current percentage.
     ///
     /// Each alert is sent at most once per discharging session. Critical takes
-    /// priority: if we cross 10% without having seen 20% first (e.g. the shell
-    /// started with the battery already below 20%), both would fire
-    /// independently.
+    /// priority: if we cross `battery.critical_threshold` without having seen
+    /// 20% first (e.g. the shell started with the battery already below 20%),
+    /// both would fire independently.
     pub async fn check(&mut self, percentage: f32) {
-        // critical alert (10%)
-        if percentage <= BATTERY_THRESHOLD_CRITICAL && !self.critical_triggered {
+        let config = settings::get_config().battery;
+
+        // critical alert
+        if percentage <= config.critical_threshold && !self.critical_triggered {
             // automatically set warn_triggered to true here, because it's implied
             self.warn_triggered = true;
             self.critical_triggered = true;
             log::info!("battery critical alert ({:.0}%)", percentage * 100.0);
             fire_battery_alert(AlertLevel::Critical).await;
+
+            if config.critical_action != CriticalBatteryAction::Notify {
+                self.countdown = start_countdown(
+                    config.critical_action,
+                    Duration::from_secs(config.critical_action_delay),
+                )
+                .await;
+            }
         } else if percentage <= BATTERY_THRESHOLD_LOW && !self.warn_triggered {
             // warning alert (20%)
             self.warn_triggered = true;
@@ -57,6 +136,92 @@ impl AlertState {
             fire_battery_alert(AlertLevel::Normal).await;
         }
     }
+
+    /// Warn once if the battery has worn below `threshold` of its design
+    /// capacity, as seen over several readings in a row.
+    pub async fn check_health(&mut self, health: &BatteryHealth, threshold: f32) {
+        let warned_at = self.health_warned_at;
+        let warn = self.record_health(health.health, threshold);
+        if self.health_warned_at != warned_at
+            && let Err(e) = write_health_warning(self.health_warned_at)
+        {
+            log::error!("couldn't save the battery health warning: {e}");
+        }
+        if !warn {
+            return;
+        }
+
+        let pct = health.health_pct().unwrap_or_default();
+        log::warn!("battery health is down to {pct}% of its design capacity");
+        notifications::send_notification(
+            "System",
+            "Battery is wearing out",
+            &format!(
+                "Your battery now holds {pct}% of its original charge. Consider replacing it \
+                 soon."
+            ),
+            1,
+            &[],
+            -1,
+        )
+        .await;
+    }
+
+    /// Counts a health reading, returning whether it's time to warn.
+    fn record_health(&mut self, health: Option<f32>, threshold: f32) -> bool {
+        // some drivers report more than the design capacity when new
+        let health = health.map(|h| h.min(1.0));
+
+        if health.is_some_and(|h| h < threshold) {
+            self.health_low_readings += 1;
+        } else {
+            self.health_low_readings = 0;
+        }
+
+        // a battery that stays well above the level it was warned at has been
+        // replaced
+        if let (Some(health), Some(warned_at)) = (health, self.health_warned_at)
+            && health >= (warned_at + HEALTH_RECOVERY_MARGIN).min(1.0)
+        {
+            self.health_recovered_readings += 1;
+        } else {
+            self.health_recovered_readings = 0;
+        }
+        if self.health_recovered_readings >= HEALTH_LOW_READINGS {
+            self.health_recovered_readings = 0;
+            self.health_warned_at = None;
+        }
+
+        // warned already, unless the threshold has been lowered since
+        let warned = self
+            .health_warned_at
+            .is_some_and(|warned_at| threshold >= warned_at);
+        if warned || self.health_low_readings < HEALTH_LOW_READINGS {
+            return false;
+        }
+        self.health_warned_at = Some(threshold);
+        true
+    }
+}
+
+fn health_warning_path() -> Result<PathBuf> {
+    Ok(get_state_directory()?.join("health_warning.json"))
+}
+
+/// The threshold the worn battery warning last fired for, saved by
+/// [`write_health_warning`].
+fn read_health_warning() -> Result<Option<f32>> {
+    let json = match fs::read_to_string(health_warning_path()?) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("couldn't read battery health warning"),
+    };
+    Ok(serde_json::from_str(&json)?)
+}
+
+fn write_health_warning(warned_at: Option<f32>) -> Result<()> {
+    fs::write(health_warning_path()?, serde_json::to_string(&warned_at)?)
+        .context("couldn't write battery health warning")
 }
 
 #[derive(Clone, Copy)]
@@ -85,53 +250,155 @@ async fn fire_battery_alert(level: AlertLevel) {
     // play sound first so any D-Bus latency doesn't delay the audio cue
     sound::play(sound_event);
 
-    send_notification(summary, body, urgency).await;
+    notifications::send_notification("System", summary, body, urgency, &[], -1).await;
+}
+
+/// Announce the pending critical action with a cancelable notification, then
+/// perform it once the grace period elapses.
+async fn start_countdown(action: CriticalBatteryAction, delay: Duration) -> Option<Countdown> {
+    let verb = match action {
+        CriticalBatteryAction::Notify => return None,
+        CriticalBatteryAction::Suspend => "suspend",
+        CriticalBatteryAction::Hibernate => "hibernate",
+    };
+
+    // subscribe before sending so we can't miss an early cancel
+    let mut events = notifications::subscribe_events();
+
+    let notification_id = notifications::send_notification(
+        "System",
+        &format!("Your device will {verb} soon"),
+        &format!(
+            "The battery is critically low. Your device will {verb} in {} seconds unless a \
+             charger is connected.",
+            delay.as_secs()
+        ),
+        2,
+        &[ACTION_CANCEL, "Cancel"],
+        0,
+    )
+    .await?;
+
+    let task = relm4::spawn(async move {
+        let cancelled = async {
+            loop {
+                match events.recv().await {
+                    Ok(NotificationEvent::ActionInvoked { id, action_key })
+                        if id == notification_id && action_key == ACTION_CANCEL =>
+                    {
+                        break;
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => std::future::pending().await,
+                }
+            }
+        };
+
+        tokio::select! {
+            _ = cancelled => {
+                log::info!("critical battery action cancelled by user");
+            }
+            _ = tokio::time::sleep(delay) => {
+                notifications::dismiss(notification_id);
+                perform_critical_action(action).await;
+            }
+        }
+    });
+
+    Some(Countdown {
+        notification_id,
+        task: task.abort_handle(),
+    })
 }
 
-/// Send a notification via the org.freedesktop.Notifications D-Bus interface.
-///
-/// This sends to our own daemon, which will display it in the shell's fresh
-/// notification overlay and store it in the notification center.
-async fn send_notification(summary: &str, body: &str, urgency: u8) {
-    let connection = match zbus::Connection::session().await {
+/// Ask logind to suspend or hibernate the system.
+async fn perform_critical_action(action: CriticalBatteryAction) {
+    log::info!("battery critically low, performing {action:?}");
+
+    let conn = match zbus::Connection::system().await {
         Ok(c) => c,
         Err(e) => {
-            log::warn!("couldn't open D-Bus session for battery alert: {}", e);
+            log::error!("couldn't connect to system D-Bus for critical battery action: {e}");
+            return;
+        }
+    };
+
+    let proxy = match Login1ManagerProxy::new(&conn).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("couldn't create login1 manager proxy: {e}");
             return;
         }
     };
 
-    // build the hints dict `a{sv}` with the urgency byte.
-    // HashMap<String, OwnedValue> serializes to `a{sv}` because OwnedValue's
-    // D-Bus signature is `v` (variant).
-    use std::collections::HashMap;
+    let result = match action {
+        CriticalBatteryAction::Notify => return,
+        CriticalBatteryAction::Suspend => proxy.suspend(false).await,
+        CriticalBatteryAction::Hibernate => proxy.hibernate(false).await,
+    };
 
-    use zbus::zvariant::{OwnedValue, Value};
-    let mut hints: HashMap<String, OwnedValue> = HashMap::new();
-    if let Ok(urgency_owned) = OwnedValue::try_from(Value::U8(urgency)) {
-        hints.insert("urgency".to_string(), urgency_owned);
+    if let Err(e) = result {
+        log::error!("couldn't perform critical battery action {action:?}: {e}");
     }
+}
 
-    let result = connection
-        .call_method(
-            Some("org.freedesktop.Notifications"),
-            "/org/freedesktop/Notifications",
-            Some("org.freedesktop.Notifications"),
-            "Notify",
-            &(
-                "System", // app_name
-                0u32,     // replaces_id
-                "",       // app_icon
-                summary,
-                body,
-                Vec::<String>::new(), // actions
-                hints,
-                -1i32, // expire_timeout (-1 = server decides)
-            ),
-        )
-        .await;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if let Err(e) = result {
-        log::warn!("couldn't send battery alert notification: {}", e);
+    #[test]
+    fn test_health_warning_needs_consecutive_low_readings() {
+        let mut state = AlertState::new();
+
+        // an odd reading now and then isn't enough
+        for _ in 0..HEALTH_LOW_READINGS - 1 {
+            assert!(!state.record_health(Some(0.7), 0.8));
+        }
+        assert!(!state.record_health(Some(1.05), 0.8));
+        assert!(!state.record_health(None, 0.8));
+
+        for _ in 0..HEALTH_LOW_READINGS - 1 {
+            assert!(!state.record_health(Some(0.7), 0.8));
+        }
+        assert!(state.record_health(Some(0.7), 0.8));
+
+        // only warns once
+        assert!(!state.record_health(Some(0.7), 0.8));
+    }
+
+    #[test]
+    fn test_health_warning_fires_again_past_a_lower_threshold() {
+        let mut state = AlertState {
+            health_warned_at: Some(0.8),
+            ..AlertState::new()
+        };
+        let warnings = |state: &mut AlertState, health, threshold| {
+            (0..HEALTH_LOW_READINGS)
+                .filter(|_| state.record_health(Some(health), threshold))
+                .count()
+        };
+
+        // warned about in an earlier run
+        assert_eq!(warnings(&mut state, 0.75, 0.8), 0);
+        // a higher threshold was already passed on the way down
+        assert_eq!(warnings(&mut state, 0.75, 0.9), 0);
+        assert_eq!(warnings(&mut state, 0.75, 0.7), 0);
+        assert_eq!(warnings(&mut state, 0.65, 0.7), 1);
+        assert_eq!(state.health_warned_at, Some(0.7));
+
+        // one good reading isn't a new battery
+        assert!(!state.record_health(Some(0.9), 0.7));
+        assert!(!state.record_health(Some(0.65), 0.7));
+        assert_eq!(state.health_warned_at, Some(0.7));
+
+        // but a run of them is, and it starts over
+        assert_eq!(warnings(&mut state, 1.0, 0.7), 0);
+        assert_eq!(state.health_warned_at, None);
+        assert_eq!(warnings(&mut state, 0.65, 0.7), 1);
+
+        // readings past the design capacity count as full
+        state.health_warned_at = Some(0.95);
+        assert_eq!(warnings(&mut state, 1.2, 0.7), 0);
+        assert_eq!(state.health_warned_at, None);
     }
 }
@@ -4,13 +4,16 @@
 use core::iter::Iterator;
 use std::{fs, path::PathBuf, time::Duration};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, ensure};
 use chrono::{DateTime, Datelike, Local, Timelike};
 use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
 
-use crate::battery::{
-    ChargingStatus, READ_INTERVAL_SECONDS, SAVE_INTERVAL, STATISTICS_ALPHA, sysfs::SysfsReading,
+use crate::{
+    battery::{
+        ChargingStatus, READ_INTERVAL_SECONDS, SAVE_INTERVAL, STATISTICS_ALPHA, sysfs::SysfsReading,
+    },
+    utils::state::get_state_directory,
 };
 
 /// Number of Fourier harmonics used to model the weekly power-usage cycle.
@@ -21,6 +24,11 @@ use crate::battery::{
 /// will fail to deserialize and fall back to a fresh profile.
 const HARMONICS: usize = 28;
 
+/// Version of the saved profile format. Bump it whenever a change means old
+/// profiles would be misread, so they're replaced by a fresh profile instead.
+/// Profiles saved before the format was versioned count as version 1.
+const FORMAT_VERSION: u32 = 1;
+
 /// Duration of one full model period: one week in seconds.
 const PERIOD_SECS: f64 = 7.0 * 24.0 * 3600.0;
 
@@ -28,6 +36,15 @@ const PERIOD_SECS: f64 = 7.0 * 24.0 * 3600.0;
 /// battery tile already displays "Until someday" for durations this long.
 const MAX_TTE: Duration = Duration::from_secs(48 * 3_600);
 
+/// How many discharging samples must be seen before predictions are trusted.
+/// At the 10-second read interval this is five minutes of discharging.
+const WARM_UP_SAMPLES: u32 = 30;
+
+/// How many discharging samples it takes before the model's age stops
+/// counting against its confidence. At the 10-second read interval this is an
+/// hour of discharging.
+const MATURE_SAMPLES: u32 = 360;
+
 /// How much of a day we expect users to be awake.
 const WAKING_HOURS_PERCENTAGE: f64 = 16.0 / 24.0;
 
@@ -39,8 +56,41 @@ const READINGS_PER_LIFETIME: u32 =
     (PERIOD_SECS * READING_LIFETIME_PERIODS * WAKING_HOURS_PERCENTAGE) as u32
         / READ_INTERVAL_SECONDS;
 
+/// How a time-to-empty estimate was produced. Useful for making sense of
+/// anomalous predictions in bug reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PredictionStrategy {
+    /// There was no energy left, so the estimate is zero.
+    Empty,
+    /// Too few samples have been seen for the model to be trusted yet.
+    WarmingUp,
+    /// No power draw has been learned yet, so the estimate is [`MAX_TTE`].
+    NoHistory,
+    /// The Fourier model was integrated and solved with Newton's method alone.
+    Newton,
+    /// The solver had to fall back to bisection for at least one step.
+    Bisection,
+    /// The solution exceeded [`MAX_TTE`] and was capped.
+    Capped,
+}
+
+/// A time-to-empty estimate along with how it was produced.
+///
+/// Only a discharge model exists, so the same model drives every estimate,
+/// including those shown while charging.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeToEmpty {
+    /// `None` while the model is still warming up.
+    pub duration: Option<Duration>,
+    pub strategy: PredictionStrategy,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct DischargeProfile {
+    /// The [`FORMAT_VERSION`] this profile was saved with.
+    #[serde(default = "unversioned")]
+    version: u32,
+
     /// Exponential moving average of instantaneous power draw, in watts.
     ema_power: f64,
 
@@ -68,6 +118,7 @@ pub struct DischargeProfile {
 impl Default for DischargeProfile {
     fn default() -> Self {
         Self {
+            version: FORMAT_VERSION,
             ema_power: Default::default(),
             cosine_coeffs: [0.0; HARMONICS],
             sine_coeffs: [0.0; HARMONICS],
@@ -210,18 +261,43 @@ impl DischargeProfile {
     /// Uses numerical energy integration with Newton's method to determine
     /// how long it will take for the battery to deplete entirely.
     ///
-    /// Capped at [`MAX_TTE`] (48 hours).
+    /// Capped at [`MAX_TTE`] (48 hours). Returns `None` until
+    /// [`WARM_UP_SAMPLES`] samples have been seen, so the UI can show that it's
+    /// still calculating instead of a wildly wrong estimate.
     pub fn predict_time_to_empty(
         &mut self,
         from_when: DateTime<Local>,
         wh_remaining: f64,
-    ) -> Duration {
+    ) -> Option<Duration> {
+        self.explain_time_to_empty(from_when, wh_remaining).duration
+    }
+
+    /// Like [`predict_time_to_empty`](Self::predict_time_to_empty), but also
+    /// reports which strategy produced the estimate.
+    pub fn explain_time_to_empty(
+        &mut self,
+        from_when: DateTime<Local>,
+        wh_remaining: f64,
+    ) -> TimeToEmpty {
         if wh_remaining == 0.0 {
-            return Duration::ZERO;
+            return TimeToEmpty {
+                duration: Some(Duration::ZERO),
+                strategy: PredictionStrategy::Empty,
+            };
+        }
+
+        if self.is_warming_up() {
+            return TimeToEmpty {
+                duration: None,
+                strategy: PredictionStrategy::WarmingUp,
+            };
         }
 
         if self.ema_power == 0.0 {
-            return MAX_TTE;
+            return TimeToEmpty {
+                duration: Some(MAX_TTE),
+                strategy: PredictionStrategy::NoHistory,
+            };
         }
 
         let ws_remaining = wh_remaining * SECONDS_PER_HOUR as f64;
@@ -234,6 +310,7 @@ impl DischargeProfile {
         // start with the tightest bracket we can establish cheaply.
         let mut lo = 0f64;
         let mut hi = max_secs;
+        let mut bisected = false;
 
         // hybrid Newton / bisection: find Δt such that
         //   f(Δt) = energy_integral(from, Δt) - ws_remaining = 0
@@ -267,10 +344,12 @@ impl DischargeProfile {
                     newton
                 } else {
                     // Newton stepped outside the bracket; bisect instead
+                    bisected = true;
                     (lo + hi) * 0.5
                 }
             } else {
                 // zero-power region at the endpoint: bisect toward lo
+                bisected = true;
                 (lo + hi) * 0.5
             };
 
@@ -286,7 +365,44 @@ impl DischargeProfile {
         self.discharging_statistics
             .update((from_when + elapsed).timestamp());
 
-        elapsed
+        let strategy = if elapsed >= MAX_TTE {
+            PredictionStrategy::Capped
+        } else if bisected {
+            PredictionStrategy::Bisection
+        } else {
+            PredictionStrategy::Newton
+        };
+        log::debug!("{:>17}: {:?}", "tte strategy", strategy);
+
+        TimeToEmpty {
+            duration: Some(elapsed),
+            strategy,
+        }
+    }
+
+    /// How far to trust `time_to_empty`, an estimate just made by this
+    /// profile, from 0 to 1.
+    ///
+    /// Confidence grows as the model sees more samples, and shrinks the more
+    /// recent estimates have jumped around compared to how far away the end
+    /// is.
+    pub fn confidence(&self, time_to_empty: Option<Duration>) -> f32 {
+        let Some(time_to_empty) = time_to_empty else {
+            return 0.0;
+        };
+        if time_to_empty.is_zero() {
+            return 1.0;
+        }
+
+        let maturity = (self.sample_count as f64 / MATURE_SAMPLES as f64).min(1.0);
+        let spread = self.discharging_statistics.variance_ema.sqrt() / time_to_empty.as_secs_f64();
+
+        (maturity / (1.0 + spread)) as f32
+    }
+
+    /// Whether too few samples have been seen to trust predictions.
+    pub fn is_warming_up(&self) -> bool {
+        self.sample_count < WARM_UP_SAMPLES
     }
 
     /// Get the path to the history file.
@@ -298,10 +414,20 @@ impl DischargeProfile {
         let path = Self::get_state_path()?;
         let json = fs::read_to_string(&path).context("couldn't read power history")?;
 
-        Ok(serde_json::from_str(&json)?)
+        Self::from_json(&json)
+    }
+
+    fn from_json(json: &str) -> Result<Self> {
+        let profile: Self = serde_json::from_str(json)?;
+        ensure!(
+            profile.version == FORMAT_VERSION,
+            "power history has format version {}, expected {FORMAT_VERSION}",
+            profile.version
+        );
+        Ok(profile)
     }
 
-    fn save_to_disk(&self) -> Result<()> {
+    pub fn save_to_disk(&self) -> Result<()> {
         let json = serde_json::to_string_pretty(&self)?;
         let path = Self::get_state_path()?;
         fs::write(&path, json).context("couldn't write predictor state")?;
@@ -310,13 +436,8 @@ impl DischargeProfile {
     }
 }
 
-pub(super) fn get_state_directory() -> Result<PathBuf> {
-    let state_dir = dirs::state_dir()
-        .or_else(dirs::data_local_dir)
-        .context("couldn't find state directory")?;
-    let cadenza_state = state_dir.join("cadenza-shell");
-    fs::create_dir_all(&cadenza_state).context("couldn't create state directory")?;
-    Ok(cadenza_state)
+fn unversioned() -> u32 {
+    1
 }
 
 const SECONDS_PER_MINUTE: u32 = 60;
@@ -497,11 +618,13 @@ mod tests {
 
     // ── helpers ───────────────────────────────────────────────────────────────
 
-    /// Return a [`DischargeProfile`] whose EMA power is `power_watts` and
-    /// whose Fourier coefficients are all zero (i.e., constant-power model).
+    /// Return a warmed-up [`DischargeProfile`] whose EMA power is
+    /// `power_watts` and whose Fourier coefficients are all zero (i.e.,
+    /// constant-power model).
     fn constant_power_profile(power_watts: f64) -> DischargeProfile {
         DischargeProfile {
             ema_power: power_watts,
+            sample_count: WARM_UP_SAMPLES,
             ..Default::default()
         }
     }
@@ -602,14 +725,14 @@ mod tests {
     fn tte_zero_wh_returns_zero_duration() {
         let mut profile = constant_power_profile(10.0);
         let tte = profile.predict_time_to_empty(Local::now(), 0.0);
-        assert_eq!(tte, Duration::ZERO);
+        assert_eq!(tte, Some(Duration::ZERO));
     }
 
     #[test]
     fn tte_zero_ema_returns_max_tte() {
         let mut profile = constant_power_profile(0.0);
         let tte = profile.predict_time_to_empty(Local::now(), 30.0);
-        assert_eq!(tte, MAX_TTE);
+        assert_eq!(tte, Some(MAX_TTE));
     }
 
     #[test]
@@ -619,7 +742,7 @@ mod tests {
         let wh = 30.0;
         let mut profile = constant_power_profile(power_w);
         let from = Local::now();
-        let tte = profile.predict_time_to_empty(from, wh);
+        let tte = profile.predict_time_to_empty(from, wh).unwrap();
 
         let expected_secs = wh / power_w * 3_600.0;
         let error_secs = (tte.as_secs_f64() - expected_secs).abs();
@@ -635,7 +758,94 @@ mod tests {
         // a very large battery and tiny power draw should cap at MAX_TTE
         let mut profile = constant_power_profile(0.1); // 0.1 W
         let tte = profile.predict_time_to_empty(Local::now(), 100.0); // 100 Wh → 1000 h
-        assert_eq!(tte, MAX_TTE, "TTE should be capped at MAX_TTE (48 h)");
+        assert_eq!(tte, Some(MAX_TTE), "TTE should be capped at MAX_TTE (48 h)");
+    }
+
+    #[test]
+    fn tte_none_while_warming_up() {
+        let mut profile = DischargeProfile {
+            ema_power: 10.0,
+            sample_count: WARM_UP_SAMPLES - 1,
+            ..Default::default()
+        };
+        let explained = profile.explain_time_to_empty(Local::now(), 20.0);
+        assert_eq!(explained.duration, None);
+        assert_eq!(explained.strategy, PredictionStrategy::WarmingUp);
+
+        // the very next sample ends the warm-up period
+        profile.sample_count += 1;
+        assert!(profile.predict_time_to_empty(Local::now(), 20.0).is_some());
+    }
+
+    #[test]
+    fn tte_zero_wh_is_zero_even_while_warming_up() {
+        let mut profile = DischargeProfile::default();
+        let tte = profile.predict_time_to_empty(Local::now(), 0.0);
+        assert_eq!(tte, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn tte_reports_strategy() {
+        let from = Local::now();
+
+        let empty = constant_power_profile(12.0).explain_time_to_empty(from, 0.0);
+        assert_eq!(empty.strategy, PredictionStrategy::Empty);
+
+        let capped = constant_power_profile(0.1).explain_time_to_empty(from, 100.0);
+        assert_eq!(capped.strategy, PredictionStrategy::Capped);
+
+        let no_history = constant_power_profile(0.0).explain_time_to_empty(from, 30.0);
+        assert_eq!(no_history.strategy, PredictionStrategy::NoHistory);
+    }
+
+    // ── persistence ───────────────────────────────────────────────────────────
+
+    #[test]
+    fn profile_round_trips_through_json() {
+        let profile = train_constant(constant_power_profile(9.0), Local::now(), 9.0, 20);
+        let json = serde_json::to_string(&profile).unwrap();
+        let loaded = DischargeProfile::from_json(&json).unwrap();
+
+        assert_eq!(loaded.ema_power, profile.ema_power);
+        assert_eq!(loaded.cosine_coeffs, profile.cosine_coeffs);
+        assert_eq!(loaded.sine_coeffs, profile.sine_coeffs);
+        assert_eq!(loaded.sample_count, profile.sample_count);
+    }
+
+    #[test]
+    fn profile_format_version_is_checked() {
+        let mut json = serde_json::to_value(DischargeProfile::default()).unwrap();
+
+        // profiles from before versioning are still read
+        json.as_object_mut().unwrap().remove("version");
+        assert!(DischargeProfile::from_json(&json.to_string()).is_ok());
+
+        json["version"] = (FORMAT_VERSION + 1).into();
+        assert!(DischargeProfile::from_json(&json.to_string()).is_err());
+    }
+
+    #[test]
+    fn confidence_grows_with_samples_and_shrinks_with_spread() {
+        let tte = Some(Duration::from_secs(4 * 3_600));
+        let mut profile = constant_power_profile(10.0);
+
+        assert_eq!(profile.confidence(None), 0.0);
+        assert_eq!(profile.confidence(Some(Duration::ZERO)), 1.0);
+
+        // freshly warmed up
+        let young = profile.confidence(tte);
+        assert!(young < 0.4, "young model was {young}");
+
+        profile.sample_count = MATURE_SAMPLES;
+        assert_eq!(profile.confidence(tte), 1.0);
+
+        // estimates that wander by an hour either way
+        profile.discharging_statistics.variance_ema = 3_600.0 * 3_600.0;
+        let wandering = profile.confidence(tte);
+        assert!(
+            (0.4..0.9).contains(&wandering),
+            "wandering model was {wandering}"
+        );
     }
 
     #[test]
@@ -647,7 +857,7 @@ mod tests {
         let base = Local::now();
         let profile = train_constant(constant_power_profile(power_w), base, power_w, 500);
         let mut profile = profile;
-        let tte = profile.predict_time_to_empty(base, wh);
+        let tte = profile.predict_time_to_empty(base, wh).unwrap();
 
         let expected_secs = wh / power_w * 3_600.0;
         let relative_error = (tte.as_secs_f64() - expected_secs).abs() / expected_secs;
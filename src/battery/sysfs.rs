@@ -6,7 +6,7 @@ use std::{
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 
-use crate::battery::{BatteryCapacity, ChargingStatus};
+use crate::battery::{BatteryCapacity, BatteryHealth, ChargingStatus};
 
 /// Raw reading from sysfs battery interface.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -97,6 +97,39 @@ pub fn read_battery_sysfs(battery_path: &Path) -> Option<SysfsReading> {
     })
 }
 
+/// Read cycle count and wear level from sysfs. Values the driver doesn't
+/// expose are left as `None`.
+pub fn read_battery_health(battery_path: &Path) -> BatteryHealth {
+    let cycle_count = read_sysfs_u64(battery_path, "cycle_count").and_then(|c| c.try_into().ok());
+
+    // prefer energy over charge, matching read_battery_sysfs
+    let full_and_design = |unit: fn(u64) -> BatteryCapacity, full: &str, design: &str| {
+        Some((
+            unit(read_sysfs_u64(battery_path, full)?),
+            unit(read_sysfs_u64(battery_path, design)?),
+        ))
+    };
+    let health = full_and_design(
+        BatteryCapacity::MicroWattHours,
+        "energy_full",
+        "energy_full_design",
+    )
+    .or_else(|| {
+        full_and_design(
+            BatteryCapacity::MicroAmpereHours,
+            "charge_full",
+            "charge_full_design",
+        )
+    })
+    .and_then(|(full, design)| full.div(design))
+    .map(|h| h as f32);
+
+    BatteryHealth {
+        cycle_count,
+        health,
+    }
+}
+
 /// Stable identity for a battery device, used to key per-device learned
 /// parameters across reboots.
 #[derive(Debug, Clone)]
@@ -1,17 +1,44 @@
 use std::{path::Path, time::Duration};
 
 use chrono::Local;
-use tokio::io::unix::AsyncFd;
-
-use super::{BATTERY_STATE, BatteryState, ChargingStatus};
-use crate::battery::{
-    READ_INTERVAL_SECONDS,
-    alerts::AlertState,
-    discharging::DischargeProfile,
-    sysfs::{detect_battery_path, read_battery_identity, read_battery_sysfs},
-    udev::{create_battery_monitor, is_battery_change},
+use tokio::{
+    io::unix::AsyncFd,
+    sync::{mpsc, oneshot},
 };
 
+use super::{BATTERY_STATE, BatteryState, ChargingStatus, SAVE_TX};
+use crate::{
+    battery::{
+        READ_INTERVAL_SECONDS,
+        alerts::AlertState,
+        discharging::DischargeProfile,
+        sysfs::{
+            detect_battery_path, read_battery_health, read_battery_identity, read_battery_sysfs,
+        },
+        udev::{create_battery_monitor, is_battery_change},
+    },
+    settings,
+};
+
+/// Describes the time-to-empty estimate the saved discharge profile gives for
+/// the battery's current charge, and which strategy produced it, for
+/// diagnostics.
+pub fn explain_estimate(battery_path: &Path) -> anyhow::Result<String> {
+    let mut profile = DischargeProfile::read_from_disk()?;
+    let reading = read_battery_sysfs(battery_path)
+        .ok_or_else(|| anyhow::anyhow!("couldn't read the battery"))?;
+
+    let estimate = profile.explain_time_to_empty(Local::now(), reading.remaining_wh());
+    let duration = match estimate.duration {
+        Some(duration) => format!("{}m", duration.as_secs() / 60),
+        None => "none".to_string(),
+    };
+    Ok(format!(
+        "estimate {duration} to empty ({:?})",
+        estimate.strategy
+    ))
+}
+
 pub async fn start_battery_service() {
     // detect battery sysfs path
     let Some(battery_path) = detect_battery_path() else {
@@ -52,6 +79,8 @@ pub async fn start_battery_service() {
         percentage: reading.percentage().unwrap_or_default() as f32,
         status: reading.status,
         discharging_time_remaining,
+        confidence: power_history.confidence(discharging_time_remaining),
+        health: read_battery_health(&battery_path),
     });
 
     // set up udev monitor for immediate status change events
@@ -71,11 +100,18 @@ pub async fn start_battery_service() {
         }
     };
 
-    let mut alert_state = AlertState::new();
+    let (save_tx, save_rx) = mpsc::unbounded_channel();
+    if SAVE_TX.set(save_tx).is_err() {
+        log::warn!("battery service started more than once; extra instance exiting");
+        return;
+    }
+
+    let mut alert_state = AlertState::load();
 
     watch_battery(
         &battery_path,
         async_fd,
+        save_rx,
         &mut power_history,
         &mut alert_state,
     )
@@ -85,6 +121,7 @@ pub async fn start_battery_service() {
 async fn watch_battery(
     battery_path: &Path,
     async_fd: AsyncFd<udev::MonitorSocket>,
+    mut save_rx: mpsc::UnboundedReceiver<oneshot::Sender<()>>,
     power_history: &mut DischargeProfile,
     alert_state: &mut AlertState,
 ) -> Option<!> {
@@ -136,6 +173,14 @@ async fn watch_battery(
                     alert_state,
                 ).await;
             }
+
+            Some(done) = save_rx.recv() => {
+                match power_history.save_to_disk() {
+                    Ok(()) => log::info!("saved power history"),
+                    Err(e) => log::error!("couldn't save discharge profile: {e}"),
+                }
+                let _ = done.send(());
+            }
         }
     }
 }
@@ -161,13 +206,20 @@ async fn update_battery_state(
 
     let percentage = reading.percentage().unwrap_or_default() as f32;
     let status = reading.status;
+    let health = read_battery_health(battery_path);
 
     *BATTERY_STATE.write() = Some(BatteryState {
         percentage,
         status,
         discharging_time_remaining,
+        confidence: power_history.confidence(discharging_time_remaining),
+        health,
     });
 
+    alert_state
+        .check_health(&health, settings::get_config().battery.health_warning)
+        .await;
+
     // check alerts only while discharging; reset flags when we leave that state
     if status == ChargingStatus::Discharging {
         alert_state.check(percentage).await;
@@ -1,16 +1,27 @@
-use std::collections::{HashMap, hash_map};
+use std::{
+    collections::{HashMap, hash_map},
+    sync::{Arc, Mutex},
+};
 
 use bluer::{
     Adapter, AdapterEvent, AdapterProperty, Address, Device, DeviceEvent, DeviceProperty, Session,
+    agent::{Agent, ReqError, ReqResult},
 };
 use futures_lite::StreamExt;
 use relm4::SharedState;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::sync::{
+    mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
+    oneshot,
+};
 
 use crate::sleep_monitor;
 
 pub static BLUETOOTH_STATE: SharedState<Option<BluetoothState>> = SharedState::new();
 
+/// What BlueZ wants shown or answered while pairing, if anything. The
+/// bluetooth menu shows it.
+pub static PAIRING_PROMPT: SharedState<Option<PairingPrompt>> = SharedState::new();
+
 #[derive(Debug)]
 pub enum BluetoothEvent {
     Adapter(AdapterEvent),
@@ -56,6 +67,12 @@ pub async fn run_bluetooth_service() {
         return;
     };
 
+    // kept registered for as long as the service runs
+    let _agent = session
+        .register_agent(pairing_agent())
+        .await
+        .inspect_err(|e| log::error!("couldn't register bluetooth pairing agent: {e}"));
+
     let mut devices = HashMap::new();
     if let Ok(addresses) = adapter.device_addresses().await {
         for address in addresses {
@@ -171,6 +188,11 @@ async fn update(input: BluetoothEvent, event_tx: &UnboundedSender<BluetoothEvent
         return;
     }
 
+    // a device that goes away mid-pairing can't answer its prompt
+    if let BluetoothEvent::Adapter(AdapterEvent::DeviceRemoved(address)) = &input {
+        cancel_pairing_prompt(*address);
+    }
+
     // update_from_event is sync so the write lock is always released before the
     // async subscription below
     let new_device = update_from_event(input);
@@ -296,3 +318,218 @@ async fn subscribe_device_events(
         }
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct PairingPrompt {
+    pub device: Address,
+    pub request: PairingRequest,
+    /// Taken by the first answer; `None` for requests that only show something.
+    reply: Arc<Mutex<Option<oneshot::Sender<PairingReply>>>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PairingRequest {
+    /// Show a passkey to type on the other device.
+    DisplayPasskey(u32),
+    /// Show a PIN code to type on the other device.
+    DisplayPinCode(String),
+    /// Ask whether the passkey shown on the other device is this one.
+    ConfirmPasskey(u32),
+    /// Ask for the PIN code the other device expects.
+    RequestPinCode,
+    /// Ask for the numeric passkey the other device expects.
+    RequestPasskey,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PairingReply {
+    Confirm,
+    Enter(String),
+    Cancel,
+}
+
+impl PairingPrompt {
+    /// Whether this prompt needs an answer, rather than just showing a code.
+    pub fn is_question(&self) -> bool {
+        matches!(
+            self.request,
+            PairingRequest::ConfirmPasskey(_)
+                | PairingRequest::RequestPinCode
+                | PairingRequest::RequestPasskey
+        )
+    }
+
+    /// Answers the prompt and takes it down. Only the first answer counts, so
+    /// menus on other monitors showing the same prompt can't answer it again.
+    pub fn answer(&self, reply: PairingReply) {
+        if let Some(reply_tx) = self.reply.lock().unwrap().take() {
+            let _ = reply_tx.send(reply);
+        }
+        clear_pairing_prompt();
+    }
+}
+
+/// Takes down whatever pairing prompt is showing, e.g. once pairing is over.
+pub fn clear_pairing_prompt() {
+    let mut prompt = PAIRING_PROMPT.write();
+    if prompt.is_some() {
+        *prompt = None;
+    }
+}
+
+/// Takes down the prompt for `device`, e.g. because the device went away
+/// while pairing. A question that wasn't answered yet counts as cancelled.
+fn cancel_pairing_prompt(device: Address) {
+    let mut prompt = PAIRING_PROMPT.write();
+    if let Some(current) = prompt.as_ref()
+        && current.device == device
+    {
+        current.reply.lock().unwrap().take();
+        *prompt = None;
+    }
+}
+
+/// Shows `request` and waits for the answer. Replacing or clearing the prompt
+/// before it's answered counts as cancelling it.
+async fn ask(device: Address, request: PairingRequest) -> PairingReply {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let reply = Arc::new(Mutex::new(Some(reply_tx)));
+    *PAIRING_PROMPT.write() = Some(PairingPrompt {
+        device,
+        request,
+        reply: Arc::clone(&reply),
+    });
+
+    // BlueZ cancelling the request drops this future, which takes the prompt
+    // down with it
+    let _prompt = AskedPrompt(reply);
+    reply_rx.await.unwrap_or(PairingReply::Cancel)
+}
+
+/// The reply channel of a prompt [`ask`] is waiting on. Dropping it drops the
+/// channel, so nothing can answer, and takes the prompt down if it's still
+/// showing.
+struct AskedPrompt(Arc<Mutex<Option<oneshot::Sender<PairingReply>>>>);
+
+impl Drop for AskedPrompt {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().take();
+
+        let mut prompt = PAIRING_PROMPT.write();
+        if prompt
+            .as_ref()
+            .is_some_and(|prompt| Arc::ptr_eq(&prompt.reply, &self.0))
+        {
+            *prompt = None;
+        }
+    }
+}
+
+/// Shows `request` until BlueZ sends `cancel`, unless something else has
+/// replaced it by then.
+fn show(device: Address, request: PairingRequest, cancel: oneshot::Receiver<()>) {
+    *PAIRING_PROMPT.write() = Some(PairingPrompt {
+        device,
+        request: request.clone(),
+        reply: Arc::default(),
+    });
+
+    relm4::spawn(async move {
+        let _ = cancel.await;
+        let mut prompt = PAIRING_PROMPT.write();
+        if prompt
+            .as_ref()
+            .is_some_and(|prompt| prompt.request == request)
+        {
+            *prompt = None;
+        }
+    });
+}
+
+/// The agent BlueZ asks when pairing needs a code shown or confirmed. bluer
+/// rejects anything else, like authorizing services for untrusted devices.
+fn pairing_agent() -> Agent {
+    Agent {
+        request_default: true,
+        request_pin_code: Some(Box::new(|req| {
+            Box::pin(async move {
+                match ask(req.device, PairingRequest::RequestPinCode).await {
+                    PairingReply::Enter(pin) if !pin.is_empty() => Ok(pin),
+                    _ => Err(ReqError::Rejected),
+                }
+            })
+        })),
+        display_pin_code: Some(Box::new(|req| {
+            Box::pin(async move {
+                show(
+                    req.device,
+                    PairingRequest::DisplayPinCode(req.pincode),
+                    req.cancel,
+                );
+                Ok(())
+            })
+        })),
+        request_passkey: Some(Box::new(|req| {
+            Box::pin(async move {
+                match ask(req.device, PairingRequest::RequestPasskey).await {
+                    PairingReply::Enter(passkey) => parse_passkey(&passkey),
+                    _ => Err(ReqError::Rejected),
+                }
+            })
+        })),
+        display_passkey: Some(Box::new(|req| {
+            Box::pin(async move {
+                show(
+                    req.device,
+                    PairingRequest::DisplayPasskey(req.passkey),
+                    req.cancel,
+                );
+                Ok(())
+            })
+        })),
+        request_confirmation: Some(Box::new(|req| {
+            Box::pin(async move {
+                match ask(req.device, PairingRequest::ConfirmPasskey(req.passkey)).await {
+                    PairingReply::Confirm => Ok(()),
+                    _ => Err(ReqError::Rejected),
+                }
+            })
+        })),
+        ..Default::default()
+    }
+}
+
+/// Passkeys are six digits at most.
+fn parse_passkey(passkey: &str) -> ReqResult<u32> {
+    passkey
+        .trim()
+        .parse()
+        .ok()
+        .filter(|&passkey| passkey <= 999_999)
+        .ok_or(ReqError::Rejected)
+}
+
+/// Keeps `adapter` discovering new devices until `stop` is sent or dropped.
+/// Found devices come through the service's adapter events like any other.
+pub async fn discover_devices(adapter: Adapter, mut stop: oneshot::Receiver<()>) {
+    let discovery = match adapter.discover_devices().await {
+        Ok(discovery) => discovery,
+        Err(e) => {
+            log::error!("couldn't start bluetooth discovery: {e}");
+            return;
+        }
+    };
+    let mut discovery = std::pin::pin!(discovery);
+
+    // discovery stops when the stream is dropped
+    loop {
+        tokio::select! {
+            _ = &mut stop => break,
+            event = discovery.next() => {
+                if event.is_none() {
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -1,24 +1,47 @@
 use bluer::{Address, Device};
 use gtk4::prelude::*;
 use relm4::prelude::*;
+use tokio::sync::oneshot;
 
 use crate::{
-    bluetooth::{BLUETOOTH_STATE, BluetoothState},
+    bluetooth::{
+        self, BLUETOOTH_STATE, BluetoothState, PAIRING_PROMPT, PairingPrompt, PairingReply,
+        PairingRequest,
+    },
     icon_names::{BLUETOOTH, BLUETOOTH_DOTS, BLUETOOTH_NO, BLUETOOTH_X},
+    pulseaudio::{self, BLUETOOTH_CARDS, BluetoothCard},
 };
 
 #[derive(Debug)]
 pub struct BluetoothMenu {
     bluetooth_state: Option<BluetoothState>,
+    /// Paired devices.
     devices: AsyncFactoryVecDeque<BluetoothDeviceWidget>,
+    /// Devices found nearby that haven't been paired, listed while scanning.
+    new_devices: AsyncFactoryVecDeque<BluetoothDeviceWidget>,
+    /// Stops scanning for new devices when sent or dropped.
+    scan: Option<oneshot::Sender<()>>,
+    pairing_prompt: Option<PairingPrompt>,
+    /// Counts state updates, so device lists sorted for older ones are dropped
+    /// if they finish late.
+    state_count: u32,
 }
 
 #[derive(Debug)]
 pub enum BluetoothMenuMsg {
     ToggleBluetooth(bool),
+    ToggleScan(bool),
     ConnectToDevice(Address),
     DisconnectFromDevice(Address),
+    PairWithDevice(Address),
     UpdateState(Option<BluetoothState>),
+    /// The adapter's devices, and whether each is paired, as of the state
+    /// update with this count.
+    SetDevices(u32, Vec<(Device, bool)>),
+    UpdatePairingPrompt(Option<PairingPrompt>),
+    AnswerPairingPrompt(PairingReply),
+    /// The menu was closed, so scanning stops.
+    Closed,
 }
 
 #[derive(Debug)]
@@ -26,6 +49,13 @@ pub struct BluetoothMenuWidgets {
     icon: gtk::Image,
     toggle_switch: gtk::Switch,
     status_label: gtk::Label,
+    scan_button: gtk::ToggleButton,
+    new_devices_label: gtk::Label,
+    pairing_dialog_box: gtk::Box,
+    pairing_dialog_label: gtk::Label,
+    pairing_entry: gtk::Entry,
+    pairing_cancel_button: gtk::Button,
+    pairing_confirm_button: gtk::Button,
 }
 
 impl SimpleComponent for BluetoothMenu {
@@ -40,25 +70,34 @@ impl SimpleComponent for BluetoothMenu {
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        // create devices factory
-        let devices = AsyncFactoryVecDeque::builder()
-            .launch(
-                gtk::Box::builder()
-                    .orientation(gtk::Orientation::Vertical)
-                    .spacing(8)
-                    .build(),
-            )
-            .forward(sender.input_sender(), |output| match output {
-                BluetoothDeviceOutput::Connect(addr) => BluetoothMenuMsg::ConnectToDevice(addr),
-                BluetoothDeviceOutput::Disconnect(addr) => {
-                    BluetoothMenuMsg::DisconnectFromDevice(addr)
-                }
-            });
+        // create devices factories
+        let device_list = || {
+            AsyncFactoryVecDeque::builder()
+                .launch(
+                    gtk::Box::builder()
+                        .orientation(gtk::Orientation::Vertical)
+                        .spacing(8)
+                        .build(),
+                )
+                .forward(sender.input_sender(), |output| match output {
+                    BluetoothDeviceOutput::Connect(addr) => BluetoothMenuMsg::ConnectToDevice(addr),
+                    BluetoothDeviceOutput::Disconnect(addr) => {
+                        BluetoothMenuMsg::DisconnectFromDevice(addr)
+                    }
+                    BluetoothDeviceOutput::Pair(addr) => BluetoothMenuMsg::PairWithDevice(addr),
+                })
+        };
 
         let model = BluetoothMenu {
             bluetooth_state: BLUETOOTH_STATE.read().clone(),
-            devices,
+            devices: device_list(),
+            new_devices: device_list(),
+            scan: None,
+            pairing_prompt: PAIRING_PROMPT.read().clone(),
+            state_count: 0,
         };
+        // fills in the device lists
+        sender.input(BluetoothMenuMsg::UpdateState(model.bluetooth_state.clone()));
 
         // create header box
         let header_box = gtk::Box::builder()
@@ -122,9 +161,78 @@ impl SimpleComponent for BluetoothMenu {
             .label(get_status_text(&model.bluetooth_state))
             .build();
 
+        let scan_button = gtk::ToggleButton::builder()
+            .label("Scan for new devices")
+            .halign(gtk::Align::Start)
+            .css_classes(["flat"])
+            .build();
+        scan_button.connect_toggled({
+            let sender = sender.clone();
+            move |button| sender.input(BluetoothMenuMsg::ToggleScan(button.is_active()))
+        });
+
+        // asks to confirm or enter a code while pairing
+        let pairing_dialog_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(8)
+            .visible(false)
+            .build();
+
+        let pairing_dialog_label = gtk::Label::builder().wrap(true).build();
+
+        let pairing_entry = gtk::Entry::builder().visible(false).build();
+        pairing_entry.connect_activate({
+            let sender = sender.clone();
+            move |entry| {
+                sender.input(BluetoothMenuMsg::AnswerPairingPrompt(PairingReply::Enter(
+                    entry.text().to_string(),
+                )))
+            }
+        });
+
+        let dialog_buttons_box = gtk::Box::builder().spacing(8).build();
+
+        let pairing_cancel_button = gtk::Button::builder().label("Cancel").build();
+        pairing_cancel_button.connect_clicked({
+            let sender = sender.clone();
+            move |_| sender.input(BluetoothMenuMsg::AnswerPairingPrompt(PairingReply::Cancel))
+        });
+
+        let pairing_confirm_button = gtk::Button::builder().label("Pair").build();
+        pairing_confirm_button.connect_clicked({
+            let sender = sender.clone();
+            let pairing_entry = pairing_entry.clone();
+            move |_| {
+                let reply = if pairing_entry.is_visible() {
+                    PairingReply::Enter(pairing_entry.text().to_string())
+                } else {
+                    PairingReply::Confirm
+                };
+                sender.input(BluetoothMenuMsg::AnswerPairingPrompt(reply));
+            }
+        });
+
+        dialog_buttons_box.append(&pairing_cancel_button);
+        dialog_buttons_box.append(&pairing_confirm_button);
+
+        pairing_dialog_box.append(&pairing_dialog_label);
+        pairing_dialog_box.append(&pairing_entry);
+        pairing_dialog_box.append(&dialog_buttons_box);
+
+        let new_devices_label = gtk::Label::builder()
+            .label("Other devices")
+            .halign(gtk::Align::Start)
+            .css_classes(["section-title"])
+            .visible(false)
+            .build();
+
         status_box.append(&status_label);
+        status_box.append(&scan_button);
         content_box.append(&status_box);
+        content_box.append(&pairing_dialog_box);
         content_box.append(model.devices.widget());
+        content_box.append(&new_devices_label);
+        content_box.append(model.new_devices.widget());
         scrolled_window.set_child(Some(&content_box));
         root.append(&scrolled_window);
 
@@ -132,11 +240,21 @@ impl SimpleComponent for BluetoothMenu {
         BLUETOOTH_STATE.subscribe(sender.input_sender(), |state| {
             BluetoothMenuMsg::UpdateState(state.to_owned())
         });
+        PAIRING_PROMPT.subscribe(sender.input_sender(), |prompt| {
+            BluetoothMenuMsg::UpdatePairingPrompt(prompt.clone())
+        });
 
         let widgets = BluetoothMenuWidgets {
             icon,
             toggle_switch,
             status_label,
+            scan_button,
+            new_devices_label,
+            pairing_dialog_box,
+            pairing_dialog_label,
+            pairing_entry,
+            pairing_cancel_button,
+            pairing_confirm_button,
         };
 
         ComponentParts { model, widgets }
@@ -145,18 +263,66 @@ impl SimpleComponent for BluetoothMenu {
     fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         match msg {
             BluetoothMenuMsg::UpdateState(state) => {
-                self.bluetooth_state = state.clone();
+                // stop scanning if the adapter goes away or is turned off
+                if !state.as_ref().is_some_and(|s| s.powered) {
+                    self.scan = None;
+                }
+
+                // sort devices into paired and not, which takes asking each
+                let devices: Vec<Device> = state
+                    .as_ref()
+                    .map(|s| s.devices().cloned().collect())
+                    .unwrap_or_default();
+                self.state_count += 1;
+                let count = self.state_count;
+                let input = sender.input_sender().clone();
+                sender.oneshot_command(async move {
+                    let mut sorted = Vec::with_capacity(devices.len());
+                    for device in devices {
+                        let paired = device.is_paired().await.unwrap_or(false);
+                        sorted.push((device, paired));
+                    }
+                    input.emit(BluetoothMenuMsg::SetDevices(count, sorted));
+                });
+
+                self.bluetooth_state = state;
+            }
+            BluetoothMenuMsg::SetDevices(count, devices) => {
+                // a newer state's devices are on their way
+                if count != self.state_count {
+                    return;
+                }
 
-                // update device list
                 let mut devices_guard = self.devices.guard();
+                let mut new_devices_guard = self.new_devices.guard();
                 devices_guard.clear();
+                new_devices_guard.clear();
 
-                if let Some(ref state) = state {
-                    for device in state.devices() {
-                        devices_guard.push_back(device.clone());
+                for (device, paired) in devices {
+                    if paired {
+                        devices_guard.push_back((device, true));
+                    } else {
+                        new_devices_guard.push_back((device, false));
                     }
                 }
             }
+            BluetoothMenuMsg::ToggleScan(scan) => {
+                if scan == self.scan.is_some() {
+                    return;
+                }
+
+                if scan && let Some(state) = &self.bluetooth_state {
+                    let (stop_tx, stop_rx) = oneshot::channel();
+                    self.scan = Some(stop_tx);
+                    let adapter = state.adapter.clone();
+                    sender.oneshot_command(bluetooth::discover_devices(adapter, stop_rx));
+                } else {
+                    self.scan = None;
+                }
+            }
+            BluetoothMenuMsg::Closed => {
+                self.scan = None;
+            }
             BluetoothMenuMsg::ToggleBluetooth(enabled) => {
                 let state_clone = self.bluetooth_state.clone();
                 sender.oneshot_command(async move {
@@ -189,6 +355,39 @@ impl SimpleComponent for BluetoothMenu {
                     }
                 });
             }
+            BluetoothMenuMsg::PairWithDevice(addr) => {
+                let state_clone = self.bluetooth_state.clone();
+                sender.oneshot_command(async move {
+                    let Some(device) = state_clone
+                        .as_ref()
+                        .and_then(|state| state.get_device(&addr))
+                    else {
+                        return;
+                    };
+
+                    let result = async {
+                        device.pair().await?;
+                        device.set_trusted(true).await?;
+                        device.connect().await
+                    }
+                    .await;
+
+                    // a code may still be showing
+                    bluetooth::clear_pairing_prompt();
+
+                    if let Err(e) = result {
+                        log::error!("failed to pair with device {}: {}", addr, e);
+                    }
+                });
+            }
+            BluetoothMenuMsg::UpdatePairingPrompt(prompt) => {
+                self.pairing_prompt = prompt;
+            }
+            BluetoothMenuMsg::AnswerPairingPrompt(reply) => {
+                if let Some(prompt) = self.pairing_prompt.take() {
+                    prompt.answer(reply);
+                }
+            }
         }
     }
 
@@ -206,6 +405,40 @@ impl SimpleComponent for BluetoothMenu {
         widgets
             .status_label
             .set_label(&get_status_text(&self.bluetooth_state));
+
+        widgets.scan_button.set_sensitive(is_powered);
+        widgets.scan_button.set_active(self.scan.is_some());
+        widgets.new_devices_label.set_visible(self.scan.is_some());
+        self.new_devices.widget().set_visible(self.scan.is_some());
+
+        // update pairing dialog
+        widgets
+            .pairing_dialog_box
+            .set_visible(self.pairing_prompt.is_some());
+        if let Some(prompt) = &self.pairing_prompt {
+            widgets
+                .pairing_dialog_label
+                .set_label(&get_pairing_text(&prompt.request));
+
+            let wants_entry = matches!(
+                prompt.request,
+                PairingRequest::RequestPinCode | PairingRequest::RequestPasskey
+            );
+            if wants_entry && !widgets.pairing_entry.is_visible() {
+                widgets.pairing_entry.set_text("");
+            }
+            widgets.pairing_entry.set_visible(wants_entry);
+            widgets
+                .pairing_confirm_button
+                .set_visible(prompt.is_question());
+            widgets
+                .pairing_cancel_button
+                .set_label(if prompt.is_question() {
+                    "Cancel"
+                } else {
+                    "Hide"
+                });
+        }
     }
 
     fn init_root() -> Self::Root {
@@ -229,6 +462,20 @@ fn get_icon(state: &Option<BluetoothState>) -> &str {
     }
 }
 
+fn get_pairing_text(request: &PairingRequest) -> String {
+    match request {
+        PairingRequest::DisplayPasskey(passkey) => {
+            format!("Type {passkey:06} on the device, then press Enter")
+        }
+        PairingRequest::DisplayPinCode(pin) => format!("Type {pin} on the device"),
+        PairingRequest::ConfirmPasskey(passkey) => {
+            format!("Does the device show {passkey:06}?")
+        }
+        PairingRequest::RequestPinCode => "Enter the device's PIN code".to_string(),
+        PairingRequest::RequestPasskey => "Enter the passkey shown on the device".to_string(),
+    }
+}
+
 fn get_status_text(state: &Option<BluetoothState>) -> String {
     match state {
         Some(s) if !s.powered => "Bluetooth disabled".to_string(),
@@ -248,33 +495,51 @@ struct BluetoothDeviceWidget {
     device: Device,
     name: Option<String>,
     is_connected: bool,
+    is_paired: bool,
+    /// From BlueZ's Battery1 interface, for devices that report it.
+    battery_percentage: Option<u8>,
+    /// The device's audio card, if it's an audio device.
+    card: Option<BluetoothCard>,
 }
 
 #[derive(Debug)]
 pub enum BluetoothDeviceMsg {
     Toggle,
-    UpdateInfo(Option<String>, bool),
+    UpdateCard(Option<BluetoothCard>),
+    /// A profile was picked from the dropdown, by its position.
+    SelectProfile(u32),
+    UpdateInfo {
+        name: Option<String>,
+        is_connected: bool,
+        battery_percentage: Option<u8>,
+    },
 }
 
 #[derive(Debug)]
 pub enum BluetoothDeviceOutput {
     Connect(Address),
     Disconnect(Address),
+    Pair(Address),
 }
 
 pub struct BluetoothDeviceWidgetWidgets {
     _main_box: gtk::Box,
     device_label: gtk::Label,
     status_label: gtk::Label,
+    profile_dropdown: gtk::DropDown,
+    profile_handler: glib::SignalHandlerId,
+    /// Names of the profiles listed in the dropdown, in order.
+    profile_names: Vec<String>,
 }
 
 impl AsyncFactoryComponent for BluetoothDeviceWidget {
     type CommandOutput = BluetoothDeviceMsg;
-    type Init = Device;
+    /// The device and whether it's paired.
+    type Init = (Device, bool);
     type Input = BluetoothDeviceMsg;
     type Output = BluetoothDeviceOutput;
     type ParentWidget = gtk::Box;
-    type Root = gtk::Button;
+    type Root = gtk::Box;
     type Widgets = BluetoothDeviceWidgetWidgets;
 
     async fn init_model(
@@ -282,19 +547,35 @@ impl AsyncFactoryComponent for BluetoothDeviceWidget {
         _index: &DynamicIndex,
         sender: AsyncFactorySender<Self>,
     ) -> Self {
-        let device = init;
+        let (device, is_paired) = init;
 
         // fetch device info
         let name = device.name().await.ok().flatten();
         let is_connected = device.is_connected().await.unwrap_or(false);
+        // the whole list is rebuilt whenever a device's properties change, so
+        // this is fetched again then
+        let battery_percentage = device.battery_percentage().await.ok().flatten();
+
+        let address = device.address().to_string();
+        let card = find_card(&BLUETOOTH_CARDS.read(), &address);
+        BLUETOOTH_CARDS.subscribe(sender.input_sender(), move |cards| {
+            BluetoothDeviceMsg::UpdateCard(find_card(cards, &address))
+        });
 
         // send update message to self
-        sender.input(BluetoothDeviceMsg::UpdateInfo(name.clone(), is_connected));
+        sender.input(BluetoothDeviceMsg::UpdateInfo {
+            name,
+            is_connected,
+            battery_percentage,
+        });
 
         Self {
             device,
             name: None,
             is_connected: false,
+            is_paired,
+            battery_percentage: None,
+            card,
         }
     }
 
@@ -302,21 +583,42 @@ impl AsyncFactoryComponent for BluetoothDeviceWidget {
         match msg {
             BluetoothDeviceMsg::Toggle => {
                 let addr = self.device.address();
-                if self.is_connected {
+                if !self.is_paired {
+                    let _ = sender.output(BluetoothDeviceOutput::Pair(addr));
+                } else if self.is_connected {
                     let _ = sender.output(BluetoothDeviceOutput::Disconnect(addr));
                 } else {
                     let _ = sender.output(BluetoothDeviceOutput::Connect(addr));
                 }
             }
-            BluetoothDeviceMsg::UpdateInfo(name, is_connected) => {
+            BluetoothDeviceMsg::UpdateInfo {
+                name,
+                is_connected,
+                battery_percentage,
+            } => {
                 self.name = name;
                 self.is_connected = is_connected;
+                self.battery_percentage = battery_percentage;
+            }
+            BluetoothDeviceMsg::UpdateCard(card) => {
+                self.card = card;
+            }
+            BluetoothDeviceMsg::SelectProfile(position) => {
+                if let Some(card) = &self.card
+                    && let Some(profile) = card.profiles.get(position as usize)
+                    && card.active_profile.as_ref() != Some(&profile.name)
+                {
+                    pulseaudio::set_card_profile(card.index, profile.name.clone());
+                }
             }
         }
     }
 
     fn init_root() -> Self::Root {
-        gtk::Button::builder().build()
+        gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(4)
+            .build()
     }
 
     fn init_widgets(
@@ -326,7 +628,11 @@ impl AsyncFactoryComponent for BluetoothDeviceWidget {
         _returned_widget: &<Self::ParentWidget as relm4::factory::FactoryView>::ReturnedWidget,
         sender: AsyncFactorySender<Self>,
     ) -> Self::Widgets {
-        root.connect_clicked(move |_| sender.input(BluetoothDeviceMsg::Toggle));
+        let button = gtk::Button::new();
+        button.connect_clicked({
+            let sender = sender.clone();
+            move |_| sender.input(BluetoothDeviceMsg::Toggle)
+        });
 
         let main_box = gtk::Box::builder()
             .orientation(gtk::Orientation::Horizontal)
@@ -343,19 +649,33 @@ impl AsyncFactoryComponent for BluetoothDeviceWidget {
         let status_label = gtk::Label::builder()
             .css_classes(["dim"])
             .halign(gtk::Align::End)
-            .label("Connected")
-            .visible(self.is_connected)
+            .visible(false)
             .build();
 
         main_box.append(&device_label);
         main_box.append(&status_label);
 
-        root.set_child(Some(&main_box));
+        button.set_child(Some(&main_box));
+
+        // for audio devices with more than one profile, like A2DP and HFP
+        let profile_dropdown = gtk::DropDown::builder()
+            .tooltip_text("Audio profile")
+            .visible(false)
+            .build();
+        let profile_handler = profile_dropdown.connect_selected_notify(move |dropdown| {
+            sender.input(BluetoothDeviceMsg::SelectProfile(dropdown.selected()))
+        });
+
+        root.append(&button);
+        root.append(&profile_dropdown);
 
         BluetoothDeviceWidgetWidgets {
             _main_box: main_box,
             device_label,
             status_label,
+            profile_dropdown,
+            profile_handler,
+            profile_names: Vec::new(),
         }
     }
 
@@ -367,6 +687,62 @@ impl AsyncFactoryComponent for BluetoothDeviceWidget {
             .unwrap_or(self.device.address().to_string());
 
         widgets.device_label.set_label(&device_name);
-        widgets.status_label.set_visible(self.is_connected);
+
+        let status = get_device_status(self.is_connected, self.battery_percentage);
+        widgets
+            .status_label
+            .set_label(status.as_deref().unwrap_or_default());
+        widgets.status_label.set_visible(status.is_some());
+
+        let profiles = self
+            .card
+            .as_ref()
+            .map(|card| card.profiles.as_slice())
+            .unwrap_or_default();
+        widgets.profile_dropdown.set_visible(profiles.len() > 1);
+
+        // the selection follows the card, so a profile that fails to apply
+        // snaps back
+        widgets
+            .profile_dropdown
+            .block_signal(&widgets.profile_handler);
+        let names: Vec<String> = profiles.iter().map(|p| p.name.clone()).collect();
+        if names != widgets.profile_names {
+            let descriptions: Vec<&str> = profiles.iter().map(|p| p.description.as_str()).collect();
+            widgets
+                .profile_dropdown
+                .set_model(Some(&gtk::StringList::new(&descriptions)));
+            widgets.profile_names = names;
+        }
+        let active = self
+            .card
+            .as_ref()
+            .and_then(|card| card.active_profile.as_ref())
+            .and_then(|active| widgets.profile_names.iter().position(|name| name == active));
+        widgets
+            .profile_dropdown
+            .set_selected(active.map_or(gtk::INVALID_LIST_POSITION, |position| position as u32));
+        widgets
+            .profile_dropdown
+            .unblock_signal(&widgets.profile_handler);
+    }
+}
+
+/// Finds the audio card for the device with `address`, if it has one.
+fn find_card(cards: &[BluetoothCard], address: &str) -> Option<BluetoothCard> {
+    cards
+        .iter()
+        .find(|card| card.address.eq_ignore_ascii_case(address))
+        .cloned()
+}
+
+/// Describes a device's connection and battery, e.g. "Connected · 72%", or
+/// `None` if there's nothing to show.
+fn get_device_status(is_connected: bool, battery_percentage: Option<u8>) -> Option<String> {
+    match (is_connected, battery_percentage) {
+        (true, Some(percentage)) => Some(format!("Connected · {percentage}%")),
+        (true, None) => Some("Connected".to_string()),
+        (false, Some(percentage)) => Some(format!("{percentage}%")),
+        (false, None) => None,
     }
 }
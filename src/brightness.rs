@@ -1,11 +1,22 @@
-use std::{fs, path::Path};
+mod schedule;
+
+use std::{fs, path::Path, sync::OnceLock, time::Duration};
 
 use anyhow::{Context, Result};
 use inotify::{Inotify, WatchMask};
 use relm4::SharedState;
+pub use schedule::run_brightness_schedule;
+use tokio::sync::watch;
+use zbus::proxy;
+
+use crate::settings;
 
 pub static BRIGHTNESS_STATE: SharedState<Option<f64>> = SharedState::new();
 
+/// The level for [`run_brightness_control`] to write next. Only the latest is
+/// kept, so levels set faster than the backlight takes them are coalesced.
+static TARGET_TX: OnceLock<watch::Sender<Option<f64>>> = OnceLock::new();
+
 pub async fn start_brightness_watcher() {
     // read initial backlight properties. if any fail, we will not consider the
     // service available.
@@ -56,7 +67,123 @@ pub async fn start_brightness_watcher() {
     }
 }
 
-fn detect_interface() -> Result<String> {
+#[proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/session/auto"
+)]
+trait Login1Session {
+    /// Sets a backlight's raw brightness without needing root.
+    fn set_brightness(&self, subsystem: &str, name: &str, brightness: u32) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn active(&self) -> zbus::Result<bool>;
+}
+
+/// How many writes a brightness ramp is split into.
+const RAMP_STEPS: u32 = 20;
+
+/// Moves the backlight to `target` (from 0 to 1) over `duration` in small
+/// steps, so scheduled changes fade rather than snap.
+pub async fn ramp_brightness(target: f64, duration: Duration) -> Result<()> {
+    let (interface, max_val, start) = read_all()?;
+    let conn = zbus::Connection::system().await?;
+    let session = Login1SessionProxy::new(&conn).await?;
+
+    let target = target.clamp(0.0, 1.0);
+    for step in 1..=RAMP_STEPS {
+        let percentage = start + (target - start) * step as f64 / RAMP_STEPS as f64;
+        let raw = (percentage * max_val as f64).round() as u32;
+        session
+            .set_brightness("backlight", &interface, raw)
+            .await
+            .context("couldn't set brightness through logind")?;
+        tokio::time::sleep(duration / RAMP_STEPS).await;
+    }
+
+    Ok(())
+}
+
+/// Sets the backlight to `level` (from 0 to 1), but no lower than the
+/// configured minimum so the screen can't be turned fully black.
+pub fn set_brightness(level: f64) {
+    let min_level = settings::get_config().brightness.min_level;
+    match TARGET_TX.get() {
+        Some(tx) => {
+            tx.send_replace(Some(level.clamp(min_level, 1.0)));
+        }
+        None => log::warn!("brightness control isn't running"),
+    }
+}
+
+/// Writes levels given to [`set_brightness`] to the backlight, through logind
+/// if it allows it and sysfs otherwise.
+pub async fn run_brightness_control() {
+    let (tx, mut rx) = watch::channel(None);
+    if TARGET_TX.set(tx).is_err() {
+        log::warn!("brightness control is already running");
+        return;
+    }
+
+    let Ok(interface) = detect_interface() else {
+        return;
+    };
+    let max_val = match read_max_brightness(&interface) {
+        Ok(max_val) => max_val,
+        Err(e) => {
+            log::error!("couldn't read max brightness value: {e}");
+            return;
+        }
+    };
+
+    let session = async {
+        let conn = zbus::Connection::system().await?;
+        Login1SessionProxy::new(&conn).await
+    }
+    .await
+    .inspect_err(|e| log::warn!("couldn't reach logind for brightness: {e}"))
+    .ok();
+
+    while rx.changed().await.is_ok() {
+        let Some(level) = *rx.borrow_and_update() else {
+            continue;
+        };
+
+        let raw = (level * max_val as f64).round() as u32;
+        if let Err(e) = write_brightness(session.as_ref(), &interface, raw).await {
+            log::error!("couldn't set brightness: {e}");
+        }
+    }
+}
+
+async fn write_brightness(
+    session: Option<&Login1SessionProxy<'_>>,
+    interface: &str,
+    raw: u32,
+) -> Result<()> {
+    if let Some(session) = session {
+        match session.set_brightness("backlight", interface, raw).await {
+            Ok(()) => return Ok(()),
+            Err(e) => log::debug!("logind wouldn't set brightness, trying sysfs: {e}"),
+        }
+    }
+
+    let path = format!("/sys/class/backlight/{}/brightness", interface);
+    tokio::fs::write(&path, raw.to_string())
+        .await
+        .with_context(|| format!("couldn't write to {path}"))
+}
+
+/// Whether brightness can be set through logind, which only allows it for
+/// the active session.
+pub async fn logind_session_active() -> Result<bool> {
+    let conn = zbus::Connection::system().await?;
+    let session = Login1SessionProxy::new(&conn).await?;
+    Ok(session.active().await?)
+}
+
+/// The name of the first backlight under `/sys/class/backlight`.
+pub fn detect_interface() -> Result<String> {
     let backlight_path = Path::new("/sys/class/backlight");
     let mut entries = fs::read_dir(backlight_path)?;
 
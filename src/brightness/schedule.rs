@@ -0,0 +1,248 @@
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::{
+    brightness::{BRIGHTNESS_STATE, ramp_brightness},
+    idle::{self, IdleEvent},
+    settings::{self, CONFIG, DayNightConfig, IdleDimConfig},
+};
+
+/// How long scheduled brightness changes take to fade in.
+const RAMP_DURATION: Duration = Duration::from_millis(800);
+
+/// How often to check whether the sun has risen or set.
+const SUN_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Dims the backlight while the user is idle and switches between day and
+/// night brightness at sunrise and sunset, as configured under `brightness`.
+///
+/// The day/night level is only applied when the sun rises or sets, at startup,
+/// and when its settings change, so a manual brightness change holds until the
+/// next transition.
+pub async fn run_brightness_schedule() {
+    // watch the settings, so edits apply without waiting for a transition
+    let (config_tx, config_rx) = relm4::channel();
+    CONFIG.subscribe(&config_tx, |config| {
+        (
+            config.brightness.idle_dim.clone(),
+            config.brightness.day_night.clone(),
+        )
+    });
+
+    let mut config = settings::get_config().brightness;
+    let mut idle_rx = watch_idle(config.idle_dim.as_ref());
+
+    let mut sun_check = tokio::time::interval(SUN_CHECK_INTERVAL);
+    let mut is_day = None;
+    // the level to go back to once the user is active again
+    let mut restore_to = None;
+
+    loop {
+        tokio::select! {
+            Some((idle_dim, day_night)) = config_rx.recv() => {
+                if idle_dim != config.idle_dim {
+                    log::debug!("idle dimming settings changed");
+                    // the old watcher won't say when the user is back
+                    if let Some(level) = restore_to.take() {
+                        ramp(level).await;
+                    }
+                    idle_rx = watch_idle(idle_dim.as_ref());
+                    config.idle_dim = idle_dim;
+                }
+                if day_night != config.day_night {
+                    log::debug!("day/night brightness settings changed");
+                    // applied at the next check, which is made right away
+                    is_day = None;
+                    sun_check.reset_immediately();
+                    config.day_night = day_night;
+                }
+            }
+            Some(event) = next_idle_event(&mut idle_rx) => {
+                let Some(idle_dim) = &config.idle_dim else {
+                    continue;
+                };
+
+                match event {
+                    IdleEvent::Idle => {
+                        let current = BRIGHTNESS_STATE.read().unwrap_or_default();
+                        if current > idle_dim.level {
+                            restore_to = Some(current);
+                            ramp(idle_dim.level).await;
+                        }
+                    }
+                    IdleEvent::Resumed => {
+                        if let Some(level) = restore_to.take() {
+                            ramp(level).await;
+                        }
+                    }
+                }
+            }
+            _ = sun_check.tick() => {
+                let Some(day_night) = &config.day_night else {
+                    continue;
+                };
+
+                let now_day = is_daytime(Utc::now(), day_night.latitude, day_night.longitude);
+                if is_day == Some(now_day) {
+                    continue;
+                }
+                is_day = Some(now_day);
+
+                let level = day_night_level(day_night, now_day);
+                let period = if now_day { "day" } else { "night" };
+                log::info!("it's {period}; setting brightness to {level}");
+                if restore_to.is_some() {
+                    // dimmed for idle; apply the new level on resume instead
+                    restore_to = Some(level);
+                } else {
+                    ramp(level).await;
+                }
+            }
+        }
+    }
+}
+
+/// Starts watching for idle if idle dimming is on.
+fn watch_idle(idle_dim: Option<&IdleDimConfig>) -> Option<UnboundedReceiver<IdleEvent>> {
+    idle::watch(Duration::from_secs(idle_dim?.timeout))
+        .inspect_err(|e| log::error!("couldn't watch for idle, so idle dimming is off: {e}"))
+        .ok()
+}
+
+async fn next_idle_event(rx: &mut Option<UnboundedReceiver<IdleEvent>>) -> Option<IdleEvent> {
+    let Some(receiver) = rx else {
+        return std::future::pending().await;
+    };
+
+    let event = receiver.recv().await;
+    if event.is_none() {
+        // the watcher is gone; stop polling it
+        *rx = None;
+    }
+    event
+}
+
+async fn ramp(level: f64) {
+    if let Err(e) = ramp_brightness(level, RAMP_DURATION).await {
+        log::error!("couldn't change brightness: {e}");
+    }
+}
+
+fn day_night_level(config: &DayNightConfig, is_day: bool) -> f64 {
+    if is_day {
+        config.day_level
+    } else {
+        config.night_level
+    }
+}
+
+/// Whether the sun is up at `now` at the given coordinates (in degrees, north
+/// and east positive).
+///
+/// Neighbouring days are checked too, since a day's sunset can fall on the
+/// next UTC date far from Greenwich.
+fn is_daytime(now: DateTime<Utc>, latitude: f64, longitude: f64) -> bool {
+    let today = now.date_naive();
+    [
+        today - TimeDelta::days(1),
+        today,
+        today + TimeDelta::days(1),
+    ]
+    .into_iter()
+    .any(|date| match sun_times(date, latitude, longitude) {
+        SunTimes::RiseAndSet(rise, set) => rise <= now && now < set,
+        SunTimes::AlwaysUp => date == today,
+        SunTimes::AlwaysDown => false,
+    })
+}
+
+#[derive(Debug, PartialEq)]
+enum SunTimes {
+    RiseAndSet(DateTime<Utc>, DateTime<Utc>),
+    /// Midnight sun.
+    AlwaysUp,
+    /// Polar night.
+    AlwaysDown,
+}
+
+/// Sunrise and sunset for a date, using the sunrise equation. Accurate to a
+/// minute or two, which is plenty for switching brightness.
+fn sun_times(date: NaiveDate, latitude: f64, longitude: f64) -> SunTimes {
+    const J2000: f64 = 2451545.0;
+    let epoch = NaiveDate::from_ymd_opt(2000, 1, 1).expect("valid date");
+
+    // mean solar time and anomaly
+    let day = (date - epoch).num_days() as f64;
+    let mean_solar_time = day - longitude / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * mean_solar_time).rem_euclid(360.0);
+    let anomaly_rad = anomaly.to_radians();
+
+    // ecliptic longitude, from the equation of the center
+    let center = 1.9148 * anomaly_rad.sin()
+        + 0.02 * (2.0 * anomaly_rad).sin()
+        + 0.0003 * (3.0 * anomaly_rad).sin();
+    let ecliptic_longitude = (anomaly + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+
+    let transit = J2000 + mean_solar_time + 0.0053 * anomaly_rad.sin()
+        - 0.0069 * (2.0 * ecliptic_longitude).sin();
+
+    let declination = (ecliptic_longitude.sin() * 23.4397_f64.to_radians().sin()).asin();
+    let latitude = latitude.to_radians();
+    let cos_hour_angle = ((-0.833_f64).to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+
+    if cos_hour_angle < -1.0 {
+        return SunTimes::AlwaysUp;
+    }
+    if cos_hour_angle > 1.0 {
+        return SunTimes::AlwaysDown;
+    }
+
+    let half_day = cos_hour_angle.acos().to_degrees() / 360.0;
+    let to_utc = |julian_date: f64| {
+        let unix_seconds = (julian_date - 2440587.5) * 86400.0;
+        DateTime::from_timestamp(unix_seconds as i64, 0).unwrap_or_default()
+    };
+
+    SunTimes::RiseAndSet(to_utc(transit - half_day), to_utc(transit + half_day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_sun_times_near_greenwich() {
+        // London on the summer solstice: sunrise 03:43, sunset 20:21 UTC
+        let SunTimes::RiseAndSet(rise, set) =
+            sun_times(NaiveDate::from_ymd_opt(2024, 6, 21).unwrap(), 51.5, -0.13)
+        else {
+            panic!("the sun should rise and set in London");
+        };
+
+        assert!((rise - utc("2024-06-21T03:43:00Z")).num_minutes().abs() <= 3);
+        assert!((set - utc("2024-06-21T20:21:00Z")).num_minutes().abs() <= 3);
+    }
+
+    #[test]
+    fn test_is_daytime() {
+        // Denver, where the evening spills over into the next UTC day
+        let (lat, lon) = (39.74, -104.99);
+        assert!(is_daytime(utc("2024-06-21T18:00:00Z"), lat, lon));
+        assert!(is_daytime(utc("2024-06-22T01:00:00Z"), lat, lon));
+        assert!(!is_daytime(utc("2024-06-22T04:00:00Z"), lat, lon));
+        assert!(!is_daytime(utc("2024-06-22T10:00:00Z"), lat, lon));
+
+        // Svalbard has midnight sun in June and polar night in December
+        assert!(is_daytime(utc("2024-06-21T00:00:00Z"), 78.2, 15.6));
+        assert!(!is_daytime(utc("2024-12-21T12:00:00Z"), 78.2, 15.6));
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    rc::Rc,
+};
+
+use chrono::{Datelike, Local, Months, NaiveDate, NaiveDateTime};
+use gtk4::prelude::*;
+
+use crate::settings;
+
+/// Event summaries keyed by the day they start on.
+pub type Events = BTreeMap<NaiveDate, Vec<String>>;
+
+/// Builds the clock's calendar popover. It goes back to today whenever it's
+/// opened and, if `calendar.events_dir` is set, marks days that have events in
+/// the `.ics` files there. The selected day's events are shown as the
+/// calendar's tooltip.
+///
+/// The files are read in the background each time the popover opens, so new
+/// events show up without a restart. Until they're read, the events found the
+/// last time are shown.
+pub fn calendar_popover() -> gtk::Popover {
+    let calendar = gtk::Calendar::new();
+    let popover = gtk::Popover::builder()
+        .css_classes(["calendar-popover"])
+        .child(&calendar)
+        .build();
+    let events: Rc<RefCell<Events>> = Rc::default();
+    // counts loads, so one that finishes after a newer one is dropped
+    let load_count: Rc<Cell<u32>> = Rc::default();
+
+    {
+        let calendar = calendar.clone();
+        let events = Rc::clone(&events);
+        popover.connect_show(move |_| {
+            if let Ok(today) = glib::DateTime::now_local() {
+                calendar.select_day(&today);
+            }
+            refresh(&calendar, &events.borrow());
+
+            let dir = settings::get_config().calendar.events_dir;
+            load_count.set(load_count.get() + 1);
+            let load = load_count.get();
+            let calendar = calendar.clone();
+            let events = Rc::clone(&events);
+            let load_count = Rc::clone(&load_count);
+            relm4::spawn_local(async move {
+                let loaded = match dir {
+                    Some(dir) => match relm4::spawn_blocking(move || load_events(&dir)).await {
+                        Ok(loaded) => loaded,
+                        Err(e) => {
+                            log::error!("couldn't load calendar events: {e}");
+                            return;
+                        }
+                    },
+                    None => Events::new(),
+                };
+                if load_count.get() == load {
+                    *events.borrow_mut() = loaded;
+                    refresh(&calendar, &events.borrow());
+                }
+            });
+        });
+    }
+
+    // marks belong to the month being shown, so they're redone on every move
+    let on_change = move |calendar: &gtk::Calendar| refresh(calendar, &events.borrow());
+    calendar.connect_day_selected(on_change.clone());
+    calendar.connect_next_month(on_change.clone());
+    calendar.connect_prev_month(on_change.clone());
+    calendar.connect_next_year(on_change.clone());
+    calendar.connect_prev_year(on_change);
+
+    popover
+}
+
+/// Marks the shown month's days that have events and lists the selected
+/// day's events in the tooltip.
+fn refresh(calendar: &gtk::Calendar, events: &Events) {
+    calendar.clear_marks();
+
+    let selected = calendar.date();
+    let Some(first) = NaiveDate::from_ymd_opt(selected.year(), selected.month() as u32, 1) else {
+        return;
+    };
+    for (date, _) in events.range(first..first + Months::new(1)) {
+        calendar.mark_day(date.day());
+    }
+
+    let summaries = first
+        .with_day(selected.day_of_month() as u32)
+        .and_then(|date| events.get(&date));
+    calendar.set_tooltip_text(summaries.map(|summaries| summaries.join("\n")).as_deref());
+}
+
+/// Reads every `.ics` file in `dir`. Files that can't be read or parsed are
+/// skipped with a warning.
+pub fn load_events(dir: &Path) -> Events {
+    let mut events = Events::new();
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("couldn't read calendar directory {}: {e}", dir.display());
+            return events;
+        }
+    };
+
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().is_none_or(|extension| extension != "ics") {
+            continue;
+        }
+
+        let parsed = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_ics(&text));
+        match parsed {
+            Ok(parsed) => {
+                for (date, summary) in parsed {
+                    events.entry(date).or_default().push(summary);
+                }
+            }
+            Err(e) => log::warn!("skipping calendar file {}: {e}", path.display()),
+        }
+    }
+
+    events
+}
+
+/// Reads the start day and summary of each event in an iCalendar file.
+/// Recurrence and end dates aren't followed, so an event is only found on the
+/// day it first starts. Events without a readable start date are skipped with a
+/// warning; only a file that isn't iCalendar at all is an error.
+pub fn parse_ics(text: &str) -> Result<Vec<(NaiveDate, String)>, String> {
+    // long lines are folded onto following lines that start with whitespace
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match line.strip_prefix([' ', '\t']) {
+            Some(rest) => match lines.last_mut() {
+                Some(last) => last.push_str(rest),
+                None => return Err("file starts with a continuation line".to_string()),
+            },
+            None => lines.push(line.to_string()),
+        }
+    }
+
+    if lines.first().map(String::as_str) != Some("BEGIN:VCALENDAR") {
+        return Err("not an iCalendar file".to_string());
+    }
+
+    let mut events = Vec::new();
+    let mut event: Option<(Result<NaiveDate, String>, Option<String>)> = None;
+    for line in &lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = name.split(';').next().unwrap_or_default();
+
+        match (name, &mut event) {
+            ("BEGIN", None) if value == "VEVENT" => {
+                event = Some((Err("no start date".to_string()), None));
+            }
+            ("END", Some((start, summary))) if value == "VEVENT" => {
+                let summary = summary.take().unwrap_or_else(|| "Event".to_string());
+                match start {
+                    Ok(start) => events.push((*start, summary)),
+                    Err(e) => log::warn!("skipping calendar event {summary:?}: {e}"),
+                }
+                event = None;
+            }
+            ("DTSTART", Some((start, _))) => {
+                *start = parse_date(value).ok_or_else(|| format!("bad start date {value:?}"));
+            }
+            ("SUMMARY", Some((_, summary))) => *summary = Some(unescape(value)),
+            _ => {}
+        }
+    }
+
+    Ok(events)
+}
+
+/// Reads the day from a `DATE` or `DATE-TIME` value. UTC times are moved to
+/// the local day; other times are taken as written.
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(time.and_utc().with_timezone(&Local).date_naive());
+    }
+
+    NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push(' '),
+            Some(escaped) => unescaped.push(escaped),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_parse_ics() {
+        let text = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;VALUE=DATE:20261016\r\n\
+            SUMMARY:Dentist\\, then lunch\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            SUMMARY:Standup with a very long title that has been folded onto\r\n  the next line\r\n\
+            DTSTART;TZID=Europe/Berlin:20261020T090000\r\n\
+            BEGIN:VALARM\r\n\
+            END:VALARM\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+
+        assert_eq!(
+            parse_ics(text).unwrap(),
+            [
+                (date(2026, 10, 16), "Dentist, then lunch".to_string()),
+                (
+                    date(2026, 10, 20),
+                    "Standup with a very long title that has been folded onto the next line"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_ics_rejects_broken_files() {
+        assert!(parse_ics("").is_err());
+        assert!(parse_ics("not a calendar").is_err());
+    }
+
+    #[test]
+    fn test_parse_ics_skips_broken_events() {
+        let text = "BEGIN:VCALENDAR\n\
+            BEGIN:VEVENT\nSUMMARY:No date\nEND:VEVENT\n\
+            BEGIN:VEVENT\nDTSTART:tomorrow\nEND:VEVENT\n\
+            BEGIN:VEVENT\nDTSTART:20261016\nSUMMARY:Dentist\nEND:VEVENT\n\
+            END:VCALENDAR\n";
+
+        assert_eq!(
+            parse_ics(text).unwrap(),
+            [(date(2026, 10, 16), "Dentist".to_string())]
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Timelike};
+use relm4::SharedState;
+
+use crate::settings::ClockConfig;
+
+/// The time as of the last whole second. Clocks update when it changes rather
+/// than each running their own timer.
+pub static NOW: SharedState<DateTime<Local>> = SharedState::new();
+
+/// Updates [`NOW`] as each second starts.
+pub async fn run_clock() {
+    loop {
+        let now = Local::now();
+        *NOW.write() = now;
+
+        // leap seconds count past a billion nanoseconds
+        let into_second = Duration::from_nanos((now.nanosecond() % 1_000_000_000).into());
+        tokio::time::sleep(Duration::from_secs(1) - into_second).await;
+    }
+}
+
+const TIME_12_HOUR: &str = "%-I:%M %P";
+const TIME_24_HOUR: &str = "%H:%M";
+const DATE: &str = "%A, %B %-d, %Y";
+const BAR_DATE: &str = "%a, %b %-d";
+
+/// The patterns the notification center's clock shows the time and date with,
+/// checked once when settings change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockFormat {
+    time: String,
+    date: String,
+}
+
+impl ClockFormat {
+    /// Takes the patterns from `config`, falling back to the default for
+    /// either one that GLib can't format with.
+    pub fn from_config(config: &ClockConfig) -> Self {
+        let default_time = if config.twenty_four_hour {
+            TIME_24_HOUR
+        } else {
+            TIME_12_HOUR
+        };
+
+        Self {
+            time: checked_pattern(config.time_format.as_deref(), default_time),
+            date: checked_pattern(config.date_format.as_deref(), DATE),
+        }
+    }
+
+    pub fn time(&self, time: &glib::DateTime) -> String {
+        time.format(&self.time)
+            .map(String::from)
+            .unwrap_or_default()
+    }
+
+    pub fn date(&self, time: &glib::DateTime) -> String {
+        time.format(&self.date)
+            .map(String::from)
+            .unwrap_or_default()
+    }
+}
+
+fn checked_pattern(pattern: Option<&str>, default: &str) -> String {
+    let Some(pattern) = pattern else {
+        return default.to_string();
+    };
+
+    let formats = glib::DateTime::now_local()
+        .and_then(|now| now.format(pattern))
+        .is_ok_and(|formatted| !formatted.is_empty());
+    if formats {
+        pattern.to_string()
+    } else {
+        log::warn!("can't format the time with '{pattern}'; using '{default}' instead");
+        default.to_string()
+    }
+}
+
+/// How the bar's clock shows the time, which only follows the 12 or 24-hour
+/// setting.
+pub fn bar_time_pattern(config: &ClockConfig) -> &'static str {
+    if config.twenty_four_hour {
+        TIME_24_HOUR
+    } else {
+        TIME_12_HOUR
+    }
+}
+
+/// The short date the bar shows, e.g. "Fri, Oct 16", with the day and month
+/// names in the user's language.
+pub fn bar_date(time: &DateTime<Local>) -> String {
+    format_local(time, BAR_DATE)
+}
+
+/// Formats `time` with GLib rather than chrono, so that names follow the
+/// locale.
+pub fn format_local(time: &DateTime<Local>, pattern: &str) -> String {
+    glib::DateTime::from_unix_local(time.timestamp())
+        .and_then(|time| time.format(pattern))
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns_fall_back_when_invalid() {
+        let config = ClockConfig {
+            twenty_four_hour: true,
+            time_format: Some("%Q".to_string()),
+            date_format: Some(String::new()),
+        };
+        let format = ClockFormat::from_config(&config);
+        assert_eq!(format.time, TIME_24_HOUR);
+        assert_eq!(format.date, DATE);
+
+        let config = ClockConfig {
+            twenty_four_hour: true,
+            time_format: Some("%H:%M:%S".to_string()),
+            date_format: None,
+        };
+        let format = ClockFormat::from_config(&config);
+        assert_eq!(format.time, "%H:%M:%S");
+        assert_eq!(format.date, DATE);
+    }
+}
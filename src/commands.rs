@@ -1,7 +1,55 @@
 // temporary until we figure things out
 #![allow(dead_code)]
 
-use std::collections::VecDeque;
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    sync::{Arc, OnceLock},
+};
+
+use tokio::sync::{Mutex, mpsc};
+
+use crate::{
+    brightness::{self, BRIGHTNESS_STATE},
+    compositor::CompositorService,
+    mpris::{self, MPRIS_STATE},
+    notifications,
+    pulseaudio::{self, VOLUME_STATE},
+    widgets::bar::BarMenu,
+};
+
+/// Sends requests to the app, once it's listening for them.
+static APP_TX: OnceLock<mpsc::UnboundedSender<AppRequest>> = OnceLock::new();
+
+/// Things commands need the app itself to do, since only it can reach the
+/// components involved.
+#[derive(Debug)]
+pub enum AppRequest {
+    ToggleNotificationCenter,
+    /// Toggles a menu on the focused monitor's bar.
+    ToggleMenu(BarMenu),
+}
+
+/// Passes requests from commands to `on_request` for as long as the app runs.
+pub async fn run_app_requests(on_request: impl Fn(AppRequest)) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    if APP_TX.set(tx).is_err() {
+        log::warn!("app requests are already being handled");
+        return;
+    }
+
+    while let Some(request) = rx.recv().await {
+        on_request(request);
+    }
+}
+
+fn send_app_request(request: AppRequest) -> Result<(), String> {
+    APP_TX
+        .get()
+        .ok_or("the app isn't listening for commands yet")?
+        .send(request)
+        .map_err(|_| "the app has stopped listening for commands".to_string())
+}
 
 /// Trait for commands that can be executed and undone
 pub trait Command: std::fmt::Debug + Send {
@@ -126,6 +174,30 @@ impl CommandManager {
     }
 }
 
+/// A value from before a command ran, which undoing it goes back to. It's
+/// recorded when the command is executed, unless it was already known when
+/// the command was made.
+#[derive(Debug, Default)]
+pub struct Prior<T: Copy>(Cell<Option<T>>);
+
+impl<T: Copy> Prior<T> {
+    pub fn known(value: T) -> Self {
+        Self(Cell::new(Some(value)))
+    }
+
+    /// Keeps `value` unless one was recorded already, so redoing a command
+    /// doesn't forget where it started.
+    fn record(&self, value: Option<T>) {
+        if self.0.get().is_none() {
+            self.0.set(value);
+        }
+    }
+
+    fn get(&self) -> Option<T> {
+        self.0.get()
+    }
+}
+
 /// Application-specific commands for cadenza-shell
 #[derive(Debug)]
 pub enum AppCommand {
@@ -135,52 +207,119 @@ pub enum AppCommand {
     DismissNotification(u32),
     /// Toggle network menu
     ToggleNetworkMenu,
-    /// Switch to workspace
-    SwitchWorkspace(u32),
-    /// Adjust volume
-    SetVolume(f64),
-    /// Adjust brightness
-    SetBrightness(f64),
+    /// Toggle volume menu
+    ToggleVolumeMenu,
+    /// Switch to the workspace with `id`, as the compositor knows it.
+    SwitchWorkspace {
+        id: u64,
+        compositor: Arc<dyn CompositorService>,
+        previous: Prior<u64>,
+    },
+    /// Set the output volume to `level`, from 0 to 1.
+    SetVolume { level: f64, previous: Prior<f64> },
+    /// Set the backlight to `level`, from 0 to 1.
+    SetBrightness { level: f64, previous: Prior<f64> },
     /// Toggle media playback
     ToggleMediaPlayback,
 }
 
+impl AppCommand {
+    /// Looks up a command by the name shortcuts and `cadenza-shell run` know
+    /// it by. Only commands that don't need arguments have one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "toggle-notification-center" => Some(Self::ToggleNotificationCenter),
+            "toggle-network-menu" => Some(Self::ToggleNetworkMenu),
+            "toggle-volume-menu" => Some(Self::ToggleVolumeMenu),
+            "toggle-media-playback" => Some(Self::ToggleMediaPlayback),
+            _ => None,
+        }
+    }
+
+    pub fn switch_workspace(id: u64, compositor: Arc<dyn CompositorService>) -> Self {
+        Self::SwitchWorkspace {
+            id,
+            compositor,
+            previous: Prior::default(),
+        }
+    }
+
+    pub fn set_volume(level: f64) -> Self {
+        Self::SetVolume {
+            level,
+            previous: Prior::default(),
+        }
+    }
+
+    pub fn set_brightness(level: f64) -> Self {
+        Self::SetBrightness {
+            level,
+            previous: Prior::default(),
+        }
+    }
+}
+
+/// Focuses a workspace in the background, since compositors are talked to
+/// asynchronously.
+fn focus_workspace(compositor: &Arc<dyn CompositorService>, id: u64) {
+    let compositor = Arc::clone(compositor);
+    relm4::spawn(async move {
+        if let Err(e) = compositor.focus_workspace(id).await {
+            log::error!("couldn't switch to workspace {id}: {e}");
+        }
+    });
+}
+
 impl Command for AppCommand {
     fn execute(&self) -> Result<(), String> {
         match self {
             AppCommand::ToggleNotificationCenter => {
                 log::info!("toggling notification center");
-                // in a real implementation, this would send a message to the notification
-                // center component
-                Ok(())
+                send_app_request(AppRequest::ToggleNotificationCenter)
             }
             AppCommand::DismissNotification(id) => {
                 log::info!("dismissing notification {}", id);
-                // in a real implementation, this would interact with the notification service
+                notifications::dismiss(*id);
                 Ok(())
             }
             AppCommand::ToggleNetworkMenu => {
                 log::info!("toggling network menu");
-                Ok(())
+                send_app_request(AppRequest::ToggleMenu(BarMenu::Network))
+            }
+            AppCommand::ToggleVolumeMenu => {
+                log::info!("toggling volume menu");
+                send_app_request(AppRequest::ToggleMenu(BarMenu::Volume))
             }
-            AppCommand::SwitchWorkspace(workspace) => {
-                log::info!("switching to workspace {}", workspace);
-                // this would interact with Hyprland service
+            AppCommand::SwitchWorkspace {
+                id,
+                compositor,
+                previous,
+            } => {
+                log::info!("switching to workspace {}", id);
+                previous.record(compositor.focused_workspace());
+                focus_workspace(compositor, *id);
                 Ok(())
             }
-            AppCommand::SetVolume(volume) => {
-                log::info!("setting volume to {}", volume);
-                // this would interact with audio service
+            AppCommand::SetVolume { level, previous } => {
+                log::info!("setting volume to {}", level);
+                previous.record(Some(VOLUME_STATE.read().volume / 100.0));
+                pulseaudio::set_volume(level * 100.0);
                 Ok(())
             }
-            AppCommand::SetBrightness(brightness) => {
-                log::info!("setting brightness to {}", brightness);
-                // this would interact with brightness service
+            AppCommand::SetBrightness { level, previous } => {
+                log::info!("setting brightness to {}", level);
+                previous.record(*BRIGHTNESS_STATE.read());
+                brightness::set_brightness(*level);
                 Ok(())
             }
             AppCommand::ToggleMediaPlayback => {
                 log::info!("toggling media playback");
-                // this would interact with MPRIS service
+                let bus_name = MPRIS_STATE
+                    .read()
+                    .as_ref()
+                    .map(|state| state.bus_name.clone())
+                    .ok_or("no media player is running")?;
+                relm4::spawn(mpris::play_pause(bus_name));
                 Ok(())
             }
         }
@@ -189,16 +328,28 @@ impl Command for AppCommand {
     fn undo(&self) -> Result<(), String> {
         match self {
             // some commands can be undone
-            AppCommand::SwitchWorkspace(_) => {
-                log::info!("undoing workspace switch (return to previous)");
+            AppCommand::SwitchWorkspace {
+                compositor,
+                previous,
+                ..
+            } => {
+                let previous = previous.get().ok_or("the previous workspace isn't known")?;
+                log::info!("undoing workspace switch (returning to {})", previous);
+                focus_workspace(compositor, previous);
                 Ok(())
             }
-            AppCommand::SetVolume(_) => {
-                log::info!("undoing volume change (restore previous level)");
+            AppCommand::SetVolume { previous, .. } => {
+                let previous = previous.get().ok_or("the previous volume isn't known")?;
+                log::info!("undoing volume change (restoring {})", previous);
+                pulseaudio::set_volume(previous * 100.0);
                 Ok(())
             }
-            AppCommand::SetBrightness(_) => {
-                log::info!("undoing brightness change (restore previous level)");
+            AppCommand::SetBrightness { previous, .. } => {
+                let previous = previous
+                    .get()
+                    .ok_or("the previous brightness isn't known")?;
+                log::info!("undoing brightness change (restoring {})", previous);
+                brightness::set_brightness(previous);
                 Ok(())
             }
             // others cannot be meaningfully undone
@@ -211,9 +362,14 @@ impl Command for AppCommand {
             AppCommand::ToggleNotificationCenter => "toggle notification center".to_string(),
             AppCommand::DismissNotification(id) => format!("dismiss notification {}", id),
             AppCommand::ToggleNetworkMenu => "toggle network menu".to_string(),
-            AppCommand::SwitchWorkspace(ws) => format!("switch to workspace {}", ws),
-            AppCommand::SetVolume(vol) => format!("set volume to {:.0}%", vol * 100.0),
-            AppCommand::SetBrightness(br) => format!("set brightness to {:.0}%", br * 100.0),
+            AppCommand::ToggleVolumeMenu => "toggle volume menu".to_string(),
+            AppCommand::SwitchWorkspace { id, .. } => format!("switch to workspace {}", id),
+            AppCommand::SetVolume { level, .. } => {
+                format!("set volume to {:.0}%", level * 100.0)
+            }
+            AppCommand::SetBrightness { level, .. } => {
+                format!("set brightness to {:.0}%", level * 100.0)
+            }
             AppCommand::ToggleMediaPlayback => "toggle media playback".to_string(),
         }
     }
@@ -221,13 +377,21 @@ impl Command for AppCommand {
     fn can_undo(&self) -> bool {
         matches!(
             self,
-            AppCommand::SwitchWorkspace(_)
-                | AppCommand::SetVolume(_)
-                | AppCommand::SetBrightness(_)
+            AppCommand::SwitchWorkspace { .. }
+                | AppCommand::SetVolume { .. }
+                | AppCommand::SetBrightness { .. }
         )
     }
 }
 
+/// Runs the command called `name` (see [`AppCommand::from_name`]) through
+/// `executor`. A name that isn't a command is an error for the caller to
+/// report, rather than a panic.
+pub async fn run_named(executor: &Mutex<GlobalCommandExecutor>, name: &str) -> Result<(), String> {
+    let command = AppCommand::from_name(name).ok_or_else(|| format!("unknown command '{name}'"))?;
+    executor.lock().await.execute(command)
+}
+
 /// Global command executor that can be used across the application
 #[derive(Debug)]
 pub struct GlobalCommandExecutor {
@@ -276,14 +440,36 @@ impl Default for GlobalCommandExecutor {
 
 #[cfg(test)]
 mod tests {
+    use std::{future::Future, pin::Pin};
+
     use super::*;
 
+    #[derive(Debug)]
+    struct FakeCompositor;
+
+    impl CompositorService for FakeCompositor {
+        fn focused_workspace(&self) -> Option<u64> {
+            Some(1)
+        }
+
+        fn focus_workspace(
+            &self,
+            _id: u64,
+        ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + '_>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn version(&self) -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send + '_>> {
+            Box::pin(async { Ok("fake".to_string()) })
+        }
+    }
+
     #[test]
     fn test_command_manager_execute_and_undo() {
         let mut manager = CommandManager::new(10);
 
         // test executing a command that can be undone
-        let command = Box::new(AppCommand::SetVolume(0.5));
+        let command = Box::new(AppCommand::set_volume(0.5));
         assert!(manager.execute_command(command).is_ok());
         assert!(manager.can_undo());
         assert!(!manager.can_redo());
@@ -303,10 +489,55 @@ mod tests {
 
     #[test]
     fn test_command_descriptions() {
-        let volume_cmd = AppCommand::SetVolume(0.75);
+        let volume_cmd = AppCommand::set_volume(0.75);
         assert_eq!(volume_cmd.description(), "set volume to 75%");
 
-        let workspace_cmd = AppCommand::SwitchWorkspace(3);
+        let workspace_cmd = AppCommand::switch_workspace(3, Arc::new(FakeCompositor));
         assert_eq!(workspace_cmd.description(), "switch to workspace 3");
+
+        let brightness_cmd = AppCommand::SetBrightness {
+            level: 0.4,
+            previous: Prior::known(0.6),
+        };
+        assert_eq!(brightness_cmd.description(), "set brightness to 40%");
+    }
+
+    #[test]
+    fn test_brightness_undo_needs_previous_level() {
+        let command = AppCommand::set_brightness(0.4);
+        assert!(command.can_undo());
+        assert!(command.undo().is_err());
+    }
+
+    #[test]
+    fn test_commands_by_name() {
+        assert!(matches!(
+            AppCommand::from_name("toggle-notification-center"),
+            Some(AppCommand::ToggleNotificationCenter)
+        ));
+        assert!(matches!(
+            AppCommand::from_name("toggle-volume-menu"),
+            Some(AppCommand::ToggleVolumeMenu)
+        ));
+        assert!(AppCommand::from_name("").is_none());
+        assert!(AppCommand::from_name("launch-missiles").is_none());
+    }
+
+    #[test]
+    fn test_prior_keeps_first_value() {
+        let prior = Prior::default();
+        assert_eq!(prior.get(), None);
+
+        prior.record(None);
+        assert_eq!(prior.get(), None);
+
+        // redoing a command records again, which mustn't move its start
+        prior.record(Some(0.3));
+        prior.record(Some(0.8));
+        assert_eq!(prior.get(), Some(0.3));
+
+        let known = Prior::known(0.5);
+        known.record(Some(0.9));
+        assert_eq!(known.get(), Some(0.5));
     }
 }
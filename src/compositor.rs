@@ -0,0 +1,29 @@
+use std::{fmt, future::Future, pin::Pin, sync::Arc};
+
+/// The workspace side of a compositor, so that widgets like the workspace
+/// indicators can stay compositor-agnostic instead of branching on the backend.
+pub(crate) trait CompositorService: Send + Sync + fmt::Debug {
+    /// The workspace that has focus, if the backend knows.
+    fn focused_workspace(&self) -> Option<u64>;
+
+    /// Switches to a workspace, by the id the backend gave it.
+    fn focus_workspace(
+        &self,
+        id: u64,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + '_>>;
+
+    /// Asks the compositor for its name and version, which also shows that
+    /// it's reachable.
+    fn version(&self) -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send + '_>>;
+}
+
+/// The backend for the running compositor, or `None` if it isn't one the
+/// shell was built to talk to.
+pub(crate) fn connect() -> Option<Arc<dyn CompositorService>> {
+    #[cfg(feature = "niri")]
+    if let Ok(socket_path) = std::env::var("NIRI_SOCKET") {
+        return Some(Arc::new(crate::niri::NiriCompositor::new(socket_path)));
+    }
+
+    None
+}
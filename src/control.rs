@@ -0,0 +1,233 @@
+use tokio::sync::oneshot;
+use zbus::{fdo, interface, proxy};
+
+use crate::{
+    doctor,
+    network::{self, NETWORK_STATE},
+};
+
+const CONTROL_NAME: &str = "com.musicaloft.CadenzaShell";
+const CONTROL_PATH: &str = "/com/musicaloft/CadenzaShell";
+
+/// Requests from the control interface that the app handles on the main
+/// thread.
+#[derive(Debug)]
+pub enum ControlRequest {
+    IdentifyDisplays,
+    /// Opens the power menu on the focused monitor's bar.
+    ShowPowerMenu,
+    /// Asks for the monitor diagnostics report, sent back on the channel.
+    MonitorReport(oneshot::Sender<String>),
+    /// Re-reads settings and recompiles styles, replying with a summary or
+    /// what went wrong.
+    Reload(oneshot::Sender<Result<String, String>>),
+    /// Runs the named command (see `AppCommand::from_name`), replying with
+    /// what went wrong, if anything.
+    Run(String, oneshot::Sender<Result<(), String>>),
+    /// Shuts the shell down cleanly.
+    Quit,
+}
+
+/// Serves `com.musicaloft.CadenzaShell` on the session bus so a running shell
+/// can be driven from the command line (see [`run_command`]).
+struct ControlInterface {
+    on_request: Box<dyn Fn(ControlRequest) + Send + Sync>,
+}
+
+#[interface(name = "com.musicaloft.CadenzaShell")]
+impl ControlInterface {
+    /// Briefly shows each monitor's connector name and model on that monitor.
+    async fn identify_displays(&self) {
+        (self.on_request)(ControlRequest::IdentifyDisplays);
+    }
+
+    /// Opens the power menu on the focused monitor's bar.
+    async fn show_power_menu(&self) {
+        (self.on_request)(ControlRequest::ShowPowerMenu);
+    }
+
+    /// Returns a plain-text report of the connected monitors and their bars.
+    async fn monitor_report(&self) -> fdo::Result<String> {
+        let (tx, rx) = oneshot::channel();
+        (self.on_request)(ControlRequest::MonitorReport(tx));
+        rx.await
+            .map_err(|_| fdo::Error::Failed("the shell didn't produce a report".to_string()))
+    }
+
+    /// Returns the shell's view of the network, as JSON or as plain text.
+    async fn network_status(&self, json: bool) -> fdo::Result<String> {
+        let info = NETWORK_STATE.read().clone();
+        if json {
+            serde_json::to_string_pretty(&info).map_err(|e| fdo::Error::Failed(e.to_string()))
+        } else {
+            Ok(network::status_report(&info))
+        }
+    }
+
+    /// Re-reads settings and recompiles styles without restarting the shell.
+    async fn reload(&self) -> fdo::Result<String> {
+        let (tx, rx) = oneshot::channel();
+        (self.on_request)(ControlRequest::Reload(tx));
+        rx.await
+            .map_err(|_| fdo::Error::Failed("the shell didn't finish reloading".to_string()))?
+            .map_err(fdo::Error::Failed)
+    }
+
+    /// Runs a command by name, like the ones keyboard shortcuts are bound to.
+    async fn run(&self, name: String) -> fdo::Result<()> {
+        let (tx, rx) = oneshot::channel();
+        (self.on_request)(ControlRequest::Run(name, tx));
+        rx.await
+            .map_err(|_| fdo::Error::Failed("the shell didn't run the command".to_string()))?
+            .map_err(fdo::Error::Failed)
+    }
+
+    /// Shuts the shell down. Returns before the shell has finished exiting.
+    async fn quit(&self) {
+        (self.on_request)(ControlRequest::Quit);
+    }
+}
+
+#[proxy(
+    interface = "com.musicaloft.CadenzaShell",
+    default_service = "com.musicaloft.CadenzaShell",
+    default_path = "/com/musicaloft/CadenzaShell"
+)]
+trait Control {
+    fn identify_displays(&self) -> zbus::Result<()>;
+
+    fn show_power_menu(&self) -> zbus::Result<()>;
+
+    fn monitor_report(&self) -> zbus::Result<String>;
+
+    fn network_status(&self, json: bool) -> zbus::Result<String>;
+
+    fn reload(&self) -> zbus::Result<String>;
+
+    fn run(&self, name: &str) -> zbus::Result<()>;
+
+    fn quit(&self) -> zbus::Result<()>;
+}
+
+/// Registers the control interface and keeps it alive for as long as the
+/// returned future runs. Each incoming request is passed to `on_request`.
+pub async fn run_control_interface(on_request: impl Fn(ControlRequest) + Send + Sync + 'static) {
+    let interface = ControlInterface {
+        on_request: Box::new(on_request),
+    };
+
+    let _connection = match zbus::connection::Builder::session()
+        .and_then(|b| b.name(CONTROL_NAME))
+        .and_then(|b| b.serve_at(CONTROL_PATH, interface))
+    {
+        Ok(builder) => match builder.build().await {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("couldn't register control interface: {}", e);
+                return;
+            }
+        },
+        Err(e) => {
+            log::error!("couldn't set up control interface: {}", e);
+            return;
+        }
+    };
+
+    log::info!("control interface registered as {}", CONTROL_NAME);
+    std::future::pending::<()>().await;
+}
+
+/// Subcommands accepted by [`run_command`].
+const COMMANDS: &[&str] = &[
+    "doctor", "identify", "monitors", "network", "power", "quit", "reload", "run",
+];
+
+const USAGE: &str = "\
+usage: cadenza-shell [command]
+
+Runs the shell when no command is given. Otherwise, sends the command to the
+running shell:
+
+    doctor      check everything the shell depends on (works without a
+                running shell)
+    identify    show each monitor's connector name on that monitor
+    monitors    print a diagnostics report of connected monitors
+    network status [--json]
+                print what the shell thinks the network state is
+    power       open the power menu
+    quit        shut the shell down
+    reload      re-read settings and styles
+    run <command>
+                run a command, e.g. from a compositor keybind:
+                toggle-notification-center, toggle-network-menu,
+                toggle-volume-menu or toggle-media-playback";
+
+/// Runs a `cadenza-shell <command> [args...]` subcommand against the running
+/// shell.
+///
+/// `main` has already initialized logging by the time this runs, so nothing
+/// on this path may call `env_logger::init()` again; it panics when a logger
+/// is already installed.
+pub async fn run_command(command: &str, args: &[String]) -> glib::ExitCode {
+    if matches!(command, "-h" | "--help" | "help") {
+        println!("{USAGE}");
+        return glib::ExitCode::SUCCESS;
+    }
+
+    if !COMMANDS.contains(&command) {
+        eprintln!("unknown command '{command}'\n\n{USAGE}");
+        return glib::ExitCode::FAILURE;
+    }
+
+    if command == "doctor" {
+        return doctor::run().await;
+    }
+
+    if command == "network" && args.first().map(String::as_str) != Some("status") {
+        eprintln!("usage: cadenza-shell network status [--json]");
+        return glib::ExitCode::FAILURE;
+    }
+
+    if command == "run" && args.len() != 1 {
+        eprintln!("usage: cadenza-shell run <command>");
+        return glib::ExitCode::FAILURE;
+    }
+
+    let proxy = match connect().await {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("couldn't reach cadenza-shell; is it running? ({e})");
+            return glib::ExitCode::FAILURE;
+        }
+    };
+
+    let result = match command {
+        "identify" => proxy.identify_displays().await,
+        "power" => proxy.show_power_menu().await,
+        "monitors" => proxy
+            .monitor_report()
+            .await
+            .map(|report| print!("{report}")),
+        "network" => proxy
+            .network_status(args.iter().any(|arg| arg == "--json"))
+            .await
+            .map(|status| println!("{}", status.trim_end())),
+        "quit" => proxy.quit().await,
+        "reload" => proxy.reload().await.map(|summary| println!("{summary}")),
+        "run" => proxy.run(&args[0]).await,
+        _ => unreachable!("commands are validated above"),
+    };
+
+    match result {
+        Ok(()) => glib::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{command} failed: {e}");
+            glib::ExitCode::FAILURE
+        }
+    }
+}
+
+async fn connect() -> zbus::Result<ControlProxy<'static>> {
+    let connection = zbus::Connection::session().await?;
+    ControlProxy::new(&connection).await
+}
@@ -0,0 +1,195 @@
+use std::fs::{self, OpenOptions};
+
+use anyhow::Context;
+use zbus::{Connection, fdo, names::BusName};
+
+use crate::{battery, brightness, compositor, network::dbus::NetworkManagerProxy, notifications};
+
+/// How a single check turned out, with a short explanation.
+enum Outcome {
+    Pass(String),
+    Fail(String),
+    /// Not applicable to this build or machine.
+    Skip(String),
+}
+
+impl From<anyhow::Result<String>> for Outcome {
+    fn from(result: anyhow::Result<String>) -> Self {
+        match result {
+            Ok(detail) => Outcome::Pass(detail),
+            Err(e) => Outcome::Fail(format!("{e:#}")),
+        }
+    }
+}
+
+/// Runs `cadenza-shell doctor`: checks everything the shell depends on and
+/// prints a pass/fail line for each, so bug reports can say what's missing
+/// instead of pointing at log lines. Works whether or not the shell is
+/// running.
+pub async fn run() -> glib::ExitCode {
+    let session = Connection::session().await;
+    let system = Connection::system().await;
+
+    let checks = [
+        ("D-Bus session bus", bus_reachable(&session)),
+        ("D-Bus system bus", bus_reachable(&system)),
+        ("NetworkManager", network_manager(&system).await.into()),
+        ("BlueZ", bluez().await),
+        ("Notifications", notifications_name(&session).await),
+        ("Compositor", compositor().await.into()),
+        ("Backlight", backlight().await),
+        ("Battery", battery()),
+        ("Layer shell", layer_shell()),
+    ];
+
+    let mut failed = false;
+    for (name, outcome) in checks {
+        let (status, detail) = match outcome {
+            Outcome::Pass(detail) => ("pass", detail),
+            Outcome::Fail(detail) => {
+                failed = true;
+                ("FAIL", detail)
+            }
+            Outcome::Skip(detail) => ("skip", detail),
+        };
+        println!("{status}  {name}: {detail}");
+    }
+
+    if failed {
+        glib::ExitCode::FAILURE
+    } else {
+        glib::ExitCode::SUCCESS
+    }
+}
+
+fn bus_reachable(connection: &zbus::Result<Connection>) -> Outcome {
+    match connection {
+        Ok(_) => Outcome::Pass("reachable".to_string()),
+        Err(e) => Outcome::Fail(format!("couldn't connect: {e}")),
+    }
+}
+
+async fn network_manager(system: &zbus::Result<Connection>) -> anyhow::Result<String> {
+    let Ok(system) = system else {
+        anyhow::bail!("needs the system bus");
+    };
+
+    let version = NetworkManagerProxy::new(system)
+        .await?
+        .version()
+        .await
+        .context("NetworkManager isn't running")?;
+    Ok(format!("version {version}"))
+}
+
+#[cfg(feature = "bluetooth")]
+async fn bluez() -> Outcome {
+    let result = async {
+        let session = bluer::Session::new().await?;
+        let adapter = session
+            .default_adapter()
+            .await
+            .context("no bluetooth adapter")?;
+        Ok::<_, anyhow::Error>(format!("adapter {}", adapter.name()))
+    };
+    result.await.into()
+}
+
+#[cfg(not(feature = "bluetooth"))]
+async fn bluez() -> Outcome {
+    Outcome::Skip("built without bluetooth support".to_string())
+}
+
+/// Whether the shell can own the notifications name: it's free, or the
+/// running shell already has it. Names whoever else owns it otherwise.
+async fn notifications_name(session: &zbus::Result<Connection>) -> Outcome {
+    let result = async {
+        let Ok(session) = session else {
+            anyhow::bail!("needs the session bus");
+        };
+
+        let dbus = fdo::DBusProxy::new(session).await?;
+        let owner = match dbus
+            .get_name_owner(BusName::try_from(notifications::DAEMON_NAME)?)
+            .await
+        {
+            Ok(owner) => owner,
+            Err(fdo::Error::NameHasNoOwner(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let pid = dbus
+            .get_connection_unix_process_id(BusName::from(owner.into_inner()))
+            .await?;
+        let process = fs::read_to_string(format!("/proc/{pid}/comm"))
+            .map(|comm| comm.trim().to_string())
+            .unwrap_or_else(|_| "an unknown process".to_string());
+        Ok::<_, anyhow::Error>(Some((process, pid)))
+    };
+
+    match result.await {
+        Ok(None) => Outcome::Pass("available".to_string()),
+        Ok(Some((process, _))) if process == env!("CARGO_PKG_NAME") => {
+            Outcome::Pass("owned by the running shell".to_string())
+        }
+        Ok(Some((process, pid))) => Outcome::Fail(format!(
+            "owned by {process} (pid {pid}), so the shell can't show notifications"
+        )),
+        Err(e) => Outcome::Fail(format!("{e:#}")),
+    }
+}
+
+async fn compositor() -> anyhow::Result<String> {
+    let Some(compositor) = compositor::connect() else {
+        anyhow::bail!("no supported compositor found (is NIRI_SOCKET set?)");
+    };
+
+    compositor.version().await
+}
+
+/// Whether the backlight can be changed, either by writing to sysfs directly
+/// or through logind like the brightness schedule does.
+async fn backlight() -> Outcome {
+    let Ok(interface) = brightness::detect_interface() else {
+        return Outcome::Skip("no backlight found".to_string());
+    };
+
+    let path = format!("/sys/class/backlight/{interface}/brightness");
+    if OpenOptions::new().write(true).open(&path).is_ok() {
+        return Outcome::Pass(format!("{path} is writable"));
+    }
+
+    match brightness::logind_session_active().await {
+        Ok(true) => Outcome::Pass(format!("{interface} is writable through logind")),
+        Ok(false) => Outcome::Fail(format!(
+            "{path} isn't writable and the logind session isn't active"
+        )),
+        Err(e) => Outcome::Fail(format!(
+            "{path} isn't writable and logind couldn't be asked: {e:#}"
+        )),
+    }
+}
+
+fn battery() -> Outcome {
+    let Some(path) = battery::detect_battery_path() else {
+        return Outcome::Skip("no battery found".to_string());
+    };
+
+    // the estimate is only there to explain odd predictions, so a missing
+    // profile doesn't fail the check
+    let estimate =
+        battery::explain_estimate(&path).unwrap_or_else(|e| format!("no estimate: {e:#}"));
+    Outcome::Pass(format!("{}; {estimate}", path.display()))
+}
+
+fn layer_shell() -> Outcome {
+    if let Err(e) = gtk4::init() {
+        return Outcome::Fail(format!("couldn't open a display: {e}"));
+    }
+
+    if gtk4_layer_shell::is_supported() {
+        Outcome::Pass("supported".to_string())
+    } else {
+        Outcome::Fail("the compositor doesn't support wlr-layer-shell".to_string())
+    }
+}
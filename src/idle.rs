@@ -0,0 +1,143 @@
+use std::{io, os::fd::OwnedFd, time::Duration};
+
+use tokio::{
+    io::unix::AsyncFd,
+    sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
+};
+use wayland_client::{
+    Connection, Dispatch, EventQueue, QueueHandle,
+    backend::WaylandError,
+    globals::{GlobalListContents, registry_queue_init},
+    protocol::{wl_registry::WlRegistry, wl_seat::WlSeat},
+};
+use wayland_protocols::ext::idle_notify::v1::client::{
+    ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+    ext_idle_notifier_v1::ExtIdleNotifierV1,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleEvent {
+    /// The user has been inactive for the whole timeout.
+    Idle,
+    /// The user is active again after being idle.
+    Resumed,
+}
+
+struct IdleState {
+    tx: UnboundedSender<IdleEvent>,
+}
+
+/// Watches for the user going idle through the compositor's `ext-idle-notify`
+/// protocol. Idle inhibitors (e.g. a playing video) are respected, so no
+/// `Idle` event arrives while one is active.
+///
+/// The Wayland connection is read on a background task, which stops once the
+/// receiver is dropped. Must be called from within the tokio runtime.
+pub fn watch(timeout: Duration) -> anyhow::Result<UnboundedReceiver<IdleEvent>> {
+    let conn = Connection::connect_to_env()?;
+    let (globals, mut queue) = registry_queue_init::<IdleState>(&conn)?;
+    let qh = queue.handle();
+
+    let seat: WlSeat = globals.bind(&qh, 1..=1, ())?;
+    let notifier: ExtIdleNotifierV1 = globals.bind(&qh, 1..=1, ())?;
+    let timeout_ms = timeout.as_millis().try_into().unwrap_or(u32::MAX);
+    let notification = notifier.get_idle_notification(timeout_ms, &seat, &qh, ());
+
+    let fd = AsyncFd::new(conn.backend().poll_fd().try_clone_to_owned()?)?;
+
+    let (tx, rx) = unbounded_channel();
+    let receiver_gone = tx.clone();
+    let mut state = IdleState { tx };
+    relm4::spawn(async move {
+        tokio::select! {
+            Err(e) = dispatch_events(&mut queue, &mut state, &fd) => {
+                log::error!("lost connection to the compositor's idle notifier: {e}");
+            }
+            _ = receiver_gone.closed() => {}
+        }
+        notification.destroy();
+        let _ = conn.flush();
+    });
+
+    Ok(rx)
+}
+
+/// Reads and dispatches events as they arrive. Only returns on error.
+async fn dispatch_events(
+    queue: &mut EventQueue<IdleState>,
+    state: &mut IdleState,
+    fd: &AsyncFd<OwnedFd>,
+) -> anyhow::Result<()> {
+    loop {
+        queue.dispatch_pending(state)?;
+        queue.flush()?;
+
+        let mut ready = fd.readable().await?;
+        // `None` means events are already queued, so they're dispatched first
+        if let Some(guard) = queue.prepare_read() {
+            match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {
+                    ready.clear_ready();
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, ()> for IdleState {
+    fn event(
+        state: &mut Self,
+        _proxy: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let event = match event {
+            ext_idle_notification_v1::Event::Idled => IdleEvent::Idle,
+            ext_idle_notification_v1::Event::Resumed => IdleEvent::Resumed,
+            _ => return,
+        };
+        let _ = state.tx.send(event);
+    }
+}
+
+// the remaining objects have no events we care about
+
+impl Dispatch<WlRegistry, GlobalListContents> for IdleState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WlRegistry,
+        _event: <WlRegistry as wayland_client::Proxy>::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlSeat, ()> for IdleState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WlSeat,
+        _event: <WlSeat as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtIdleNotifierV1, ()> for IdleState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ExtIdleNotifierV1,
+        _event: <ExtIdleNotifierV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
@@ -0,0 +1,88 @@
+use tokio::process::Command;
+
+/// Checks that a user-configured command is worth handing to `sh -c`: it must
+/// not be blank, contain NUL bytes or leave a quote unclosed.
+pub fn validate_command(command: &str) -> Result<(), String> {
+    if command.trim().is_empty() {
+        return Err("command is empty".to_string());
+    }
+
+    if command.contains('\0') {
+        return Err("command contains a NUL byte".to_string());
+    }
+
+    let mut quote = None;
+    let mut escaped = false;
+    for c in command.chars() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (None, '\'' | '"') => quote = Some(c),
+            (Some('"'), '"') => quote = None,
+            _ => {}
+        }
+    }
+
+    match quote {
+        Some(q) => Err(format!("command has an unclosed {q} quote")),
+        None => Ok(()),
+    }
+}
+
+/// Runs a user-configured command with `sh -c` in the background, logging
+/// rather than returning any failure.
+pub fn spawn_command(command: &str) {
+    if let Err(e) = validate_command(command) {
+        log::error!("not running `{command}`: {e}");
+        return;
+    }
+
+    let command = command.to_string();
+    relm4::spawn(async move {
+        match Command::new("sh").arg("-c").arg(&command).spawn() {
+            Ok(mut child) => match child.wait().await {
+                Ok(status) if !status.success() => {
+                    log::warn!("`{command}` exited with {status}");
+                }
+                Ok(_) => {}
+                Err(e) => log::error!("couldn't wait for `{command}`: {e}"),
+            },
+            Err(e) => log::error!("couldn't spawn `{command}`: {e}"),
+        }
+    });
+}
+
+/// Opens a link with `xdg-open` in the background. The link is passed as an
+/// argument rather than through a shell.
+pub fn open_uri(uri: &str) {
+    let uri = uri.to_string();
+    relm4::spawn(async move {
+        match Command::new("xdg-open").arg(&uri).spawn() {
+            Ok(mut child) => {
+                if let Err(e) = child.wait().await {
+                    log::error!("couldn't wait for xdg-open: {e}");
+                }
+            }
+            Err(e) => log::error!("couldn't open {uri}: {e}"),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_command() {
+        assert!(validate_command("gnome-calendar").is_ok());
+        assert!(validate_command(r#"notify-send "it's late""#).is_ok());
+        assert!(validate_command(r"echo don\'t").is_ok());
+
+        assert!(validate_command("   ").is_err());
+        assert!(validate_command("echo \0").is_err());
+        assert!(validate_command("notify-send 'unclosed").is_err());
+        assert!(validate_command(r#"notify-send "unclosed"#).is_err());
+    }
+}
@@ -3,23 +3,42 @@
 
 mod analog_clock;
 mod app;
+mod appearance;
 mod battery;
+#[cfg(feature = "bluetooth")]
 mod bluetooth;
+#[cfg(feature = "bluetooth")]
 mod bluetooth_menu;
 mod brightness;
+mod calendar;
+mod clock;
 mod commands;
+mod compositor;
+mod control;
+mod doctor;
+mod idle;
+mod launch;
+mod monitors;
 mod mpris;
 mod network;
 mod network_menu;
+#[cfg(feature = "niri")]
 mod niri;
 mod notifications;
+mod power;
+mod power_menu;
 mod pulseaudio;
+mod services;
 mod settings;
+mod shortcuts;
 mod sleep_monitor;
 mod sound;
 mod style;
+mod systemd;
 mod tiles;
+mod tray_backend;
 mod utils;
+mod volume_menu;
 mod weather;
 mod widgets;
 
@@ -30,12 +49,18 @@ mod icon_names {
 
 use relm4::{RELM_THREADS, RelmApp};
 
-use crate::{app::CadenzaShellModel, style::compile_styles};
+use crate::{app::CadenzaShellModel, style::apply_styles};
 
 #[tokio::main]
 async fn main() -> glib::ExitCode {
     env_logger::init();
 
+    // any argument is a command for the already running shell
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some((command, args)) = args.split_first() {
+        return control::run_command(command, args).await;
+    }
+
     RELM_THREADS.set(16).unwrap();
 
     relm4_icons::initialize_icons(icon_names::GRESOURCE_BYTES, icon_names::RESOURCE_PREFIX);
@@ -45,14 +70,22 @@ async fn main() -> glib::ExitCode {
         log::error!("failed to initialize settings: {}", e);
     }
 
-    match compile_styles() {
-        Ok(css) => relm4::set_global_css(&css),
-        Err(e) => log::error!("couldn't load scss: {e}"),
+    // styles depend on the color scheme, so read it before they're applied
+    appearance::read_color_scheme().await;
+
+    if let Err(e) = apply_styles() {
+        log::error!("couldn't load scss: {e}");
     }
 
     RelmApp::new("com.musicaloft.cadenza-shell")
         .visible_on_activate(false)
         .run_async::<CadenzaShellModel>(());
 
-    gtk4::glib::ExitCode::FAILURE
+    // quitting through the control interface or a termination signal sets
+    // this; the app only stops on its own when something went wrong
+    if app::quit_requested() {
+        gtk4::glib::ExitCode::SUCCESS
+    } else {
+        gtk4::glib::ExitCode::FAILURE
+    }
 }
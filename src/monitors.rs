@@ -0,0 +1,163 @@
+use std::fmt::Write;
+
+use gdk4::prelude::*;
+
+/// A snapshot of what GDK knows about a connected monitor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    /// The output name, e.g. `DP-1` or `eDP-1`. This is what per-monitor
+    /// settings are keyed by.
+    pub connector: String,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    /// Position and size in the compositor's logical coordinate space.
+    pub geometry: (i32, i32, i32, i32),
+    pub scale_factor: i32,
+    /// Refresh rate in millihertz, or 0 if unknown.
+    pub refresh_rate: i32,
+}
+
+impl MonitorInfo {
+    /// Reads the details of a monitor, or `None` if it has no connector name
+    /// (in which case the shell doesn't put anything on it).
+    pub fn from_monitor(monitor: &gdk4::Monitor) -> Option<Self> {
+        Some(Self {
+            connector: monitor.connector()?.to_string(),
+            manufacturer: monitor.manufacturer().map(|s| s.to_string()),
+            model: monitor.model().map(|s| s.to_string()),
+            geometry: {
+                let g = monitor.geometry();
+                (g.x(), g.y(), g.width(), g.height())
+            },
+            scale_factor: monitor.scale_factor(),
+            refresh_rate: monitor.refresh_rate(),
+        })
+    }
+
+    /// The make and model, e.g. "Dell Inc. DELL U2720Q", if the monitor
+    /// reports either.
+    pub fn display_name(&self) -> Option<String> {
+        let name = [self.manufacturer.as_deref(), self.model.as_deref()]
+            .into_iter()
+            .flatten()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        (!name.is_empty()).then_some(name)
+    }
+}
+
+/// Every currently connected monitor that has a connector name.
+pub fn connected_monitors(display: &gdk4::Display) -> Vec<(gdk4::Monitor, MonitorInfo)> {
+    display
+        .monitors()
+        .iter::<gdk4::Monitor>()
+        .filter_map(|m| m.ok())
+        .filter_map(|m| MonitorInfo::from_monitor(&m).map(|info| (m, info)))
+        .collect()
+}
+
+/// The monitor that shell-wide windows (like notification popups) go on when
+/// there's no better choice.
+///
+/// Wayland has no notion of a primary output, so this is the first connected
+/// monitor in GDK's list, which is normally the built-in or first-plugged one.
+pub fn primary_monitor(display: &gdk4::Display) -> Option<gdk4::Monitor> {
+    connected_monitors(display)
+        .into_iter()
+        .next()
+        .map(|(monitor, _)| monitor)
+}
+
+/// The connector name of the output the compositor reports as focused, if the
+/// compositor backend tracks it.
+pub fn focused_output() -> Option<String> {
+    #[cfg(feature = "niri")]
+    {
+        crate::niri::NIRI_STATE
+            .read()
+            .as_ref()
+            .map(|state| state.focused_output.clone())
+            .filter(|output| !output.is_empty())
+    }
+
+    #[cfg(not(feature = "niri"))]
+    None
+}
+
+/// Builds a plain-text summary of the connected monitors, suitable for pasting
+/// into bug reports about display handling. `bars` lists the connectors that
+/// currently have a bar.
+pub fn generate_monitor_report(monitors: &[MonitorInfo], bars: &[&str]) -> String {
+    let mut report = format!("{} monitor(s) connected\n", monitors.len());
+
+    for info in monitors {
+        let (x, y, width, height) = info.geometry;
+        let refresh_rate = if info.refresh_rate > 0 {
+            format!("{:.2} Hz", info.refresh_rate as f64 / 1000.0)
+        } else {
+            "unknown".to_string()
+        };
+        let has_bar = bars.contains(&info.connector.as_str());
+
+        let _ = writeln!(report, "\n{}", info.connector);
+        let _ = writeln!(
+            report,
+            "  name: {}",
+            info.display_name().as_deref().unwrap_or("unknown")
+        );
+        let _ = writeln!(report, "  geometry: {width}x{height} at {x},{y}");
+        let _ = writeln!(report, "  scale: {}", info.scale_factor);
+        let _ = writeln!(report, "  refresh rate: {refresh_rate}");
+        let _ = writeln!(report, "  bar: {}", if has_bar { "yes" } else { "no" });
+    }
+
+    // bars left behind after a hotplug are the usual culprit for display bugs
+    for orphan in bars
+        .iter()
+        .filter(|c| !monitors.iter().any(|m| m.connector == **c))
+    {
+        let _ = writeln!(
+            report,
+            "\nwarning: bar exists for disconnected monitor {orphan}"
+        );
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(connector: &str) -> MonitorInfo {
+        MonitorInfo {
+            connector: connector.to_string(),
+            manufacturer: Some("Dell Inc.".to_string()),
+            model: Some("DELL U2720Q".to_string()),
+            geometry: (0, 0, 2560, 1440),
+            scale_factor: 1,
+            refresh_rate: 59951,
+        }
+    }
+
+    #[test]
+    fn test_report_lists_monitors_and_orphaned_bars() {
+        let report = generate_monitor_report(&[monitor("DP-1")], &["DP-1", "HDMI-A-1"]);
+
+        assert!(report.starts_with("1 monitor(s) connected"));
+        assert!(report.contains("DP-1\n  name: Dell Inc. DELL U2720Q"));
+        assert!(report.contains("2560x1440 at 0,0"));
+        assert!(report.contains("59.95 Hz"));
+        assert!(report.contains("bar: yes"));
+        assert!(report.contains("bar exists for disconnected monitor HDMI-A-1"));
+    }
+
+    #[test]
+    fn test_report_without_monitors() {
+        let report = generate_monitor_report(&[], &[]);
+        assert_eq!(report, "0 monitor(s) connected\n");
+    }
+}
@@ -1,4 +1,9 @@
-use anyhow::Result;
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result};
 use mpris::{Event, PlaybackStatus, Player, PlayerFinder};
 use relm4::SharedState;
 
@@ -6,8 +11,12 @@ pub static MPRIS_STATE: SharedState<Option<MprisState>> = SharedState::new();
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct MprisState {
+    /// The player's D-Bus name, to control it with.
+    pub bus_name: String,
     pub title: Option<String>,
     pub artist: Option<String>,
+    /// `mpris:artUrl`, which may be a local file or on the web.
+    pub art_url: Option<String>,
     pub status: PlaybackStatus,
 }
 
@@ -30,8 +39,11 @@ pub async fn run_mpris_service() {
                             match event {
                                 Ok(event) => {
                                     // update state based on event
-                                    let new_state =
-                                        handle_player_event(&event, MPRIS_STATE.read().clone());
+                                    let new_state = handle_player_event(
+                                        &event,
+                                        MPRIS_STATE.read().clone(),
+                                        player.bus_name(),
+                                    );
 
                                     if new_state != *MPRIS_STATE.read() {
                                         *MPRIS_STATE.write() = new_state;
@@ -70,10 +82,70 @@ pub async fn run_mpris_service() {
     }
 }
 
+/// Finds the player to show, preferring one that's playing, then one that's
+/// paused.
 fn find_active_player() -> Result<Player> {
     Ok(PlayerFinder::new()?.find_active()?)
 }
 
+/// Toggles playback on the player at `bus_name`.
+pub async fn play_pause(bus_name: String) {
+    // the mpris crate blocks on D-Bus calls
+    let result = tokio::task::spawn_blocking(move || -> Result<()> {
+        let player = PlayerFinder::new()?
+            .find_all()?
+            .into_iter()
+            .find(|player| player.bus_name() == bus_name)
+            .context("player has gone away")?;
+        player.play_pause()?;
+        Ok(())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => log::error!("couldn't toggle playback: {e}"),
+        Err(e) => log::error!("couldn't toggle playback: {e}"),
+    }
+}
+
+/// Where album art from the web is kept.
+fn art_cache_dir() -> PathBuf {
+    glib::user_cache_dir().join("cadenza-shell").join("art")
+}
+
+/// Finds the album art at `art_url` as a local file, downloading it first if
+/// it's on the web. Downloads are cached by URL.
+pub async fn album_art(art_url: &str) -> Result<PathBuf> {
+    if art_url.starts_with("file://") {
+        let (path, _) = glib::filename_from_uri(art_url)?;
+        return Ok(path);
+    }
+
+    anyhow::ensure!(
+        art_url.starts_with("http://") || art_url.starts_with("https://"),
+        "unsupported art url {art_url}"
+    );
+
+    let mut hasher = DefaultHasher::new();
+    art_url.hash(&mut hasher);
+    let path = art_cache_dir().join(format!("{:016x}", hasher.finish()));
+    if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        return Ok(path);
+    }
+
+    let bytes = reqwest::get(art_url)
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    tokio::fs::create_dir_all(art_cache_dir()).await?;
+    tokio::fs::write(&path, bytes).await?;
+    log::debug!("saved album art from {art_url} to {path:?}");
+
+    Ok(path)
+}
+
 fn get_player_state(player: &Player) -> Option<MprisState> {
     let metadata = player.get_metadata().unwrap_or_default();
 
@@ -81,24 +153,33 @@ fn get_player_state(player: &Player) -> Option<MprisState> {
     let artist = metadata
         .artists()
         .and_then(|artists| artists.first().map(|s| s.to_string()));
+    let art_url = metadata.art_url().map(String::from);
 
     let status = player
         .get_playback_status()
         .unwrap_or(PlaybackStatus::Stopped);
 
     Some(MprisState {
+        bus_name: player.bus_name().to_string(),
         title,
         artist,
+        art_url,
         status,
     })
 }
 
-fn handle_player_event(event: &Event, mut current_state: Option<MprisState>) -> Option<MprisState> {
+fn handle_player_event(
+    event: &Event,
+    mut current_state: Option<MprisState>,
+    bus_name: &str,
+) -> Option<MprisState> {
     match current_state.as_mut() {
         Some(MprisState {
             title,
             artist,
+            art_url,
             status,
+            ..
         }) => {
             match event {
                 Event::Playing => *status = PlaybackStatus::Playing,
@@ -109,6 +190,7 @@ fn handle_player_event(event: &Event, mut current_state: Option<MprisState>) ->
                     *artist = metadata
                         .album_artists()
                         .and_then(|artists| artists.first().map(|s| s.to_string()));
+                    *art_url = metadata.art_url().map(String::from);
                 }
                 Event::PlayerShutDown => return None,
 
@@ -120,25 +202,33 @@ fn handle_player_event(event: &Event, mut current_state: Option<MprisState>) ->
         }
         None => match event {
             Event::Paused => Some(MprisState {
+                bus_name: bus_name.to_string(),
                 title: None,
                 artist: None,
+                art_url: None,
                 status: PlaybackStatus::Paused,
             }),
             Event::Stopped => Some(MprisState {
+                bus_name: bus_name.to_string(),
                 title: None,
                 artist: None,
+                art_url: None,
                 status: PlaybackStatus::Stopped,
             }),
             Event::Playing => Some(MprisState {
+                bus_name: bus_name.to_string(),
                 title: None,
                 artist: None,
+                art_url: None,
                 status: PlaybackStatus::Playing,
             }),
             Event::TrackChanged(metadata) => Some(MprisState {
+                bus_name: bus_name.to_string(),
                 title: metadata.title().map(String::from),
                 artist: metadata
                     .album_artists()
                     .and_then(|a| a.first().map(|s| s.to_string())),
+                art_url: metadata.art_url().map(String::from),
                 status: PlaybackStatus::Paused,
             }),
             _ => current_state,
@@ -1,8 +1,13 @@
+pub mod connect;
 pub mod dbus;
+pub mod scan;
 pub mod types;
 
+use std::time::{Duration, Instant};
+
 use futures_lite::StreamExt;
 use relm4::SharedState;
+use serde::Serialize;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use zbus::zvariant::OwnedObjectPath;
 
@@ -10,24 +15,42 @@ use crate::{
     network::{
         dbus::{
             AccessPointProxy, ActiveConnectionProxy, NetworkDeviceProxy, NetworkManagerProxy,
-            WirelessDeviceProxy,
+            WiredDeviceProxy, WirelessDeviceProxy,
         },
         types::{ConnectivityState, DeviceType, State},
     },
     sleep_monitor,
-    utils::icons::{
-        NETWORK_WIFI_DISABLED, NETWORK_WIFI_ICON_NAMES, NETWORK_WIRED_CONNECTED,
-        NETWORK_WIRED_DISABLED, percentage_to_icon_from_list,
+    utils::{
+        coalesce::{self, Coalescer},
+        icons::{
+            NETWORK_METERED, NETWORK_VPN, NETWORK_WIFI_DISABLED, NETWORK_WIFI_ICON_NAMES,
+            NETWORK_WIRED_CONNECTED, NETWORK_WIRED_DISABLED, NETWORK_WIRED_UNPLUGGED,
+            percentage_to_icon_from_list,
+        },
     },
 };
 
 pub static NETWORK_STATE: SharedState<NetworkInfo> = SharedState::new();
 
-#[derive(Debug, Clone)]
+/// How long to gather connection changes before refetching everything.
+const REFETCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the WiFi strength shown may change.
+const STRENGTH_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize)]
 pub struct NetworkInfo {
     pub connection_state: State,
     pub connectivity: ConnectivityState,
     pub specific_info: Option<SpecificNetworkInfo>,
+    /// Whether the WiFi radio is switched on in NetworkManager.
+    pub wireless_enabled: bool,
+    /// The id of the VPN or WireGuard connection that's up, with a "+N"
+    /// suffix when there are more.
+    pub vpn_active: Option<String>,
+    /// Whether NetworkManager thinks the connection is metered, e.g. a phone
+    /// hotspot, so background downloads should be avoided.
+    pub metered: bool,
 }
 
 impl Default for NetworkInfo {
@@ -36,6 +59,9 @@ impl Default for NetworkInfo {
             connection_state: State::Unknown,
             connectivity: ConnectivityState::Unknown,
             specific_info: None,
+            wireless_enabled: false,
+            vpn_active: None,
+            metered: false,
         }
     }
 }
@@ -54,13 +80,31 @@ impl NetworkInfo {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
 pub enum SpecificNetworkInfo {
+    #[serde(rename = "wifi")]
     WiFi {
         wifi_ssid: String,
         wifi_strength: u8,
     },
-    Wired,
+    Wired {
+        /// Link speed in Mb/s, if the device reports one.
+        wired_speed: Option<u32>,
+        /// Whether a cable is plugged in.
+        carrier: bool,
+    },
+}
+
+impl SpecificNetworkInfo {
+    /// Builds the wired variant from what the device reports. Virtual
+    /// interfaces often report a speed of 0, which means it's unknown.
+    fn wired(speed: u32, carrier: bool) -> Self {
+        Self::Wired {
+            wired_speed: (speed > 0).then_some(speed),
+            carrier,
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -72,6 +116,13 @@ pub enum NetworkPropertyChange {
     Primary(OwnedObjectPath),
     /// The active access point's signal strength changed.
     Strength(u8),
+    /// WiFi was switched on or off.
+    WirelessEnabled(bool),
+    /// The connection became metered or stopped being metered.
+    Metered(bool),
+    /// A connection was activated or deactivated; triggers a full refetch so
+    /// VPNs coming and going are picked up.
+    ActiveConnections,
     /// The system just woke from sleep; triggers a full refetch.
     Wake,
 }
@@ -110,49 +161,42 @@ pub async fn run_network_service() {
         log::warn!("couldn't fetch initial network state: {e}");
     }
 
-    while let Some(event) = event_rx.recv().await {
-        match event {
-            NetworkPropertyChange::State(state) => {
-                NETWORK_STATE.write().connection_state = state;
-
-                // if we just transitioned to a connected state but specific_info
-                // is still None (e.g. the wake refetch ran before NM finished
-                // reconnecting and no PrimaryConnection change will fire since
-                // we reconnected to the same network), do a full refetch now to
-                // populate the missing device details
-                if let State::ConnectedLocal | State::ConnectedSite | State::ConnectedGlobal = state
-                    && NETWORK_STATE.read().specific_info.is_none()
-                {
-                    log::debug!("connected state with no device info, refetching network state");
+    // NetworkManager fires several changes in a row while (re)connecting, so
+    // refetches and strength updates are coalesced instead of handled one by
+    // one
+    let mut refetch = Coalescer::new(REFETCH_INTERVAL);
+    let mut strength_update = Coalescer::new(STRENGTH_INTERVAL);
+
+    loop {
+        let deadlines = [refetch.deadline(), strength_update.deadline()];
+        tokio::select! {
+            event = event_rx.recv() => {
+                let Some(event) = event else {
+                    break;
+                };
+                handle_event(event, &mut refetch, &mut strength_update);
+            }
+            _ = coalesce::sleep_until(deadlines) => {
+                let now = Instant::now();
+
+                if let Some(reason) = refetch.poll(now) {
+                    log::debug!("refetching network state after {reason}");
                     if let Err(e) =
                         handle_primary_change(&conn, &event_tx, &mut strength_task).await
                     {
-                        log::warn!("couldn't refetch network info after state change: {e}");
+                        log::warn!("couldn't refetch network state after {reason}: {e}");
                     }
                 }
-            }
-            NetworkPropertyChange::Connectivity(connectivity) => {
-                NETWORK_STATE.write().connectivity = connectivity
-            }
-            NetworkPropertyChange::Primary(_) => {
-                if let Err(e) = handle_primary_change(&conn, &event_tx, &mut strength_task).await {
-                    log::error!("couldn't handle primary connection change: {e}");
-                }
-            }
-            NetworkPropertyChange::Strength(strength) => {
-                let mut state = NETWORK_STATE.write();
-                if let Some(SpecificNetworkInfo::WiFi {
-                    ref mut wifi_strength,
-                    ..
-                }) = state.specific_info
-                {
-                    *wifi_strength = strength;
-                }
-            }
-            NetworkPropertyChange::Wake => {
-                log::debug!("system wake: refreshing network state");
-                if let Err(e) = handle_primary_change(&conn, &event_tx, &mut strength_task).await {
-                    log::warn!("couldn't refresh network state after wake: {e}");
+
+                if let Some(strength) = strength_update.poll(now) {
+                    let mut state = NETWORK_STATE.write();
+                    if let Some(SpecificNetworkInfo::WiFi {
+                        ref mut wifi_strength,
+                        ..
+                    }) = state.specific_info
+                    {
+                        *wifi_strength = strength;
+                    }
                 }
             }
         }
@@ -160,6 +204,54 @@ pub async fn run_network_service() {
     log::warn!("network service has stopped receiving events");
 }
 
+/// Applies cheap changes to [`NETWORK_STATE`] right away and queues full
+/// refetches and strength updates on their coalescers.
+fn handle_event(
+    event: NetworkPropertyChange,
+    refetch: &mut Coalescer<&'static str>,
+    strength_update: &mut Coalescer<u8>,
+) {
+    let now = Instant::now();
+    match event {
+        NetworkPropertyChange::State(state) => {
+            NETWORK_STATE.write().connection_state = state;
+
+            // if we just transitioned to a connected state but specific_info
+            // is still None (e.g. the wake refetch ran before NM finished
+            // reconnecting and no PrimaryConnection change will fire since
+            // we reconnected to the same network), do a full refetch to
+            // populate the missing device details
+            if let State::ConnectedLocal | State::ConnectedSite | State::ConnectedGlobal = state
+                && NETWORK_STATE.read().specific_info.is_none()
+            {
+                refetch.push("connecting without device info", now);
+            }
+        }
+        NetworkPropertyChange::Connectivity(connectivity) => {
+            NETWORK_STATE.write().connectivity = connectivity
+        }
+        NetworkPropertyChange::Primary(_) => refetch.push("primary connection change", now),
+        NetworkPropertyChange::Strength(strength) => strength_update.push(strength, now),
+        NetworkPropertyChange::WirelessEnabled(enabled) => {
+            NETWORK_STATE.write().wireless_enabled = enabled
+        }
+        NetworkPropertyChange::Metered(metered) => NETWORK_STATE.write().metered = metered,
+        NetworkPropertyChange::ActiveConnections => refetch.push("active connections change", now),
+        NetworkPropertyChange::Wake => refetch.push("system wake", now),
+    }
+}
+
+/// Switches the WiFi radio on or off through NetworkManager. The new state
+/// reaches [`NETWORK_STATE`] through the usual property watch.
+pub async fn set_wireless_enabled(enabled: bool) -> anyhow::Result<()> {
+    let conn = zbus::Connection::system().await?;
+    NetworkManagerProxy::new(&conn)
+        .await?
+        .set_wireless_enabled(enabled)
+        .await?;
+    Ok(())
+}
+
 /// Fetches current NM state, updates [`NETWORK_STATE`], and (re)subscribes to
 /// access point signal strength changes if on WiFi.
 ///
@@ -265,6 +357,54 @@ async fn setup_property_watching() -> anyhow::Result<(
         log::warn!("stream for primary connection state changes has closed");
     });
 
+    // watch for wifi being switched on or off
+    let mut wireless_enabled_stream = nm_proxy.receive_wireless_enabled_changed().await;
+    let event_tx_clone = event_tx.clone();
+    relm4::spawn(async move {
+        while let Some(change) = wireless_enabled_stream.next().await {
+            if let Ok(enabled) = change
+                .get()
+                .await
+                .inspect_err(|e| log::error!("couldn't get wireless enabled change value: {e}"))
+            {
+                event_tx_clone
+                    .send(NetworkPropertyChange::WirelessEnabled(enabled))
+                    .unwrap_or_else(|e| log::error!("couldn't send wireless enabled change: {e}"));
+            }
+        }
+        log::warn!("stream for wireless enabled changes has closed");
+    });
+
+    // watch for the connection becoming metered or unmetered
+    let mut metered_stream = nm_proxy.receive_metered_changed().await;
+    let event_tx_clone = event_tx.clone();
+    relm4::spawn(async move {
+        while let Some(change) = metered_stream.next().await {
+            if let Ok(metered) = change
+                .get()
+                .await
+                .inspect_err(|e| log::error!("couldn't get metered change value: {e}"))
+            {
+                event_tx_clone
+                    .send(NetworkPropertyChange::Metered(is_metered(metered)))
+                    .unwrap_or_else(|e| log::error!("couldn't send metered change: {e}"));
+            }
+        }
+        log::warn!("stream for metered changes has closed");
+    });
+
+    // watch for connections coming and going, which is how VPNs show up
+    let mut active_connections_stream = nm_proxy.receive_active_connections_changed().await;
+    let event_tx_clone = event_tx.clone();
+    relm4::spawn(async move {
+        while active_connections_stream.next().await.is_some() {
+            event_tx_clone
+                .send(NetworkPropertyChange::ActiveConnections)
+                .unwrap_or_else(|e| log::error!("couldn't send active connections change: {e}"));
+        }
+        log::warn!("stream for active connection changes has closed");
+    });
+
     Ok((conn, event_tx, event_rx))
 }
 
@@ -284,6 +424,12 @@ async fn fetch_network_info(
     // get connectivity
     let connectivity = nm_proxy.connectivity().await?;
 
+    let wireless_enabled = nm_proxy.wireless_enabled().await?;
+
+    let vpn_active = vpn_summary(&fetch_active_vpns(conn, &nm_proxy).await?);
+
+    let metered = is_metered(nm_proxy.metered().await?);
+
     let is_connected = matches!(
         connection_state,
         State::ConnectedLocal | State::ConnectedSite | State::ConnectedGlobal
@@ -308,14 +454,25 @@ async fn fetch_network_info(
             let device_type = device_proxy.device_type().await?;
 
             match device_type {
-                DeviceType::Ethernet => Ok((
-                    NetworkInfo {
-                        connection_state,
-                        connectivity,
-                        specific_info: Some(SpecificNetworkInfo::Wired),
-                    },
-                    None,
-                )),
+                DeviceType::Ethernet => {
+                    let wired_proxy = WiredDeviceProxy::builder(conn)
+                        .path(device_path)?
+                        .build()
+                        .await?;
+                    let speed = wired_proxy.speed().await?;
+                    let carrier = wired_proxy.carrier().await?;
+                    Ok((
+                        NetworkInfo {
+                            connection_state,
+                            connectivity,
+                            specific_info: Some(SpecificNetworkInfo::wired(speed, carrier)),
+                            wireless_enabled,
+                            vpn_active,
+                            metered,
+                        },
+                        None,
+                    ))
+                }
                 DeviceType::Wifi => {
                     let (ssid, strength, ap_path) = get_wifi_info(conn, device_path).await?;
                     Ok((
@@ -326,6 +483,9 @@ async fn fetch_network_info(
                                 wifi_ssid: ssid,
                                 wifi_strength: strength,
                             }),
+                            wireless_enabled,
+                            vpn_active,
+                            metered,
                         },
                         Some(ap_path),
                     ))
@@ -335,6 +495,9 @@ async fn fetch_network_info(
                         connection_state,
                         connectivity,
                         specific_info: None,
+                        wireless_enabled,
+                        vpn_active,
+                        metered,
                     },
                     None,
                 )),
@@ -345,6 +508,9 @@ async fn fetch_network_info(
                     connection_state,
                     connectivity,
                     specific_info: None,
+                    wireless_enabled,
+                    vpn_active,
+                    metered,
                 },
                 None,
             ))
@@ -355,12 +521,59 @@ async fn fetch_network_info(
                 connection_state,
                 connectivity,
                 specific_info: None,
+                wireless_enabled,
+                vpn_active,
+                metered,
             },
             None,
         ))
     }
 }
 
+/// Returns the ids of the active VPN and WireGuard connections, in the order
+/// NetworkManager lists them.
+async fn fetch_active_vpns(
+    conn: &zbus::Connection,
+    nm_proxy: &NetworkManagerProxy<'_>,
+) -> anyhow::Result<Vec<String>> {
+    let mut ids = Vec::new();
+    for path in nm_proxy.active_connections().await? {
+        let proxy = ActiveConnectionProxy::builder(conn)
+            .path(&path)?
+            .build()
+            .await?;
+
+        // connections can go away while we look through them
+        let Ok(type_) = proxy.type_().await else {
+            continue;
+        };
+        if (type_ == "vpn" || type_ == "wireguard")
+            && let Ok(id) = proxy.id().await
+        {
+            ids.push(id);
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Whether NetworkManager's `NMMetered` value means metered, either because
+/// it's set that way or because NetworkManager guessed so.
+fn is_metered(metered: u32) -> bool {
+    // unknown (0), yes (1), no (2), guess yes (3), guess no (4)
+    matches!(metered, 1 | 3)
+}
+
+/// Names the first of `ids`, noting how many more there are.
+fn vpn_summary(ids: &[String]) -> Option<String> {
+    let (first, rest) = ids.split_first()?;
+    Some(if rest.is_empty() {
+        first.clone()
+    } else {
+        format!("{first} +{}", rest.len())
+    })
+}
+
 /// Returns the SSID, current strength, and object path of the active access
 /// point for the given wireless device.
 async fn get_wifi_info(
@@ -454,11 +667,126 @@ pub fn get_icon(info: &NetworkInfo) -> &str {
 
     match info.specific_info {
         Some(SpecificNetworkInfo::WiFi { wifi_strength, .. }) => get_strength_icon(wifi_strength),
-        Some(_) => NETWORK_WIRED_CONNECTED,
+        Some(SpecificNetworkInfo::Wired { carrier: false, .. }) => NETWORK_WIRED_UNPLUGGED,
+        Some(SpecificNetworkInfo::Wired { .. }) => NETWORK_WIRED_CONNECTED,
         None => NETWORK_WIFI_DISABLED,
     }
 }
 
+/// Returns the glyph to layer over [`get_icon`]'s icon, if any. Shows that a
+/// VPN is up or, failing that, that the connection is metered.
+pub fn get_overlay_icon(info: &NetworkInfo) -> Option<&'static str> {
+    if info.vpn_active.is_some() {
+        Some(NETWORK_VPN)
+    } else if info.metered {
+        Some(NETWORK_METERED)
+    } else {
+        None
+    }
+}
+
 pub fn get_strength_icon(strength: u8) -> &'static str {
     percentage_to_icon_from_list(strength as f64 / 100.0, NETWORK_WIFI_ICON_NAMES)
 }
+
+/// A plain-text summary of `info`, for `cadenza-shell network status`.
+pub fn status_report(info: &NetworkInfo) -> String {
+    let mut report = format!(
+        "state: {}\nconnectivity: {}\nwifi enabled: {}\nmetered: {}\n",
+        info.connection_state,
+        info.connectivity,
+        if info.wireless_enabled { "yes" } else { "no" },
+        if info.metered { "yes" } else { "no" }
+    );
+
+    match &info.specific_info {
+        Some(SpecificNetworkInfo::WiFi {
+            wifi_ssid,
+            wifi_strength,
+        }) => report.push_str(&format!("wifi: {wifi_ssid} ({wifi_strength}%)\n")),
+        Some(SpecificNetworkInfo::Wired { carrier: false, .. }) => {
+            report.push_str("wired: cable unplugged\n")
+        }
+        Some(SpecificNetworkInfo::Wired {
+            wired_speed: Some(speed),
+            ..
+        }) => report.push_str(&format!("wired: {speed} Mb/s\n")),
+        Some(SpecificNetworkInfo::Wired { .. }) => report.push_str("wired\n"),
+        None => {}
+    }
+
+    if let Some(vpn) = &info.vpn_active {
+        report.push_str(&format!("vpn: {vpn}\n"));
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_stable_names() {
+        let info = NetworkInfo {
+            connection_state: State::ConnectedGlobal,
+            connectivity: ConnectivityState::Portal,
+            specific_info: Some(SpecificNetworkInfo::WiFi {
+                wifi_ssid: "cafe".to_string(),
+                wifi_strength: 64,
+            }),
+            wireless_enabled: true,
+            vpn_active: Some("work".to_string()),
+            metered: false,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&info).unwrap(),
+            serde_json::json!({
+                "connection_state": "connected_global",
+                "connectivity": "portal",
+                "specific_info": {
+                    "kind": "wifi",
+                    "wifi_ssid": "cafe",
+                    "wifi_strength": 64,
+                },
+                "wireless_enabled": true,
+                "vpn_active": "work",
+                "metered": false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_wired_speed_zero_is_unknown() {
+        let info = NetworkInfo {
+            specific_info: Some(SpecificNetworkInfo::wired(0, true)),
+            ..Default::default()
+        };
+        assert!(status_report(&info).ends_with("wired\n"));
+
+        let info = NetworkInfo {
+            specific_info: Some(SpecificNetworkInfo::wired(1000, true)),
+            ..Default::default()
+        };
+        assert!(status_report(&info).ends_with("wired: 1000 Mb/s\n"));
+    }
+
+    #[test]
+    fn test_is_metered() {
+        let values = [0, 1, 2, 3, 4].map(is_metered);
+        assert_eq!(values, [false, true, false, true, false]);
+    }
+
+    #[test]
+    fn test_vpn_summary() {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(vpn_summary(&[]), None);
+        assert_eq!(vpn_summary(&ids(&["work"])), Some("work".to_string()));
+        assert_eq!(
+            vpn_summary(&ids(&["work", "home", "wg0"])),
+            Some("work +2".to_string())
+        );
+    }
+}
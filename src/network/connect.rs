@@ -0,0 +1,330 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::Context;
+use futures_lite::StreamExt;
+use zbus::zvariant::{OwnedObjectPath, Value};
+
+use crate::network::{
+    dbus::{
+        ActiveConnectionProxy, NetworkDeviceProxy, NetworkManagerProxy, SettingsConnectionProxy,
+        SettingsProxy,
+    },
+    scan::{AccessPointInfo, find_wireless_device},
+    types::{ActiveConnectionState, DeviceState},
+};
+
+/// How long to wait, once a connection has gone down, for the device to say
+/// whether it was for want of a password. NetworkManager can report the two on
+/// separate objects in either order.
+const AUTH_GRACE: Duration = Duration::from_secs(1);
+
+/// How a connection attempt ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectOutcome {
+    /// NetworkManager tried the connection and it ended up in this state,
+    /// `Activated` on success.
+    Finished(ActiveConnectionState),
+    /// The network is secured and there's no saved connection for it, so a
+    /// password is needed.
+    NeedsPassword,
+    /// The password given didn't work.
+    WrongPassword,
+}
+
+/// Connects to `ap`, reusing a saved connection for its SSID if there is one
+/// and creating one for open networks otherwise.
+///
+/// `on_state` is called as NetworkManager works through the connection, until
+/// it's either up or has failed.
+pub async fn connect(
+    ap: &AccessPointInfo,
+    on_state: impl FnMut(ActiveConnectionState),
+) -> anyhow::Result<ConnectOutcome> {
+    let conn = zbus::Connection::system().await?;
+    let nm_proxy = NetworkManagerProxy::new(&conn).await?;
+    let device_path = find_wireless_device(&conn)
+        .await?
+        .context("no wifi device found")?;
+
+    let active_path = if let Some(saved) = find_saved_connection(&conn, &ap.ssid).await? {
+        log::debug!("connecting to {} with saved connection {saved}", ap.ssid);
+        nm_proxy
+            .activate_connection(&saved, &device_path, &ap.path)
+            .await?
+    } else if !ap.secured {
+        log::debug!("connecting to open network {}", ap.ssid);
+        let (_, active_path) = nm_proxy
+            .add_and_activate_connection(
+                connection_settings(&ap.ssid, None),
+                &device_path,
+                &ap.path,
+            )
+            .await?;
+        active_path
+    } else {
+        return Ok(ConnectOutcome::NeedsPassword);
+    };
+
+    watch_activation(&conn, &active_path, None, on_state).await
+}
+
+/// Connects to the WPA-secured `ap` with `password`, saving the connection if
+/// it works.
+///
+/// If the device asks for secrets again or fails, the password is taken to
+/// be wrong and the new connection is deleted, so it isn't tried again later.
+pub async fn connect_with_password(
+    ap: &AccessPointInfo,
+    password: &str,
+    on_state: impl FnMut(ActiveConnectionState),
+) -> anyhow::Result<ConnectOutcome> {
+    let conn = zbus::Connection::system().await?;
+    let nm_proxy = NetworkManagerProxy::new(&conn).await?;
+    let device_path = find_wireless_device(&conn)
+        .await?
+        .context("no wifi device found")?;
+
+    log::debug!("connecting to {} with a password", ap.ssid);
+    let (connection_path, active_path, _) = nm_proxy
+        .add_and_activate_connection2(
+            connection_settings(&ap.ssid, Some(password)),
+            &device_path,
+            &ap.path,
+            HashMap::new(),
+        )
+        .await?;
+
+    let outcome = watch_activation(&conn, &active_path, Some(&device_path), on_state).await?;
+    if outcome != ConnectOutcome::Finished(ActiveConnectionState::Activated) {
+        // deleting the connection also stops NetworkManager from retrying it
+        let deleted = async {
+            SettingsConnectionProxy::builder(&conn)
+                .path(&connection_path)?
+                .build()
+                .await?
+                .delete()
+                .await
+        };
+        if let Err(e) = deleted.await {
+            log::warn!("couldn't delete failed connection to {}: {e}", ap.ssid);
+        }
+    }
+
+    Ok(outcome)
+}
+
+/// Finds a saved WiFi connection for `ssid`.
+async fn find_saved_connection(
+    conn: &zbus::Connection,
+    ssid: &str,
+) -> anyhow::Result<Option<OwnedObjectPath>> {
+    let settings_proxy = SettingsProxy::new(conn).await?;
+    for path in settings_proxy.list_connections().await? {
+        let connection_proxy = SettingsConnectionProxy::builder(conn)
+            .path(&path)?
+            .build()
+            .await?;
+
+        // connections can be removed while we look through them
+        let Ok(mut settings) = connection_proxy.get_settings().await else {
+            continue;
+        };
+        let Some(saved_ssid) = settings
+            .get_mut("802-11-wireless")
+            .and_then(|wireless| wireless.remove("ssid"))
+        else {
+            continue;
+        };
+
+        if Vec::<u8>::try_from(saved_ssid).is_ok_and(|saved_ssid| saved_ssid == ssid.as_bytes()) {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+/// The least NetworkManager needs to add a connection to an open network, or
+/// a WPA-PSK one with `password`. The rest is filled in from the access
+/// point.
+fn connection_settings(
+    ssid: &str,
+    password: Option<&str>,
+) -> HashMap<String, HashMap<String, Value<'static>>> {
+    let mut wireless = HashMap::from([("ssid".to_string(), Value::from(ssid.as_bytes().to_vec()))]);
+    let mut settings = HashMap::from([(
+        "connection".to_string(),
+        HashMap::from([
+            ("type".to_string(), Value::from("802-11-wireless")),
+            ("id".to_string(), Value::from(ssid.to_string())),
+        ]),
+    )]);
+
+    if let Some(password) = password {
+        wireless.insert(
+            "security".to_string(),
+            Value::from("802-11-wireless-security"),
+        );
+        settings.insert(
+            "802-11-wireless-security".to_string(),
+            HashMap::from([
+                ("key-mgmt".to_string(), Value::from("wpa-psk")),
+                ("psk".to_string(), Value::from(password.to_string())),
+            ]),
+        );
+    }
+
+    settings.insert("802-11-wireless".to_string(), wireless);
+    settings
+}
+
+/// Reports the active connection's state until it's up or has gone away.
+///
+/// With `device_path`, also watches the device, and gives up with
+/// [`ConnectOutcome::WrongPassword`] once it asks for secrets or fails after
+/// starting to connect. That's decided by the device's latest state, even if
+/// the connection is reported down first.
+async fn watch_activation(
+    conn: &zbus::Connection,
+    active_path: &OwnedObjectPath,
+    device_path: Option<&OwnedObjectPath>,
+    mut on_state: impl FnMut(ActiveConnectionState),
+) -> anyhow::Result<ConnectOutcome> {
+    let proxy = ActiveConnectionProxy::builder(conn)
+        .path(active_path)?
+        .build()
+        .await?;
+    let mut state_changes = proxy.receive_state_changed().await;
+
+    let mut device = DeviceWatch::default();
+    let mut device_changes = match device_path {
+        Some(path) => {
+            let device_proxy = NetworkDeviceProxy::builder(conn)
+                .path(path)?
+                .build()
+                .await?;
+            let changes = device_proxy.receive_state_changed().await;
+            // the device may be partway through already, and property changes
+            // can be merged, so the steps before this may never be seen
+            if device.rejects(device_proxy.state().await?) {
+                on_state(ActiveConnectionState::Deactivated);
+                return Ok(ConnectOutcome::WrongPassword);
+            }
+            Some(changes)
+        }
+        None => None,
+    };
+
+    let mut state = proxy.state().await?;
+    on_state(state);
+    loop {
+        match state {
+            ActiveConnectionState::Activated => return Ok(ConnectOutcome::Finished(state)),
+            ActiveConnectionState::Deactivated => {
+                // the device may not have said why yet
+                if let Some(changes) = &mut device_changes {
+                    let rejected = async {
+                        while let Some(change) = changes.next().await {
+                            if let Ok(device_state) = change.get().await
+                                && device.rejects(device_state)
+                            {
+                                return true;
+                            }
+                        }
+                        false
+                    };
+                    if tokio::time::timeout(AUTH_GRACE, rejected)
+                        .await
+                        .unwrap_or(false)
+                    {
+                        return Ok(ConnectOutcome::WrongPassword);
+                    }
+                }
+                return Ok(ConnectOutcome::Finished(state));
+            }
+            _ => {}
+        }
+
+        let next_device_state = async {
+            match &mut device_changes {
+                Some(changes) => match changes.next().await {
+                    Some(change) => change.get().await.ok(),
+                    None => std::future::pending().await,
+                },
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            // a device change and the connection going down can arrive
+            // together, and the device's says why
+            biased;
+
+            Some(device_state) = next_device_state => {
+                if device.rejects(device_state) {
+                    on_state(ActiveConnectionState::Deactivated);
+                    return Ok(ConnectOutcome::WrongPassword);
+                }
+            }
+            // NetworkManager removes active connections that fail, which
+            // ends the stream
+            change = state_changes.next() => {
+                state = match change {
+                    Some(change) => change
+                        .get()
+                        .await
+                        .unwrap_or(ActiveConnectionState::Deactivated),
+                    None => ActiveConnectionState::Deactivated,
+                };
+                on_state(state);
+            }
+        }
+    }
+}
+
+/// What's been seen of the device while connecting.
+#[derive(Debug, Default)]
+struct DeviceWatch {
+    /// Whether the device has started on this connection. Until then, a
+    /// failure may be how its previous connection ended.
+    started: bool,
+}
+
+impl DeviceWatch {
+    /// Takes in the device's latest state, returning whether it means the
+    /// password was refused.
+    fn rejects(&mut self, state: DeviceState) -> bool {
+        match state {
+            // only ever asked while connecting, so it's about this connection
+            DeviceState::NeedAuth => true,
+            DeviceState::Failed => self.started,
+            DeviceState::Prepare
+            | DeviceState::Config
+            | DeviceState::IpConfig
+            | DeviceState::IpCheck
+            | DeviceState::Secondaries => {
+                self.started = true;
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_watch_rejects() {
+        // a failure left over from the previous connection doesn't count
+        let mut device = DeviceWatch::default();
+        assert!(!device.rejects(DeviceState::Failed));
+        assert!(!device.rejects(DeviceState::Config));
+        assert!(device.rejects(DeviceState::Failed));
+
+        // asking for secrets does, even if the earlier steps were missed
+        let mut device = DeviceWatch::default();
+        assert!(device.rejects(DeviceState::NeedAuth));
+    }
+}
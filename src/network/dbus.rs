@@ -2,7 +2,9 @@ use std::collections::HashMap;
 
 use zbus::{proxy, zvariant};
 
-use crate::network::types::{ConnectivityState, DeviceState, DeviceStateReason, DeviceType, State};
+use crate::network::types::{
+    ActiveConnectionState, ConnectivityState, DeviceState, DeviceStateReason, DeviceType, State,
+};
 
 #[proxy(
     interface = "org.freedesktop.NetworkManager",
@@ -249,13 +251,23 @@ pub trait WirelessDevice {
     #[zbus(property)]
     fn active_access_point(&self) -> zbus::Result<zvariant::OwnedObjectPath>;
 
-    /// Request the device to scan.
-    fn request_scan(&self) -> zbus::Result<()>;
+    /// Request the device to scan. No options are needed to scan for every
+    /// SSID.
+    fn request_scan(&self, options: HashMap<&str, zvariant::Value<'_>>) -> zbus::Result<()>;
+
+    /// Get the list of all access points visible to this device, including
+    /// hidden ones for which the SSID is not yet known.
+    fn get_all_access_points(&self) -> zbus::Result<Vec<zvariant::OwnedObjectPath>>;
 
     /// The bit rate currently used by the wireless device, in kilobits/second
     /// (Kb/s).
     #[zbus(property)]
     fn bitrate(&self) -> zbus::Result<u32>;
+
+    /// When the last scan finished, in CLOCK_BOOTTIME milliseconds, or -1 if
+    /// the device hasn't scanned yet.
+    #[zbus(property)]
+    fn last_scan(&self) -> zbus::Result<i64>;
 }
 
 #[proxy(
@@ -355,9 +367,32 @@ pub trait ActiveConnection {
 
     /// The state of this active connection.
     #[zbus(property)]
-    fn state(&self) -> zbus::Result<u32>;
+    fn state(&self) -> zbus::Result<ActiveConnectionState>;
 
     /// The path of the connection object.
     #[zbus(property)]
     fn connection(&self) -> zbus::Result<zvariant::OwnedObjectPath>;
 }
+
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Settings",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager/Settings"
+)]
+pub trait Settings {
+    /// List the saved network connections known to NetworkManager.
+    fn list_connections(&self) -> zbus::Result<Vec<zvariant::OwnedObjectPath>>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Settings.Connection",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+pub trait SettingsConnection {
+    /// Get the settings maps describing this network configuration. Secrets
+    /// are left out.
+    fn get_settings(&self) -> zbus::Result<HashMap<String, HashMap<String, zvariant::OwnedValue>>>;
+
+    /// Delete the connection.
+    fn delete(&self) -> zbus::Result<()>;
+}
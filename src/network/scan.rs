@@ -0,0 +1,275 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::Context;
+use futures_lite::StreamExt;
+use zbus::zvariant::OwnedObjectPath;
+
+use crate::network::{
+    dbus::{AccessPointProxy, NetworkDeviceProxy, NetworkManagerProxy, WirelessDeviceProxy},
+    types::DeviceType,
+};
+
+/// How long to wait for a requested scan before listing what's been found so
+/// far. Scans usually take a few seconds.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The radio band an access point is broadcasting on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Band {
+    TwoPointFour,
+    Five,
+    Six,
+}
+
+impl Band {
+    /// Maps a channel frequency in MHz to its band, or `None` if the frequency
+    /// doesn't fall within a known WiFi band.
+    pub fn from_frequency(frequency: u32) -> Option<Self> {
+        match frequency {
+            2400..=2500 => Some(Self::TwoPointFour),
+            // 5.9 GHz channels end at 5925 MHz, where 6 GHz begins
+            4900..=5924 => Some(Self::Five),
+            5925..=7125 => Some(Self::Six),
+            _ => None,
+        }
+    }
+
+    /// A short label for display in band chips.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::TwoPointFour => "2.4",
+            Self::Five => "5",
+            Self::Six => "6",
+        }
+    }
+}
+
+/// A single access point (one BSSID) found in scan results.
+#[derive(Debug, Clone)]
+pub struct AccessPointInfo {
+    pub path: OwnedObjectPath,
+    pub ssid: String,
+    pub bssid: String,
+    pub frequency: u32,
+    pub strength: u8,
+    pub secured: bool,
+}
+
+impl AccessPointInfo {
+    pub fn band(&self) -> Option<Band> {
+        Band::from_frequency(self.frequency)
+    }
+}
+
+/// All access points sharing one SSID, e.g. the 2.4 and 5 GHz radios of a
+/// dual-band router. Access points are ordered strongest first.
+#[derive(Debug, Clone)]
+pub struct WifiNetwork {
+    pub ssid: String,
+    pub access_points: Vec<AccessPointInfo>,
+}
+
+impl WifiNetwork {
+    /// The access point we connect to by default.
+    pub fn strongest(&self) -> &AccessPointInfo {
+        &self.access_points[0]
+    }
+
+    pub fn strength(&self) -> u8 {
+        self.strongest().strength
+    }
+
+    pub fn secured(&self) -> bool {
+        self.access_points.iter().any(|ap| ap.secured)
+    }
+
+    /// The distinct bands this network is available on, in ascending order.
+    pub fn bands(&self) -> Vec<Band> {
+        let mut bands: Vec<Band> = self
+            .access_points
+            .iter()
+            .filter_map(|ap| ap.band())
+            .collect();
+        bands.sort();
+        bands.dedup();
+        bands
+    }
+}
+
+/// Groups access points by SSID, keeping every BSSID so the user can still
+/// pick a specific band. Networks are sorted by their strongest access point,
+/// descending. Hidden networks (empty SSIDs) are dropped.
+pub fn group_by_ssid(access_points: Vec<AccessPointInfo>) -> Vec<WifiNetwork> {
+    let mut groups: HashMap<String, Vec<AccessPointInfo>> = HashMap::new();
+    for ap in access_points {
+        if ap.ssid.trim().is_empty() {
+            continue;
+        }
+        groups.entry(ap.ssid.clone()).or_default().push(ap);
+    }
+
+    let mut networks: Vec<WifiNetwork> = groups
+        .into_iter()
+        .map(|(ssid, mut access_points)| {
+            access_points.sort_by(|a, b| b.strength.cmp(&a.strength));
+            WifiNetwork {
+                ssid,
+                access_points,
+            }
+        })
+        .collect();
+
+    networks.sort_by(|a, b| {
+        b.strength()
+            .cmp(&a.strength())
+            .then_with(|| a.ssid.cmp(&b.ssid))
+    });
+    networks
+}
+
+/// Asks the first WiFi device to scan, waits for the scan to finish, and
+/// returns every network it can see, grouped by SSID (see [`group_by_ssid`]).
+///
+/// If NetworkManager won't scan right now (e.g. it scanned moments ago), the
+/// results of the last scan are returned instead.
+pub async fn scan() -> anyhow::Result<Vec<WifiNetwork>> {
+    let conn = zbus::Connection::system().await?;
+    let device_path = find_wireless_device(&conn)
+        .await?
+        .context("no wifi device found")?;
+
+    let wifi_proxy = WirelessDeviceProxy::builder(&conn)
+        .path(&device_path)?
+        .build()
+        .await?;
+
+    let previous_scan = wifi_proxy.last_scan().await?;
+    let mut last_scan_changes = wifi_proxy.receive_last_scan_changed().await;
+
+    match wifi_proxy.request_scan(HashMap::new()).await {
+        Ok(()) => {
+            let finished = async {
+                while let Some(change) = last_scan_changes.next().await {
+                    if change.get().await.is_ok_and(|last| last != previous_scan) {
+                        break;
+                    }
+                }
+            };
+            if tokio::time::timeout(SCAN_TIMEOUT, finished).await.is_err() {
+                log::warn!("wifi scan didn't finish in time, listing what's been found so far");
+            }
+        }
+        Err(e) => log::debug!("couldn't request a wifi scan, listing the last results: {e}"),
+    }
+
+    let access_points = fetch_access_points(&conn, &device_path).await?;
+    Ok(group_by_ssid(access_points))
+}
+
+/// Finds the first WiFi device NetworkManager knows about.
+pub async fn find_wireless_device(
+    conn: &zbus::Connection,
+) -> anyhow::Result<Option<OwnedObjectPath>> {
+    let nm_proxy = NetworkManagerProxy::new(conn).await?;
+    for path in nm_proxy.get_devices().await? {
+        let device_proxy = NetworkDeviceProxy::builder(conn)
+            .path(&path)?
+            .build()
+            .await?;
+        if device_proxy.device_type().await? == DeviceType::Wifi {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Reads every access point currently visible to the given wireless device.
+///
+/// Access points that vanish between listing and reading their properties are
+/// skipped rather than failing the whole scan.
+pub async fn fetch_access_points(
+    conn: &zbus::Connection,
+    device_path: &OwnedObjectPath,
+) -> anyhow::Result<Vec<AccessPointInfo>> {
+    let wifi_proxy = WirelessDeviceProxy::builder(conn)
+        .path(device_path)?
+        .build()
+        .await?;
+
+    let mut access_points = Vec::new();
+    for path in wifi_proxy.get_all_access_points().await? {
+        match fetch_access_point(conn, path).await {
+            Ok(ap) => access_points.push(ap),
+            Err(e) => log::debug!("skipping access point: {e}"),
+        }
+    }
+
+    Ok(access_points)
+}
+
+async fn fetch_access_point(
+    conn: &zbus::Connection,
+    path: OwnedObjectPath,
+) -> anyhow::Result<AccessPointInfo> {
+    let proxy = AccessPointProxy::builder(conn).path(&path)?.build().await?;
+
+    let ssid = String::from_utf8_lossy(&proxy.ssid().await?).to_string();
+    let secured =
+        proxy.flags().await? != 0 || proxy.wpa_flags().await? != 0 || proxy.rsn_flags().await? != 0;
+
+    Ok(AccessPointInfo {
+        ssid,
+        bssid: proxy.hw_address().await?,
+        frequency: proxy.frequency().await?,
+        strength: proxy.strength().await?,
+        secured,
+        path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ap(ssid: &str, bssid: &str, frequency: u32, strength: u8) -> AccessPointInfo {
+        AccessPointInfo {
+            path: OwnedObjectPath::try_from(format!(
+                "/org/freedesktop/NetworkManager/AccessPoint/{}",
+                bssid.replace(':', "")
+            ))
+            .unwrap(),
+            ssid: ssid.to_string(),
+            bssid: bssid.to_string(),
+            frequency,
+            strength,
+            secured: true,
+        }
+    }
+
+    #[test]
+    fn test_band_from_frequency() {
+        assert_eq!(Band::from_frequency(2412), Some(Band::TwoPointFour));
+        assert_eq!(Band::from_frequency(5180), Some(Band::Five));
+        assert_eq!(Band::from_frequency(5955), Some(Band::Six));
+        assert_eq!(Band::from_frequency(60480), None);
+    }
+
+    #[test]
+    fn test_group_by_ssid_keeps_each_band() {
+        let networks = group_by_ssid(vec![
+            ap("home", "aa:00:00:00:00:01", 2437, 80),
+            ap("home", "aa:00:00:00:00:02", 5180, 65),
+            ap("cafe", "bb:00:00:00:00:01", 2412, 90),
+            ap("", "cc:00:00:00:00:01", 2412, 99),
+        ]);
+
+        assert_eq!(networks.len(), 2);
+        assert_eq!(networks[0].ssid, "cafe");
+
+        let home = &networks[1];
+        assert_eq!(home.access_points.len(), 2);
+        assert_eq!(home.strongest().bssid, "aa:00:00:00:00:01");
+        assert_eq!(home.bands(), vec![Band::TwoPointFour, Band::Five]);
+    }
+}
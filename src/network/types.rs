@@ -1,11 +1,13 @@
 // temporary until we figure things out
 #![allow(dead_code)]
 
-use std::fmt;
+use std::{fmt, str::FromStr};
 
+use serde::Serialize;
 use zbus::zvariant::OwnedValue;
 
-#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum State {
     #[default]
     /// Networking state is unknown. This indicates a daemon error that makes it
@@ -51,6 +53,19 @@ pub enum State {
     ConnectedGlobal = 70,
 }
 
+impl State {
+    pub const ALL: [Self; 8] = [
+        Self::Unknown,
+        Self::Asleep,
+        Self::Disconnected,
+        Self::Disconnecting,
+        Self::Connecting,
+        Self::ConnectedLocal,
+        Self::ConnectedSite,
+        Self::ConnectedGlobal,
+    ];
+}
+
 impl From<u32> for State {
     fn from(value: u32) -> Self {
         match value {
@@ -90,6 +105,14 @@ impl fmt::Display for State {
     }
 }
 
+impl FromStr for State {
+    type Err = UnknownLabel;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_label(&Self::ALL, s)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum DeviceType {
     #[default]
@@ -247,6 +270,96 @@ impl From<OwnedValue> for DeviceType {
     }
 }
 
+impl DeviceType {
+    pub const ALL: [Self; 35] = [
+        Self::Unknown,
+        Self::Generic,
+        Self::Ethernet,
+        Self::Wifi,
+        Self::Unused1,
+        Self::Unused2,
+        Self::Bt,
+        Self::OlpcMesh,
+        Self::Wimax,
+        Self::Modem,
+        Self::Infiniband,
+        Self::Bond,
+        Self::Vlan,
+        Self::Adsl,
+        Self::Bridge,
+        Self::Team,
+        Self::Tun,
+        Self::IpTunnel,
+        Self::Macvlan,
+        Self::Vxlan,
+        Self::Veth,
+        Self::Macsec,
+        Self::Dummy,
+        Self::Ppp,
+        Self::OvsInterface,
+        Self::OvsPort,
+        Self::OvsBridge,
+        Self::Wpan,
+        Self::Lowpan6,
+        Self::Wireguard,
+        Self::WifiP2p,
+        Self::Vrf,
+        Self::Loopback,
+        Self::Hsr,
+        Self::Ipvlan,
+    ];
+}
+
+impl fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Unknown => "Unknown device",
+            Self::Generic => "Generic",
+            Self::Ethernet => "Ethernet",
+            Self::Wifi => "Wi-Fi",
+            Self::Unused1 => "Unused (3)",
+            Self::Unused2 => "Unused (4)",
+            Self::Bt => "Bluetooth",
+            Self::OlpcMesh => "OLPC mesh",
+            Self::Wimax => "WiMAX",
+            Self::Modem => "Modem",
+            Self::Infiniband => "InfiniBand",
+            Self::Bond => "Bond",
+            Self::Vlan => "VLAN",
+            Self::Adsl => "ADSL",
+            Self::Bridge => "Bridge",
+            Self::Team => "Team",
+            Self::Tun => "TUN/TAP",
+            Self::IpTunnel => "IP tunnel",
+            Self::Macvlan => "MACVLAN",
+            Self::Vxlan => "VXLAN",
+            Self::Veth => "Virtual ethernet",
+            Self::Macsec => "MACsec",
+            Self::Dummy => "Dummy",
+            Self::Ppp => "PPP",
+            Self::OvsInterface => "Open vSwitch interface",
+            Self::OvsPort => "Open vSwitch port",
+            Self::OvsBridge => "Open vSwitch bridge",
+            Self::Wpan => "WPAN",
+            Self::Lowpan6 => "6LoWPAN",
+            Self::Wireguard => "WireGuard",
+            Self::WifiP2p => "Wi-Fi P2P",
+            Self::Vrf => "VRF",
+            Self::Loopback => "Loopback",
+            Self::Hsr => "HSR/PRP",
+            Self::Ipvlan => "IPVLAN",
+        })
+    }
+}
+
+impl FromStr for DeviceType {
+    type Err = UnknownLabel;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_label(&Self::ALL, s)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum DeviceState {
     #[default]
@@ -338,6 +451,52 @@ impl From<OwnedValue> for DeviceState {
     }
 }
 
+impl DeviceState {
+    pub const ALL: [Self; 13] = [
+        Self::Unknown,
+        Self::Unmanaged,
+        Self::Unavailable,
+        Self::Disconnected,
+        Self::Prepare,
+        Self::Config,
+        Self::NeedAuth,
+        Self::IpConfig,
+        Self::IpCheck,
+        Self::Secondaries,
+        Self::Activated,
+        Self::Deactivating,
+        Self::Failed,
+    ];
+}
+
+impl fmt::Display for DeviceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Unknown => "State unknown",
+            Self::Unmanaged => "Unmanaged",
+            Self::Unavailable => "Unavailable",
+            Self::Disconnected => "Disconnected",
+            Self::Prepare => "Preparing",
+            Self::Config => "Configuring",
+            Self::NeedAuth => "Needs authentication",
+            Self::IpConfig => "Getting an address",
+            Self::IpCheck => "Checking connectivity",
+            Self::Secondaries => "Waiting for secondary connections",
+            Self::Activated => "Connected",
+            Self::Deactivating => "Disconnecting",
+            Self::Failed => "Failed",
+        })
+    }
+}
+
+impl FromStr for DeviceState {
+    type Err = UnknownLabel;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_label(&Self::ALL, s)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum DeviceStateReason {
     #[default]
@@ -664,7 +823,9 @@ impl From<OwnedValue> for DeviceStateReason {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum ActiveConnectionState {
+    #[default]
     /// The state of the connection is unknown.
     Unknown = 0,
 
@@ -681,6 +842,27 @@ pub enum ActiveConnectionState {
     Deactivated = 4,
 }
 
+impl From<u32> for ActiveConnectionState {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Self::Activating,
+            2 => Self::Activated,
+            3 => Self::Deactivating,
+            4 => Self::Deactivated,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl From<OwnedValue> for ActiveConnectionState {
+    fn from(value: OwnedValue) -> Self {
+        value
+            .downcast_ref::<u32>()
+            .map(Self::from)
+            .unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum ActiveConnectionStateReason {
     #[default]
@@ -764,7 +946,8 @@ impl From<OwnedValue> for ActiveConnectionStateReason {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ConnectivityState {
     #[default]
 
@@ -797,6 +980,16 @@ pub enum ConnectivityState {
     Full = 4,
 }
 
+impl ConnectivityState {
+    pub const ALL: [Self; 5] = [
+        Self::Unknown,
+        Self::None,
+        Self::Portal,
+        Self::Limited,
+        Self::Full,
+    ];
+}
+
 impl From<u32> for ConnectivityState {
     fn from(value: u32) -> Self {
         match value {
@@ -829,3 +1022,63 @@ impl fmt::Display for ConnectivityState {
         })
     }
 }
+
+impl FromStr for ConnectivityState {
+    type Err = UnknownLabel;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_label(&Self::ALL, s)
+    }
+}
+
+/// Returned when parsing one of these enums from text that isn't how any of
+/// its variants display.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownLabel(pub String);
+
+impl fmt::Display for UnknownLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown label '{}'", self.0)
+    }
+}
+
+impl std::error::Error for UnknownLabel {}
+
+/// Finds the variant in `all` that displays as `s`, so parsing always
+/// round-trips with `Display`.
+fn parse_label<T: Copy + fmt::Display>(all: &[T], s: &str) -> Result<T, UnknownLabel> {
+    all.iter()
+        .copied()
+        .find(|variant| variant.to_string() == s)
+        .ok_or_else(|| UnknownLabel(s.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_round_trips<T>(all: &[T])
+    where
+        T: Copy + fmt::Debug + fmt::Display + FromStr<Err = UnknownLabel> + PartialEq,
+    {
+        for &variant in all {
+            assert_eq!(variant.to_string().parse(), Ok(variant));
+        }
+    }
+
+    #[test]
+    fn test_labels_round_trip() {
+        assert_round_trips(&State::ALL);
+        assert_round_trips(&ConnectivityState::ALL);
+        assert_round_trips(&DeviceType::ALL);
+        assert_round_trips(&DeviceState::ALL);
+    }
+
+    #[test]
+    fn test_unknown_label() {
+        assert_eq!(
+            "Very connected".parse::<State>(),
+            Err(UnknownLabel("Very connected".to_string()))
+        );
+    }
+}
@@ -4,35 +4,61 @@
 use gtk4::prelude::*;
 use relm4::prelude::*;
 
-use crate::network::{NETWORK_STATE, NetworkInfo, dbus::AccessPointProxy, get_icon};
+use crate::network::{
+    self, NETWORK_STATE, NetworkInfo, SpecificNetworkInfo,
+    connect::{self, ConnectOutcome},
+    get_icon, get_strength_icon,
+    scan::{self, AccessPointInfo, WifiNetwork},
+    types::ActiveConnectionState,
+};
 
 #[derive(Debug)]
 pub struct NetworkMenu {
     network_state: NetworkInfo,
-    show_password_dialog: Option<String>, // SSID requiring password
+    show_password_dialog: Option<AccessPointInfo>, // access point requiring password
+    /// Whether the password dialog is back because the last password failed.
+    password_rejected: bool,
     access_points: AsyncFactoryVecDeque<AccessPointWidget>,
     scanning: bool,
+    /// Whether a WiFi toggle is waiting on NetworkManager.
+    toggling_wifi: bool,
+    /// The network last connected to from this menu and how that's going.
+    activation: Option<(String, ActiveConnectionState)>,
 }
 
 #[derive(Debug)]
 pub enum NetworkMenuMsg {
     ToggleWifi(bool),
+    /// A WiFi toggle finished, carrying whether WiFi ended up enabled.
+    WifiToggled(bool),
     ScanNetworks,
-    ConnectToNetwork(String),   // SSID
-    ShowPasswordDialog(String), // SSID
+    /// A scan finished, with the networks found if it succeeded.
+    NetworksScanned(Option<Vec<WifiNetwork>>),
+    ConnectToNetwork(AccessPointInfo),
+    /// A connection started from the menu changed state.
+    ActivationChanged(String, ActiveConnectionState),
+    ShowPasswordDialog(AccessPointInfo),
     HidePasswordDialog,
-    ConnectWithPassword(String, String), // SSID, Password
+    /// Submits the password dialog for the access point it was opened for.
+    SubmitPassword(String),
+    ConnectWithPassword(AccessPointInfo, String),
+    /// The password given for this access point didn't work; asks again.
+    PasswordRejected(AccessPointInfo),
     UpdateState(NetworkInfo),
 }
 
 pub struct NetworkMenuWidgets {
     wifi_icon: gtk::Image,
     wifi_switch: gtk::Switch,
-    ssid_label: gtk::Label,
+    refresh_button: gtk::Button,
+    scan_spinner: gtk::Spinner,
+    link_label: gtk::Label,
     connectivity_label: gtk::Label,
     connection_state_label: gtk::Label,
+    vpn_label: gtk::Label,
     password_dialog_box: gtk::Box,
     password_dialog_label: gtk::Label,
+    password_error_label: gtk::Label,
     password_entry: gtk::Entry,
     connect_button: gtk::Button,
 }
@@ -61,10 +87,7 @@ impl SimpleComponent for NetworkMenu {
         let access_points = AsyncFactoryVecDeque::builder()
             .launch(gtk::Box::default())
             .forward(sender.input_sender(), |output| match output {
-                AccessPointOutput::Connect(ssid) => NetworkMenuMsg::ConnectToNetwork(ssid),
-                AccessPointOutput::RequestPassword(ssid) => {
-                    NetworkMenuMsg::ShowPasswordDialog(ssid)
-                }
+                AccessPointOutput::Connect(ap) => NetworkMenuMsg::ConnectToNetwork(ap),
             });
 
         NETWORK_STATE.subscribe(sender.input_sender(), |state| {
@@ -89,7 +112,7 @@ impl SimpleComponent for NetworkMenu {
             .build();
 
         let wifi_switch = gtk::Switch::builder()
-            .active(!current_state.is_asleep())
+            .active(current_state.wireless_enabled)
             .halign(gtk::Align::End)
             .valign(gtk::Align::End)
             .build();
@@ -102,7 +125,24 @@ impl SimpleComponent for NetworkMenu {
             }
         });
 
+        let scan_spinner = gtk::Spinner::builder()
+            .valign(gtk::Align::Center)
+            .visible(false)
+            .build();
+
+        let refresh_button = gtk::Button::builder()
+            .icon_name("view-refresh-symbolic")
+            .tooltip_text("Scan for networks")
+            .valign(gtk::Align::Center)
+            .build();
+        refresh_button.connect_clicked({
+            let sender = sender.clone();
+            move |_| sender.input(NetworkMenuMsg::ScanNetworks)
+        });
+
         header_box.append(&wifi_icon);
+        header_box.append(&scan_spinner);
+        header_box.append(&refresh_button);
         header_box.append(&wifi_switch);
 
         // scrollable content
@@ -123,15 +163,10 @@ impl SimpleComponent for NetworkMenu {
             .spacing(4)
             .build();
 
-        let ssid_label = gtk::Label::builder()
+        let link_label = gtk::Label::builder()
             .halign(gtk::Align::Start)
-            .visible(current_state.wifi_ssid().is_some())
-            .label(
-                current_state
-                    .wifi_ssid()
-                    .map(|ssid| format!("Connected to {}", ssid))
-                    .unwrap_or_default(),
-            )
+            .visible(link_status(&current_state).is_some())
+            .label(link_status(&current_state).unwrap_or_default())
             .build();
 
         let connectivity_label = gtk::Label::builder()
@@ -144,9 +179,16 @@ impl SimpleComponent for NetworkMenu {
             .label(current_state.connection_state.to_string())
             .build();
 
-        status_box.append(&ssid_label);
+        status_box.append(&link_label);
         status_box.append(&connectivity_label);
+        let vpn_label = gtk::Label::builder()
+            .halign(gtk::Align::Start)
+            .visible(current_state.vpn_active.is_some())
+            .label(vpn_status(&current_state))
+            .build();
+
         status_box.append(&connection_state_label);
+        status_box.append(&vpn_label);
 
         // password dialog box (initially hidden)
         let password_dialog_box = gtk::Box::builder()
@@ -157,10 +199,20 @@ impl SimpleComponent for NetworkMenu {
 
         let password_dialog_label = gtk::Label::new(None);
 
+        let password_error_label = gtk::Label::builder()
+            .label("That password didn't work")
+            .css_classes(["error"])
+            .visible(false)
+            .build();
+
         let password_entry = gtk::Entry::builder()
             .visibility(false)
             .placeholder_text("Password")
             .build();
+        password_entry.connect_activate({
+            let sender = sender.clone();
+            move |entry| sender.input(NetworkMenuMsg::SubmitPassword(entry.text().to_string()))
+        });
 
         let dialog_buttons_box = gtk::Box::builder().spacing(8).build();
 
@@ -178,11 +230,7 @@ impl SimpleComponent for NetworkMenu {
             let password_entry = password_entry.clone();
             move |_| {
                 let password = password_entry.text().to_string();
-                // TODO: need to get SSID from model state
-                sender.input(NetworkMenuMsg::ConnectWithPassword(
-                    "current_ssid".to_string(),
-                    password,
-                ));
+                sender.input(NetworkMenuMsg::SubmitPassword(password));
             }
         });
 
@@ -190,32 +238,47 @@ impl SimpleComponent for NetworkMenu {
         dialog_buttons_box.append(&connect_button);
 
         password_dialog_box.append(&password_dialog_label);
+        password_dialog_box.append(&password_error_label);
         password_dialog_box.append(&password_entry);
         password_dialog_box.append(&dialog_buttons_box);
 
         content_box.append(&status_box);
         content_box.append(&password_dialog_box);
+        content_box.append(access_points.widget());
 
         scrolled_window.set_child(Some(&content_box));
 
         root.append(&header_box);
         root.append(&scrolled_window);
 
+        // look for networks each time the menu is opened
+        root.connect_map({
+            let sender = sender.clone();
+            move |_| sender.input(NetworkMenuMsg::ScanNetworks)
+        });
+
         let model = NetworkMenu {
             network_state: current_state,
             show_password_dialog: None,
+            password_rejected: false,
             access_points,
             scanning: false,
+            toggling_wifi: false,
+            activation: None,
         };
 
         let widgets = NetworkMenuWidgets {
             wifi_icon,
             wifi_switch,
-            ssid_label,
+            refresh_button,
+            scan_spinner,
+            link_label,
             connectivity_label,
             connection_state_label,
+            vpn_label,
             password_dialog_box,
             password_dialog_label,
+            password_error_label,
             password_entry,
             connect_button,
         };
@@ -223,33 +286,114 @@ impl SimpleComponent for NetworkMenu {
         ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         match msg {
             NetworkMenuMsg::UpdateState(state) => {
-                self.network_state = state;
+                // once we've moved on from a network connected to here, show
+                // the overall state again
+                if let Some((ssid, ActiveConnectionState::Activated)) = &self.activation
+                    && state.wifi_ssid().as_ref() != Some(ssid)
+                {
+                    self.activation = None;
+                }
+
+                // keep showing where the switch was flipped to until the
+                // toggle finishes
+                let wireless_enabled = if self.toggling_wifi {
+                    self.network_state.wireless_enabled
+                } else {
+                    state.wireless_enabled
+                };
+                self.network_state = NetworkInfo {
+                    wireless_enabled,
+                    ..state
+                };
             }
             NetworkMenuMsg::ToggleWifi(enabled) => {
-                // TODO: implement actual wifi toggle
-                todo!("toggle wifi: {}", enabled);
+                // the switch also fires when update_view syncs it
+                if self.toggling_wifi || enabled == self.network_state.wireless_enabled {
+                    return;
+                }
+
+                self.toggling_wifi = true;
+                self.network_state.wireless_enabled = enabled;
+
+                let input = sender.input_sender().clone();
+                sender.oneshot_command(async move {
+                    // flip the switch back if NetworkManager refused
+                    let now_enabled = match network::set_wireless_enabled(enabled).await {
+                        Ok(()) => enabled,
+                        Err(e) => {
+                            let action = if enabled { "enable" } else { "disable" };
+                            log::error!("couldn't {action} wifi: {e}");
+                            !enabled
+                        }
+                    };
+                    input.emit(NetworkMenuMsg::WifiToggled(now_enabled));
+                });
+            }
+            NetworkMenuMsg::WifiToggled(enabled) => {
+                self.toggling_wifi = false;
+                self.network_state.wireless_enabled = enabled;
             }
             NetworkMenuMsg::ScanNetworks => {
-                // TODO: implement network scan
-                todo!("scanning for networks");
+                if self.scanning {
+                    return;
+                }
+                self.scanning = true;
+
+                let input = sender.input_sender().clone();
+                sender.oneshot_command(async move {
+                    let networks = scan::scan()
+                        .await
+                        .inspect_err(|e| log::error!("couldn't scan for wifi networks: {e}"))
+                        .ok();
+                    input.emit(NetworkMenuMsg::NetworksScanned(networks));
+                });
             }
-            NetworkMenuMsg::ConnectToNetwork(ssid) => {
-                // TODO: implement connection logic
-                todo!("connecting to network: {}", ssid);
+            NetworkMenuMsg::NetworksScanned(networks) => {
+                self.scanning = false;
+
+                // keep the old list around if the scan failed
+                if let Some(networks) = networks {
+                    let mut guard = self.access_points.guard();
+                    guard.clear();
+                    for network in networks {
+                        guard.push_back(network);
+                    }
+                }
             }
-            NetworkMenuMsg::ShowPasswordDialog(ssid) => {
-                self.show_password_dialog = Some(ssid);
+            NetworkMenuMsg::ConnectToNetwork(ap) => self.connect(ap, None, &sender),
+            NetworkMenuMsg::ActivationChanged(ssid, state) => {
+                self.activation = Some((ssid, state));
+            }
+            NetworkMenuMsg::ShowPasswordDialog(ap) => {
+                self.activation = None;
+                self.password_rejected = false;
+                self.show_password_dialog = Some(ap);
+            }
+            NetworkMenuMsg::PasswordRejected(ap) => {
+                // another network may have been picked since
+                if self
+                    .activation
+                    .as_ref()
+                    .is_some_and(|(ssid, _)| *ssid == ap.ssid)
+                {
+                    self.password_rejected = true;
+                    self.show_password_dialog = Some(ap);
+                }
             }
             NetworkMenuMsg::HidePasswordDialog => {
                 self.show_password_dialog = None;
             }
-            NetworkMenuMsg::ConnectWithPassword(ssid, _password) => {
-                // TODO: implement password connection
+            NetworkMenuMsg::SubmitPassword(password) => {
+                if let Some(ap) = self.show_password_dialog.clone() {
+                    sender.input(NetworkMenuMsg::ConnectWithPassword(ap, password));
+                }
+            }
+            NetworkMenuMsg::ConnectWithPassword(ap, password) => {
                 self.show_password_dialog = None;
-                todo!("connecting to {} with password", ssid);
+                self.connect(ap, Some(password), &sender);
             }
         }
     }
@@ -260,32 +404,39 @@ impl SimpleComponent for NetworkMenu {
             .set_icon_name(Some(get_icon(&self.network_state)));
         widgets
             .wifi_switch
-            .set_active(!self.network_state.is_asleep());
+            .set_active(self.network_state.wireless_enabled);
+        widgets.wifi_switch.set_sensitive(!self.toggling_wifi);
 
-        widgets
-            .ssid_label
-            .set_visible(self.network_state.wifi_ssid().is_some());
-        widgets.ssid_label.set_label(
-            &self
-                .network_state
-                .wifi_ssid()
-                .map(|ssid| format!("Connected to {}", ssid))
-                .unwrap_or_default(),
-        );
+        widgets.scan_spinner.set_visible(self.scanning);
+        widgets.scan_spinner.set_spinning(self.scanning);
+        widgets.refresh_button.set_sensitive(!self.scanning);
+
+        let link = link_status(&self.network_state);
+        widgets.link_label.set_visible(link.is_some());
+        widgets.link_label.set_label(&link.unwrap_or_default());
 
         widgets
             .connectivity_label
             .set_label(&self.network_state.connectivity.to_string());
         widgets
             .connection_state_label
-            .set_label(&self.network_state.connection_state.to_string());
+            .set_label(&self.connection_status());
+        widgets
+            .vpn_label
+            .set_visible(self.network_state.vpn_active.is_some());
+        widgets
+            .vpn_label
+            .set_label(&vpn_status(&self.network_state));
 
         // update password dialog visibility
-        if let Some(ssid) = &self.show_password_dialog {
+        widgets
+            .password_error_label
+            .set_visible(self.password_rejected);
+        if let Some(ap) = &self.show_password_dialog {
             widgets.password_dialog_box.set_visible(true);
             widgets
                 .password_dialog_label
-                .set_label(&format!("Enter password for {}", ssid));
+                .set_label(&format!("Enter password for {}", ap.ssid));
         } else {
             widgets.password_dialog_box.set_visible(false);
             widgets.password_entry.set_text("");
@@ -293,33 +444,117 @@ impl SimpleComponent for NetworkMenu {
     }
 }
 
-// factory for individual access point items
+impl NetworkMenu {
+    /// Connects to `ap` in the background, with `password` if one was asked
+    /// for, reporting progress back to the menu.
+    fn connect(
+        &mut self,
+        ap: AccessPointInfo,
+        password: Option<String>,
+        sender: &ComponentSender<Self>,
+    ) {
+        self.activation = Some((ap.ssid.clone(), ActiveConnectionState::Activating));
+
+        let input = sender.input_sender().clone();
+        sender.oneshot_command(async move {
+            let on_state = {
+                let input = input.clone();
+                let ssid = ap.ssid.clone();
+                move |state| input.emit(NetworkMenuMsg::ActivationChanged(ssid.clone(), state))
+            };
+
+            let outcome = match &password {
+                Some(password) => connect::connect_with_password(&ap, password, on_state).await,
+                None => connect::connect(&ap, on_state).await,
+            };
+
+            match outcome {
+                Ok(ConnectOutcome::Finished(_)) => {}
+                Ok(ConnectOutcome::NeedsPassword) => {
+                    input.emit(NetworkMenuMsg::ShowPasswordDialog(ap));
+                }
+                Ok(ConnectOutcome::WrongPassword) => {
+                    input.emit(NetworkMenuMsg::PasswordRejected(ap));
+                }
+                Err(e) => {
+                    log::error!("couldn't connect to {}: {e}", ap.ssid);
+                    input.emit(NetworkMenuMsg::ActivationChanged(
+                        ap.ssid,
+                        ActiveConnectionState::Deactivated,
+                    ));
+                }
+            }
+        });
+    }
+
+    /// How the connection started from this menu is going, or the overall
+    /// connection state if there isn't one.
+    fn connection_status(&self) -> String {
+        match &self.activation {
+            Some((ssid, ActiveConnectionState::Activating)) => format!("Connecting to {ssid}"),
+            Some((ssid, ActiveConnectionState::Activated)) => format!("Connected to {ssid}"),
+            Some((ssid, ActiveConnectionState::Deactivated)) => {
+                format!("Couldn't connect to {ssid}")
+            }
+            _ => self.network_state.connection_state.to_string(),
+        }
+    }
+}
+
+/// What the shell is connected through, for the status section.
+fn link_status(info: &NetworkInfo) -> Option<String> {
+    match info.specific_info.as_ref()? {
+        SpecificNetworkInfo::WiFi { wifi_ssid, .. } => Some(format!("Connected to {wifi_ssid}")),
+        SpecificNetworkInfo::Wired { carrier: false, .. } => Some("Cable unplugged".to_string()),
+        SpecificNetworkInfo::Wired {
+            wired_speed: Some(speed),
+            ..
+        } => Some(format!("Wired, {speed} Mb/s")),
+        SpecificNetworkInfo::Wired { .. } => Some("Wired".to_string()),
+    }
+}
+
+/// Which VPN is up, for the status section.
+fn vpn_status(info: &NetworkInfo) -> String {
+    info.vpn_active
+        .as_ref()
+        .map(|vpn| format!("VPN: {vpn}"))
+        .unwrap_or_default()
+}
+
+// factory for individual networks, grouping every access point that shares an
+// SSID
 #[derive(Debug)]
 struct AccessPointWidget {
-    access_point_proxy: AccessPointProxy<'static>,
+    network: WifiNetwork,
 }
 
 #[derive(Debug)]
 pub enum AccessPointMsg {
+    /// Connect to the strongest access point for this network.
     Connect,
+    /// Connect to a specific access point (BSSID) chosen by the user.
+    ConnectTo(usize),
 }
 
 #[derive(Debug)]
 pub enum AccessPointOutput {
-    Connect(String),         // SSID
-    RequestPassword(String), // SSID
+    /// Connect to this access point. The menu asks for a password if it
+    /// turns out to be needed.
+    Connect(AccessPointInfo),
 }
 
 pub struct AccessPointWidgetWidgets {
     main_box: gtk::Box,
     strength_icon: gtk::Image,
     ssid_label: gtk::Label,
-    frequency_label: gtk::Label,
+    bands_box: gtk::Box,
+    bssid_popover: gtk::Popover,
 }
 
 impl AsyncFactoryComponent for AccessPointWidget {
     type CommandOutput = ();
-    type Init = AccessPointProxy<'static>;
+    type Init = WifiNetwork;
     type Input = AccessPointMsg;
     type Output = AccessPointOutput;
     type ParentWidget = gtk::Box;
@@ -331,20 +566,20 @@ impl AsyncFactoryComponent for AccessPointWidget {
         _index: &DynamicIndex,
         _sender: AsyncFactorySender<Self>,
     ) -> Self {
-        Self {
-            access_point_proxy: init,
-        }
+        Self { network: init }
     }
 
     async fn update(&mut self, msg: Self::Input, sender: AsyncFactorySender<Self>) {
-        match msg {
-            AccessPointMsg::Connect => {
-                if let Ok(ssid_bytes) = self.access_point_proxy.ssid().await {
-                    let ssid = String::from_utf8_lossy(&ssid_bytes).to_string();
-                    let _ = sender.output(AccessPointOutput::RequestPassword(ssid));
-                }
-            }
+        let ap = match msg {
+            AccessPointMsg::Connect => self.network.strongest(),
+            AccessPointMsg::ConnectTo(i) => match self.network.access_points.get(i) {
+                Some(ap) => ap,
+                None => return,
+            },
         }
+        .clone();
+
+        let _ = sender.output(AccessPointOutput::Connect(ap));
     }
 
     fn init_root() -> Self::Root {
@@ -358,7 +593,10 @@ impl AsyncFactoryComponent for AccessPointWidget {
         _returned_widget: &<Self::ParentWidget as relm4::factory::FactoryView>::ReturnedWidget,
         sender: AsyncFactorySender<Self>,
     ) -> Self::Widgets {
-        root.connect_clicked(move |_| sender.input(AccessPointMsg::Connect));
+        root.connect_clicked({
+            let sender = sender.clone();
+            move |_| sender.input(AccessPointMsg::Connect)
+        });
 
         let main_box = gtk::Box::builder()
             .spacing(8)
@@ -366,23 +604,78 @@ impl AsyncFactoryComponent for AccessPointWidget {
             .hexpand(true)
             .build();
 
-        let strength_icon = gtk::Image::builder().width_request(32).build();
+        let strength_icon = gtk::Image::builder()
+            .icon_name(get_strength_icon(self.network.strength()))
+            .width_request(32)
+            .build();
 
-        let ssid_label = gtk::Label::new(None);
+        let ssid_label = gtk::Label::new(Some(&self.network.ssid));
 
-        let frequency_label = gtk::Label::builder()
-            .css_classes(["dim", "access-point-frequency"])
-            .build();
+        // one chip per band this network is available on
+        let bands_box = gtk::Box::builder().spacing(4).build();
+        for band in self.network.bands() {
+            let chip = gtk::Label::builder()
+                .label(band.label())
+                .css_classes(["dim", "access-point-frequency", "band-chip"])
+                .build();
+            bands_box.append(&chip);
+        }
 
         main_box.append(&strength_icon);
         main_box.append(&ssid_label);
-        main_box.append(&frequency_label);
+        main_box.append(&bands_box);
+        root.set_child(Some(&main_box));
+
+        // long-pressing the row lets the user pick a specific BSSID instead of
+        // the strongest one
+        let bssid_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(4)
+            .build();
+        let bssid_popover = gtk::Popover::builder().child(&bssid_box).build();
+        bssid_popover.set_parent(&root);
+
+        for (i, ap) in self.network.access_points.iter().enumerate() {
+            let band = ap.band().map(|b| format!("{} GHz", b.label()));
+            let label = format!(
+                "{} · {} · {}%",
+                band.as_deref().unwrap_or("? GHz"),
+                ap.bssid,
+                ap.strength
+            );
+            let button = gtk::Button::builder().label(label).build();
+            button.connect_clicked({
+                let sender = sender.clone();
+                let popover = bssid_popover.clone();
+                move |_| {
+                    popover.popdown();
+                    sender.input(AccessPointMsg::ConnectTo(i));
+                }
+            });
+            bssid_box.append(&button);
+        }
+
+        let long_press = gtk::GestureLongPress::new();
+        long_press.connect_pressed({
+            let popover = bssid_popover.clone();
+            move |gesture, _, _| {
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+                popover.popup();
+            }
+        });
+        root.add_controller(long_press);
 
         AccessPointWidgetWidgets {
             main_box,
             strength_icon,
             ssid_label,
-            frequency_label,
+            bands_box,
+            bssid_popover,
         }
     }
+
+    fn shutdown(&mut self, widgets: &mut Self::Widgets, _output: relm4::Sender<Self::Output>) {
+        // popovers aren't children of the row, so they'd outlive it
+        widgets.bssid_popover.unparent();
+    }
 }
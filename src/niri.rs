@@ -1,16 +1,24 @@
-use niri_ipc::{Event, Reply, Request, Response, Window as NiriWindow, Workspace as NiriWorkspace};
+use std::{future::Future, pin::Pin};
+
+use niri_ipc::{
+    Action, Event, Reply, Request, Response, Window as NiriWindow, Workspace as NiriWorkspace,
+    WorkspaceReferenceArg,
+};
 use relm4::SharedState;
 use tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
     net::UnixStream,
 };
 
+use crate::compositor::CompositorService;
+
 pub static NIRI_STATE: SharedState<Option<NiriState>> = SharedState::new();
 
 #[derive(Debug, Clone)]
 pub struct NiriState {
     pub workspaces: Vec<NiriWorkspace>,
     pub focused_window_title: String,
+    pub focused_window_app_id: String,
     pub focused_output: String,
 }
 
@@ -38,11 +46,11 @@ async fn fetch_and_update(socket_path: &str) -> anyhow::Result<()> {
         Vec::new()
     };
 
-    let focused_window_title =
-        if let Ok(Ok(Response::FocusedWindow(Some(NiriWindow { title, .. })))) =
+    let (focused_window_title, focused_window_app_id) =
+        if let Ok(Ok(Response::FocusedWindow(Some(NiriWindow { title, app_id, .. })))) =
             send_request(socket_path, Request::FocusedWindow).await
         {
-            title.unwrap_or_default()
+            (title.unwrap_or_default(), app_id.unwrap_or_default())
         } else {
             Default::default()
         };
@@ -56,12 +64,64 @@ async fn fetch_and_update(socket_path: &str) -> anyhow::Result<()> {
     *NIRI_STATE.write() = Some(NiriState {
         workspaces,
         focused_window_title,
+        focused_window_app_id,
         focused_output,
     });
 
     Ok(())
 }
 
+/// [`CompositorService`] backed by niri's IPC socket and [`NIRI_STATE`].
+#[derive(Debug)]
+pub struct NiriCompositor {
+    socket_path: String,
+}
+
+impl NiriCompositor {
+    pub fn new(socket_path: String) -> Self {
+        Self { socket_path }
+    }
+}
+
+impl CompositorService for NiriCompositor {
+    fn focused_workspace(&self) -> Option<u64> {
+        NIRI_STATE
+            .read()
+            .as_ref()?
+            .workspaces
+            .iter()
+            .find(|workspace| workspace.is_focused)
+            .map(|workspace| workspace.id)
+    }
+
+    fn focus_workspace(
+        &self,
+        id: u64,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + '_>> {
+        Box::pin(async move {
+            send_request(
+                &self.socket_path,
+                Request::Action(Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(id),
+                }),
+            )
+            .await?
+            .map_err(|e| anyhow::anyhow!("niri couldn't focus workspace {id}: {e}"))?;
+            Ok(())
+        })
+    }
+
+    fn version(&self) -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send + '_>> {
+        Box::pin(async move {
+            match send_request(&self.socket_path, Request::Version).await? {
+                Ok(Response::Version(version)) => Ok(format!("niri {version}")),
+                Ok(other) => anyhow::bail!("unexpected reply from niri: {other:?}"),
+                Err(e) => anyhow::bail!("niri couldn't report its version: {e}"),
+            }
+        })
+    }
+}
+
 pub async fn start_event_listener() {
     if let Ok(socket_path) = std::env::var("NIRI_SOCKET") {
         // initial fetch
@@ -1,28 +1,42 @@
+pub mod app_key;
 pub mod card;
 pub mod daemon;
 pub mod fresh;
+pub mod group;
+mod icon_cache;
+mod image;
+mod markup;
 pub mod panel;
 pub mod types;
 
-use std::{collections::HashMap, sync::OnceLock};
+use std::{collections::HashMap, sync::OnceLock, time::Duration};
 
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
+use futures_lite::StreamExt;
 use relm4::SharedState;
-use serde::{Deserialize, Serialize};
-use tokio::sync::{broadcast, mpsc};
+use serde::{Deserialize, Deserializer, Serialize};
+use tokio::sync::{broadcast, mpsc, oneshot};
 use zbus::{
     Connection,
+    fdo::DBusProxy,
+    object_server::InterfaceRef,
     zvariant::{
-        OwnedValue, Type,
+        OwnedValue, Type, Value,
         as_value::{self, optional},
     },
 };
 
-use crate::notifications::{
-    daemon::{NotificationsDaemon, NotificationsDaemonSignals},
-    types::{Notification, NotificationUrgency},
+use crate::{
+    notifications::{
+        daemon::{NotificationsDaemon, NotificationsDaemonSignals},
+        image::{ImageData, remove_saved_image, save_image_data},
+        types::{CloseReason, Notification, NotificationUrgency},
+    },
+    settings, sound,
 };
 
+pub const DAEMON_NAME: &str = "org.freedesktop.Notifications";
+
 /// Global snapshot of all current notifications.
 ///
 /// Consumers subscribe via `NOTIFICATIONS_STATE.subscribe(sender, mapper)` for
@@ -34,6 +48,12 @@ pub static NOTIFICATIONS_STATE: SharedState<NotificationsState> = SharedState::n
 #[derive(Debug, Clone, Default)]
 pub struct NotificationsState {
     pub notifications: HashMap<u32, Notification>,
+    /// Whether the daemon currently owns `org.freedesktop.Notifications`.
+    /// False before the service starts and while it is reconnecting.
+    pub healthy: bool,
+    /// While set, only critical notifications pop up. The rest are still kept
+    /// for the notification center.
+    pub do_not_disturb: bool,
 }
 
 /// A discrete notification event broadcast to all subscribers.
@@ -44,19 +64,17 @@ pub enum NotificationEvent {
     Received(Notification),
     Closed {
         id: u32,
-        // reason codes defined by the freedesktop spec; retained even if
-        // consumers currently pattern-match with `..`
+        // retained for consumers that care why; currently matched with `..`
         #[allow(dead_code)]
-        reason: u32,
+        reason: CloseReason,
     },
     ActionInvoked {
-        // retained for future consumers; currently matched with `..`
-        #[allow(dead_code)]
         id: u32,
-        #[allow(dead_code)]
         action_key: String,
     },
     AllCleared,
+    /// Do-not-disturb was turned on or off.
+    DoNotDisturbChanged(bool),
 }
 
 // capacity of 64 events; lagging receivers miss old events but never block
@@ -76,15 +94,44 @@ pub fn subscribe_events() -> broadcast::Receiver<NotificationEvent> {
     event_tx().subscribe()
 }
 
+/// Removes a notification from state and broadcasts a
+/// [`NotificationEvent::Closed`] with the given reason.
+///
+/// Returns whether the notification existed; nothing is broadcast if it
+/// didn't (e.g. the user already dismissed it).
+pub(crate) fn close(id: u32, reason: CloseReason) -> bool {
+    let Some(notification) = NOTIFICATIONS_STATE.write().notifications.remove(&id) else {
+        return false;
+    };
+
+    remove_saved_image(&notification.image);
+    let _ = event_tx().send(NotificationEvent::Closed { id, reason });
+    true
+}
+
+/// Closes a notification the user dismissed, returning the reason to report
+/// if it was open.
+fn dismissed(id: u32) -> Option<CloseReason> {
+    let reason = CloseReason::Dismissed;
+    close(id, reason).then_some(reason)
+}
+
 /// Commands that consumers can send to the notification service.
 pub(crate) enum NotificationCommand {
     Dismiss(u32),
+    Snooze(u32),
     ClearAll,
     InvokeAction { id: u32, action_key: String },
+    SetDoNotDisturb(bool),
 }
 
 static COMMAND_TX: OnceLock<mpsc::UnboundedSender<NotificationCommand>> = OnceLock::new();
 
+/// Asks the service to give up the bus name and stop, replying once done.
+/// Kept apart from the commands since it ends the service rather than acting
+/// on notifications.
+static SHUTDOWN_TX: OnceLock<mpsc::UnboundedSender<oneshot::Sender<()>>> = OnceLock::new();
+
 /// Dismiss a notification by ID.
 ///
 /// Removes the notification from state and emits a `NotificationClosed` event.
@@ -95,10 +142,23 @@ pub fn dismiss(id: u32) {
     }
 }
 
+/// Snooze a notification by ID.
+///
+/// The notification stays in the notification center and is shown as a popup
+/// again once the configured snooze duration elapses, unless it has been
+/// dismissed or replaced in the meantime. Has no effect if the service has not
+/// been started.
+pub fn snooze(id: u32) {
+    if let Some(tx) = COMMAND_TX.get() {
+        let _ = tx.send(NotificationCommand::Snooze(id));
+    }
+}
+
 /// Clear all notifications.
 ///
-/// Removes all notifications from state and emits an `AllCleared` event.
-/// Has no effect if the service has not been started.
+/// Removes all notifications from state, emits a `NotificationClosed` signal
+/// for each so their apps know, and an `AllCleared` event. Has no effect if the
+/// service has not been started.
 pub fn clear_all() {
     if let Some(tx) = COMMAND_TX.get() {
         let _ = tx.send(NotificationCommand::ClearAll);
@@ -114,13 +174,105 @@ pub fn invoke_action(id: u32, action_key: String) {
     }
 }
 
+/// Turns do-not-disturb on or off, broadcasting a
+/// [`NotificationEvent::DoNotDisturbChanged`] if that changed it.
+///
+/// Has no effect if the service has not been started.
+pub fn set_do_not_disturb(enabled: bool) {
+    if let Some(tx) = COMMAND_TX.get() {
+        let _ = tx.send(NotificationCommand::SetDoNotDisturb(enabled));
+    }
+}
+
+/// Whether do-not-disturb is on.
+pub fn do_not_disturb() -> bool {
+    NOTIFICATIONS_STATE.read().do_not_disturb
+}
+
+/// Whether `notification` should pop up, or only go to the notification
+/// center. Critical notifications always pop up, even under do-not-disturb.
+pub fn should_pop_up(notification: &Notification) -> bool {
+    !do_not_disturb() || notification.urgency == NotificationUrgency::Critical
+}
+
+/// Releases `org.freedesktop.Notifications` and stops the service, so another
+/// daemon can take over right away. Returns once the name is released, or
+/// immediately if the service has not been started.
+pub async fn shutdown() {
+    if let Some(tx) = SHUTDOWN_TX.get() {
+        let (done_tx, done_rx) = oneshot::channel();
+        if tx.send(done_tx).is_ok() {
+            let _ = done_rx.await;
+        }
+    }
+}
+
+/// Sends a notification through the org.freedesktop.Notifications D-Bus
+/// interface, on behalf of the shell itself.
+///
+/// This normally reaches our own daemon, which will display it in the shell's
+/// fresh notification overlay and store it in the notification center.
+/// `actions` is the flat `[key, label, ...]` list from the spec. Returns the
+/// notification id on success.
+pub(crate) async fn send_notification(
+    app_name: &str,
+    summary: &str,
+    body: &str,
+    urgency: u8,
+    actions: &[&str],
+    expire_timeout: i32,
+) -> Option<u32> {
+    let connection = match Connection::session().await {
+        Ok(c) => c,
+        Err(e) => {
+            log::warn!("couldn't open D-Bus session to send notification: {}", e);
+            return None;
+        }
+    };
+
+    // build the hints dict `a{sv}` with the urgency byte.
+    // HashMap<String, OwnedValue> serializes to `a{sv}` because OwnedValue's
+    // D-Bus signature is `v` (variant).
+    let mut hints: HashMap<String, OwnedValue> = HashMap::new();
+    if let Ok(urgency_owned) = OwnedValue::try_from(Value::U8(urgency)) {
+        hints.insert("urgency".to_string(), urgency_owned);
+    }
+
+    let result = connection
+        .call_method(
+            Some(DAEMON_NAME),
+            "/org/freedesktop/Notifications",
+            Some(DAEMON_NAME),
+            "Notify",
+            &(
+                app_name,
+                0u32, // replaces_id
+                "",   // app_icon
+                summary,
+                body,
+                actions,
+                hints,
+                expire_timeout, // -1 = server decides, 0 = never
+            ),
+        )
+        .await;
+
+    match result.and_then(|reply| reply.body().deserialize::<u32>()) {
+        Ok(id) => Some(id),
+        Err(e) => {
+            log::warn!("couldn't send notification \"{}\": {}", summary, e);
+            None
+        }
+    }
+}
+
 /// D-Bus hints passed with each `Notify` call.
 #[derive(Deserialize, Serialize, Type, Default)]
 #[zvariant(signature = "dict")]
 #[serde(default, rename_all = "kebab-case")]
 pub struct NotificationHints {
     #[serde(with = "as_value")]
-    action_icons: bool,
+    pub action_icons: bool,
 
     #[serde(with = "optional", skip_serializing_if = "Option::is_none")]
     category: Option<String>,
@@ -128,6 +280,36 @@ pub struct NotificationHints {
     #[serde(with = "optional", skip_serializing_if = "Option::is_none")]
     pub desktop_entry: Option<String>,
 
+    #[serde(with = "optional", skip_serializing_if = "Option::is_none")]
+    image_data: Option<ImageData>,
+
+    /// `image-data` as spelled before version 1.2 of the spec.
+    #[serde(
+        rename = "image_data",
+        with = "optional",
+        skip_serializing_if = "Option::is_none"
+    )]
+    image_data_legacy: Option<ImageData>,
+
+    /// `image-data` as spelled before version 1.1 of the spec.
+    #[serde(
+        rename = "icon_data",
+        with = "optional",
+        skip_serializing_if = "Option::is_none"
+    )]
+    icon_data: Option<ImageData>,
+
+    #[serde(with = "optional", skip_serializing_if = "Option::is_none")]
+    image_path: Option<String>,
+
+    /// `image-path` as spelled before version 1.2 of the spec.
+    #[serde(
+        rename = "image_path",
+        with = "optional",
+        skip_serializing_if = "Option::is_none"
+    )]
+    image_path_legacy: Option<String>,
+
     #[serde(with = "as_value")]
     resident: bool,
 
@@ -143,20 +325,125 @@ pub struct NotificationHints {
     #[serde(with = "as_value")]
     transient: bool,
 
-    #[serde(with = "optional", skip_serializing_if = "Option::is_none")]
+    #[serde(
+        serialize_with = "optional::serialize",
+        deserialize_with = "de_urgency",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub urgency: Option<NotificationUrgency>,
 
     #[serde(flatten)]
     others: HashMap<String, OwnedValue>,
 }
 
+/// A sound a notification asked to be played with.
+#[derive(Debug, PartialEq, Eq)]
+pub enum NotificationSound {
+    File(String),
+    /// A sound from the freedesktop sound theme, e.g. `message-new-instant`.
+    Name(String),
+}
+
+impl NotificationSound {
+    /// Plays the sound in the background.
+    pub fn play(&self) {
+        match self {
+            Self::File(path) => sound::play_file(path),
+            Self::Name(name) => sound::play(name),
+        }
+    }
+}
+
+impl NotificationHints {
+    /// The sound to play with the notification, if any. A file is preferred
+    /// over a named sound, and `suppress-sound` rules out both.
+    pub fn sound(&self) -> Option<NotificationSound> {
+        if self.suppress_sound {
+            return None;
+        }
+
+        let file = self.sound_file.as_deref().and_then(|file| {
+            // the spec asks for a path, but some apps send a URI. only local
+            // ones can be played
+            if glib::Uri::peek_scheme(file).is_some() {
+                let path = gio::File::for_uri(file).path()?;
+                Some(path.to_string_lossy().into_owned())
+            } else {
+                Some(file.to_string())
+            }
+        });
+        match (file, self.sound_name.as_deref()) {
+            (Some(file), _) if !file.is_empty() => Some(NotificationSound::File(file)),
+            (_, Some(name)) if !name.is_empty() => Some(NotificationSound::Name(name.to_string())),
+            _ => None,
+        }
+    }
+
+    /// The image to show with notification `id`, as a path. Inline image data
+    /// is saved to a file first. Follows the spec's order of preference,
+    /// except that `app_icon` is handled separately.
+    pub fn image(&self, id: u32) -> String {
+        let image_data = self.image_data.as_ref().or(self.image_data_legacy.as_ref());
+        let image_path = self.image_path.as_ref().or(self.image_path_legacy.as_ref());
+
+        let data = match (image_data, image_path) {
+            (Some(data), _) => data,
+            (None, Some(path)) => return path.clone(),
+            (None, None) => match &self.icon_data {
+                Some(data) => data,
+                None => return String::new(),
+            },
+        };
+
+        match save_image_data(id, data) {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(e) => {
+                log::warn!("couldn't save image for notification {id}: {e:#}");
+                String::new()
+            }
+        }
+    }
+}
+
+/// Reads the urgency hint leniently. The spec says it's a byte, but some apps
+/// send other integer types, and a hint we can't read shouldn't make the whole
+/// notification fail.
+fn de_urgency<'de, D>(deserializer: D) -> Result<Option<NotificationUrgency>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = OwnedValue::deserialize(deserializer)?;
+    let level = match &*value {
+        Value::U8(level) => i64::from(*level),
+        Value::I16(level) => i64::from(*level),
+        Value::U16(level) => i64::from(*level),
+        Value::I32(level) => i64::from(*level),
+        Value::U32(level) => i64::from(*level),
+        Value::I64(level) => *level,
+        Value::U64(level) => i64::try_from(*level).unwrap_or(i64::MAX),
+        other => {
+            log::debug!("ignoring urgency hint of type {}", other.value_signature());
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(NotificationUrgency::from_level(level)))
+}
+
+/// How long to wait between attempts to re-register the daemon after the
+/// session bus connection is lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
 /// Runs the notification service.
 ///
 /// Registers `org.freedesktop.Notifications` on the session D-Bus, then drives
-/// a command loop that handles [`dismiss`], [`clear_all`], and
-/// [`invoke_action`] calls from UI components. Writes all state changes to
-/// [`NOTIFICATIONS_STATE`] and broadcasts [`NotificationEvent`]s to every
-/// subscriber obtained via [`subscribe_events`].
+/// a command loop that handles [`dismiss`], [`clear_all`], [`invoke_action`]
+/// and [`set_do_not_disturb`] calls from UI components. Writes all state
+/// changes to [`NOTIFICATIONS_STATE`] and broadcasts [`NotificationEvent`]s to
+/// every subscriber obtained via [`subscribe_events`].
+///
+/// If the bus connection drops or the name is taken away, the daemon is
+/// registered again; [`NotificationsState::healthy`] is false in the meantime.
 ///
 /// Must be started exactly once, from `app.rs`, before any UI component
 /// subscribes to the state or issues commands.
@@ -165,75 +452,192 @@ pub async fn run_notifications_service() {
     // before the first event arrives
     let _ = event_tx();
 
-    let connection = match initialize_notifications_daemon().await {
-        Ok(c) => {
-            log::info!("notifications service started");
-            c
-        }
-        Err(e) => {
-            log::error!("failed to start notifications service: {}", e);
-            return;
-        }
-    };
-
-    // look up the interface ref so we can emit D-Bus signals for commands
-    let interface_ref = match connection
-        .object_server()
-        .interface::<_, NotificationsDaemon>("/org/freedesktop/Notifications")
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            log::error!("couldn't look up notifications daemon interface: {}", e);
-            return;
-        }
-    };
-
-    // install the command sender so free functions can push commands
+    // install the command sender so free functions can push commands. the
+    // channel outlives any single bus connection, so commands issued while
+    // reconnecting are handled once the daemon is back
     let (cmd_tx, mut cmd_rx) = mpsc::unbounded_channel::<NotificationCommand>();
     if COMMAND_TX.set(cmd_tx).is_err() {
         log::warn!("notifications service started more than once; extra instance exiting");
         return;
     }
+    let (shutdown_tx, mut shutdown_rx) = mpsc::unbounded_channel();
+    let _ = SHUTDOWN_TX.set(shutdown_tx);
 
-    // drive the command loop
+    let mut reconnecting = false;
     loop {
-        let Some(cmd) = cmd_rx.recv().await else {
-            log::warn!("notification command channel closed; service stopping");
-            break;
+        let connection = match initialize_notifications_daemon().await {
+            Ok(c) if reconnecting => {
+                log::info!("notifications service reconnected to the session bus");
+                c
+            }
+            Ok(c) => {
+                log::info!("notifications service started");
+                c
+            }
+            Err(e) if reconnecting => {
+                log::warn!("couldn't reconnect notifications service, retrying: {}", e);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+            Err(e) => {
+                log::error!("failed to start notifications service: {}", e);
+                return;
+            }
         };
 
-        match cmd {
-            NotificationCommand::Dismiss(id) => {
-                NOTIFICATIONS_STATE.write().notifications.remove(&id);
-                let _ = event_tx().send(NotificationEvent::Closed { id, reason: 2 });
+        NOTIFICATIONS_STATE.write().healthy = true;
 
-                // also emit the D-Bus signal so external clients are notified
-                if let Err(e) = interface_ref.notification_closed(id, 2).await {
-                    log::error!("couldn't emit notification_closed signal: {}", e);
+        let result = serve_commands(&connection, &mut cmd_rx, &mut shutdown_rx).await;
+        NOTIFICATIONS_STATE.write().healthy = false;
+
+        match result {
+            Ok(()) => {
+                log::info!("notifications service stopped");
+                break;
+            }
+            Err(e) => {
+                log::warn!("notifications daemon went away ({}); reconnecting", e);
+                reconnecting = true;
+            }
+        }
+    }
+}
+
+/// Handles commands on behalf of the daemon served on `connection`.
+///
+/// Returns `Ok` once the command channel closes or the service is shut down,
+/// or an error if the connection is lost or the daemon loses its bus name.
+async fn serve_commands(
+    connection: &Connection,
+    cmd_rx: &mut mpsc::UnboundedReceiver<NotificationCommand>,
+    shutdown_rx: &mut mpsc::UnboundedReceiver<oneshot::Sender<()>>,
+) -> Result<()> {
+    // look up the interface ref so we can emit D-Bus signals for commands
+    let interface_ref = connection
+        .object_server()
+        .interface::<_, NotificationsDaemon>("/org/freedesktop/Notifications")
+        .await
+        .context("couldn't look up notifications daemon interface")?;
+
+    // the signal stream ends when the connection closes
+    let dbus_proxy = DBusProxy::new(connection).await?;
+    let mut name_lost = dbus_proxy.receive_name_lost().await?;
+
+    loop {
+        tokio::select! {
+            cmd = cmd_rx.recv() => {
+                match cmd {
+                    None => return Ok(()),
+                    Some(cmd) => handle_command(cmd, &interface_ref).await,
                 }
             }
-            NotificationCommand::ClearAll => {
-                NOTIFICATIONS_STATE.write().notifications.clear();
-                let _ = event_tx().send(NotificationEvent::AllCleared);
+            Some(done) = shutdown_rx.recv() => {
+                if let Err(e) = connection.release_name(DAEMON_NAME).await {
+                    log::warn!("couldn't release the {} name: {}", DAEMON_NAME, e);
+                }
+                let _ = done.send(());
+                return Ok(());
             }
-            NotificationCommand::InvokeAction { id, action_key } => {
-                let _ = event_tx().send(NotificationEvent::ActionInvoked {
-                    id,
-                    action_key: action_key.clone(),
-                });
+            lost = name_lost.next() => {
+                let Some(lost) = lost else {
+                    bail!("session bus connection closed");
+                };
+                if lost.args()?.name().as_str() == DAEMON_NAME {
+                    bail!("lost the {} name", DAEMON_NAME);
+                }
+            }
+        }
+    }
+}
 
-                if let Err(e) = interface_ref.action_invoked(id, action_key).await {
-                    log::error!("couldn't emit action_invoked signal: {}", e);
+async fn handle_command(
+    cmd: NotificationCommand,
+    interface_ref: &InterfaceRef<NotificationsDaemon>,
+) {
+    match cmd {
+        NotificationCommand::Dismiss(id) => {
+            // also emit the D-Bus signal so external clients are notified
+            if let Some(reason) = dismissed(id)
+                && let Err(e) = interface_ref.notification_closed(id, reason.into()).await
+            {
+                log::error!("couldn't emit notification_closed signal: {}", e);
+            }
+        }
+        NotificationCommand::Snooze(id) => {
+            let delay = Duration::from_secs(settings::get_config().notifications.snooze_duration);
+            let snoozed_until = chrono::Local::now().timestamp() + delay.as_secs() as i64;
+
+            // marked in the state, so cards can show when it comes back
+            {
+                let mut state = NOTIFICATIONS_STATE.write();
+                let Some(notification) = state.notifications.get_mut(&id) else {
+                    return;
+                };
+                notification.snoozed_until = Some(snoozed_until);
+            }
+            log::debug!("snoozing notification {id} for {delay:?}");
+
+            relm4::spawn(async move {
+                tokio::time::sleep(delay).await;
+
+                // only re-pop if the notification wasn't dismissed, replaced or
+                // snoozed again while it was snoozed
+                let woken = {
+                    let mut state = NOTIFICATIONS_STATE.write();
+                    let current = state
+                        .notifications
+                        .get_mut(&id)
+                        .filter(|current| current.snoozed_until == Some(snoozed_until));
+                    current.map(|current| {
+                        current.snoozed_until = None;
+                        current.clone()
+                    })
+                };
+
+                if let Some(notification) = woken {
+                    let _ = event_tx().send(NotificationEvent::Received(notification));
+                }
+            });
+        }
+        NotificationCommand::ClearAll => {
+            // taken in one go, so views are rebuilt once rather than once per
+            // notification
+            let cleared = std::mem::take(&mut NOTIFICATIONS_STATE.write().notifications);
+            let _ = event_tx().send(NotificationEvent::AllCleared);
+
+            let reason = CloseReason::Dismissed;
+            for (&id, notification) in &cleared {
+                remove_saved_image(&notification.image);
+                if let Err(e) = interface_ref.notification_closed(id, reason.into()).await {
+                    log::error!("couldn't emit notification_closed signal: {}", e);
                 }
             }
         }
+        NotificationCommand::InvokeAction { id, action_key } => {
+            let _ = event_tx().send(NotificationEvent::ActionInvoked {
+                id,
+                action_key: action_key.clone(),
+            });
+
+            if let Err(e) = interface_ref.action_invoked(id, action_key).await {
+                log::error!("couldn't emit action_invoked signal: {}", e);
+            }
+        }
+        NotificationCommand::SetDoNotDisturb(enabled) => {
+            if enabled == do_not_disturb() {
+                return;
+            }
+
+            log::info!("do not disturb {}", if enabled { "on" } else { "off" });
+            NOTIFICATIONS_STATE.write().do_not_disturb = enabled;
+            let _ = event_tx().send(NotificationEvent::DoNotDisturbChanged(enabled));
+        }
     }
 }
 
 async fn initialize_notifications_daemon() -> Result<Connection> {
     Ok(zbus::connection::Builder::session()?
-        .name("org.freedesktop.Notifications")?
+        .name(DAEMON_NAME)?
         .serve_at(
             "/org/freedesktop/Notifications",
             NotificationsDaemon::new(event_tx().clone()),
@@ -241,3 +645,128 @@ async fn initialize_notifications_daemon() -> Result<Connection> {
         .build()
         .await?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(super) fn notification(id: u32) -> Notification {
+        Notification {
+            id,
+            app_name: "test".to_string(),
+            app_icon: String::new(),
+            desktop_entry: String::new(),
+            image: String::new(),
+            summary: "summary".to_string(),
+            body: String::new(),
+            urgency: NotificationUrgency::Normal,
+            timeout: -1,
+            timestamp: 0,
+            actions: Vec::new(),
+            action_icons: false,
+            snoozed_until: None,
+        }
+    }
+
+    pub(super) fn insert_notification(id: u32) {
+        NOTIFICATIONS_STATE
+            .write()
+            .notifications
+            .insert(id, notification(id));
+    }
+
+    /// Returns the reason code broadcast for the given notification id.
+    fn closed_reason(rx: &mut broadcast::Receiver<NotificationEvent>, id: u32) -> u32 {
+        loop {
+            match rx.try_recv() {
+                Ok(NotificationEvent::Closed {
+                    id: closed_id,
+                    reason,
+                }) if closed_id == id => return reason.into(),
+                Ok(_) => continue,
+                Err(e) => panic!("no close event for notification {id}: {e}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_dismiss_emits_reason_2() {
+        let mut rx = subscribe_events();
+        insert_notification(9002);
+
+        assert_eq!(dismissed(9002).map(u32::from), Some(2));
+        assert_eq!(closed_reason(&mut rx, 9002), 2);
+
+        // dismissing again is a no-op and broadcasts nothing
+        assert_eq!(dismissed(9002), None);
+    }
+
+    fn parse_hints(hints: HashMap<&str, Value>) -> NotificationHints {
+        use zbus::zvariant::{LE, serialized::Context, to_bytes};
+
+        let encoded = to_bytes(Context::new_dbus(LE, 0), &hints).unwrap();
+        encoded.deserialize().unwrap().0
+    }
+
+    #[test]
+    fn test_urgency_hint_is_read_leniently() {
+        let urgency = |value: Value| parse_hints(HashMap::from([("urgency", value)])).urgency;
+
+        assert_eq!(urgency(Value::U8(0)), Some(NotificationUrgency::Low));
+        assert_eq!(urgency(Value::U8(1)), Some(NotificationUrgency::Normal));
+        assert_eq!(urgency(Value::U8(2)), Some(NotificationUrgency::Critical));
+
+        // other integer types, and levels past critical
+        assert_eq!(urgency(Value::I32(2)), Some(NotificationUrgency::Critical));
+        assert_eq!(urgency(Value::U32(1)), Some(NotificationUrgency::Normal));
+        assert_eq!(urgency(Value::U8(7)), Some(NotificationUrgency::Critical));
+
+        // unreadable or missing hints leave the daemon's default (normal)
+        assert_eq!(urgency(Value::from("high")), None);
+        assert_eq!(parse_hints(HashMap::new()).urgency, None);
+    }
+
+    #[test]
+    fn test_sound_hints() {
+        let sound = |hints: Vec<(&str, Value)>| parse_hints(hints.into_iter().collect()).sound();
+
+        assert_eq!(sound(vec![]), None);
+        assert_eq!(
+            sound(vec![("sound-name", Value::from("message-new-instant"))]),
+            Some(NotificationSound::Name("message-new-instant".to_string()))
+        );
+        assert_eq!(
+            sound(vec![
+                ("sound-file", Value::from("file:///tmp/ding.oga")),
+                ("sound-name", Value::from("bell")),
+            ]),
+            Some(NotificationSound::File("/tmp/ding.oga".to_string()))
+        );
+        assert_eq!(
+            sound(vec![("sound-file", Value::from("file:///tmp/a%20b.oga"))]),
+            Some(NotificationSound::File("/tmp/a b.oga".to_string()))
+        );
+        assert_eq!(
+            sound(vec![
+                ("sound-file", Value::from("https://example.com/ding.oga")),
+                ("sound-name", Value::from("bell")),
+            ]),
+            Some(NotificationSound::Name("bell".to_string()))
+        );
+        assert_eq!(
+            sound(vec![
+                ("sound-name", Value::from("bell")),
+                ("suppress-sound", Value::Bool(true)),
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_close_reason_round_trip() {
+        for code in 1..=4 {
+            assert_eq!(u32::from(CloseReason::from(code)), code);
+        }
+        assert_eq!(CloseReason::from(0), CloseReason::Undefined);
+    }
+}
@@ -0,0 +1,82 @@
+/// Words that say who makes an app or what kind of build it is, rather than
+/// which app it is, e.g. "Mozilla Firefox" or "Signal Desktop".
+const NOISE_WORDS: &[&str] = &["app", "desktop", "google", "microsoft", "mozilla"];
+
+/// Works out a stable key for the app that sent a notification, so one app is
+/// recognized however it names itself. The desktop entry is preferred since
+/// it's the most reliable, falling back to the app name.
+///
+/// Both are reduced the same way: reverse-DNS prefixes, vendor names and
+/// build suffixes are dropped and the rest is lowercased, so
+/// `org.mozilla.firefox`, "Mozilla Firefox" and "firefox" all give `firefox`.
+pub fn app_key(desktop_entry: &str, app_name: &str) -> String {
+    let entry = desktop_entry.trim();
+    let entry = entry.strip_suffix(".desktop").unwrap_or(entry);
+    if !entry.is_empty() {
+        return normalize(entry);
+    }
+
+    normalize(app_name.trim())
+}
+
+fn normalize(name: &str) -> String {
+    // in reverse-DNS names the app is usually last, but not always (e.g.
+    // `org.telegram.desktop`)
+    name.rsplit('.')
+        .map(|segment| {
+            segment
+                .split([' ', '-', '_'])
+                .map(str::to_lowercase)
+                .filter(|word| !word.is_empty() && !NOISE_WORDS.contains(&word.as_str()))
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .find(|key| !key.is_empty())
+        .unwrap_or_else(|| name.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variants_share_a_key() {
+        let variants = [
+            ("", "Mozilla Firefox"),
+            ("", "firefox"),
+            ("org.mozilla.firefox", "Firefox"),
+            ("firefox.desktop", ""),
+        ];
+        for (entry, name) in variants {
+            assert_eq!(app_key(entry, name), "firefox", "{entry:?} / {name:?}");
+        }
+
+        let variants = [
+            ("", "Signal"),
+            ("", "Signal Desktop"),
+            ("signal-desktop", "Signal"),
+        ];
+        for (entry, name) in variants {
+            assert_eq!(app_key(entry, name), "signal", "{entry:?} / {name:?}");
+        }
+
+        assert_eq!(app_key("org.telegram.desktop", ""), "telegram");
+        assert_eq!(app_key("", "Telegram Desktop"), "telegram");
+        assert_eq!(app_key("", "Google Chrome"), "chrome");
+        assert_eq!(app_key("google-chrome", ""), "chrome");
+    }
+
+    #[test]
+    fn test_desktop_entry_wins_over_name() {
+        assert_eq!(app_key("org.gnome.Nautilus", "Files"), "nautilus");
+        assert_eq!(app_key("", "Files"), "files");
+    }
+
+    #[test]
+    fn test_unusual_names() {
+        assert_eq!(app_key("", ""), "");
+        // nothing but noise is kept as is rather than dropped entirely
+        assert_eq!(app_key("", "Desktop"), "desktop");
+        assert_eq!(app_key("", "notify-send"), "notify-send");
+    }
+}
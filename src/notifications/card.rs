@@ -1,9 +1,19 @@
-use std::path::Path;
-
 use gtk4::prelude::*;
 use relm4::prelude::*;
 
-use crate::notifications::types::{Notification, NotificationUrgency};
+use crate::{
+    launch,
+    notifications::{
+        icon_cache::cached_app_icon,
+        image::{ImageSource, ImageSourceExt, resolve_image},
+        markup::body_markup,
+        types::{Notification, NotificationUrgency},
+    },
+};
+
+/// How fast, in pixels per second, a sideways swipe has to be to dismiss a
+/// card.
+const DISMISS_SWIPE_VELOCITY: f64 = 800.0;
 
 fn is_icon(icon: &str) -> bool {
     if let Some(display) = gtk4::gdk::Display::default() {
@@ -14,24 +24,45 @@ fn is_icon(icon: &str) -> bool {
     }
 }
 
-fn file_exists(path: &str) -> bool {
-    !path.is_empty() && Path::new(path).exists()
+/// Guesses an icon for an app that didn't send one, trying its desktop entry
+/// and name in a few common spellings, e.g. `org.gnome.Nautilus` ->
+/// `nautilus`, or "Signal Desktop" -> `signal-desktop`.
+fn resolve_app_icon(desktop_entry: &str, app_name: &str) -> Option<String> {
+    let short_entry = desktop_entry.rsplit('.').next().unwrap_or_default();
+    let candidates = [
+        desktop_entry.to_string(),
+        desktop_entry.to_lowercase(),
+        short_entry.to_lowercase(),
+        app_name.to_lowercase().replace(' ', "-"),
+    ];
+
+    candidates.into_iter().find(|icon| is_icon(icon))
 }
 
 #[derive(Debug)]
 pub struct NotificationCard {
     notification: Notification,
+    /// Set once the card has asked to be dismissed, so a click that comes
+    /// with the swipe that dismissed it isn't taken as a click too.
+    dismissed: bool,
 }
 
 #[derive(Debug)]
 pub enum NotificationCardMsg {
     Dismiss,
+    Snooze,
     Action(String), // action_id
+    /// The card's content was clicked.
+    Clicked,
+    /// The app replaced this notification, so the card shows the new one in
+    /// place.
+    Update(Notification),
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum NotificationCardOutput {
     Dismiss(u32),        // notification_id
+    Snooze(u32),         // notification_id
     Action(u32, String), // notification_id, action_id
 }
 
@@ -60,9 +91,9 @@ impl FactoryComponent for NotificationCard {
                     // App icon (if available)
                     gtk4::Image {
                         #[watch]
-                        set_visible: self.has_app_icon(),
+                        set_visible: self.app_icon() != ImageSource::None,
                         #[watch]
-                        set_icon_name: self.get_app_icon_name().as_deref(),
+                        set_image_source: &self.app_icon(),
                         add_css_class: "app-icon",
                     },
 
@@ -85,6 +116,32 @@ impl FactoryComponent for NotificationCard {
                         set_halign: gtk4::Align::End,
                     },
 
+                    // When a snoozed notification comes back
+                    gtk4::Label {
+                        #[watch]
+                        set_text: &self
+                            .notification
+                            .snoozed_until
+                            .map(|until| format!("snoozed until {}", self.format_time(until)))
+                            .unwrap_or_default(),
+                        add_css_class: "snoozed",
+                        #[watch]
+                        set_visible: self.notification.snoozed_until.is_some(),
+                    },
+
+                    // Snooze button
+                    gtk4::Button {
+                        add_css_class: "snooze-button",
+                        set_tooltip_text: Some("Snooze"),
+                        connect_clicked[sender] => move |_| {
+                            sender.input(NotificationCardMsg::Snooze);
+                        },
+
+                        gtk4::Image {
+                            set_icon_name: Some("alarm-symbolic"),
+                        },
+                    },
+
                     // Close button
                     gtk4::Button {
                         add_css_class: "close-button",
@@ -99,29 +156,21 @@ impl FactoryComponent for NotificationCard {
                 },
 
                 // Content section
-                #[name = "content_container"]
                 gtk4::Button {
                     add_css_class: "content",
                     set_hexpand: true,
+                    connect_clicked[sender] => move |_| {
+                        sender.input(NotificationCardMsg::Clicked);
+                    },
 
                     // Notification image (if available)
                     gtk4::Image {
                         #[watch]
-                        set_visible: self.has_notification_image(),
-                        #[watch]
-                        set_from_file: if self.has_notification_image() && file_exists(&self.notification.image) {
-                            Some(&self.notification.image)
-                        } else {
-                            None
-                        },
+                        set_visible: self.image() != ImageSource::None,
                         #[watch]
-                        set_icon_name: if self.has_notification_image() && !file_exists(&self.notification.image) {
-                            Some(&self.notification.image)
-                        } else {
-                            None
-                        },
+                        set_image_source: &self.image(),
                         set_valign: gtk4::Align::Start,
-                        add_css_class: if file_exists(&self.notification.image) { "image" } else { "icon-image" },
+                        add_css_class: if matches!(self.image(), ImageSource::File(_)) { "image" } else { "icon-image" },
                     },
 
                     // Text content
@@ -142,10 +191,15 @@ impl FactoryComponent for NotificationCard {
                             set_hexpand: true,
                         },
 
-                        // Body (if present)
+                        // Body (if present), with the spec's markup and
+                        // clickable links
                         gtk4::Label {
                             #[watch]
-                            set_text: &self.notification.body,
+                            set_markup: &body_markup(&self.notification.body),
+                            connect_activate_link => |_, uri| {
+                                launch::open_uri(uri);
+                                glib::Propagation::Stop
+                            },
                             add_css_class: "body",
                             set_wrap: true,
                             set_halign: gtk4::Align::Start,
@@ -165,7 +219,7 @@ impl FactoryComponent for NotificationCard {
                 gtk4::Box {
                     add_css_class: "actions",
                     #[watch]
-                    set_visible: self.notification.actions.len() > 1,
+                    set_visible: self.button_actions().next().is_some(),
                 },
             }
         }
@@ -176,7 +230,10 @@ impl FactoryComponent for NotificationCard {
         _index: &Self::Index,
         _sender: FactorySender<Self>,
     ) -> Self {
-        Self { notification }
+        Self {
+            notification,
+            dismissed: false,
+        }
     }
 
     fn init_widgets(
@@ -187,63 +244,118 @@ impl FactoryComponent for NotificationCard {
         sender: FactorySender<Self>,
     ) -> Self::Widgets {
         let widgets = view_output!();
+        self.fill_actions(&widgets.actions_box, &sender);
+
+        // flicking the card sideways dismisses it. the gesture runs in the
+        // capture phase, so it sees swipes that start on the content button,
+        // and claims them so the button doesn't take them as a click
+        let swipe = gtk4::GestureSwipe::new();
+        swipe.set_propagation_phase(gtk4::PropagationPhase::Capture);
+        swipe.connect_swipe(move |gesture, velocity_x, velocity_y| {
+            if is_dismiss_swipe(velocity_x, velocity_y) {
+                gesture.set_state(gtk4::EventSequenceState::Claimed);
+                sender.input(NotificationCardMsg::Dismiss);
+            }
+        });
+        widgets.card.add_controller(swipe);
 
-        // execute the first action when the notification is clicked
-        if let Some(first_action) = self.notification.actions.first() {
-            let action_id = first_action.0.as_str();
+        widgets
+    }
 
-            let sender_clone = sender.clone();
-            let action_id = action_id.to_string();
-            widgets.content_container.connect_clicked(move |_| {
-                sender_clone.input(NotificationCardMsg::Action(action_id.clone()));
-            });
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: FactorySender<Self>,
+    ) {
+        // a replacement can change what isn't watched, i.e. the urgency and
+        // the action buttons
+        let replaced = matches!(message, NotificationCardMsg::Update(_));
+        if replaced {
+            widgets.card.remove_css_class(self.get_urgency_class());
+        }
+
+        self.update(message, sender.clone());
+
+        if replaced {
+            widgets.card.add_css_class(self.get_urgency_class());
+            self.fill_actions(&widgets.actions_box, &sender);
+        }
+        self.update_view(widgets, sender);
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: FactorySender<Self>) {
+        if let Some(output) = self.handle(msg) {
+            let _ = sender.output(output);
+        }
+    }
+}
+
+/// Whether a swipe ending at this velocity should dismiss a card: it has to
+/// be fast and mostly sideways, so scrolling the list doesn't count.
+fn is_dismiss_swipe(velocity_x: f64, velocity_y: f64) -> bool {
+    velocity_x.abs() >= DISMISS_SWIPE_VELOCITY && velocity_x.abs() > velocity_y.abs() * 2.0
+}
+
+impl NotificationCard {
+    /// Applies a message, returning what to tell the card's owner, if
+    /// anything.
+    fn handle(&mut self, msg: NotificationCardMsg) -> Option<NotificationCardOutput> {
+        let id = self.notification.id;
+        match msg {
+            NotificationCardMsg::Dismiss => {
+                self.dismissed = true;
+                Some(NotificationCardOutput::Dismiss(id))
+            }
+            NotificationCardMsg::Snooze => Some(NotificationCardOutput::Snooze(id)),
+            NotificationCardMsg::Action(action_id) => {
+                Some(NotificationCardOutput::Action(id, action_id))
+            }
+            // execute the default action when the notification is clicked
+            NotificationCardMsg::Clicked if !self.dismissed => self
+                .click_action()
+                .map(|action_id| NotificationCardOutput::Action(id, action_id.to_string())),
+            NotificationCardMsg::Clicked => None,
+            NotificationCardMsg::Update(notification) => {
+                self.notification = notification;
+                None
+            }
         }
+    }
 
-        // if multiple actions, create action buttons
-        if self.notification.actions.len() > 1 {
-            for chunk in &self.notification.actions {
-                let action_id = chunk.0.as_str();
-                let label = chunk.1.as_str();
+    /// Replaces the buttons in `actions_box` with one for each action that
+    /// isn't invoked by clicking the card.
+    fn fill_actions(&self, actions_box: &gtk4::Box, sender: &FactorySender<Self>) {
+        while let Some(child) = actions_box.first_child() {
+            actions_box.remove(&child);
+        }
 
-                let action_button = gtk4::Button::builder().hexpand(true).build();
+        for (action_id, label) in self.button_actions() {
+            let action_button = gtk4::Button::builder().hexpand(true).build();
 
+            // with action-icons, labels are icon names. they're shown as
+            // text if the theme doesn't have them
+            if self.notification.action_icons && is_icon(label) {
+                action_button.set_child(Some(&gtk4::Image::from_icon_name(label)));
+            } else {
                 let button_label = gtk4::Label::builder()
                     .label(label)
                     .halign(gtk4::Align::Center)
                     .hexpand(true)
                     .build();
-
                 action_button.set_child(Some(&button_label));
-
-                let sender_clone = sender.clone();
-                let action_id = action_id.to_string();
-                action_button.connect_clicked(move |_| {
-                    sender_clone.input(NotificationCardMsg::Action(action_id.clone()));
-                });
-
-                widgets.actions_box.append(&action_button);
             }
-        }
 
-        widgets
-    }
+            let sender_clone = sender.clone();
+            let action_id = action_id.to_string();
+            action_button.connect_clicked(move |_| {
+                sender_clone.input(NotificationCardMsg::Action(action_id.clone()));
+            });
 
-    fn update(&mut self, msg: Self::Input, sender: FactorySender<Self>) {
-        match msg {
-            NotificationCardMsg::Dismiss => {
-                let _ = sender.output(NotificationCardOutput::Dismiss(self.notification.id));
-            }
-            NotificationCardMsg::Action(action_id) => {
-                let _ = sender.output(NotificationCardOutput::Action(
-                    self.notification.id,
-                    action_id,
-                ));
-            }
+            actions_box.append(&action_button);
         }
     }
-}
 
-impl NotificationCard {
     fn get_urgency_class(&self) -> &'static str {
         match self.notification.urgency {
             NotificationUrgency::Low => "low",
@@ -252,22 +364,47 @@ impl NotificationCard {
         }
     }
 
-    fn has_app_icon(&self) -> bool {
-        !self.notification.app_icon.is_empty() || is_icon(&self.notification.desktop_entry)
+    /// The icon the app sent, or one guessed from its desktop entry or name.
+    fn app_icon(&self) -> ImageSource {
+        let source = resolve_image(&self.notification.app_icon);
+        if source != ImageSource::None {
+            return source;
+        }
+
+        let Notification {
+            desktop_entry,
+            app_name,
+            ..
+        } = &self.notification;
+        cached_app_icon(desktop_entry, app_name, || {
+            resolve_app_icon(desktop_entry, app_name)
+        })
+        .map_or(ImageSource::None, ImageSource::IconName)
+    }
+
+    /// The action to invoke when the card is clicked: the `default` action, or
+    /// the only action if there's just one.
+    fn click_action(&self) -> Option<&str> {
+        let actions = &self.notification.actions;
+        actions
+            .iter()
+            .find(|(id, _)| id == "default")
+            .or(actions.first().filter(|_| actions.len() == 1))
+            .map(|(id, _)| id.as_str())
     }
 
-    fn get_app_icon_name(&self) -> Option<String> {
-        if !self.notification.app_icon.is_empty() {
-            Some(self.notification.app_icon.clone())
-        } else if is_icon(&self.notification.desktop_entry) {
-            Some(self.notification.desktop_entry.clone())
-        } else {
-            None
-        }
+    /// Actions that get their own button. The `default` action is invoked by
+    /// clicking the card instead, as is a lone action.
+    fn button_actions(&self) -> impl Iterator<Item = (&str, &str)> {
+        let actions = &self.notification.actions;
+        actions
+            .iter()
+            .filter(move |(id, _)| actions.len() > 1 && id != "default")
+            .map(|(id, label)| (id.as_str(), label.as_str()))
     }
 
-    fn has_notification_image(&self) -> bool {
-        !self.notification.image.is_empty()
+    fn image(&self) -> ImageSource {
+        resolve_image(&self.notification.image)
     }
 
     fn format_time(&self, timestamp: i64) -> String {
@@ -284,3 +421,44 @@ impl NotificationCard {
         self.notification.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::tests::notification;
+
+    #[test]
+    fn test_is_dismiss_swipe() {
+        assert!(is_dismiss_swipe(1200.0, 100.0));
+        assert!(is_dismiss_swipe(-1200.0, -100.0));
+
+        // too slow, or scrolling the list
+        assert!(!is_dismiss_swipe(300.0, 0.0));
+        assert!(!is_dismiss_swipe(1000.0, 900.0));
+    }
+
+    #[test]
+    fn test_swipe_on_content_dismisses_without_clicking() {
+        let card = || NotificationCard {
+            notification: Notification {
+                actions: vec![("default".to_string(), "Open".to_string())],
+                ..notification(7)
+            },
+            dismissed: false,
+        };
+
+        // a plain click invokes the default action
+        assert_eq!(
+            card().handle(NotificationCardMsg::Clicked),
+            Some(NotificationCardOutput::Action(7, "default".to_string()))
+        );
+
+        // a swipe that ends over the content only dismisses
+        let mut swiped = card();
+        assert_eq!(
+            swiped.handle(NotificationCardMsg::Dismiss),
+            Some(NotificationCardOutput::Dismiss(7))
+        );
+        assert_eq!(swiped.handle(NotificationCardMsg::Clicked), None);
+    }
+}
@@ -1,22 +1,44 @@
 use std::{
-    sync::atomic::{AtomicU32, Ordering},
-    time::SystemTime,
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime},
 };
 
 use tokio::sync::broadcast;
 use zbus::{interface, object_server::SignalEmitter};
 
-use crate::notifications::{
-    NOTIFICATIONS_STATE, NotificationEvent, NotificationHints,
-    types::{Notification, NotificationUrgency},
+use crate::{
+    notifications::{
+        NOTIFICATIONS_STATE, NotificationEvent, NotificationHints, close,
+        image::remove_saved_image,
+        should_pop_up,
+        types::{CloseReason, Notification, NotificationUrgency},
+    },
+    settings,
 };
 
 static NOTIFICATION_ID: AtomicU32 = AtomicU32::new(1);
 
+/// Tells expiry timers apart, so a stale one can see it's been superseded.
+static EXPIRY_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// How long normal notifications last when the sender leaves it to us.
+const DEFAULT_EXPIRY_NORMAL: Duration = Duration::from_secs(5);
+
+/// How long low-urgency notifications last when the sender leaves it to us.
+const DEFAULT_EXPIRY_LOW: Duration = Duration::from_secs(8);
+
 /// Implements https://specifications.freedesktop.org/notification-spec/latest/protocol.html.
 #[derive(Debug)]
 pub struct NotificationsDaemon {
     event_tx: broadcast::Sender<NotificationEvent>,
+    /// The generation of the running expiry timer for each notification. A
+    /// replacement or a close changes or drops it, which stops the older
+    /// timer from firing.
+    expiries: Arc<Mutex<HashMap<u32, u64>>>,
 }
 
 #[interface(name = "org.freedesktop.Notifications")]
@@ -34,7 +56,7 @@ impl NotificationsDaemon {
         actions: Vec<String>,
         hints: NotificationHints,
         expire_timeout: i32,
-        #[zbus(signal_emitter)] _emitter: SignalEmitter<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
     ) -> u32 {
         let id = if replaces_id != 0 {
             replaces_id
@@ -43,12 +65,19 @@ impl NotificationsDaemon {
         };
 
         let urgency = hints.urgency.unwrap_or(NotificationUrgency::Normal);
+        let sound = hints.sound();
 
         let timestamp = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap()
             .as_secs() as i64;
 
+        // some apps dump whole logs into the body
+        let body = match settings::get_config().notifications.max_body_length {
+            Some(max_length) => truncate_body(body, max_length),
+            None => body,
+        };
+
         let actions = {
             let evens = actions.iter().step_by(2).cloned();
             let odds = actions.iter().skip(1).step_by(2).cloned();
@@ -59,36 +88,70 @@ impl NotificationsDaemon {
             id,
             app_name: app_name.clone(),
             app_icon: app_icon.clone(),
+            image: hints.image(id),
             desktop_entry: hints.desktop_entry.unwrap_or_default(),
-            image: String::new(), // TODO: Extract from hints if available
             summary: summary.clone(),
             body: body.clone(),
             urgency,
             timeout: expire_timeout,
             timestamp,
             actions,
+            action_icons: hints.action_icons,
+            snoozed_until: None,
         };
 
         log::debug!("new notification received: {:?}", notification);
 
         // write to the global state
-        NOTIFICATIONS_STATE
+        let replaced = NOTIFICATIONS_STATE
             .write()
             .notifications
             .insert(id, notification.clone());
 
+        // a replacement with inline data rewrites the same file, so only
+        // clean up if it no longer points there
+        if let Some(replaced) = replaced
+            && replaced.image != notification.image
+        {
+            remove_saved_image(&replaced.image);
+        }
+
+        // sounds go with popups, so do-not-disturb silences them too. playing
+        // happens in the background, so the sender isn't kept waiting
+        if let Some(sound) = sound
+            && should_pop_up(&notification)
+        {
+            sound.play();
+        }
+
         // broadcast the event to all subscribers
         let _ = self
             .event_tx
             .send(NotificationEvent::Received(notification.clone()));
 
-        // handle timeout — auto-close non-persistent notifications
-        if expire_timeout > 0 {
-            let event_tx = self.event_tx.clone();
+        // expire non-persistent notifications. a replacement restarts the
+        // timer, or cancels it if the replacement is persistent
+        let expiry = expire_after(expire_timeout, urgency);
+        let generation = EXPIRY_GENERATION.fetch_add(1, Ordering::SeqCst);
+        {
+            let mut expiries = self.expiries.lock().unwrap();
+            match expiry {
+                Some(_) => expiries.insert(id, generation),
+                None => expiries.remove(&id),
+            };
+        }
+
+        if let Some(expiry) = expiry {
+            let expiries = self.expiries.clone();
+            let emitter = emitter.to_owned();
             relm4::spawn(async move {
-                tokio::time::sleep(tokio::time::Duration::from_millis(expire_timeout as u64)).await;
-                NOTIFICATIONS_STATE.write().notifications.remove(&id);
-                let _ = event_tx.send(NotificationEvent::Closed { id, reason: 1 });
+                tokio::time::sleep(expiry).await;
+
+                if let Some(reason) = expire(&expiries, id, generation)
+                    && let Err(e) = emitter.notification_closed(id, reason.into()).await
+                {
+                    log::error!("failed to emit notification_closed signal: {}", e);
+                }
             });
         }
 
@@ -100,16 +163,11 @@ impl NotificationsDaemon {
         id: u32,
         #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
     ) {
-        NOTIFICATIONS_STATE.write().notifications.remove(&id);
-
-        // emit D-Bus signal (reason 2 = closed by the notification server)
-        if let Err(e) = emitter.notification_closed(id, 2).await {
+        if let Some(reason) = self.close_requested(id)
+            && let Err(e) = emitter.notification_closed(id, reason.into()).await
+        {
             log::error!("failed to emit notification_closed signal: {}", e);
         }
-
-        let _ = self
-            .event_tx
-            .send(NotificationEvent::Closed { id, reason: 2 });
     }
 
     async fn get_capabilities(&self) -> Vec<String> {
@@ -155,6 +213,139 @@ impl NotificationsDaemon {
 impl NotificationsDaemon {
     /// Creates a new daemon that broadcasts events onto `event_tx`.
     pub fn new(event_tx: broadcast::Sender<NotificationEvent>) -> Self {
-        Self { event_tx }
+        Self {
+            event_tx,
+            expiries: Arc::default(),
+        }
+    }
+
+    /// Closes a notification for a `CloseNotification` call, returning the
+    /// reason to report if it was open.
+    fn close_requested(&self, id: u32) -> Option<CloseReason> {
+        self.expiries.lock().unwrap().remove(&id);
+
+        let reason = CloseReason::Closed;
+        close(id, reason).then_some(reason)
+    }
+}
+
+/// Runs out a notification's expiry timer, returning the reason to report,
+/// or `None` if a newer timer superseded it or the notification is gone.
+///
+/// Expiring only takes the popup down, which it does on its own. The
+/// notification stays in the notification center.
+fn expire(expiries: &Mutex<HashMap<u32, u64>>, id: u32, generation: u64) -> Option<CloseReason> {
+    {
+        let mut expiries = expiries.lock().unwrap();
+        if expiries.get(&id) != Some(&generation) {
+            return None;
+        }
+        expiries.remove(&id);
+    }
+
+    NOTIFICATIONS_STATE
+        .read()
+        .notifications
+        .contains_key(&id)
+        .then_some(CloseReason::Expired)
+}
+
+/// Works out when a notification expires from the `expire_timeout` its sender
+/// gave, or `None` if it stays until dismissed.
+///
+/// Per the spec, 0 means never and a positive value is in milliseconds. -1
+/// (or any other negative value) leaves it to us: critical notifications never
+/// expire, and the rest get a default for their urgency.
+pub(crate) fn expire_after(expire_timeout: i32, urgency: NotificationUrgency) -> Option<Duration> {
+    match expire_timeout {
+        0 => None,
+        1.. => Some(Duration::from_millis(expire_timeout as u64)),
+        _ => match urgency {
+            NotificationUrgency::Low => Some(DEFAULT_EXPIRY_LOW),
+            NotificationUrgency::Normal => Some(DEFAULT_EXPIRY_NORMAL),
+            NotificationUrgency::Critical => None,
+        },
+    }
+}
+
+/// Cuts `body` down to at most `max_length` characters, ending it with an
+/// ellipsis if anything was removed.
+fn truncate_body(body: String, max_length: usize) -> String {
+    if body.chars().count() <= max_length {
+        return body;
+    }
+
+    let mut truncated: String = body.chars().take(max_length.saturating_sub(1)).collect();
+    if max_length > 0 {
+        truncated.push('…');
+    }
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::{event_tx, tests::insert_notification};
+
+    #[test]
+    fn test_truncate_body_boundaries() {
+        // at or under the limit, bodies are kept whole
+        assert_eq!(truncate_body("hello".to_string(), 6), "hello");
+        assert_eq!(truncate_body("hello".to_string(), 5), "hello");
+
+        // over it, the ellipsis counts towards the limit
+        assert_eq!(truncate_body("hello!".to_string(), 5), "hell…");
+        assert_eq!(truncate_body("hello".to_string(), 1), "…");
+        assert_eq!(truncate_body("hello".to_string(), 0), "");
+
+        // lengths are in characters, not bytes
+        assert_eq!(truncate_body("héllö wörld".to_string(), 6), "héllö…");
+    }
+
+    #[test]
+    fn test_expire_after() {
+        use NotificationUrgency::*;
+
+        // the server decides
+        assert_eq!(expire_after(-1, Low), Some(DEFAULT_EXPIRY_LOW));
+        assert_eq!(expire_after(-1, Normal), Some(DEFAULT_EXPIRY_NORMAL));
+        assert_eq!(expire_after(-1, Critical), None);
+
+        // never, whatever the urgency
+        assert_eq!(expire_after(0, Low), None);
+        assert_eq!(expire_after(0, Normal), None);
+
+        // milliseconds, even for critical notifications
+        assert_eq!(
+            expire_after(1500, Normal),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(expire_after(3000, Critical), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_expiry_reports_reason_1() {
+        let expiries = Mutex::new(HashMap::from([(9001, 2)]));
+        insert_notification(9001);
+
+        // a timer a replacement superseded stays quiet
+        assert_eq!(expire(&expiries, 9001, 1), None);
+
+        assert_eq!(expire(&expiries, 9001, 2).map(u32::from), Some(1));
+        // the notification center still has it
+        assert!(NOTIFICATIONS_STATE.read().notifications.contains_key(&9001));
+
+        // and it only expires once
+        assert_eq!(expire(&expiries, 9001, 2), None);
+    }
+
+    #[test]
+    fn test_close_notification_reports_reason_3() {
+        let daemon = NotificationsDaemon::new(event_tx().clone());
+        insert_notification(9003);
+
+        assert_eq!(daemon.close_requested(9003).map(u32::from), Some(3));
+        assert!(!NOTIFICATIONS_STATE.read().notifications.contains_key(&9003));
+        assert_eq!(daemon.close_requested(9003), None);
     }
 }
@@ -1,35 +1,73 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use gdk4::Monitor;
 use gtk4::prelude::*;
 use gtk4_layer_shell::{Edge, Layer, LayerShell};
 use relm4::{factory::FactoryVecDeque, prelude::*};
 
-use crate::notifications::{
-    card::{NotificationCard, NotificationCardOutput},
-    types::{Notification, NotificationUrgency},
+use crate::{
+    monitors::{focused_output, primary_monitor},
+    notifications::{
+        self,
+        card::{NotificationCard, NotificationCardMsg, NotificationCardOutput},
+        daemon,
+        types::Notification,
+    },
+    settings::{self, CONFIG, NotificationConfig, PopupPlacement},
 };
 
+/// How long the focused output has to stay put before popups follow it.
+const FOCUS_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// The notification popup stack for one bar's monitor.
+///
+/// Every bar has one, and all of them receive the same notifications;
+/// `notifications.show_on` decides which of them are visible. With the
+/// default `active_monitor`, popups appear to follow focus between monitors.
 #[derive(Debug)]
 pub struct FreshNotifications {
     visible: bool,
     cards: FactoryVecDeque<NotificationCard>,
+    /// The monitor of the bar that owns this popup.
     monitor: Monitor,
-    auto_dismiss_timeouts: HashMap<u32, glib::SourceId>,
+    /// Whether popups should currently appear on this popup's monitor,
+    /// according to `notifications.show_on`.
+    is_target: bool,
+    /// Counts focus changes, so only the delay started by the latest one
+    /// re-evaluates the target.
+    focus_count: u32,
+    /// Tracks hotplug, since that can change which monitor is primary.
+    monitors_changed: Option<(gio::ListModel, glib::SignalHandlerId)>,
+    /// The count of each running auto-dismiss timeout, by notification. A
+    /// timeout whose count is no longer here was cancelled and does nothing.
+    auto_dismiss_timeouts: HashMap<u32, u32>,
+    auto_dismiss_count: u32,
+    config: NotificationConfig,
 }
 
 #[derive(Debug)]
 pub enum FreshNotificationsMsg {
     NewNotification(Notification),
     RemoveNotification(u32),
-    AutoDismiss(u32),                // auto-dismiss a notification by ID
+    /// Every notification was cleared from the notification center.
+    RemoveAll,
+    AutoDismiss(u32, u32),           // notification_id, timeout count
     NotificationAction(u32, String), // notification_id, action_id
     DismissNotification(u32),        // notification_id
+    SnoozeNotification(u32),         // notification_id
+    ConfigUpdate(NotificationConfig),
+    /// A monitor was plugged or unplugged.
+    UpdateTarget,
+    /// The compositor's focus changed; re-evaluated after a short delay.
+    FocusChanged,
+    /// The focus change with this count hasn't been followed by another.
+    FocusSettled(u32),
 }
 
 #[derive(Debug)]
 pub enum FreshNotificationsOutput {
     NotificationDismissed(u32),
+    NotificationSnoozed(u32),
     NotificationActionTriggered(u32, String),
 }
 
@@ -43,13 +81,21 @@ impl SimpleComponent for FreshNotifications {
         #[root]
         window = gtk4::Window {
             #[watch]
-            set_visible: model.visible && !model.cards.is_empty(),
+            set_visible: model.visible && model.is_target && !model.cards.is_empty(),
+
+            gtk4::ScrolledWindow {
+                set_hscrollbar_policy: gtk4::PolicyType::Never,
+                set_propagate_natural_height: true,
+                #[watch]
+                set_max_content_height: model.config.popup_max_height,
 
-            #[local_ref]
-            notifications_container -> gtk4::Box {
-                set_orientation: gtk4::Orientation::Vertical,
-                set_spacing: 8,
-                set_width_request: 400,
+                #[local_ref]
+                notifications_container -> gtk4::Box {
+                    set_orientation: gtk4::Orientation::Vertical,
+                    set_spacing: 8,
+                    #[watch]
+                    set_width_request: model.config.popup_width,
+                },
             },
         }
     }
@@ -65,16 +111,39 @@ impl SimpleComponent for FreshNotifications {
                 NotificationCardOutput::Dismiss(id) => {
                     FreshNotificationsMsg::DismissNotification(id)
                 }
+                NotificationCardOutput::Snooze(id) => FreshNotificationsMsg::SnoozeNotification(id),
                 NotificationCardOutput::Action(id, action) => {
                     FreshNotificationsMsg::NotificationAction(id, action)
                 }
             });
 
+        CONFIG.subscribe(sender.input_sender(), |config| {
+            FreshNotificationsMsg::ConfigUpdate(config.notifications.clone())
+        });
+
+        // re-evaluate the target whenever a monitor is plugged or unplugged
+        let monitors = root.display().monitors();
+        let handler = monitors.connect_items_changed({
+            let sender = sender.input_sender().clone();
+            move |_, _, _, _| sender.emit(FreshNotificationsMsg::UpdateTarget)
+        });
+
+        #[cfg(feature = "niri")]
+        crate::niri::NIRI_STATE.subscribe(sender.input_sender(), |_| {
+            FreshNotificationsMsg::FocusChanged
+        });
+
+        let config = settings::get_config().notifications;
         let model = FreshNotifications {
             visible: true,
             cards,
+            is_target: is_target(&monitor, config.show_on),
             monitor,
+            focus_count: 0,
+            monitors_changed: Some((monitors, handler)),
             auto_dismiss_timeouts: HashMap::new(),
+            auto_dismiss_count: 0,
+            config,
         };
 
         let notifications_container = model.cards.widget();
@@ -96,31 +165,53 @@ impl SimpleComponent for FreshNotifications {
     fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         match msg {
             FreshNotificationsMsg::NewNotification(notification) => {
-                // add to the beginning (top) of the list
                 let notification_id = notification.id;
-                let urgency = notification.urgency;
+                let expires = daemon::expire_after(notification.timeout, notification.urgency);
 
-                self.cards.guard().push_front(notification);
+                // a replacement, e.g. a progress update, changes its card in
+                // place and keeps its timeout running, so the popup doesn't
+                // jump or stay up for as long as updates keep coming
+                let existing = self
+                    .cards
+                    .iter()
+                    .position(|card| card.notification_id() == notification_id);
+                if let Some(index) = existing {
+                    self.cards
+                        .send(index, NotificationCardMsg::Update(notification));
+
+                    // a timeout is only started if the card didn't have one,
+                    // and dropped if the replacement is persistent
+                    if expires.is_none() {
+                        self.auto_dismiss_timeouts.remove(&notification_id);
+                    }
+                    if expires.is_none()
+                        || self.auto_dismiss_timeouts.contains_key(&notification_id)
+                    {
+                        return;
+                    }
+                } else if notifications::should_pop_up(&notification) {
+                    // add to the beginning (top) of the list
+                    self.cards.guard().push_front(notification);
+                } else {
+                    return;
+                }
 
-                // set up auto-dismiss for non-critical notifications
-                if !matches!(urgency, NotificationUrgency::Critical) {
+                // persistent notifications stay up until they're dismissed
+                if let Some(expires) = expires {
+                    self.auto_dismiss_count += 1;
+                    let count = self.auto_dismiss_count;
                     let dismiss_sender = sender.clone();
-                    let timeout_id = glib::timeout_add_local_once(
-                        std::time::Duration::from_secs(10),
-                        move || {
-                            dismiss_sender
-                                .input(FreshNotificationsMsg::AutoDismiss(notification_id));
-                        },
-                    );
-                    self.auto_dismiss_timeouts
-                        .insert(notification_id, timeout_id);
+                    glib::timeout_add_local_once(expires, move || {
+                        dismiss_sender
+                            .input(FreshNotificationsMsg::AutoDismiss(notification_id, count));
+                    });
+                    self.auto_dismiss_timeouts.insert(notification_id, count);
                 }
             }
             FreshNotificationsMsg::RemoveNotification(id) => {
-                // remove auto-dismiss timeout if it exists
-                if let Some(timeout_id) = self.auto_dismiss_timeouts.remove(&id) {
-                    timeout_id.remove();
-                }
+                // cancel the auto-dismiss timeout if there is one. it's left
+                // to run out, since it may already have fired
+                self.auto_dismiss_timeouts.remove(&id);
 
                 // remove from notifications list
                 let mut guard = self.cards.guard();
@@ -134,13 +225,21 @@ impl SimpleComponent for FreshNotifications {
                     guard.remove(index);
                 }
             }
-            FreshNotificationsMsg::AutoDismiss(id) => {
-                // remove the timeout tracking since it fired
+            FreshNotificationsMsg::RemoveAll => {
+                self.auto_dismiss_timeouts.clear();
+                self.cards.guard().clear();
+            }
+            FreshNotificationsMsg::AutoDismiss(id, count) => {
+                // the timeout may have been cancelled, or replaced by a newer
+                // one, since it was started
+                if self.auto_dismiss_timeouts.get(&id) != Some(&count) {
+                    return;
+                }
                 self.auto_dismiss_timeouts.remove(&id);
 
-                // remove the notification and notify service
+                // only the popup goes away. the notification stays in the
+                // notification center, and the daemon reports the expiry
                 sender.input(FreshNotificationsMsg::RemoveNotification(id));
-                sender.input(FreshNotificationsMsg::DismissNotification(id));
             }
             FreshNotificationsMsg::DismissNotification(id) => {
                 // remove from our display
@@ -153,6 +252,37 @@ impl SimpleComponent for FreshNotifications {
                         log::error!("couldn't output action trigger event from popup")
                     });
             }
+            FreshNotificationsMsg::SnoozeNotification(id) => {
+                // hide the popup but keep the notification in the center
+                sender.input(FreshNotificationsMsg::RemoveNotification(id));
+
+                sender
+                    .output(FreshNotificationsOutput::NotificationSnoozed(id))
+                    .unwrap_or_else(|_| log::error!("couldn't output snooze event from popup"));
+            }
+            FreshNotificationsMsg::ConfigUpdate(config) => {
+                self.is_target = is_target(&self.monitor, config.show_on);
+                self.config = config;
+            }
+            FreshNotificationsMsg::UpdateTarget => {
+                self.is_target = is_target(&self.monitor, self.config.show_on);
+            }
+            FreshNotificationsMsg::FocusChanged => {
+                // restart the delay so rapid switching between monitors
+                // doesn't bounce popups back and forth. earlier delays are
+                // left to run out, since one may already have fired
+                self.focus_count += 1;
+                let count = self.focus_count;
+                let sender = sender.clone();
+                glib::timeout_add_local_once(FOCUS_DEBOUNCE, move || {
+                    sender.input(FreshNotificationsMsg::FocusSettled(count))
+                });
+            }
+            FreshNotificationsMsg::FocusSettled(count) => {
+                if count == self.focus_count {
+                    self.is_target = is_target(&self.monitor, self.config.show_on);
+                }
+            }
             FreshNotificationsMsg::NotificationAction(id, action) => {
                 sender
                     .output(FreshNotificationsOutput::NotificationActionTriggered(
@@ -164,4 +294,80 @@ impl SimpleComponent for FreshNotifications {
             }
         }
     }
+
+    fn shutdown(&mut self, _widgets: &mut Self::Widgets, _output: relm4::Sender<Self::Output>) {
+        if let Some((monitors, handler)) = self.monitors_changed.take() {
+            monitors.disconnect(handler);
+        }
+    }
+}
+
+/// Whether popups belong on `monitor` right now.
+fn is_target(monitor: &Monitor, show_on: PopupPlacement) -> bool {
+    let Some(connector) = monitor.connector() else {
+        return false;
+    };
+
+    let primary = primary_monitor(&monitor.display()).and_then(|m| m.connector());
+    shows_popups(
+        show_on,
+        &connector,
+        primary.as_deref(),
+        focused_output().as_deref(),
+    )
+}
+
+/// Decides whether the monitor named `connector` shows popups. The active
+/// monitor falls back to the primary one when the compositor can't tell us
+/// which output is focused.
+fn shows_popups(
+    show_on: PopupPlacement,
+    connector: &str,
+    primary: Option<&str>,
+    focused: Option<&str>,
+) -> bool {
+    match show_on {
+        PopupPlacement::AllMonitors => true,
+        PopupPlacement::PrimaryMonitor => primary == Some(connector),
+        PopupPlacement::ActiveMonitor => focused.or(primary) == Some(connector),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shows_popups() {
+        use PopupPlacement::*;
+
+        assert!(shows_popups(AllMonitors, "HDMI-A-1", Some("eDP-1"), None));
+        assert!(shows_popups(
+            PrimaryMonitor,
+            "eDP-1",
+            Some("eDP-1"),
+            Some("HDMI-A-1")
+        ));
+        assert!(!shows_popups(
+            PrimaryMonitor,
+            "HDMI-A-1",
+            Some("eDP-1"),
+            Some("HDMI-A-1")
+        ));
+        assert!(shows_popups(
+            ActiveMonitor,
+            "HDMI-A-1",
+            Some("eDP-1"),
+            Some("HDMI-A-1")
+        ));
+        assert!(!shows_popups(
+            ActiveMonitor,
+            "eDP-1",
+            Some("eDP-1"),
+            Some("HDMI-A-1")
+        ));
+
+        // no focus information falls back to the primary monitor
+        assert!(shows_popups(ActiveMonitor, "eDP-1", Some("eDP-1"), None));
+    }
 }
@@ -0,0 +1,270 @@
+use std::{cell::RefCell, cmp::Reverse, collections::HashSet, rc::Rc};
+
+use gtk4::prelude::*;
+use relm4::{factory::FactoryVecDeque, prelude::*};
+
+use crate::notifications::{
+    card::{NotificationCard, NotificationCardOutput},
+    types::Notification,
+};
+
+/// The newest notification ids of the groups that are expanded. The
+/// notification center keeps these, since it rebuilds its groups whenever the
+/// notifications change.
+///
+/// A group stays expanded as long as it holds one of these, so it doesn't
+/// collapse when a newer notification joins it, and two runs of notifications
+/// from one app are expanded separately.
+pub type ExpandedGroups = Rc<RefCell<HashSet<u32>>>;
+
+/// Sorts notifications newest first and groups runs of them from one app, so
+/// groups stay in order of their newest notification.
+pub fn group_notifications<'a>(
+    notifications: impl IntoIterator<Item = &'a Notification>,
+) -> Vec<Vec<Notification>> {
+    let mut notifications: Vec<&Notification> = notifications.into_iter().collect();
+    // ids break ties between notifications sent in the same second
+    notifications.sort_by_key(|n| Reverse((n.timestamp, n.id)));
+
+    notifications
+        .chunk_by(|a, b| a.app_key() == b.app_key())
+        .map(|group| group.iter().map(|&n| n.clone()).collect())
+        .collect()
+}
+
+/// Notifications in a row from one app, newest first. A lone notification is
+/// shown as a plain card; more are collapsed under a header with the count
+/// and the latest summary, and expand to show every card.
+#[derive(Debug)]
+pub struct NotificationGroup {
+    app_name: String,
+    latest_summary: String,
+    ids: Vec<u32>,
+    cards: FactoryVecDeque<NotificationCard>,
+    expanded: bool,
+    expanded_groups: ExpandedGroups,
+}
+
+pub struct NotificationGroupInit {
+    /// Newest first.
+    pub notifications: Vec<Notification>,
+    pub expanded_groups: ExpandedGroups,
+}
+
+#[derive(Debug)]
+pub enum NotificationGroupMsg {
+    Toggle,
+    Dismiss,
+}
+
+#[derive(Debug)]
+pub enum NotificationGroupOutput {
+    Card(NotificationCardOutput),
+    /// Dismiss every notification in the group.
+    Dismiss(Vec<u32>),
+}
+
+pub struct NotificationGroupWidgets {
+    revealer: gtk4::Revealer,
+    chevron: gtk4::Image,
+}
+
+impl FactoryComponent for NotificationGroup {
+    type CommandOutput = ();
+    type Index = DynamicIndex;
+    type Init = NotificationGroupInit;
+    type Input = NotificationGroupMsg;
+    type Output = NotificationGroupOutput;
+    type ParentWidget = gtk4::Box;
+    type Root = gtk4::Box;
+    type Widgets = NotificationGroupWidgets;
+
+    fn init_root(&self) -> Self::Root {
+        gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .css_classes(["notification-group"])
+            .build()
+    }
+
+    fn init_model(
+        NotificationGroupInit {
+            notifications,
+            expanded_groups,
+        }: Self::Init,
+        _index: &DynamicIndex,
+        sender: FactorySender<Self>,
+    ) -> Self {
+        let mut cards = FactoryVecDeque::builder()
+            .launch(
+                gtk4::Box::builder()
+                    .orientation(gtk4::Orientation::Vertical)
+                    .build(),
+            )
+            .forward(sender.output_sender(), NotificationGroupOutput::Card);
+
+        let latest = notifications.first();
+        let app_name = latest.map(|n| n.app_name.clone()).unwrap_or_default();
+        let latest_summary = latest.map(|n| n.summary.clone()).unwrap_or_default();
+        let ids = notifications.iter().map(|n| n.id).collect::<Vec<_>>();
+
+        {
+            let mut guard = cards.guard();
+            for notification in notifications {
+                guard.push_back(notification);
+            }
+        }
+
+        let expanded = ids.len() <= 1 || {
+            let expanded_groups = expanded_groups.borrow();
+            ids.iter().any(|id| expanded_groups.contains(id))
+        };
+
+        Self {
+            expanded,
+            app_name,
+            latest_summary,
+            ids,
+            cards,
+            expanded_groups,
+        }
+    }
+
+    fn init_widgets(
+        &mut self,
+        _index: &DynamicIndex,
+        root: Self::Root,
+        _returned_widget: &gtk4::Widget,
+        sender: FactorySender<Self>,
+    ) -> Self::Widgets {
+        let chevron = gtk4::Image::builder()
+            .icon_name(chevron_icon(self.expanded))
+            .build();
+
+        // a lone notification doesn't need a header
+        if self.ids.len() > 1 {
+            let header = gtk4::Box::builder()
+                .css_classes(["notification-group-header"])
+                .spacing(8)
+                .build();
+
+            let summary = gtk4::Box::builder().spacing(8).build();
+            summary.append(
+                &gtk4::Label::builder()
+                    .label(self.app_name.as_str())
+                    .css_classes(["app-name"])
+                    .build(),
+            );
+            summary.append(
+                &gtk4::Label::builder()
+                    .label(self.ids.len().to_string())
+                    .css_classes(["notification-count"])
+                    .build(),
+            );
+            summary.append(
+                &gtk4::Label::builder()
+                    .label(self.latest_summary.as_str())
+                    .css_classes(["dim"])
+                    .ellipsize(gtk4::pango::EllipsizeMode::End)
+                    .hexpand(true)
+                    .xalign(0.0)
+                    .build(),
+            );
+            summary.append(&chevron);
+
+            let toggle = gtk4::Button::builder()
+                .css_classes(["flat"])
+                .hexpand(true)
+                .child(&summary)
+                .build();
+            toggle.connect_clicked({
+                let sender = sender.clone();
+                move |_| sender.input(NotificationGroupMsg::Toggle)
+            });
+
+            let dismiss = gtk4::Button::builder()
+                .icon_name("window-close-symbolic")
+                .css_classes(["close-button"])
+                .tooltip_text("Dismiss all")
+                .build();
+            dismiss.connect_clicked(move |_| sender.input(NotificationGroupMsg::Dismiss));
+
+            header.append(&toggle);
+            header.append(&dismiss);
+            root.append(&header);
+        }
+
+        let revealer = gtk4::Revealer::builder()
+            .reveal_child(self.expanded)
+            .transition_type(gtk4::RevealerTransitionType::SlideDown)
+            .child(self.cards.widget())
+            .build();
+        root.append(&revealer);
+
+        NotificationGroupWidgets { revealer, chevron }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: FactorySender<Self>) {
+        match msg {
+            NotificationGroupMsg::Toggle => {
+                self.expanded = !self.expanded;
+                let mut expanded_groups = self.expanded_groups.borrow_mut();
+                if self.expanded {
+                    // ids are newest first
+                    expanded_groups.extend(self.ids.first());
+                } else {
+                    for id in &self.ids {
+                        expanded_groups.remove(id);
+                    }
+                }
+            }
+            NotificationGroupMsg::Dismiss => {
+                let _ = sender.output(NotificationGroupOutput::Dismiss(self.ids.clone()));
+            }
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, _sender: FactorySender<Self>) {
+        widgets.revealer.set_reveal_child(self.expanded);
+        widgets
+            .chevron
+            .set_icon_name(Some(chevron_icon(self.expanded)));
+    }
+}
+
+fn chevron_icon(expanded: bool) -> &'static str {
+    if expanded {
+        "pan-up-symbolic"
+    } else {
+        "pan-down-symbolic"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::tests::notification;
+
+    #[test]
+    fn test_group_notifications() {
+        let sent = |id, app_name: &str, timestamp| Notification {
+            app_name: app_name.to_string(),
+            timestamp,
+            ..notification(id)
+        };
+        let notifications = [
+            sent(1, "Firefox", 10),
+            sent(2, "Signal", 20),
+            sent(3, "Signal", 30),
+            sent(4, "Firefox", 30),
+            sent(5, "Mozilla Firefox", 40),
+        ];
+
+        let ids: Vec<Vec<u32>> = group_notifications(&notifications)
+            .iter()
+            .map(|group| group.iter().map(|n| n.id).collect())
+            .collect();
+        // newest first, with runs from one app grouped however it names
+        // itself, and the older Firefox run kept apart
+        assert_eq!(ids, [vec![5, 4], vec![3, 2], vec![1]]);
+    }
+}
@@ -0,0 +1,140 @@
+use std::{cell::RefCell, collections::VecDeque};
+
+/// How many apps' icons are remembered at once.
+const CAPACITY: usize = 64;
+
+thread_local! {
+    static ICON_CACHE: RefCell<IconCache> = RefCell::new(IconCache::new(CAPACITY));
+}
+
+/// A small least-recently-used cache of app icon lookups. Misses are cached
+/// too, so apps without an icon aren't looked up again on every notification.
+#[derive(Debug)]
+pub struct IconCache {
+    capacity: usize,
+    /// Most recently used entries first.
+    entries: VecDeque<(String, Option<String>)>,
+}
+
+impl IconCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the cached icon for `key`, calling `resolve` and caching its
+    /// result on a miss.
+    pub fn get_or_resolve(
+        &mut self,
+        key: &str,
+        resolve: impl FnOnce() -> Option<String>,
+    ) -> Option<String> {
+        if let Some(index) = self.entries.iter().position(|(k, _)| k == key) {
+            let entry = self.entries.remove(index)?;
+            let icon = entry.1.clone();
+            self.entries.push_front(entry);
+            return icon;
+        }
+
+        let icon = resolve();
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_back();
+        }
+        self.entries.push_front((key.to_string(), icon.clone()));
+        icon
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Looks up an app's icon through the shared cache. The cache is cleared
+/// whenever the icon theme changes, since names may resolve differently.
+///
+/// Lookups are keyed by the desktop entry and app name that `resolve` tries,
+/// rather than the app key they share: a miss for one spelling of an app
+/// mustn't hide the icon another spelling would find.
+pub fn cached_app_icon(
+    desktop_entry: &str,
+    app_name: &str,
+    resolve: impl FnOnce() -> Option<String>,
+) -> Option<String> {
+    watch_icon_theme();
+
+    // desktop entry ids can't contain newlines
+    let key = format!("{desktop_entry}\n{app_name}");
+    ICON_CACHE.with_borrow_mut(|cache| cache.get_or_resolve(&key, resolve))
+}
+
+fn watch_icon_theme() {
+    thread_local! {
+        static WATCHING: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+    }
+
+    if WATCHING.get() {
+        return;
+    }
+
+    if let Some(display) = gtk4::gdk::Display::default() {
+        gtk4::IconTheme::for_display(&display).connect_changed(|_| {
+            log::debug!("icon theme changed; clearing app icon cache");
+            ICON_CACHE.with_borrow_mut(IconCache::clear);
+        });
+        WATCHING.set(true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn test_repeated_lookups_hit_cache() {
+        let mut cache = IconCache::new(2);
+        let resolved = Cell::new(0);
+        let resolve = |icon: &str| {
+            resolved.set(resolved.get() + 1);
+            Some(icon.to_string())
+        };
+
+        assert_eq!(
+            cache.get_or_resolve("firefox", || resolve("firefox")),
+            Some("firefox".to_string())
+        );
+        assert_eq!(
+            cache.get_or_resolve("firefox", || resolve("wrong")),
+            Some("firefox".to_string())
+        );
+        assert_eq!(resolved.get(), 1);
+
+        // misses are remembered as well
+        assert_eq!(cache.get_or_resolve("unknown", || None), None);
+        assert_eq!(cache.get_or_resolve("unknown", || resolve("wrong")), None);
+        assert_eq!(resolved.get(), 1);
+
+        // the least recently used entry ("firefox") is evicted first
+        cache.get_or_resolve("unknown", || None);
+        cache.get_or_resolve("discord", || resolve("discord"));
+        cache.get_or_resolve("firefox", || resolve("firefox"));
+        assert_eq!(resolved.get(), 3);
+    }
+
+    #[test]
+    fn test_theme_change_clears_cache() {
+        let mut cache = IconCache::new(4);
+        cache.get_or_resolve("firefox", || Some("firefox".to_string()));
+
+        // what the icon theme's `changed` handler does
+        cache.clear();
+
+        assert_eq!(
+            cache.get_or_resolve("firefox", || Some("firefox-symbolic".to_string())),
+            Some("firefox-symbolic".to_string())
+        );
+    }
+}
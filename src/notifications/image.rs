@@ -0,0 +1,224 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+use gtk4::gdk_pixbuf::{Colorspace, Pixbuf};
+use serde::{Deserialize, Serialize};
+use zbus::zvariant::Type;
+
+/// Where a notification's app icon or image comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageSource {
+    /// A name to look up in the icon theme.
+    IconName(String),
+    /// An image file on disk.
+    File(PathBuf),
+    None,
+}
+
+/// Works out what an `app_icon` or image hint refers to. The spec allows
+/// themed icon names, absolute paths and `file://` URIs.
+///
+/// Anything else is taken as an icon name, even if a file by that name
+/// happens to exist relative to the shell's working directory.
+pub fn resolve_image(source: &str) -> ImageSource {
+    if source.is_empty() {
+        return ImageSource::None;
+    }
+
+    if source.starts_with("file://") {
+        return match glib::filename_from_uri(source) {
+            Ok((path, _)) => ImageSource::File(path),
+            Err(e) => {
+                log::debug!("ignoring notification image with bad uri {source}: {e}");
+                ImageSource::None
+            }
+        };
+    }
+
+    if source.starts_with('/') {
+        ImageSource::File(PathBuf::from(source))
+    } else {
+        ImageSource::IconName(source.to_string())
+    }
+}
+
+/// Raw pixels sent in the `image-data` hint, as `(iiibiiay)`.
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+pub struct ImageData {
+    width: i32,
+    height: i32,
+    rowstride: i32,
+    has_alpha: bool,
+    bits_per_sample: i32,
+    channels: i32,
+    data: Vec<u8>,
+}
+
+impl ImageData {
+    /// Checks the image is laid out the way the spec (and gdk-pixbuf)
+    /// expects, since gdk-pixbuf won't load anything else.
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.width <= 0 || self.height <= 0 {
+            bail!("image is {}x{}", self.width, self.height);
+        }
+        if self.bits_per_sample != 8 {
+            bail!("{} bits per sample isn't supported", self.bits_per_sample);
+        }
+        if self.channels != if self.has_alpha { 4 } else { 3 } {
+            bail!(
+                "{} channels doesn't match has_alpha = {}",
+                self.channels,
+                self.has_alpha
+            );
+        }
+
+        // the last row doesn't need padding out to the rowstride. worked out
+        // in i64, since senders can claim sizes that overflow an i32
+        let row_length = i64::from(self.width) * i64::from(self.channels);
+        let needed = i64::from(self.rowstride) * i64::from(self.height - 1) + row_length;
+        if i64::from(self.rowstride) < row_length || (self.data.len() as i64) < needed {
+            bail!(
+                "{} bytes with a rowstride of {} is too short for a {}x{} image",
+                self.data.len(),
+                self.rowstride,
+                self.width,
+                self.height
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Where images sent inline with notifications are written, so cards can
+/// load them like any other file.
+fn saved_image_dir() -> PathBuf {
+    glib::user_runtime_dir().join("cadenza-shell")
+}
+
+/// Writes `image` to a PNG for notification `id`, returning its path. A
+/// notification replacing this one overwrites the same file.
+pub fn save_image_data(id: u32, image: &ImageData) -> anyhow::Result<PathBuf> {
+    image.validate()?;
+
+    let dir = saved_image_dir();
+    std::fs::create_dir_all(&dir).with_context(|| format!("couldn't create {}", dir.display()))?;
+    let path = dir.join(format!("notification-{id}.png"));
+
+    Pixbuf::from_bytes(
+        &glib::Bytes::from(image.data.as_slice()),
+        Colorspace::Rgb,
+        image.has_alpha,
+        image.bits_per_sample,
+        image.width,
+        image.height,
+        image.rowstride,
+    )
+    .savev(&path, "png", &[])
+    .with_context(|| format!("couldn't write {}", path.display()))?;
+
+    Ok(path)
+}
+
+/// Deletes `image` if it's one [`save_image_data`] wrote. Anything else is
+/// the app's own file and is left alone.
+pub fn remove_saved_image(image: &str) {
+    let path = Path::new(image);
+    if image.is_empty() || path.parent() != Some(saved_image_dir().as_path()) {
+        return;
+    }
+
+    if let Err(e) = std::fs::remove_file(path) {
+        log::debug!("couldn't remove saved notification image {image}: {e}");
+    }
+}
+
+/// Lets `view!` set an [`ImageSource`] on a [`gtk4::Image`] with
+/// `set_image_source`.
+pub trait ImageSourceExt {
+    fn set_image_source(&self, source: &ImageSource);
+}
+
+impl ImageSourceExt for gtk4::Image {
+    fn set_image_source(&self, source: &ImageSource) {
+        match source {
+            ImageSource::IconName(name) => self.set_icon_name(Some(name)),
+            ImageSource::File(path) => self.set_from_file(Some(path)),
+            ImageSource::None => self.clear(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_image_kinds() {
+        assert_eq!(resolve_image(""), ImageSource::None);
+        assert_eq!(
+            resolve_image("mail-unread-symbolic"),
+            ImageSource::IconName("mail-unread-symbolic".to_string())
+        );
+        assert_eq!(
+            resolve_image("/usr/share/pixmaps/app.png"),
+            ImageSource::File(PathBuf::from("/usr/share/pixmaps/app.png"))
+        );
+        assert_eq!(
+            resolve_image("file:///home/me/My%20Pictures/cat.png"),
+            ImageSource::File(PathBuf::from("/home/me/My Pictures/cat.png"))
+        );
+        assert_eq!(resolve_image("file:///tmp/%zz.png"), ImageSource::None);
+    }
+
+    #[test]
+    fn test_image_data_validation() {
+        let image = |width, height, rowstride, len| ImageData {
+            width,
+            height,
+            rowstride,
+            has_alpha: true,
+            bits_per_sample: 8,
+            channels: 4,
+            data: vec![0; len],
+        };
+
+        assert!(image(2, 2, 8, 16).validate().is_ok());
+        // padded rows, with the last one left short
+        assert!(image(2, 2, 12, 20).validate().is_ok());
+
+        assert!(image(2, 2, 8, 15).validate().is_err());
+        assert!(image(2, 2, 4, 16).validate().is_err());
+        assert!(image(0, 2, 8, 16).validate().is_err());
+        // a row too long for an i32, which mustn't wrap around to pass
+        assert!(image(i32::MAX / 2, 1, 8, 16).validate().is_err());
+        assert!(
+            ImageData {
+                channels: 3,
+                ..image(2, 2, 8, 16)
+            }
+            .validate()
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_only_saved_images_are_removed() {
+        let file = std::env::temp_dir().join(format!("cadenza-image-{}", std::process::id()));
+        std::fs::write(&file, b"").unwrap();
+
+        remove_saved_image(file.to_str().unwrap());
+        assert!(file.exists());
+
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_relative_names_stay_themed() {
+        // `src` exists in the working directory during tests and
+        // `org.gnome.Nautilus` looks like a file name, but both are icon names
+        for name in ["src", "org.gnome.Nautilus"] {
+            assert_eq!(resolve_image(name), ImageSource::IconName(name.to_string()));
+        }
+    }
+}
@@ -0,0 +1,227 @@
+/// Tags the notification spec allows in bodies, besides `a` and `img`.
+const STYLE_TAGS: &[&str] = &["b", "i", "u"];
+
+/// Link schemes that are kept clickable. Links to anything else, like local
+/// files, are shown as plain text.
+const LINK_SCHEMES: &[&str] = &["http://", "https://", "mailto:"];
+
+/// Turns a notification body into Pango markup. The spec's subset is kept
+/// (`<b>`, `<i>`, `<u>` and `<a href>`), images are replaced with their alt
+/// text, and anything else is escaped so it shows up as written. Tags left
+/// open, e.g. by a body cut short, are closed. If Pango still can't read the
+/// result, the whole body is escaped instead.
+pub fn body_markup(body: &str) -> String {
+    // links are handled by the label rather than Pango, so they're left out
+    // when checking
+    if pango::parse_markup(&sanitize(body, false), '\0').is_ok() {
+        sanitize(body, true)
+    } else {
+        log::debug!("couldn't read notification body as markup: {body:?}");
+        glib::markup_escape_text(body).into()
+    }
+}
+
+fn sanitize(body: &str, keep_links: bool) -> String {
+    let mut markup = String::with_capacity(body.len());
+    // open tags, innermost last. links that were dropped are kept as `None`,
+    // so their closing tags are dropped too
+    let mut open: Vec<Option<&'static str>> = Vec::new();
+
+    let mut rest = body;
+    while let Some(c) = rest.chars().next() {
+        if c == '<'
+            && let Some(end) = rest.find('>')
+            && let Some(tag) = convert_tag(&rest[1..end], keep_links, &mut open)
+        {
+            markup.push_str(&tag);
+            rest = &rest[end + 1..];
+            continue;
+        }
+
+        push_escaped(&mut markup, &rest[..c.len_utf8()]);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    for tag in open.into_iter().rev().flatten() {
+        markup.push_str(&format!("</{tag}>"));
+    }
+    markup
+}
+
+/// Converts one tag's contents (between `<` and `>`) to Pango markup, or
+/// returns `None` if it isn't allowed and should be escaped.
+fn convert_tag(
+    tag: &str,
+    keep_links: bool,
+    open: &mut Vec<Option<&'static str>>,
+) -> Option<String> {
+    let tag = tag.trim().trim_end_matches('/');
+    let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+    let name = name.to_ascii_lowercase();
+
+    if let Some(closed) = name.strip_prefix('/') {
+        if closed != "a" && !STYLE_TAGS.contains(&closed) {
+            return None;
+        }
+
+        let &top = open.last()?;
+        return match top {
+            Some(top) if top == closed => {
+                open.pop();
+                Some(format!("</{top}>"))
+            }
+            None if closed == "a" => {
+                open.pop();
+                Some(String::new())
+            }
+            // closing something that isn't innermost would break nesting
+            _ => Some(String::new()),
+        };
+    }
+
+    match name.as_str() {
+        "a" => {
+            let href = attribute(attributes, "href").filter(|href| {
+                keep_links
+                    && LINK_SCHEMES
+                        .iter()
+                        .any(|scheme| href.to_ascii_lowercase().starts_with(scheme))
+            });
+            open.push(href.map(|_| "a"));
+
+            let mut markup = String::new();
+            if let Some(href) = href {
+                markup.push_str("<a href=\"");
+                push_escaped(&mut markup, href);
+                markup.push_str("\">");
+            }
+            Some(markup)
+        }
+        "img" => {
+            let mut markup = String::new();
+            push_escaped(
+                &mut markup,
+                attribute(attributes, "alt").unwrap_or_default(),
+            );
+            Some(markup)
+        }
+        _ => {
+            let &style = STYLE_TAGS.iter().find(|&&style| style == name)?;
+            open.push(Some(style));
+            Some(format!("<{style}>"))
+        }
+    }
+}
+
+/// Reads a quoted attribute's value.
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attributes;
+    loop {
+        let (key, after) = rest.trim_start().split_once('=')?;
+        let after = after.trim_start();
+        let quote = after.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+        let (value, remaining) = after[1..].split_once(quote)?;
+
+        if key.trim().eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+        rest = remaining;
+    }
+}
+
+/// Escapes `text` for markup, keeping entities that are already escaped.
+fn push_escaped(markup: &mut String, text: &str) {
+    for (i, c) in text.char_indices() {
+        match c {
+            '&' if is_entity(&text[i + 1..]) => markup.push('&'),
+            '&' => markup.push_str("&amp;"),
+            '<' => markup.push_str("&lt;"),
+            '>' => markup.push_str("&gt;"),
+            '"' => markup.push_str("&quot;"),
+            c => markup.push(c),
+        }
+    }
+}
+
+/// Whether `text`, just after an `&`, starts with an entity Pango knows.
+fn is_entity(text: &str) -> bool {
+    let Some((name, _)) = text.split_once(';') else {
+        return false;
+    };
+
+    match name.strip_prefix('#') {
+        Some(code) => match code.strip_prefix(['x', 'X']) {
+            Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+            None => !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()),
+        },
+        None => matches!(name, "amp" | "lt" | "gt" | "quot" | "apos"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowed_tags_are_kept() {
+        assert_eq!(
+            sanitize("<b>bold</b>, <I>italic</I> and <u>underlined</u>", true),
+            "<b>bold</b>, <i>italic</i> and <u>underlined</u>"
+        );
+        assert_eq!(
+            sanitize(
+                r#"see <a href="https://example.com/?a=1&amp;b=2">this</a>"#,
+                true
+            ),
+            r#"see <a href="https://example.com/?a=1&amp;b=2">this</a>"#
+        );
+        assert_eq!(
+            sanitize(r#"<img src="/tmp/cat.png" alt="a cat"/> says hi"#, true),
+            "a cat says hi"
+        );
+    }
+
+    #[test]
+    fn test_everything_else_is_escaped() {
+        assert_eq!(
+            sanitize("1 < 2 && 3 > 2", true),
+            "1 &lt; 2 &amp;&amp; 3 &gt; 2"
+        );
+        assert_eq!(
+            sanitize("<script>alert(1)</script>", true),
+            "&lt;script&gt;alert(1)&lt;/script&gt;"
+        );
+        assert_eq!(
+            sanitize("caf&eacute; &#233;", true),
+            "caf&amp;eacute; &#233;"
+        );
+        assert_eq!(
+            sanitize(r#"<a href="file:///etc/passwd">secrets</a>"#, true),
+            "secrets"
+        );
+    }
+
+    #[test]
+    fn test_broken_nesting_is_repaired() {
+        assert_eq!(sanitize("<b>cut short…", true), "<b>cut short…</b>");
+        assert_eq!(
+            sanitize("<b><i>crossed</b></i>", true),
+            "<b><i>crossed</i></b>"
+        );
+        assert_eq!(sanitize("stray</b>", true), "stray&lt;/b&gt;");
+    }
+
+    #[test]
+    fn test_sanitized_markup_is_valid() {
+        for body in [
+            "<b>cut short",
+            "a <b",
+            "&",
+            "<a href='https://x'>x",
+            "</a></b>",
+        ] {
+            let markup = sanitize(body, false);
+            assert!(pango::parse_markup(&markup, '\0').is_ok(), "{body:?}");
+        }
+    }
+}
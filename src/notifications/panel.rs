@@ -1,19 +1,21 @@
-use std::cmp::Reverse;
-
-use chrono::Local;
 use gdk4::Monitor;
-use glib::ControlFlow;
 use gtk4::prelude::*;
 use gtk4_layer_shell::{Edge, Layer, LayerShell};
 use relm4::{factory::FactoryVecDeque, prelude::*};
+use tokio::sync::broadcast::error::RecvError;
 
 use crate::{
     analog_clock::AnalogClock,
+    clock::{ClockFormat, NOW},
     notifications::{
-        NOTIFICATIONS_STATE,
-        card::{NotificationCard, NotificationCardOutput},
-        types::Notification,
+        self, NOTIFICATIONS_STATE, NotificationEvent,
+        card::NotificationCardOutput,
+        group::{
+            ExpandedGroups, NotificationGroup, NotificationGroupInit, NotificationGroupOutput,
+            group_notifications,
+        },
     },
+    settings::{self, CONFIG},
 };
 
 #[derive(Debug)]
@@ -21,43 +23,58 @@ pub struct ActionPanel {
     // stored to keep the monitor object alive for the layer-shell window
     monitor: Monitor,
     visible: bool,
+    width: i32,
+    /// What the switch shows. It's flipped as soon as the switch is, rather
+    /// than waiting for the service, so the switch doesn't jump back meanwhile.
+    do_not_disturb: bool,
+    clock_format: ClockFormat,
+    expanded_groups: ExpandedGroups,
+    /// Whether the last message may have changed what's shown, so the groups
+    /// have to be rebuilt. Clock ticks only refresh the time and date.
+    rebuild_groups: bool,
+}
+
+#[derive(Debug)]
+pub struct ActionPanelInit {
+    pub monitor: Monitor,
 }
 
 #[derive(Debug)]
 pub enum ActionPanelMsg {
     Toggle,
-    // wired to a future "clear all" button in the notification center ui
-    #[allow(dead_code)]
     DismissAll,
+    SetDoNotDisturb(bool),
+    /// The service turned do-not-disturb on or off, possibly for another
+    /// monitor's panel.
+    DoNotDisturbChanged(bool),
     // payload is unused; update_view reads directly from the global
     StateUpdate,
     DismissNotification(u32),
+    /// Dismisses every notification in a group.
+    DismissGroup(Vec<u32>),
+    SnoozeNotification(u32),
     NotificationAction(u32, String),
+    SetWidth(i32),
+    SetClockFormat(ClockFormat),
+    /// Another second has passed; the time and date are refreshed in
+    /// update_view.
+    ClockTick,
 }
 
 #[derive(Debug)]
 pub struct ActionPanelWidgets {
     window: gtk4::Window,
-    cards: FactoryVecDeque<NotificationCard>,
+    groups: FactoryVecDeque<NotificationGroup>,
     panel: gtk4::Box,
+    dnd_switch: gtk4::Switch,
+    clear_button: gtk4::Button,
     clock: Controller<AnalogClock>,
     time_label: gtk4::Label,
     date_label: gtk4::Label,
 }
 
-/// Formats the current time as a 12-hour clock string (e.g. "2:34 PM").
-fn format_time() -> String {
-    Local::now().format("%-I:%M %P").to_string()
-}
-
-/// Formats the current date as a full readable string (e.g. "Sunday, May 10,
-/// 2026").
-fn format_date() -> String {
-    Local::now().format("%A, %B %-d, %Y").to_string()
-}
-
 impl SimpleComponent for ActionPanel {
-    type Init = Monitor;
+    type Init = ActionPanelInit;
     type Input = ActionPanelMsg;
     type Output = ();
     type Root = gtk4::Window;
@@ -71,7 +88,7 @@ impl SimpleComponent for ActionPanel {
     }
 
     fn init(
-        monitor: Self::Init,
+        ActionPanelInit { monitor }: Self::Init,
         window: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
@@ -79,9 +96,44 @@ impl SimpleComponent for ActionPanel {
         // reads directly from the global on each notification
         NOTIFICATIONS_STATE.subscribe(sender.input_sender(), |_| ActionPanelMsg::StateUpdate);
 
+        CONFIG.subscribe(sender.input_sender(), |config| {
+            ActionPanelMsg::SetWidth(config.notifications.center_width)
+        });
+        CONFIG.subscribe(sender.input_sender(), |config| {
+            ActionPanelMsg::SetClockFormat(ClockFormat::from_config(&config.clock))
+        });
+        NOW.subscribe(sender.input_sender(), |_| ActionPanelMsg::ClockTick);
+
+        let mut events = notifications::subscribe_events();
+        let input = sender.input_sender().clone();
+        relm4::spawn_local(async move {
+            loop {
+                match events.recv().await {
+                    Ok(NotificationEvent::DoNotDisturbChanged(enabled)) => {
+                        if input
+                            .send(ActionPanelMsg::DoNotDisturbChanged(enabled))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        // fill in the time and date without waiting for the next tick
+        sender.input(ActionPanelMsg::ClockTick);
+
+        let config = settings::get_config();
         let model = ActionPanel {
             monitor,
             visible: false,
+            width: config.notifications.center_width,
+            do_not_disturb: notifications::do_not_disturb(),
+            clock_format: ClockFormat::from_config(&config.clock),
+            expanded_groups: ExpandedGroups::default(),
+            rebuild_groups: false,
         };
 
         // set up layer shell properties
@@ -93,17 +145,27 @@ impl SimpleComponent for ActionPanel {
         window.set_anchor(Edge::Right, true);
         window.set_anchor(Edge::Bottom, true);
         window.set_margin_all(8);
-        window.set_width_request(432);
+        window.set_width_request(model.width);
 
         let widgets = ActionPanelWidgets {
             window,
-            cards: FactoryVecDeque::builder()
-                .launch(gtk4::Box::default())
+            groups: FactoryVecDeque::builder()
+                .launch(
+                    gtk4::Box::builder()
+                        .orientation(gtk4::Orientation::Vertical)
+                        .build(),
+                )
                 .forward(sender.input_sender(), |output| match output {
-                    NotificationCardOutput::Dismiss(id) => ActionPanelMsg::DismissNotification(id),
-                    NotificationCardOutput::Action(id, action) => {
+                    NotificationGroupOutput::Card(NotificationCardOutput::Dismiss(id)) => {
+                        ActionPanelMsg::DismissNotification(id)
+                    }
+                    NotificationGroupOutput::Card(NotificationCardOutput::Snooze(id)) => {
+                        ActionPanelMsg::SnoozeNotification(id)
+                    }
+                    NotificationGroupOutput::Card(NotificationCardOutput::Action(id, action)) => {
                         ActionPanelMsg::NotificationAction(id, action)
                     }
+                    NotificationGroupOutput::Dismiss(ids) => ActionPanelMsg::DismissGroup(ids),
                 }),
             panel: gtk4::Box::builder()
                 .css_classes(["notification-center", "background"])
@@ -112,14 +174,21 @@ impl SimpleComponent for ActionPanel {
                 .vexpand(true)
                 .visible(true)
                 .build(),
+            dnd_switch: gtk4::Switch::builder()
+                .active(model.do_not_disturb)
+                .valign(gtk4::Align::Center)
+                .tooltip_text("Only critical notifications pop up")
+                .build(),
+            clear_button: gtk4::Button::builder()
+                .label("Clear all")
+                .css_classes(["flat"])
+                .build(),
             clock: AnalogClock::builder().launch(32.0).detach(),
             time_label: gtk4::Label::builder()
-                .label(format_time())
                 .css_classes(["big-clock"])
                 .halign(gtk4::Align::Start)
                 .build(),
             date_label: gtk4::Label::builder()
-                .label(format_date())
                 .css_classes(["date-label"])
                 .halign(gtk4::Align::Start)
                 .build(),
@@ -144,54 +213,119 @@ impl SimpleComponent for ActionPanel {
         clock_row.append(widgets.clock.widget());
         clock_row.append(&clock_text_box);
 
-        widgets.panel.append(&clock_row);
-        widgets.panel.append(widgets.cards.widget());
-        widgets.window.set_child(Some(&widgets.panel));
+        // header with the do-not-disturb switch and the clear all button
+        let header = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .css_classes(["notification-center-header"])
+            .spacing(8)
+            .margin_bottom(8)
+            .build();
+        header.append(
+            &gtk4::Label::builder()
+                .label("Do not disturb")
+                .halign(gtk4::Align::Start)
+                .build(),
+        );
+        header.append(&widgets.dnd_switch);
+        header.append(&gtk4::Box::builder().hexpand(true).build());
+        header.append(&widgets.clear_button);
 
-        // update the digital clock and date labels every second
-        let time_label_clone = widgets.time_label.clone();
-        let date_label_clone = widgets.date_label.clone();
-        glib::timeout_add_local(std::time::Duration::from_secs(1), move || {
-            time_label_clone.set_label(&format_time());
-            date_label_clone.set_label(&format_date());
-            ControlFlow::Continue
+        widgets.dnd_switch.connect_active_notify({
+            let sender = sender.input_sender().clone();
+            move |switch| sender.emit(ActionPanelMsg::SetDoNotDisturb(switch.is_active()))
+        });
+        widgets.clear_button.connect_clicked({
+            let sender = sender.input_sender().clone();
+            move |_| sender.emit(ActionPanelMsg::DismissAll)
         });
 
+        widgets.panel.append(&clock_row);
+        widgets.panel.append(&header);
+        widgets.panel.append(widgets.groups.widget());
+        widgets.window.set_child(Some(&widgets.panel));
+
         ComponentParts { model, widgets }
     }
 
     fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+        // rebuilding replaces every card, which would interrupt clicks and
+        // animations if it happened on every tick
+        self.rebuild_groups = matches!(msg, ActionPanelMsg::Toggle | ActionPanelMsg::StateUpdate);
+
         match msg {
             ActionPanelMsg::Toggle => {
                 self.visible = !self.visible;
             }
             ActionPanelMsg::DismissAll => {
-                crate::notifications::clear_all();
+                notifications::clear_all();
+            }
+            ActionPanelMsg::SetDoNotDisturb(enabled) => {
+                // the switch is also updated from the service, so don't echo
+                // that back
+                if enabled != self.do_not_disturb {
+                    self.do_not_disturb = enabled;
+                    notifications::set_do_not_disturb(enabled);
+                }
+            }
+            ActionPanelMsg::DoNotDisturbChanged(enabled) => {
+                self.do_not_disturb = enabled;
             }
             ActionPanelMsg::StateUpdate => {
                 // view is rebuilt from the global in update_view
             }
             ActionPanelMsg::DismissNotification(id) => {
-                crate::notifications::dismiss(id);
+                notifications::dismiss(id);
+            }
+            ActionPanelMsg::DismissGroup(ids) => {
+                // each is closed on its own, so apps hear about every one
+                for id in ids {
+                    notifications::dismiss(id);
+                }
+            }
+            ActionPanelMsg::SnoozeNotification(id) => {
+                notifications::snooze(id);
             }
             ActionPanelMsg::NotificationAction(id, action) => {
-                crate::notifications::invoke_action(id, action);
+                notifications::invoke_action(id, action);
+            }
+            ActionPanelMsg::SetWidth(width) => {
+                self.width = width;
             }
+            ActionPanelMsg::SetClockFormat(format) => {
+                self.clock_format = format;
+            }
+            ActionPanelMsg::ClockTick => {}
         }
     }
 
     fn update_view(&self, widgets: &mut Self::Widgets, _sender: ComponentSender<Self>) {
         widgets.window.set_visible(self.visible);
+        widgets.window.set_width_request(self.width);
+        if let Ok(now) = glib::DateTime::now_local() {
+            widgets.time_label.set_label(&self.clock_format.time(&now));
+            widgets.date_label.set_label(&self.clock_format.date(&now));
+        }
 
-        if self.visible {
+        widgets.dnd_switch.set_active(self.do_not_disturb);
+
+        if self.visible && self.rebuild_groups {
             let state = NOTIFICATIONS_STATE.read();
-            let mut notifications: Vec<&Notification> = state.notifications.values().collect();
-            notifications.sort_by_key(|n| Reverse(n.timestamp));
+            widgets
+                .clear_button
+                .set_sensitive(!state.notifications.is_empty());
+
+            let groups = group_notifications(state.notifications.values());
+            self.expanded_groups
+                .borrow_mut()
+                .retain(|id| groups.iter().flatten().any(|n| n.id == *id));
 
-            let mut guard = widgets.cards.guard();
+            let mut guard = widgets.groups.guard();
             guard.clear();
-            for notification in notifications {
-                guard.push_back(notification.clone());
+            for notifications in groups {
+                guard.push_back(NotificationGroupInit {
+                    notifications,
+                    expanded_groups: self.expanded_groups.clone(),
+                });
             }
         }
     }
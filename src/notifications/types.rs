@@ -2,6 +2,8 @@ use serde::{Deserialize, Deserializer};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use zbus::zvariant::Type;
 
+use crate::notifications::app_key::app_key;
+
 fn de_actions<'de, D>(deserializer: D) -> Result<Vec<(String, String)>, D::Error>
 where
     D: Deserializer<'de>,
@@ -32,6 +34,22 @@ pub struct Notification {
 
     #[serde(deserialize_with = "de_actions")]
     pub actions: Vec<(String, String)>,
+    /// Whether the actions' labels are icon names, per the `action-icons`
+    /// hint.
+    #[serde(default)]
+    pub action_icons: bool,
+    /// When a snoozed notification pops up again, as a Unix timestamp.
+    #[serde(default)]
+    pub snoozed_until: Option<i64>,
+}
+
+impl Notification {
+    /// Identifies the sending app for grouping, muting and icon caching,
+    /// however it spelled its name (see [`app_key`]). Show `app_name` to
+    /// people instead.
+    pub fn app_key(&self) -> String {
+        app_key(&self.desktop_entry, &self.app_name)
+    }
 }
 
 #[derive(Serialize_repr, Deserialize_repr, PartialEq, Default, Debug, Type, Clone, Copy)]
@@ -44,3 +62,53 @@ pub enum NotificationUrgency {
 
     Critical = 2,
 }
+
+impl NotificationUrgency {
+    /// Reads a level from the `urgency` hint, clamping anything out of range.
+    pub fn from_level(level: i64) -> Self {
+        match level {
+            ..=0 => Self::Low,
+            1 => Self::Normal,
+            _ => Self::Critical,
+        }
+    }
+}
+
+/// Why a notification was closed, as reported in the `NotificationClosed`
+/// signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The notification expired.
+    Expired,
+
+    /// The notification was dismissed by the user.
+    Dismissed,
+
+    /// The notification was closed by a call to `CloseNotification`.
+    Closed,
+
+    /// Undefined/reserved reasons.
+    Undefined,
+}
+
+impl From<u32> for CloseReason {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Self::Expired,
+            2 => Self::Dismissed,
+            3 => Self::Closed,
+            _ => Self::Undefined,
+        }
+    }
+}
+
+impl From<CloseReason> for u32 {
+    fn from(value: CloseReason) -> Self {
+        match value {
+            CloseReason::Expired => 1,
+            CloseReason::Dismissed => 2,
+            CloseReason::Closed => 3,
+            CloseReason::Undefined => 4,
+        }
+    }
+}
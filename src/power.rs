@@ -0,0 +1,121 @@
+use crate::{launch, settings, sleep_monitor::Login1ManagerProxy};
+
+/// Session and power actions offered by the power menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerAction {
+    Lock,
+    Logout,
+    Suspend,
+    Reboot,
+    PowerOff,
+}
+
+impl PowerAction {
+    pub const ALL: [Self; 5] = [
+        Self::Lock,
+        Self::Logout,
+        Self::Suspend,
+        Self::Reboot,
+        Self::PowerOff,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Lock => "Lock",
+            Self::Logout => "Log out",
+            Self::Suspend => "Suspend",
+            Self::Reboot => "Reboot",
+            Self::PowerOff => "Shut down",
+        }
+    }
+
+    pub fn icon_name(&self) -> &'static str {
+        match self {
+            Self::Lock => "system-lock-screen-symbolic",
+            Self::Logout => "system-log-out-symbolic",
+            Self::Suspend => "weather-clear-night-symbolic",
+            Self::Reboot => "system-reboot-symbolic",
+            Self::PowerOff => "system-shutdown-symbolic",
+        }
+    }
+
+    /// Whether the action ends the session or loses unsaved work, and so
+    /// needs confirming first.
+    pub fn is_destructive(&self) -> bool {
+        matches!(self, Self::Logout | Self::Reboot | Self::PowerOff)
+    }
+
+    /// The command configured for this action, if any. Lock and logout always
+    /// use their command; the rest only fall back to it when logind refuses.
+    fn command(&self) -> Option<String> {
+        let power = settings::get_config().power;
+        match self {
+            Self::Lock => power.lock_command,
+            Self::Logout => power.logout_command,
+            Self::Suspend => power.suspend_command,
+            Self::Reboot => power.reboot_command,
+            Self::PowerOff => power.power_off_command,
+        }
+    }
+}
+
+/// Performs a power action. Suspend, reboot and power off go through logind;
+/// if logind refuses for lack of privileges, the configured command is run
+/// instead.
+pub async fn perform(action: PowerAction) {
+    log::info!("performing power action {action:?}");
+
+    if matches!(action, PowerAction::Lock | PowerAction::Logout) {
+        run_command(action);
+        return;
+    }
+
+    match call_logind(action).await {
+        Ok(()) => {}
+        Err(e) if is_privilege_error(&e) => {
+            log::warn!("logind refused {action:?} ({e}); trying the configured command");
+            run_command(action);
+        }
+        Err(e) => log::error!("couldn't perform {action:?} through logind: {e}"),
+    }
+}
+
+async fn call_logind(action: PowerAction) -> zbus::Result<()> {
+    let conn = zbus::Connection::system().await?;
+    let proxy = Login1ManagerProxy::new(&conn).await?;
+
+    // interactive, so polkit may ask for a password instead of refusing
+    match action {
+        PowerAction::Suspend => proxy.suspend(true).await,
+        PowerAction::Reboot => proxy.reboot(true).await,
+        PowerAction::PowerOff => proxy.power_off(true).await,
+        PowerAction::Lock | PowerAction::Logout => {
+            unreachable!("lock and logout don't go through logind")
+        }
+    }
+}
+
+fn run_command(action: PowerAction) {
+    match action.command() {
+        Some(command) => launch::spawn_command(&command),
+        None => log::error!("no command is configured for {action:?}"),
+    }
+}
+
+/// Whether logind turned down a request because the caller isn't allowed to
+/// make it.
+fn is_privilege_error(error: &zbus::Error) -> bool {
+    match error {
+        zbus::Error::MethodError(name, ..) => matches!(
+            name.as_str(),
+            "org.freedesktop.DBus.Error.AccessDenied"
+                | "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired"
+        ),
+        zbus::Error::FDO(e) => matches!(
+            **e,
+            zbus::fdo::Error::AccessDenied(_)
+                | zbus::fdo::Error::InteractiveAuthorizationRequired(_)
+        ),
+        _ => false,
+    }
+}
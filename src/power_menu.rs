@@ -0,0 +1,188 @@
+use gtk4::prelude::*;
+use relm4::prelude::*;
+
+use crate::power::{self, PowerAction};
+
+/// Buttons for locking, logging out, suspending, rebooting and shutting down.
+/// Destructive actions ask for confirmation first.
+#[derive(Debug, Default)]
+pub struct PowerMenu {
+    /// A destructive action waiting to be confirmed.
+    pending: Option<PowerAction>,
+}
+
+#[derive(Debug)]
+pub enum PowerMenuMsg {
+    Choose(PowerAction),
+    Confirm,
+    Cancel,
+}
+
+#[derive(Debug)]
+pub enum PowerMenuOutput {
+    /// An action was started, so whatever holds the menu should close it.
+    Close,
+}
+
+#[derive(Debug)]
+pub struct PowerMenuWidgets {
+    stack: gtk::Stack,
+    confirm_label: gtk::Label,
+    confirm_button: gtk::Button,
+}
+
+impl SimpleComponent for PowerMenu {
+    type Init = ();
+    type Input = PowerMenuMsg;
+    type Output = PowerMenuOutput;
+    type Root = gtk::Box;
+    type Widgets = PowerMenuWidgets;
+
+    fn init_root() -> Self::Root {
+        gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["power-menu"])
+            .build()
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let actions = gtk::Box::new(gtk::Orientation::Vertical, 4);
+        for action in PowerAction::ALL {
+            let content = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+            content.append(&gtk::Image::from_icon_name(action.icon_name()));
+            content.append(&gtk::Label::new(Some(action.label())));
+
+            let button = gtk::Button::builder()
+                .css_classes(["flat"])
+                .child(&content)
+                .build();
+            let sender = sender.clone();
+            button.connect_clicked(move |_| sender.input(PowerMenuMsg::Choose(action)));
+            actions.append(&button);
+        }
+
+        let confirm_label = gtk::Label::builder().css_classes(["bold"]).build();
+        let cancel_button = gtk::Button::with_label("Cancel");
+        let confirm_button = gtk::Button::builder()
+            .css_classes(["destructive-action"])
+            .build();
+        {
+            let sender = sender.clone();
+            cancel_button.connect_clicked(move |_| sender.input(PowerMenuMsg::Cancel));
+        }
+        {
+            let sender = sender.clone();
+            confirm_button.connect_clicked(move |_| sender.input(PowerMenuMsg::Confirm));
+        }
+
+        let confirm_buttons = gtk::Box::builder().spacing(8).homogeneous(true).build();
+        confirm_buttons.append(&cancel_button);
+        confirm_buttons.append(&confirm_button);
+
+        let confirm = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        confirm.append(&confirm_label);
+        confirm.append(&confirm_buttons);
+
+        let stack = gtk::Stack::builder()
+            .transition_type(gtk::StackTransitionType::Crossfade)
+            .build();
+        stack.add_named(&actions, Some("actions"));
+        stack.add_named(&confirm, Some("confirm"));
+        root.append(&stack);
+
+        ComponentParts {
+            model: PowerMenu::default(),
+            widgets: PowerMenuWidgets {
+                stack,
+                confirm_label,
+                confirm_button,
+            },
+        }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        let action = match msg {
+            PowerMenuMsg::Choose(action) if action.is_destructive() => {
+                self.pending = Some(action);
+                return;
+            }
+            PowerMenuMsg::Choose(action) => action,
+            PowerMenuMsg::Confirm => match self.pending.take() {
+                Some(action) => action,
+                None => return,
+            },
+            PowerMenuMsg::Cancel => {
+                self.pending = None;
+                return;
+            }
+        };
+
+        relm4::spawn(power::perform(action));
+        let _ = sender.output(PowerMenuOutput::Close);
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, _sender: ComponentSender<Self>) {
+        match self.pending {
+            Some(action) => {
+                widgets
+                    .confirm_label
+                    .set_text(&format!("{} now?", action.label()));
+                widgets.confirm_button.set_label(action.label());
+                widgets.stack.set_visible_child_name("confirm");
+            }
+            None => widgets.stack.set_visible_child_name("actions"),
+        }
+    }
+}
+
+/// A popover holding a [`PowerMenu`], for widgets that open the menu on click.
+#[derive(Debug)]
+pub struct PowerPopover {
+    popover: gtk::Popover,
+    _menu: Controller<PowerMenu>,
+}
+
+impl PowerPopover {
+    pub fn new(parent: &impl IsA<gtk::Widget>) -> Self {
+        let popover = gtk::Popover::new();
+        let menu = PowerMenu::builder().launch(()).connect_receiver({
+            let popover = popover.clone();
+            move |_, PowerMenuOutput::Close| popover.popdown()
+        });
+
+        popover.set_child(Some(menu.widget()));
+        popover.set_parent(parent);
+
+        // don't leave a confirmation up for next time
+        let menu_sender = menu.sender().clone();
+        popover.connect_closed(move |_| menu_sender.emit(PowerMenuMsg::Cancel));
+
+        Self {
+            popover,
+            _menu: menu,
+        }
+    }
+
+    pub fn popup(&self) {
+        self.popover.popup();
+    }
+
+    pub fn toggle(&self) {
+        if self.popover.is_visible() {
+            self.popover.popdown();
+        } else {
+            self.popover.popup();
+        }
+    }
+}
+
+impl Drop for PowerPopover {
+    fn drop(&mut self) {
+        // the popover isn't a child the parent knows to clean up
+        self.popover.unparent();
+    }
+}
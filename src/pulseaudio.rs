@@ -1,25 +1,37 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 
+#[cfg(feature = "bluetooth")]
+use pulse::context::introspect::CardInfo;
 use pulse::{
     callbacks::ListResult,
     context::{
         Context, FlagSet, State,
-        introspect::{ServerInfo, SinkInfo},
+        introspect::{ServerInfo, SinkInfo, SinkInputInfo, SourceInfo},
         subscribe::{Facility, InterestMaskSet, Operation},
     },
-    mainloop::standard::{IterateResult, Mainloop},
-    proplist::Proplist,
+    mainloop::threaded::Mainloop,
+    proplist::{Proplist, properties},
     volume::{ChannelVolumes, Volume},
 };
 use relm4::SharedState;
+use tokio::sync::mpsc;
 
 pub static VOLUME_STATE: SharedState<PulseAudioData> = SharedState::new();
+pub static MIC_STATE: SharedState<MicData> = SharedState::new();
+#[cfg(feature = "bluetooth")]
+pub static BLUETOOTH_CARDS: SharedState<Vec<BluetoothCard>> = SharedState::new();
+
+/// Sends commands to the pulseaudio loop, once it's running.
+static COMMAND_TX: OnceLock<mpsc::UnboundedSender<PulseCommand>> = OnceLock::new();
 
 #[derive(Debug, Clone)]
 pub struct PulseAudioData {
     pub volume: f64,
     pub muted: bool,
     pub default_sink_name: Option<String>,
+    /// Streams from apps playing sound, in the order they appeared.
+    pub streams: Vec<AppStream>,
+    channels: u8,
 }
 
 impl Default for PulseAudioData {
@@ -28,11 +40,194 @@ impl Default for PulseAudioData {
             volume: 0.0,
             muted: false,
             default_sink_name: None,
+            streams: Vec::new(),
+            channels: 0,
         }
     }
 }
 
+/// The default source, when it's a microphone rather than the monitor of an
+/// output.
+#[derive(Debug, Clone, Default)]
+pub struct MicData {
+    pub volume: f64,
+    pub muted: bool,
+    pub default_source_name: Option<String>,
+    channels: u8,
+}
+
+/// One app's playback stream (a sink input, in pulseaudio's terms).
+#[derive(Debug, Clone)]
+pub struct AppStream {
+    pub index: u32,
+    /// The app's name, e.g. "Firefox".
+    pub name: String,
+    /// What the stream is playing, e.g. a tab's title, if the app says.
+    pub title: Option<String>,
+    pub icon_name: Option<String>,
+    pub volume: f64,
+    pub muted: bool,
+    channels: u8,
+}
+
+impl AppStream {
+    fn from_info(info: &SinkInputInfo) -> Self {
+        let name = info
+            .proplist
+            .get_str(properties::APPLICATION_NAME)
+            .or_else(|| info.name.as_ref().map(ToString::to_string))
+            .unwrap_or_else(|| format!("Stream {}", info.index));
+        let title = info
+            .proplist
+            .get_str(properties::MEDIA_NAME)
+            .filter(|title| *title != name);
+        // binaries are often named like their icons when there's no icon
+        let icon_name = info
+            .proplist
+            .get_str(properties::APPLICATION_ICON_NAME)
+            .or_else(|| {
+                info.proplist
+                    .get_str(properties::APPLICATION_PROCESS_BINARY)
+            });
+
+        Self {
+            index: info.index,
+            name,
+            title,
+            icon_name,
+            volume: volume_to_percent(&info.volume),
+            muted: info.mute,
+            channels: info.volume.len(),
+        }
+    }
+}
+
+/// A Bluetooth audio device's card. Its profiles choose between e.g.
+/// high-quality playback (A2DP) and a headset with a microphone (HSP/HFP).
+#[cfg(feature = "bluetooth")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BluetoothCard {
+    pub index: u32,
+    /// The device's address, e.g. "AA:BB:CC:DD:EE:FF".
+    pub address: String,
+    /// Profiles that can be switched to right now, leaving out "off".
+    pub profiles: Vec<CardProfile>,
+    /// The name of the profile in use.
+    pub active_profile: Option<String>,
+}
+
+#[cfg(feature = "bluetooth")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardProfile {
+    pub name: String,
+    /// e.g. "High Fidelity Playback (A2DP Sink)"
+    pub description: String,
+}
+
+#[cfg(feature = "bluetooth")]
+impl BluetoothCard {
+    /// Reads a card, if it's a Bluetooth device's.
+    fn from_info(info: &CardInfo) -> Option<Self> {
+        let name = info.name.as_deref()?;
+        let card_address = name.strip_prefix("bluez_card.")?;
+        // pipewire and pulseaudio name the address differently; both put it
+        // in the card's name, with underscores
+        let address = info
+            .proplist
+            .get_str("api.bluez5.address")
+            .or_else(|| info.proplist.get_str(properties::DEVICE_STRING))
+            .filter(|address| address.contains(':'))
+            .unwrap_or_else(|| card_address.replace('_', ":"));
+
+        let profiles = info
+            .profiles
+            .iter()
+            .filter(|profile| profile.available)
+            .filter_map(|profile| {
+                let name = profile.name.as_deref()?;
+                (name != "off").then(|| CardProfile {
+                    name: name.to_string(),
+                    description: profile.description.as_deref().unwrap_or(name).to_string(),
+                })
+            })
+            .collect();
+
+        Some(Self {
+            index: info.index,
+            address,
+            profiles,
+            active_profile: info
+                .active_profile
+                .as_ref()
+                .and_then(|profile| profile.name.as_ref())
+                .map(ToString::to_string),
+        })
+    }
+}
+
+/// Changes the pulseaudio loop makes on the shell's behalf.
+#[derive(Debug)]
+enum PulseCommand {
+    SetVolume(f64),
+    SetStreamVolume(u32, f64),
+    SetStreamMuted(u32, bool),
+    SetMicVolume(f64),
+    SetMicMuted(bool),
+    #[cfg(feature = "bluetooth")]
+    SetCardProfile(u32, String),
+}
+
+/// Sets the default output's volume, as a percentage.
+pub fn set_volume(percent: f64) {
+    send_command(PulseCommand::SetVolume(percent));
+}
+
+/// Sets the volume of the app stream with `index`, as a percentage.
+pub fn set_stream_volume(index: u32, percent: f64) {
+    send_command(PulseCommand::SetStreamVolume(index, percent));
+}
+
+/// Mutes or unmutes the app stream with `index`.
+pub fn set_stream_muted(index: u32, muted: bool) {
+    send_command(PulseCommand::SetStreamMuted(index, muted));
+}
+
+/// Sets the default microphone's volume, as a percentage.
+pub fn set_mic_volume(percent: f64) {
+    send_command(PulseCommand::SetMicVolume(percent));
+}
+
+/// Mutes or unmutes the default microphone.
+pub fn set_mic_muted(muted: bool) {
+    send_command(PulseCommand::SetMicMuted(muted));
+}
+
+/// Switches the card with `index` to the profile named `profile`.
+#[cfg(feature = "bluetooth")]
+pub fn set_card_profile(index: u32, profile: String) {
+    send_command(PulseCommand::SetCardProfile(index, profile));
+}
+
+fn send_command(command: PulseCommand) {
+    match COMMAND_TX.get() {
+        Some(tx) => {
+            if tx.send(command).is_err() {
+                log::warn!("pulseaudio loop has stopped");
+            }
+        }
+        None => log::warn!("pulseaudio isn't connected yet"),
+    }
+}
+
 pub async fn run_pulseaudio_loop() {
+    // pulseaudio's objects can't move between threads, so they're all kept on
+    // one that waits for commands
+    if let Err(e) = tokio::task::spawn_blocking(run_pulseaudio).await {
+        log::error!("pulseaudio loop stopped: {e}");
+    }
+}
+
+fn run_pulseaudio() {
     let Some(mut proplist) = Proplist::new() else {
         log::error!("failed to create pulseaudio proplist");
         return;
@@ -77,14 +272,84 @@ pub async fn run_pulseaudio_loop() {
         return;
     }
 
-    // run mainloop
-    loop {
-        match mainloop.iterate(true) {
-            IterateResult::Success(_) => {}
-            IterateResult::Err(err) => {
-                log::error!("pulse mainloop error: {:?}", err);
-            }
-            IterateResult::Quit(_) => break,
+    let (command_tx, mut command_rx) = mpsc::unbounded_channel();
+    if COMMAND_TX.set(command_tx).is_err() {
+        log::warn!("pulseaudio loop is already running");
+        return;
+    }
+
+    if let Err(err) = mainloop.start() {
+        log::error!("failed to start pulse mainloop: {}", err);
+        return;
+    }
+
+    // the mainloop runs on its own thread, which sleeps until pulseaudio has
+    // something for it. commands are run here while holding its lock
+    while let Some(command) = command_rx.blocking_recv() {
+        mainloop.lock();
+        run_command(&context, command);
+        mainloop.unlock();
+    }
+
+    mainloop.stop();
+}
+
+fn run_command(context: &Arc<Mutex<Context>>, command: PulseCommand) {
+    let mut introspect = context.lock().unwrap().introspect();
+
+    match command {
+        PulseCommand::SetVolume(percent) => {
+            let data = VOLUME_STATE.read().clone();
+            let Some(sink_name) = data.default_sink_name else {
+                log::debug!("not setting volume without an output");
+                return;
+            };
+
+            let mut volumes = ChannelVolumes::default();
+            volumes.set(data.channels, Volume(percent_to_volume(percent)));
+            introspect.set_sink_volume_by_name(&sink_name, &volumes, None);
+        }
+        PulseCommand::SetStreamVolume(index, percent) => {
+            // the stream may have gone away since the command was sent
+            let Some(channels) = VOLUME_STATE
+                .read()
+                .streams
+                .iter()
+                .find(|stream| stream.index == index)
+                .map(|stream| stream.channels)
+            else {
+                log::debug!("not setting volume of stream {index}, which has gone away");
+                return;
+            };
+
+            let mut volumes = ChannelVolumes::default();
+            volumes.set(channels, Volume(percent_to_volume(percent)));
+            introspect.set_sink_input_volume(index, &volumes, None);
+        }
+        PulseCommand::SetStreamMuted(index, muted) => {
+            introspect.set_sink_input_mute(index, muted, None);
+        }
+        PulseCommand::SetMicVolume(percent) => {
+            let mic = MIC_STATE.read().clone();
+            let Some(source_name) = mic.default_source_name else {
+                log::debug!("not setting mic volume without a microphone");
+                return;
+            };
+
+            let mut volumes = ChannelVolumes::default();
+            volumes.set(mic.channels, Volume(percent_to_volume(percent)));
+            introspect.set_source_volume_by_name(&source_name, &volumes, None);
+        }
+        PulseCommand::SetMicMuted(muted) => {
+            let Some(source_name) = MIC_STATE.read().default_source_name.clone() else {
+                log::debug!("not muting without a microphone");
+                return;
+            };
+            introspect.set_source_mute_by_name(&source_name, muted, None);
+        }
+        #[cfg(feature = "bluetooth")]
+        PulseCommand::SetCardProfile(index, profile) => {
+            introspect.set_card_profile_by_index(index, &profile, None);
         }
     }
 }
@@ -107,25 +372,53 @@ fn on_state_change(context: &Arc<Mutex<Context>>) {
                 move |server_info| on_server_info(server_info, &context)
             });
 
+            // get the streams already playing
+            let mut streams = Vec::new();
+            introspect.get_sink_input_info_list(move |result| match result {
+                ListResult::Item(info) => streams.push(AppStream::from_info(info)),
+                ListResult::End => VOLUME_STATE.write().streams = std::mem::take(&mut streams),
+                ListResult::Error => log::error!("couldn't list pulseaudio streams"),
+            });
+
+            #[cfg(feature = "bluetooth")]
+            {
+                let mut cards = Vec::new();
+                introspect.get_card_info_list(move |result| match result {
+                    ListResult::Item(info) => cards.extend(BluetoothCard::from_info(info)),
+                    ListResult::End => *BLUETOOTH_CARDS.write() = std::mem::take(&mut cards),
+                    ListResult::Error => log::error!("couldn't list pulseaudio cards"),
+                });
+            }
+
             // subscribe to changes
             let subscribe_callback = Box::new({
                 let context = context.clone();
 
-                move |facility, op, _i| on_event(&context, facility, op)
+                move |facility, op, index| on_event(&context, facility, op, index)
             });
 
+            let interests = InterestMaskSet::SERVER
+                | InterestMaskSet::SINK
+                | InterestMaskSet::SINK_INPUT
+                | InterestMaskSet::SOURCE;
+            // cards are only of interest for their Bluetooth profiles
+            #[cfg(feature = "bluetooth")]
+            let interests = interests | InterestMaskSet::CARD;
+
             context
                 .lock()
                 .unwrap()
                 .set_subscribe_callback(Some(subscribe_callback));
-            context
-                .lock()
-                .unwrap()
-                .subscribe(InterestMaskSet::SERVER | InterestMaskSet::SINK, |_| ());
+            context.lock().unwrap().subscribe(interests, |_| ());
         }
         State::Failed => {
             log::error!("failed to connect to pulseaudio server");
-            VOLUME_STATE.write().default_sink_name = None;
+            let mut data_guard = VOLUME_STATE.write();
+            data_guard.default_sink_name = None;
+            data_guard.streams.clear();
+            MIC_STATE.write().default_source_name = None;
+            #[cfg(feature = "bluetooth")]
+            BLUETOOTH_CARDS.write().clear();
         }
         State::Terminated => {
             log::warn!("connection to pulseaudio server terminated");
@@ -150,6 +443,33 @@ fn on_server_info(server_info: &ServerInfo, context: &Arc<Mutex<Context>>) {
         let introspect = context.lock().unwrap().introspect();
         introspect.get_sink_info_by_name(sink_name, on_sink_info);
     }
+
+    // the default source changes when e.g. a headset is plugged in, so this
+    // is what retargets the mic controls
+    match &server_info.default_source_name {
+        Some(source_name) => {
+            let introspect = context.lock().unwrap().introspect();
+            introspect.get_source_info_by_name(source_name, on_source_info);
+        }
+        None => MIC_STATE.write().default_source_name = None,
+    }
+}
+
+fn on_source_info(source_info: ListResult<&SourceInfo>) {
+    let ListResult::Item(info) = source_info else {
+        return;
+    };
+
+    let mut mic_guard = MIC_STATE.write();
+    // without a microphone, the default source is an output's monitor
+    mic_guard.default_source_name = info
+        .name
+        .as_ref()
+        .filter(|_| info.monitor_of_sink.is_none())
+        .map(ToString::to_string);
+    mic_guard.volume = volume_to_percent(&info.volume);
+    mic_guard.muted = info.mute;
+    mic_guard.channels = info.volume.len();
 }
 
 fn on_sink_info(sink_info: ListResult<&SinkInfo>) {
@@ -162,9 +482,15 @@ fn on_sink_info(sink_info: ListResult<&SinkInfo>) {
     let mut data_guard = VOLUME_STATE.write();
     data_guard.volume = volume_percent;
     data_guard.muted = info.mute;
+    data_guard.channels = info.volume.len();
 }
 
-fn on_event(context: &Arc<Mutex<Context>>, facility: Option<Facility>, _op: Option<Operation>) {
+fn on_event(
+    context: &Arc<Mutex<Context>>,
+    facility: Option<Facility>,
+    op: Option<Operation>,
+    index: u32,
+) {
     let Some(facility) = facility else {
         return;
     };
@@ -187,10 +513,82 @@ fn on_event(context: &Arc<Mutex<Context>>, facility: Option<Facility>, _op: Opti
                 });
             }
         }
+        Facility::Source => {
+            // update default source info
+            if let Some(source_name) = MIC_STATE.read().default_source_name.clone() {
+                let introspect = context.lock().unwrap().introspect();
+                introspect.get_source_info_by_name(&source_name, on_source_info);
+            }
+        }
+        Facility::SinkInput => match op {
+            Some(Operation::Removed) => remove_stream(index),
+            Some(Operation::New | Operation::Changed) => {
+                let introspect = context.lock().unwrap().introspect();
+                introspect.get_sink_input_info(index, move |result| match result {
+                    ListResult::Item(info) => update_stream(AppStream::from_info(info)),
+                    // it went away before we could ask about it
+                    ListResult::Error => remove_stream(index),
+                    ListResult::End => {}
+                });
+            }
+            None => {}
+        },
+        #[cfg(feature = "bluetooth")]
+        Facility::Card => match op {
+            Some(Operation::Removed) => remove_card(index),
+            Some(Operation::New | Operation::Changed) => {
+                let introspect = context.lock().unwrap().introspect();
+                introspect.get_card_info_by_index(index, move |result| match result {
+                    ListResult::Item(info) => {
+                        if let Some(card) = BluetoothCard::from_info(info) {
+                            update_card(card);
+                        }
+                    }
+                    ListResult::Error => remove_card(index),
+                    ListResult::End => {}
+                });
+            }
+            None => {}
+        },
         _ => {}
     }
 }
 
+/// Adds `stream`, or replaces it if it's already known.
+fn update_stream(stream: AppStream) {
+    let mut data_guard = VOLUME_STATE.write();
+    match data_guard
+        .streams
+        .iter_mut()
+        .find(|known| known.index == stream.index)
+    {
+        Some(known) => *known = stream,
+        None => data_guard.streams.push(stream),
+    }
+}
+
+fn remove_stream(index: u32) {
+    VOLUME_STATE
+        .write()
+        .streams
+        .retain(|stream| stream.index != index);
+}
+
+/// Adds `card`, or replaces it if it's already known.
+#[cfg(feature = "bluetooth")]
+fn update_card(card: BluetoothCard) {
+    let mut cards = BLUETOOTH_CARDS.write();
+    match cards.iter_mut().find(|known| known.index == card.index) {
+        Some(known) => *known = card,
+        None => cards.push(card),
+    }
+}
+
+#[cfg(feature = "bluetooth")]
+fn remove_card(index: u32) {
+    BLUETOOTH_CARDS.write().retain(|card| card.index != index);
+}
+
 fn volume_to_percent(channel_volumes: &ChannelVolumes) -> f64 {
     if channel_volumes.len() == 0 {
         return 0.0;
@@ -205,7 +603,7 @@ fn volume_to_percent(channel_volumes: &ChannelVolumes) -> f64 {
         .max(0.0)
 }
 
-fn _percent_to_volume(target_percent: f64) -> u32 {
+fn percent_to_volume(target_percent: f64) -> u32 {
     let base_delta = (Volume::NORMAL.0 as f32 - Volume::MUTED.0 as f32) / 100.0;
 
     if target_percent < 0.0 {
@@ -0,0 +1,46 @@
+use std::{fmt, sync::Arc};
+
+use tokio::sync::Mutex;
+
+use crate::{
+    commands::GlobalCommandExecutor, compositor::CompositorService, tray_backend::TrayBackend,
+};
+
+/// Handles to the services that components act on, built once by the app and
+/// passed down to every bar.
+///
+/// Services that are always around as module globals (notifications, audio,
+/// brightness) aren't here: they're observed through their `SharedState`
+/// globals and commanded through their modules' functions, like
+/// [`notifications::dismiss`](crate::notifications::dismiss).
+#[derive(Clone)]
+pub struct Services {
+    /// Undoable changes made from the bar, like brightness.
+    pub commands: Arc<Mutex<GlobalCommandExecutor>>,
+    /// `None` while the tray client isn't connected to a StatusNotifierWatcher.
+    pub tray: Option<Arc<dyn TrayBackend>>,
+    /// Workspace focusing; `None` under unsupported compositors.
+    pub compositor: Option<Arc<dyn CompositorService>>,
+}
+
+impl Services {
+    pub fn new(
+        tray: Option<Arc<dyn TrayBackend>>,
+        compositor: Option<Arc<dyn CompositorService>>,
+    ) -> Self {
+        Self {
+            commands: Arc::new(Mutex::new(GlobalCommandExecutor::new())),
+            tray,
+            compositor,
+        }
+    }
+}
+
+impl fmt::Debug for Services {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Services")
+            .field("tray", &self.tray.is_some())
+            .field("compositor", &self.compositor.is_some())
+            .finish_non_exhaustive()
+    }
+}
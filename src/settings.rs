@@ -1,9 +1,10 @@
 // temporary until we figure things out
 #![allow(dead_code)]
 
-use std::{fs, path::PathBuf};
+use std::{collections::HashMap, fs, path::PathBuf};
 
 use anyhow::Result;
+use relm4::SharedState;
 use serde::{Deserialize, Serialize};
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -12,31 +13,136 @@ pub struct CadenzaShellConfig {
     pub bar: BarConfig,
     pub notifications: NotificationConfig,
     pub tiles: TileConfig,
+    #[serde(default)]
+    pub battery: BatteryConfig,
+    #[serde(default)]
+    pub workspaces: WorkspaceConfig,
+    #[serde(default)]
+    pub widget: WidgetsConfig,
+    #[serde(default)]
+    pub power: PowerConfig,
+    #[serde(default)]
+    pub brightness: BrightnessConfig,
+    #[serde(default)]
+    pub weather: WeatherConfig,
+    #[serde(default)]
+    pub audio: AudioConfig,
+    #[serde(default)]
+    pub shortcuts: ShortcutsConfig,
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    #[serde(default)]
+    pub analog_clock: AnalogClockConfig,
+    #[serde(default)]
+    pub clock: ClockConfig,
+    #[serde(default)]
+    pub calendar: CalendarConfig,
+    /// Bar settings for particular monitors, keyed by connector name (e.g.
+    /// `"DP-1"`). The `"*"` entry applies to monitors that aren't listed
+    #[serde(default)]
+    pub monitors: HashMap<String, MonitorBarConfig>,
+}
+
+impl CadenzaShellConfig {
+    /// The bar settings for the monitor on `connector`, falling back to the
+    /// `"*"` entry, then to a full bar.
+    pub fn monitor_bar(&self, connector: &str) -> MonitorBarConfig {
+        self.monitors
+            .get(connector)
+            .or_else(|| self.monitors.get("*"))
+            .cloned()
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UiConfig {
     /// Overall UI scaling factor
     pub scale_factor: f64,
+    /// Replace scrolling and other non-essential animations with static
+    /// fallbacks
+    #[serde(default)]
+    pub reduce_motion: bool,
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum BarPosition {
     Top,
     Bottom,
+    /// A vertical bar along the left edge
+    Left,
+    /// A vertical bar along the right edge
+    Right,
+}
+
+impl BarPosition {
+    pub fn is_vertical(self) -> bool {
+        matches!(self, BarPosition::Left | BarPosition::Right)
+    }
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BarConfig {
-    /// Bar height in pixels
+    /// Bar height in pixels, or its width when it's vertical
     pub height: i32,
-    /// Bar position (top, bottom)
+    /// Bar position (top, bottom, left, right)
     pub position: BarPosition,
     /// Spacing between tiles
     pub tile_spacing: i32,
     /// Margin from screen edges
     pub edge_padding: i32,
+    /// Show only the clock and battery until the pointer is over the bar or
+    /// a notification arrives
+    #[serde(default)]
+    pub compact: bool,
+    /// Which tiles go where on the bar
+    #[serde(default)]
+    pub layout: BarLayout,
+    /// Hide the bar until the pointer touches its edge of the screen
+    #[serde(default)]
+    pub autohide: AutohideConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutohideConfig {
+    pub enabled: bool,
+    /// Milliseconds the bar stays up after the pointer leaves it
+    pub hide_delay: u64,
+    /// Milliseconds the bar takes to slide in or out
+    pub animation_duration: u32,
+}
+
+/// Tiles on each part of the bar, in order, by id (see
+/// [`MonitorBarConfig::hidden_tiles`]). Unknown ids are skipped with a
+/// warning.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BarLayout {
+    pub left: Vec<String>,
+    pub center: Vec<String>,
+    pub right: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonitorBarConfig {
+    /// Whether the monitor gets a bar at all
+    pub enabled: bool,
+    /// Where the bar sits on this monitor, instead of `bar.position`
+    pub position: Option<BarPosition>,
+    /// Tiles left off this monitor's bar, by id: `"workspaces"`, `"clock"`,
+    /// `"weather"`, `"media"`, `"brightness"`, `"volume"`, `"microphone"`,
+    /// `"bluetooth"`, `"network"`, `"battery"`, `"tray"`, `"notifications"`
+    /// or `"power"`
+    pub hidden_tiles: Vec<String>,
+}
+
+impl MonitorBarConfig {
+    pub fn shows(&self, tile: &str) -> bool {
+        !self.hidden_tiles.iter().any(|hidden| hidden == tile)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,8 +153,34 @@ pub struct NotificationConfig {
     pub timeout: u64,
     /// Notification popup width
     pub popup_width: i32,
+    /// Maximum height of the notification popup stack before it scrolls
+    #[serde(default = "default_popup_max_height")]
+    pub popup_max_height: i32,
     /// Notification center width
     pub center_width: i32,
+    /// How long a snoozed notification stays hidden, in seconds
+    #[serde(default = "default_snooze_duration")]
+    pub snooze_duration: u64,
+    /// Which monitor(s) notification popups appear on
+    #[serde(default)]
+    pub show_on: PopupPlacement,
+    /// Longest notification body kept, in characters; longer bodies are cut
+    /// off with an ellipsis. `null` keeps bodies whole
+    #[serde(default = "default_max_body_length")]
+    pub max_body_length: Option<usize>,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PopupPlacement {
+    /// The monitor the compositor reports as focused, or the primary monitor
+    /// if that's unknown
+    #[default]
+    ActiveMonitor,
+    /// The first connected monitor
+    PrimaryMonitor,
+    /// Every monitor at once
+    AllMonitors,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -61,11 +193,330 @@ pub struct TileConfig {
     pub max_text_width: i32,
     /// Analog clock radius
     pub analog_clock_radius: f64,
+    /// Scroll long media titles instead of cutting them off
+    #[serde(default = "default_media_marquee")]
+    pub media_marquee: bool,
+    /// Longest focused window title shown in the bar, in characters, before
+    /// it's cut off with an ellipsis
+    #[serde(default = "default_window_title_width")]
+    pub window_title_width: i32,
+    /// Show tray items that say they're idle, which are hidden until they
+    /// become active otherwise
+    #[serde(default)]
+    pub show_passive_tray_items: bool,
+}
+
+/// What happens when a bar widget is clicked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickAction {
+    /// Toggle the calendar popover (clock only)
+    Calendar,
+    /// Open the power menu
+    PowerMenu,
+    /// Run a command with `sh -c`, e.g. `{"command": "gnome-calendar"}`
+    Command(String),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WidgetClickConfig {
+    /// Action to take when the widget is clicked; `null` does nothing
+    pub on_click: Option<ClickAction>,
+}
+
+/// Per-widget settings, keyed by widget name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WidgetsConfig {
+    pub clock: WidgetClickConfig,
+    pub battery: WidgetClickConfig,
+}
+
+/// Backlight control from the bar, and automatic backlight changes. The
+/// automatic ones are off unless configured.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BrightnessConfig {
+    /// How much one scroll step over the brightness tile changes it, from 0
+    /// to 1
+    pub scroll_step: f64,
+    /// The lowest brightness the bar will set, from 0 to 1
+    pub min_level: f64,
+    /// Dim the backlight while the user is idle
+    pub idle_dim: Option<IdleDimConfig>,
+    /// Switch between day and night brightness at sunrise and sunset
+    pub day_night: Option<DayNightConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IdleDimConfig {
+    /// Seconds of inactivity before dimming
+    pub timeout: u64,
+    /// Brightness to dim to, from 0 to 1
+    pub level: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DayNightConfig {
+    /// Latitude in degrees, north positive
+    pub latitude: f64,
+    /// Longitude in degrees, east positive
+    pub longitude: f64,
+    /// Brightness between sunrise and sunset, from 0 to 1
+    pub day_level: f64,
+    /// Brightness between sunset and sunrise, from 0 to 1
+    pub night_level: f64,
+}
+
+/// Commands used by the power menu. Lock and logout depend on the compositor
+/// and screen locker, so they are always commands; the others are only run if
+/// logind refuses the request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerConfig {
+    pub lock_command: Option<String>,
+    pub logout_command: Option<String>,
+    pub suspend_command: Option<String>,
+    pub reboot_command: Option<String>,
+    pub power_off_command: Option<String>,
+}
+
+/// Where the weather is for, and how the weather widget shows it on the bar.
+/// Its tooltip always shows both temperatures in full.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeatherConfig {
+    /// Where to get the weather for; guessed from the IP address if unset
+    pub location: Option<WeatherLocation>,
+    pub temperature_unit: TemperatureUnit,
+    pub wind_speed_unit: WindSpeedUnit,
+    /// Decimal places to show, 0 or 1
+    pub decimals: u8,
+    /// Show the unit after the degree sign, e.g. `72°F` instead of `72°`
+    pub show_unit: bool,
+    /// Which temperature to show
+    pub show: TemperatureShown,
+}
+
+/// How the volume tile changes the volume when scrolled over.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Percent to change the volume by per scroll step
+    pub scroll_step: f64,
+    /// Highest volume scrolling goes up to, in percent; above 100 amplifies
+    pub max_volume: f64,
+}
+
+/// How clocks show the time and date. Patterns are strftime-style, formatted
+/// by GLib so weekday and month names follow the locale.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClockConfig {
+    /// Show times with 24 hours instead of AM and PM, in the bar too
+    pub twenty_four_hour: bool,
+    /// The notification center's time pattern, e.g. `"%H:%M:%S"`
+    pub time_format: Option<String>,
+    /// The notification center's date pattern, e.g. `"%A %-d %B"`
+    pub date_format: Option<String>,
+}
+
+/// The calendar that opens from the clock.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalendarConfig {
+    /// A directory of `.ics` files whose events are marked on the calendar
+    pub events_dir: Option<PathBuf>,
+}
+
+/// The analog clock in the notification center.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalogClockConfig {
+    /// IANA timezone to show, e.g. `"Europe/Berlin"`; local time if unset
+    pub timezone: Option<String>,
+    /// Whether the second hand ticks once a second with the other clocks, or
+    /// sweeps smoothly by redrawing every frame
+    pub second_hand: SecondHand,
+    /// Draw the numbers 1 to 12 around the face
+    pub numerals: bool,
+    /// Another timezone to show on a small dial inside the clock
+    pub secondary_timezone: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecondHand {
+    Sweep,
+    #[default]
+    Tick,
+}
+
+/// Overrides for the stylesheet's variables. The bar's height comes from
+/// `bar.height`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    /// Any CSS color, e.g. `"#8cf"` or `"rgb(136, 204, 255)"`; used for
+    /// highlights like full level bars
+    pub accent_color: Option<String>,
+    /// Corner radius of panels like the notification center, in pixels.
+    /// Popups and OSDs are rounded in proportion
+    pub corner_radius: Option<f64>,
+    /// Whether to use light or dark styles
+    pub color_scheme: ColorScheme,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorScheme {
+    /// Follow the desktop's preference, falling back to dark
+    #[default]
+    Auto,
+    Light,
+    Dark,
+}
+
+/// Keys that run commands while a shell surface has keyboard focus. See
+/// `shortcuts::attach`. There are none by default, since bars rarely have
+/// focus; bind keys in the compositor to `cadenza-shell run <command>` for
+/// shortcuts that work everywhere.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShortcutsConfig {
+    /// Command names keyed by GTK accelerator, e.g. `"<Super>n"`. Commands
+    /// are `toggle-notification-center`, `toggle-network-menu`,
+    /// `toggle-volume-menu` and `toggle-media-playback`
+    pub bindings: HashMap<String, String>,
+}
+
+/// Either `{ "latitude": 52.52, "longitude": 13.40 }` or a place name like
+/// `"Berlin"`, which is looked up once and then polled by its coordinates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WeatherLocation {
+    Coordinates { latitude: f64, longitude: f64 },
+    Place(String),
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemperatureUnit {
+    Celsius,
+    #[default]
+    Fahrenheit,
+    Kelvin,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindSpeedUnit {
+    KilometersPerHour,
+    #[default]
+    MilesPerHour,
+    MetersPerSecond,
+    Knots,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemperatureShown {
+    /// The measured temperature
+    #[default]
+    Actual,
+    /// The apparent temperature
+    FeelsLike,
+    /// The measured temperature, then the apparent one in parentheses
+    Both,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CriticalBatteryAction {
+    /// Only show the critical battery notification
+    #[default]
+    Notify,
+    /// Suspend the system after the grace period
+    Suspend,
+    /// Hibernate the system after the grace period
+    Hibernate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatteryConfig {
+    /// Charge, as a fraction, at or below which the battery is critically low
+    pub critical_threshold: f32,
+    /// What to do when the battery becomes critically low
+    pub critical_action: CriticalBatteryAction,
+    /// Seconds to wait before performing the critical action
+    pub critical_action_delay: u64,
+    /// Warn once when the battery's full capacity falls below this fraction
+    /// of its design capacity; 0 turns this off
+    pub health_warning: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkspaceConfig {
+    /// Labels for workspace indicators, keyed by workspace name or index.
+    /// Values starting with `icon:` name an icon instead, e.g.
+    /// `"icon:firefox-symbolic"`. Indicators stay plain dots while this is
+    /// empty.
+    pub labels: HashMap<String, String>,
+    /// Milliseconds to show the workspace's label in the middle of its
+    /// monitor after switching to it; `null` turns this off
+    pub osd_duration: Option<u64>,
+    /// Only show indicators for workspaces with windows on them, plus the
+    /// active one
+    pub hide_empty: bool,
+}
+
+/// What a workspace indicator shows in place of its dot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkspaceLabel {
+    Text(String),
+    Icon(String),
+}
+
+impl WorkspaceConfig {
+    /// Looks up the label for a workspace by name, then by index, falling back
+    /// to the raw name or index. Returns `None` if no labels are configured.
+    pub fn label_for(&self, name: Option<&str>, idx: u8) -> Option<WorkspaceLabel> {
+        if self.labels.is_empty() {
+            return None;
+        }
+
+        let idx = idx.to_string();
+        let label = name
+            .and_then(|name| self.labels.get(name))
+            .or_else(|| self.labels.get(&idx));
+
+        Some(match label {
+            Some(value) => match value.strip_prefix("icon:") {
+                Some(icon) => WorkspaceLabel::Icon(icon.to_string()),
+                None => WorkspaceLabel::Text(value.clone()),
+            },
+            None => WorkspaceLabel::Text(name.map(String::from).unwrap_or(idx)),
+        })
+    }
+
+    /// Like [`label_for`](Self::label_for), but falls back to the name or
+    /// index even when no labels are configured.
+    pub fn label_or_name(&self, name: Option<&str>, idx: u8) -> WorkspaceLabel {
+        self.label_for(name, idx).unwrap_or_else(|| {
+            WorkspaceLabel::Text(name.map(String::from).unwrap_or_else(|| idx.to_string()))
+        })
+    }
 }
 
 impl Default for UiConfig {
     fn default() -> Self {
-        Self { scale_factor: 1.0 }
+        Self {
+            scale_factor: 1.0,
+            reduce_motion: false,
+        }
     }
 }
 
@@ -76,6 +527,50 @@ impl Default for BarConfig {
             position: BarPosition::Top,
             tile_spacing: 12,
             edge_padding: 8,
+            compact: false,
+            layout: BarLayout::default(),
+            autohide: AutohideConfig::default(),
+        }
+    }
+}
+
+impl Default for AutohideConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hide_delay: 600,
+            animation_duration: 200,
+        }
+    }
+}
+
+impl Default for BarLayout {
+    fn default() -> Self {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect();
+        Self {
+            left: ids(&["workspaces"]),
+            center: ids(&["clock", "weather", "media"]),
+            right: ids(&[
+                "brightness",
+                "volume",
+                "microphone",
+                "bluetooth",
+                "network",
+                "battery",
+                "tray",
+                "notifications",
+                "power",
+            ]),
+        }
+    }
+}
+
+impl Default for MonitorBarConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            position: None,
+            hidden_tiles: Vec::new(),
         }
     }
 }
@@ -86,11 +581,27 @@ impl Default for NotificationConfig {
             max_notifications: 10,
             timeout: 10,
             popup_width: 400,
-            center_width: 400,
+            popup_max_height: default_popup_max_height(),
+            center_width: 432,
+            snooze_duration: default_snooze_duration(),
+            show_on: PopupPlacement::default(),
+            max_body_length: default_max_body_length(),
         }
     }
 }
 
+fn default_popup_max_height() -> i32 {
+    800
+}
+
+fn default_snooze_duration() -> u64 {
+    10 * 60
+}
+
+fn default_max_body_length() -> Option<usize> {
+    Some(4000)
+}
+
 impl Default for TileConfig {
     fn default() -> Self {
         Self {
@@ -98,6 +609,71 @@ impl Default for TileConfig {
             show_labels: true,
             max_text_width: 30,
             analog_clock_radius: 60.0,
+            media_marquee: default_media_marquee(),
+            window_title_width: default_window_title_width(),
+            show_passive_tray_items: false,
+        }
+    }
+}
+
+fn default_media_marquee() -> bool {
+    true
+}
+
+fn default_window_title_width() -> i32 {
+    48
+}
+
+impl Default for WidgetsConfig {
+    fn default() -> Self {
+        Self {
+            clock: WidgetClickConfig {
+                on_click: Some(ClickAction::Calendar),
+            },
+            battery: WidgetClickConfig::default(),
+        }
+    }
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            lock_command: Some("loginctl lock-session".to_string()),
+            logout_command: Some("loginctl terminate-session \"$XDG_SESSION_ID\"".to_string()),
+            suspend_command: Some("systemctl suspend".to_string()),
+            reboot_command: Some("systemctl reboot".to_string()),
+            power_off_command: Some("systemctl poweroff".to_string()),
+        }
+    }
+}
+
+impl Default for BrightnessConfig {
+    fn default() -> Self {
+        Self {
+            scroll_step: 0.05,
+            min_level: 0.05,
+            idle_dim: None,
+            day_night: None,
+        }
+    }
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            scroll_step: 5.0,
+            max_volume: 100.0,
+        }
+    }
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            critical_threshold: 0.1,
+            critical_action: CriticalBatteryAction::Notify,
+            critical_action_delay: 60,
+            health_warning: 0.8,
         }
     }
 }
@@ -194,12 +770,13 @@ impl ConfigManager {
     }
 }
 
-use std::sync::{Mutex, OnceLock};
-
-/// Global configuration instance
-static CONFIG: OnceLock<Mutex<CadenzaShellConfig>> = OnceLock::new();
+/// Global configuration instance.
+///
+/// Components that need to react to configuration changes can subscribe via
+/// `CONFIG.subscribe(sender, mapper)`.
+pub static CONFIG: SharedState<CadenzaShellConfig> = SharedState::new();
 
-/// Initialize the global configuration manager
+/// Initialize the global configuration
 pub fn init() -> Result<()> {
     let config = match ConfigManager::new() {
         Ok(manager) => manager.config,
@@ -210,35 +787,26 @@ pub fn init() -> Result<()> {
         }
     };
 
-    CONFIG
-        .set(Mutex::new(config))
-        .map_err(|_| anyhow::anyhow!("configuration already initialized"))?;
+    *CONFIG.write() = config;
 
     Ok(())
 }
 
 /// Get a copy of the current configuration
 pub fn get_config() -> CadenzaShellConfig {
-    CONFIG
-        .get()
-        .and_then(|config| config.lock().ok())
-        .map(|config| config.clone())
-        .unwrap_or_default()
+    CONFIG.read().clone()
 }
 
 /// Update the global configuration
 pub fn update_config(new_config: CadenzaShellConfig) -> Result<()> {
-    if let Some(config_mutex) = CONFIG.get()
-        && let Ok(mut config) = config_mutex.lock()
-    {
-        *config = new_config.clone();
-
-        // also save to file
-        let config_path = ConfigManager::get_config_path();
-        let content = serde_json::to_string_pretty(&new_config)?;
-        fs::write(&config_path, content)?;
-        log::info!("updated and saved configuration");
-    }
+    *CONFIG.write() = new_config.clone();
+
+    // also save to file
+    let config_path = ConfigManager::get_config_path();
+    let content = serde_json::to_string_pretty(&new_config)?;
+    fs::write(&config_path, content)?;
+    log::info!("updated and saved configuration");
+
     Ok(())
 }
 
@@ -247,11 +815,87 @@ pub fn reload_config() -> Result<()> {
     let config_path = ConfigManager::get_config_path();
     let reloaded_config = ConfigManager::load_config(&config_path)?;
 
-    if let Some(config_mutex) = CONFIG.get()
-        && let Ok(mut config) = config_mutex.lock()
-    {
-        *config = reloaded_config;
-        log::info!("reloaded configuration from file");
-    }
+    *CONFIG.write() = reloaded_config;
+    log::info!("reloaded configuration from file");
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_label_for() {
+        let mut config = WorkspaceConfig::default();
+        assert_eq!(config.label_for(Some("chat"), 1), None);
+
+        config.labels = HashMap::from([
+            ("1".to_string(), "web".to_string()),
+            ("chat".to_string(), "icon:chat-symbolic".to_string()),
+        ]);
+
+        assert_eq!(
+            config.label_for(None, 1),
+            Some(WorkspaceLabel::Text("web".to_string()))
+        );
+        // names take precedence over indices
+        assert_eq!(
+            config.label_for(Some("chat"), 1),
+            Some(WorkspaceLabel::Icon("chat-symbolic".to_string()))
+        );
+        assert_eq!(
+            config.label_for(Some("music"), 3),
+            Some(WorkspaceLabel::Text("music".to_string()))
+        );
+        assert_eq!(
+            config.label_for(None, 2),
+            Some(WorkspaceLabel::Text("2".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_monitor_bar() {
+        let laptop = MonitorBarConfig {
+            position: Some(BarPosition::Bottom),
+            hidden_tiles: vec!["tray".to_string()],
+            ..Default::default()
+        };
+        let others = MonitorBarConfig {
+            enabled: false,
+            ..Default::default()
+        };
+
+        let mut config = CadenzaShellConfig::default();
+        assert_eq!(config.monitor_bar("eDP-1"), MonitorBarConfig::default());
+
+        config.monitors = HashMap::from([("eDP-1".to_string(), laptop.clone())]);
+        assert_eq!(config.monitor_bar("eDP-1"), laptop);
+        assert_eq!(config.monitor_bar("DP-1"), MonitorBarConfig::default());
+        assert!(!laptop.shows("tray"));
+        assert!(laptop.shows("clock"));
+
+        config.monitors.insert("*".to_string(), others.clone());
+        assert_eq!(config.monitor_bar("eDP-1"), laptop);
+        assert_eq!(config.monitor_bar("DP-1"), others);
+    }
+
+    #[test]
+    fn test_workspace_label_or_name() {
+        let mut config = WorkspaceConfig::default();
+        assert_eq!(
+            config.label_or_name(Some("chat"), 1),
+            WorkspaceLabel::Text("chat".to_string())
+        );
+        assert_eq!(
+            config.label_or_name(None, 3),
+            WorkspaceLabel::Text("3".to_string())
+        );
+
+        config.labels = HashMap::from([("1".to_string(), "web".to_string())]);
+        assert_eq!(
+            config.label_or_name(None, 1),
+            WorkspaceLabel::Text("web".to_string())
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use std::{collections::HashMap, sync::Arc};
+
+use gtk4::prelude::*;
+use tokio::sync::Mutex;
+
+use crate::{
+    commands::{self, GlobalCommandExecutor},
+    settings,
+};
+
+/// Runs commands from the `shortcuts` settings when their keys are pressed in
+/// `window`. The settings are read on each key press, so changed shortcuts
+/// apply as soon as the settings are reloaded.
+///
+/// Layer-shell surfaces only get keys while they have keyboard focus, like
+/// while the network menu is open. For shortcuts that work everywhere, bind
+/// keys in the compositor to `cadenza-shell run <command>` instead.
+///
+/// A shortcut naming a command that doesn't exist is logged and otherwise
+/// ignored, and one whose keys GTK can't parse never matches.
+pub fn attach(window: &gtk::Window, commands: Arc<Mutex<GlobalCommandExecutor>>) {
+    let keys = gtk::EventControllerKey::new();
+    keys.set_propagation_phase(gtk::PropagationPhase::Capture);
+    keys.connect_key_pressed(move |_, keyval, _, modifiers| {
+        let bindings = settings::get_config().shortcuts.bindings;
+        let Some(name) = find_binding(&bindings, keyval, modifiers) else {
+            return glib::Propagation::Proceed;
+        };
+
+        let commands = commands.clone();
+        relm4::spawn(async move {
+            if let Err(e) = commands::run_named(&commands, &name).await {
+                log::warn!("ignoring shortcut: {e}");
+            }
+        });
+        glib::Propagation::Stop
+    });
+    window.add_controller(keys);
+}
+
+/// Finds the command bound to `keyval` pressed with `modifiers`, ignoring
+/// modifiers like Caps Lock that accelerators don't mention.
+fn find_binding(
+    bindings: &HashMap<String, String>,
+    keyval: gdk4::Key,
+    modifiers: gdk4::ModifierType,
+) -> Option<String> {
+    let modifiers = modifiers & gtk::accelerator_get_default_mod_mask();
+    bindings.iter().find_map(|(accelerator, name)| {
+        let (key, mods) = gtk::accelerator_parse(accelerator)?;
+        (key.to_lower() == keyval.to_lower() && mods == modifiers).then(|| name.clone())
+    })
+}
@@ -24,7 +24,19 @@ pub fn subscribe_wake() -> broadcast::Receiver<()> {
     default_service = "org.freedesktop.login1",
     default_path = "/org/freedesktop/login1"
 )]
-trait Login1Manager {
+pub(crate) trait Login1Manager {
+    /// Suspend the system.
+    fn suspend(&self, interactive: bool) -> zbus::Result<()>;
+
+    /// Hibernate the system.
+    fn hibernate(&self, interactive: bool) -> zbus::Result<()>;
+
+    /// Reboot the system.
+    fn reboot(&self, interactive: bool) -> zbus::Result<()>;
+
+    /// Power off the system.
+    fn power_off(&self, interactive: bool) -> zbus::Result<()>;
+
     /// Signal emitted before sleep (`start = true`) and after wake (`start =
     /// false`).
     #[zbus(signal)]
@@ -6,11 +6,30 @@ use tokio::process::Command;
 /// warning if the process fails to start or exits with a non-zero status, but
 /// never panics.
 pub fn play(event_id: &str) {
-    if let Err(e) = Command::new("canberra-gtk-play")
-        .arg("--id")
-        .arg(event_id)
-        .spawn()
-    {
-        log::error!("couldn't spawn canberra-gtk-play: {e}");
-    }
+    spawn_canberra("--id", event_id);
+}
+
+/// Play a sound file using `canberra-gtk-play`, in the background like
+/// [`play`].
+pub fn play_file(path: &str) {
+    spawn_canberra("--file", path);
+}
+
+/// Runs `canberra-gtk-play` with one option, waiting on it in a task so it
+/// doesn't linger as a zombie once it's done.
+fn spawn_canberra(option: &str, value: &str) {
+    let mut command = Command::new("canberra-gtk-play");
+    command.arg(option).arg(value);
+    relm4::spawn(async move {
+        match command.spawn() {
+            Ok(mut child) => match child.wait().await {
+                Ok(status) if !status.success() => {
+                    log::warn!("canberra-gtk-play exited with {status}");
+                }
+                Ok(_) => {}
+                Err(e) => log::error!("couldn't wait for canberra-gtk-play: {e}"),
+            },
+            Err(e) => log::error!("couldn't spawn canberra-gtk-play: {e}"),
+        }
+    });
 }
@@ -1,12 +1,274 @@
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    fs,
+    path::Path,
+    time::Duration,
+};
+
+use futures_lite::StreamExt;
+use gtk4::{CssProvider, STYLE_PROVIDER_PRIORITY_APPLICATION, gdk::Display};
+use inotify::{Inotify, WatchMask};
 use rsass::{
     compile_scss,
     output::{Format, Style},
 };
 
+use crate::{
+    appearance::PREFERS_DARK,
+    settings::{self, CadenzaShellConfig, ColorScheme, ConfigManager},
+};
+
+/// Where the stylesheet lives in the source tree, for debug builds run from a
+/// checkout. Styles are read from it instead of the copy built into the binary,
+/// so edits show up without rebuilding. Release builds always use the built-in
+/// copy, even if the checkout they were built from is still around.
+fn source_dir() -> Option<&'static Path> {
+    let dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src"));
+    (cfg!(debug_assertions) && dir.is_dir()).then_some(dir)
+}
+
+/// Whether the stylesheet is read from the source tree, so that reloading
+/// picks up edits to it rather than just the theme variables.
+pub fn reads_stylesheet_from_source() -> bool {
+    source_dir().is_some()
+}
+
+/// How long the watched files have to go without changes before they're
+/// applied, since editors often write a file more than once when saving.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+thread_local! {
+    /// Holds the shell's stylesheet. Reloading replaces its contents rather
+    /// than stacking another provider on top.
+    static PROVIDER: CssProvider = {
+        let provider = CssProvider::new();
+        gtk4::style_context_add_provider_for_display(
+            &Display::default().expect("could not get default display"),
+            &provider,
+            STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
+        provider
+    };
+}
+
+fn read_source() -> Cow<'static, [u8]> {
+    match source_dir().and_then(|dir| fs::read(dir.join("style.scss")).ok()) {
+        Some(source) => Cow::Owned(source),
+        None => Cow::Borrowed(include_bytes!("style.scss")),
+    }
+}
+
+/// Whether to use dark styles, going by the desktop's preference unless the
+/// settings say otherwise.
+fn is_dark(config: &CadenzaShellConfig) -> bool {
+    match config.theme.color_scheme {
+        ColorScheme::Auto => PREFERS_DARK.read().unwrap_or(true),
+        ColorScheme::Light => false,
+        ColorScheme::Dark => true,
+    }
+}
+
+/// SCSS variables for the `theme` settings, overriding the stylesheet's
+/// defaults. Malformed values are logged and left to the defaults.
+fn theme_variables(config: &CadenzaShellConfig) -> String {
+    let mut variables = format!(
+        "$dark: {};\n$bar-height: {}px;\n",
+        is_dark(config),
+        config.bar.height
+    );
+
+    if let Some(color) = &config.theme.accent_color {
+        match gdk4::RGBA::parse(color.as_str()) {
+            Ok(rgba) => variables.push_str(&format!("$accent-color: {rgba};\n")),
+            Err(_) => log::warn!("ignoring accent color '{color}', which isn't a CSS color"),
+        }
+    }
+
+    if let Some(radius) = config.theme.corner_radius {
+        if radius.is_finite() && radius >= 0.0 {
+            variables.push_str(&format!("$corner-radius: {radius}px;\n"));
+        } else {
+            log::warn!("ignoring corner radius {radius}, which isn't a size");
+        }
+    }
+
+    variables
+}
+
+/// Puts `variables` before everything in `source` but its `@use` and
+/// `@forward` rules, which have to come first.
+fn with_variables(source: &str, variables: &str) -> String {
+    let mut split = 0;
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with("@use") || trimmed.starts_with("@forward") {
+            split = offset + line.len();
+        } else if !trimmed.is_empty() && !trimmed.starts_with("//") {
+            break;
+        }
+        offset += line.len();
+    }
+
+    format!("{}{}{}", &source[..split], variables, &source[split..])
+}
+
 pub fn compile_styles() -> Result<String, rsass::Error> {
-    compile_scss(include_bytes!("style.scss"), Format {
+    let source = with_variables(
+        &String::from_utf8_lossy(&read_source()),
+        &theme_variables(&settings::get_config()),
+    );
+    compile_scss(source.as_bytes(), Format {
         style: Style::Expanded,
         ..Default::default()
     })
     .map(|vec| String::from_utf8_lossy(&vec).into_owned())
 }
+
+/// Compiles the stylesheet and applies it, replacing any previously applied
+/// styles. If compilation fails, the current styles stay in place.
+///
+/// Must be called from the GTK main thread.
+pub fn apply_styles() -> Result<(), rsass::Error> {
+    let css = compile_styles()?;
+    PROVIDER.with(|provider| provider.load_from_string(&css));
+
+    // so GTK's own widgets, like popovers, match
+    if let Some(gtk_settings) = gtk4::Settings::default() {
+        gtk_settings.set_gtk_application_prefer_dark_theme(is_dark(&settings::get_config()));
+    }
+
+    Ok(())
+}
+
+/// What [`watch_styles`] saw change.
+#[derive(Debug)]
+pub enum StyleChange {
+    /// A `.scss` file in the source tree.
+    Stylesheet,
+    /// The settings file, which can theme the stylesheet. Its other settings
+    /// need reloading too.
+    Settings,
+}
+
+/// Calls `on_change` whenever the settings file or a `.scss` file in the source
+/// tree changes, once the writes have settled. The source tree is only
+/// watched by debug builds, and only if it's there.
+pub async fn watch_styles(on_change: impl Fn(StyleChange)) {
+    let inotify = match Inotify::init() {
+        Ok(inotify) => inotify,
+        Err(e) => {
+            log::error!("failed to init inotify: {}", e);
+            return;
+        }
+    };
+
+    // editors that save by renaming a temporary file show up as MOVED_TO
+    let watch = |dir: &Path| {
+        inotify
+            .watches()
+            .add(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)
+            .inspect_err(|e| log::error!("couldn't watch {} for changes: {}", dir.display(), e))
+            .ok()
+    };
+
+    let source_watch = source_dir().and_then(watch);
+
+    let settings_path = ConfigManager::get_config_path();
+    let settings_name = settings_path.file_name().map(OsStr::to_owned);
+    let settings_watch = settings_path.parent().and_then(watch);
+
+    if source_watch.is_none() && settings_watch.is_none() {
+        return;
+    }
+
+    let classify = |event: inotify::Event<OsString>| {
+        if source_watch.as_ref() == Some(&event.wd) && is_scss(event.name.as_deref()) {
+            Some(StyleChange::Stylesheet)
+        } else if settings_watch.as_ref() == Some(&event.wd) && event.name == settings_name {
+            Some(StyleChange::Settings)
+        } else {
+            None
+        }
+    };
+
+    let mut events = match inotify.into_event_stream([0; 1024]) {
+        Ok(events) => events,
+        Err(e) => {
+            log::error!("couldn't read inotify events for styles: {}", e);
+            return;
+        }
+    };
+
+    while let Some(event) = events.next().await {
+        let mut change = match event.map(classify) {
+            Ok(Some(change)) => change,
+            Ok(None) => continue,
+            Err(e) => {
+                log::error!("error while watching styles: {}", e);
+                return;
+            }
+        };
+
+        // wait for the writes to settle. settings changes win, since
+        // reloading them restyles too
+        while let Ok(Some(event)) = tokio::time::timeout(WATCH_DEBOUNCE, events.next()).await {
+            if let Ok(Some(StyleChange::Settings)) = event.map(classify) {
+                change = StyleChange::Settings;
+            }
+        }
+
+        on_change(change);
+    }
+}
+
+fn is_scss(file_name: Option<&OsStr>) -> bool {
+    file_name
+        .and_then(|name| Path::new(name).extension())
+        .is_some_and(|extension| extension == "scss")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variables_go_after_use_rules() {
+        let source = "@use \"sass:string\";\n\n$warn-color: #fa0;\n";
+        assert_eq!(
+            with_variables(source, "$accent-color: red;\n"),
+            "@use \"sass:string\";\n$accent-color: red;\n\n$warn-color: #fa0;\n"
+        );
+
+        assert_eq!(
+            with_variables(".bar {}\n", "$bar-height: 32px;\n"),
+            "$bar-height: 32px;\n.bar {}\n"
+        );
+    }
+
+    #[test]
+    fn test_malformed_theme_values_are_skipped() {
+        let mut config = CadenzaShellConfig::default();
+        config.theme.accent_color = Some("not a color".to_string());
+        config.theme.corner_radius = Some(-4.0);
+        assert_eq!(
+            theme_variables(&config),
+            "$dark: true;\n$bar-height: 32px;\n"
+        );
+
+        config.theme.accent_color = Some("#ff0000".to_string());
+        config.theme.corner_radius = Some(8.0);
+        assert_eq!(
+            theme_variables(&config),
+            "$dark: true;\n$bar-height: 32px;\n$accent-color: rgb(255,0,0);\n$corner-radius: 8px;\n"
+        );
+    }
+
+    #[test]
+    fn test_color_scheme_override() {
+        let mut config = CadenzaShellConfig::default();
+        config.theme.color_scheme = ColorScheme::Light;
+        assert!(theme_variables(&config).starts_with("$dark: false;\n"));
+    }
+}
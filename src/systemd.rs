@@ -0,0 +1,67 @@
+use std::{
+    ffi::OsStr,
+    io,
+    os::{
+        linux::net::SocketAddrExt,
+        unix::{
+            ffi::OsStrExt,
+            net::{SocketAddr, UnixDatagram},
+        },
+    },
+};
+
+/// Tells systemd the shell is up, for `Type=notify` units. Does nothing when
+/// not started by systemd.
+pub fn notify_ready() {
+    notify("READY=1");
+}
+
+/// Tells systemd the shell is shutting down on purpose.
+pub fn notify_stopping() {
+    notify("STOPPING=1");
+}
+
+/// Sends `state` to the socket in `$NOTIFY_SOCKET`, as `sd_notify(3)` does.
+fn notify(state: &str) {
+    let Some(socket_path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+
+    match send(&socket_path, state) {
+        Ok(()) => log::debug!("notified systemd: {state}"),
+        Err(e) => log::warn!("couldn't notify systemd ({state}): {e}"),
+    }
+}
+
+fn send(socket_path: &OsStr, state: &str) -> io::Result<()> {
+    // a leading '@' means an abstract socket
+    let addr = match socket_path.as_bytes().strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(socket_path)?,
+    };
+
+    let socket = UnixDatagram::unbound()?;
+    socket.connect_addr(&addr)?;
+    socket.send(state.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_to_path_socket() {
+        let path = std::env::temp_dir().join(format!("cadenza-notify-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixDatagram::bind(&path).unwrap();
+
+        send(path.as_os_str(), "READY=1").unwrap();
+
+        let mut buf = [0; 16];
+        let len = listener.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
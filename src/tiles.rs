@@ -1,12 +1,26 @@
+use std::cell::RefCell;
+
+use gtk4::prelude::*;
+
+use crate::{
+    launch,
+    power_menu::PowerPopover,
+    settings::{self, ClickAction, WidgetClickConfig, WidgetsConfig},
+};
+
 // Relm4 tile implementations
 pub mod battery;
+#[cfg(feature = "bluetooth")]
 pub mod bluetooth;
 pub mod brightness;
 pub mod clock;
+pub mod microphone;
 pub mod mpris;
 pub mod network;
+#[cfg(feature = "niri")]
 pub mod niri;
 pub mod notifications;
+pub mod power;
 pub mod pulseaudio;
 pub mod tray;
 pub mod weather;
@@ -29,3 +43,46 @@ impl Attention {
         }
     }
 }
+
+/// Adds each class whose flag is set to `widget` and removes the others, so
+/// themes can style a tile by its state (e.g. `.tile.battery-low`).
+pub fn set_state_classes(widget: &impl IsA<gtk::Widget>, classes: &[(&str, bool)]) {
+    for &(class, active) in classes {
+        if active {
+            widget.add_css_class(class);
+        } else {
+            widget.remove_css_class(class);
+        }
+    }
+}
+
+/// Runs the `on_click` action configured for a bar widget whenever `button` is
+/// clicked. The setting is read on every click, so reloads apply right away.
+/// `calendar` is what [`ClickAction::Calendar`] toggles, for widgets that have
+/// one.
+pub fn connect_click_action(
+    button: &gtk::Button,
+    name: &'static str,
+    on_click: fn(&WidgetsConfig) -> &WidgetClickConfig,
+    calendar: Option<gtk::Popover>,
+) {
+    // only built once the power menu is first asked for
+    let power_popover: RefCell<Option<PowerPopover>> = RefCell::default();
+
+    button.connect_clicked(move |button| {
+        let config = settings::get_config();
+        match &on_click(&config.widget).on_click {
+            Some(ClickAction::Calendar) => match &calendar {
+                Some(calendar) if calendar.is_visible() => calendar.popdown(),
+                Some(calendar) => calendar.popup(),
+                None => log::warn!("the {name} widget has no calendar; ignoring click"),
+            },
+            Some(ClickAction::PowerMenu) => power_popover
+                .borrow_mut()
+                .get_or_insert_with(|| PowerPopover::new(button))
+                .toggle(),
+            Some(ClickAction::Command(command)) => launch::spawn_command(command),
+            None => {}
+        }
+    });
+}
@@ -4,8 +4,8 @@ use gtk4::prelude::*;
 use relm4::prelude::*;
 
 use crate::{
-    battery::{BATTERY_STATE, BatteryState, ChargingStatus},
-    tiles::Attention,
+    battery::{BATTERY_STATE, BatteryHealth, BatteryState, ChargingStatus, EstimateConfidence},
+    tiles::{Attention, connect_click_action, set_state_classes},
     utils::icons::{BATTERY_CHARGING_ICON_NAMES, BATTERY_ICON_NAMES, percentage_to_icon_from_list},
     widgets::tile::{Tile, TileInit, TileMsg},
 };
@@ -19,7 +19,9 @@ pub struct BatteryTile {
 
     current_percentage: f32,
     status: ChargingStatus,
-    discharging_time_remaining: Duration,
+    discharging_time_remaining: Option<Duration>,
+    confidence: f32,
+    health: BatteryHealth,
 }
 
 #[derive(Debug)]
@@ -34,14 +36,14 @@ pub struct BatteryWidgets {
 }
 
 impl SimpleComponent for BatteryTile {
-    type Init = ();
+    type Init = gtk::Orientation;
     type Input = BatteryMsg;
     type Output = ();
     type Root = gtk::Box;
     type Widgets = BatteryWidgets;
 
     fn init(
-        _init: Self::Init,
+        orientation: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
@@ -57,6 +59,8 @@ impl SimpleComponent for BatteryTile {
             current_percentage: s.percentage,
             status: s.status,
             discharging_time_remaining: s.discharging_time_remaining,
+            confidence: s.confidence,
+            health: s.health,
         });
 
         // hide the entire tile if battery isn't available
@@ -69,11 +73,15 @@ impl SimpleComponent for BatteryTile {
                 primary: Some(model.get_text()),
                 secondary: Some(model.get_readable_time()),
                 attention: model.get_attention(),
-                tooltip: None,
+                tooltip: model.tooltip(),
+                orientation,
+                ..Default::default()
             })
             .detach();
         root.append(tile.widget());
 
+        connect_click_action(tile.widget(), "battery", |widgets| &widgets.battery, None);
+
         ComponentParts {
             model,
             widgets: BatteryWidgets { root, tile },
@@ -85,11 +93,15 @@ impl SimpleComponent for BatteryTile {
             percentage,
             status,
             discharging_time_remaining,
+            confidence,
+            health,
         }) = o
         {
             self.current_percentage = percentage;
             self.status = status;
             self.discharging_time_remaining = discharging_time_remaining;
+            self.confidence = confidence;
+            self.health = health;
             self.available = true;
         } else {
             self.available = false;
@@ -110,12 +122,15 @@ impl SimpleComponent for BatteryTile {
             widgets
                 .tile
                 .emit(TileMsg::SetSecondary(Some(self.get_readable_time())));
+            widgets.tile.emit(TileMsg::SetTooltip(self.tooltip()));
 
             // update attention state
             let attention = self.get_attention();
 
             // update visibility and attention
             widgets.tile.emit(TileMsg::SetAttention(attention));
+
+            set_state_classes(widgets.tile.widget(), &self.state_classes());
         }
     }
 
@@ -146,13 +161,17 @@ impl BatteryTile {
 
     fn is_low(&self) -> bool {
         (self.current_percentage <= 0.2
-            || self.discharging_time_remaining < TIME_REMAINING_LOW_THRESHOLD)
+            || self
+                .discharging_time_remaining
+                .is_some_and(|t| t < TIME_REMAINING_LOW_THRESHOLD))
             && !self.status.is_charging()
     }
 
     fn is_critical(&self) -> bool {
         (self.current_percentage <= 0.1
-            || self.discharging_time_remaining < TIME_REMAINING_CRITICAL_THRESHOLD)
+            || self
+                .discharging_time_remaining
+                .is_some_and(|t| t < TIME_REMAINING_CRITICAL_THRESHOLD))
             && !self.status.is_charging()
     }
 
@@ -163,7 +182,7 @@ impl BatteryTile {
             "Not charging".to_string()
         } else if self.status.is_charging() && self.current_percentage >= 0.995 {
             "Plugged in".to_string()
-        } else {
+        } else if let Some(time_remaining) = self.discharging_time_remaining {
             enum RemainingDurationCategory {
                 Soon,
                 Today,
@@ -171,12 +190,11 @@ impl BatteryTile {
                 Someday,
             }
 
-            let time_remaining = self.discharging_time_remaining;
-            let time_category = if self.discharging_time_remaining < Duration::from_mins(30) {
+            let time_category = if time_remaining < Duration::from_mins(30) {
                 RemainingDurationCategory::Soon
-            } else if self.discharging_time_remaining < Duration::from_hours(24) {
+            } else if time_remaining < Duration::from_hours(24) {
                 RemainingDurationCategory::Today
-            } else if self.discharging_time_remaining < Duration::from_hours(48) {
+            } else if time_remaining < Duration::from_hours(48) {
                 RemainingDurationCategory::Tomorrow
             } else {
                 RemainingDurationCategory::Someday
@@ -218,9 +236,47 @@ impl BatteryTile {
                     }
                 }
             }
+        } else {
+            // the discharge model hasn't seen enough samples yet
+            "Calculating…".to_string()
         }
     }
 
+    /// How long the battery should last and how sure we are of that, while
+    /// discharging.
+    fn estimate_summary(&self) -> Option<String> {
+        if self.status != ChargingStatus::Discharging {
+            return None;
+        }
+
+        let label = match EstimateConfidence::from_score(self.confidence) {
+            EstimateConfidence::Learning => return Some("Learning your usage…".to_string()),
+            EstimateConfidence::Medium => "medium",
+            EstimateConfidence::High => "high",
+        };
+        let time_remaining = self.discharging_time_remaining?;
+        Some(format!(
+            "~{} ({label} confidence)",
+            format_duration(time_remaining)
+        ))
+    }
+
+    fn tooltip(&self) -> Option<String> {
+        let lines: Vec<String> = [self.estimate_summary(), self.health.summary()]
+            .into_iter()
+            .flatten()
+            .collect();
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    fn state_classes(&self) -> [(&'static str, bool); 3] {
+        [
+            ("battery-low", self.is_low()),
+            ("battery-critical", self.is_critical()),
+            ("battery-charging", self.status.is_charging()),
+        ]
+    }
+
     fn get_attention(&self) -> Attention {
         if self.is_critical() {
             Attention::Alarm
@@ -231,3 +287,91 @@ impl BatteryTile {
         }
     }
 }
+
+/// Formats a duration in hours and minutes, e.g. "3h 20m" or "45m".
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{minutes}m"),
+        (hours, minutes) => format!("{hours}h {minutes}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_summary_follows_confidence() {
+        let mut tile = BatteryTile {
+            available: true,
+            current_percentage: 0.5,
+            status: ChargingStatus::Discharging,
+            discharging_time_remaining: Some(Duration::from_mins(200)),
+            confidence: 0.9,
+            ..Default::default()
+        };
+        assert_eq!(
+            tile.estimate_summary().as_deref(),
+            Some("~3h 20m (high confidence)")
+        );
+
+        tile.confidence = 0.5;
+        assert_eq!(
+            tile.estimate_summary().as_deref(),
+            Some("~3h 20m (medium confidence)")
+        );
+
+        // too early to show a number
+        tile.confidence = 0.2;
+        assert_eq!(
+            tile.estimate_summary().as_deref(),
+            Some("Learning your usage…")
+        );
+
+        tile.status = ChargingStatus::Charging;
+        assert_eq!(tile.estimate_summary(), None);
+    }
+
+    #[test]
+    fn test_state_classes_follow_charge() {
+        let mut tile = BatteryTile {
+            available: true,
+            current_percentage: 0.5,
+            status: ChargingStatus::Discharging,
+            ..Default::default()
+        };
+        assert!(tile.state_classes().iter().all(|(_, active)| !active));
+
+        tile.current_percentage = 0.15;
+        assert_eq!(
+            tile.state_classes(),
+            [
+                ("battery-low", true),
+                ("battery-critical", false),
+                ("battery-charging", false)
+            ]
+        );
+
+        tile.current_percentage = 0.05;
+        assert_eq!(
+            tile.state_classes(),
+            [
+                ("battery-low", true),
+                ("battery-critical", true),
+                ("battery-charging", false)
+            ]
+        );
+
+        // plugging in clears the warnings
+        tile.status = ChargingStatus::Charging;
+        assert_eq!(
+            tile.state_classes(),
+            [
+                ("battery-low", false),
+                ("battery-critical", false),
+                ("battery-charging", true)
+            ]
+        );
+    }
+}
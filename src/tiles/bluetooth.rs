@@ -3,9 +3,8 @@ use relm4::prelude::*;
 
 use crate::{
     bluetooth::{BLUETOOTH_STATE, BluetoothState},
-    bluetooth_menu::BluetoothMenu,
     icon_names::{BLUETOOTH, BLUETOOTH_NO, BLUETOOTH_X},
-    widgets::tile::{Tile, TileMsg, TileOutput},
+    widgets::tile::{Tile, TileInit, TileMsg},
 };
 
 #[derive(Debug)]
@@ -16,14 +15,15 @@ pub struct BluetoothTile {
 }
 
 #[derive(Debug)]
-pub struct BluetoothWidgets {
-    _popover: gtk::Popover,
-    _menu: Controller<BluetoothMenu>,
+pub enum BluetoothTileMsg {
+    Update(Option<BluetoothState>),
 }
 
 #[derive(Debug)]
-pub enum BluetoothTileMsg {
-    Update(Option<BluetoothState>),
+pub enum BluetoothTileOutput {
+    /// Asks the bar to open or close the bluetooth menu, pointing at the given
+    /// tile.
+    ToggleMenu(gtk::Widget),
 }
 
 #[derive(Debug)]
@@ -33,14 +33,14 @@ pub enum BluetoothTileCommandOutput {
 
 impl Component for BluetoothTile {
     type CommandOutput = BluetoothTileCommandOutput;
-    type Init = ();
+    type Init = gtk::Orientation;
     type Input = BluetoothTileMsg;
-    type Output = TileOutput;
+    type Output = BluetoothTileOutput;
     type Root = gtk::Box;
-    type Widgets = BluetoothWidgets;
+    type Widgets = ();
 
     fn init(
-        _init: Self::Init,
+        orientation: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
@@ -51,29 +51,20 @@ impl Component for BluetoothTile {
         let current_state = BLUETOOTH_STATE.read().clone();
 
         // initialize the tile component
-        let tile = Tile::builder().launch(Default::default()).detach();
-
-        // initialize the bluetooth menu component
-        let bluetooth_menu = BluetoothMenu::builder().launch(()).detach();
-
-        // create the popover
-        let popover = gtk::Popover::builder()
-            .child(bluetooth_menu.widget())
-            .width_request(384)
-            .height_request(256)
-            .autohide(true)
-            .build();
-        popover.set_parent(tile.widget());
-
-        // connect click handler to toggle popover
-        let popover_clone = popover.clone();
-        tile.widget().connect_clicked(move |_| {
-            if popover_clone.is_visible() {
-                popover_clone.popdown();
-            } else {
-                popover_clone.popup();
-            }
-        });
+        let tile = Tile::builder()
+            .launch(TileInit {
+                orientation,
+                ..Default::default()
+            })
+            .detach();
+
+        // the bar owns the menu; just ask it to toggle
+        {
+            let sender = sender.clone();
+            tile.widget().connect_clicked(move |button| {
+                let _ = sender.output(BluetoothTileOutput::ToggleMenu(button.clone().upcast()));
+            });
+        }
 
         root.append(tile.widget());
 
@@ -83,10 +74,7 @@ impl Component for BluetoothTile {
                 bluetooth_info: current_state,
                 tooltip_text: String::new(),
             },
-            widgets: BluetoothWidgets {
-                _popover: popover,
-                _menu: bluetooth_menu,
-            },
+            widgets: (),
         }
     }
 
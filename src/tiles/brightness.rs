@@ -1,46 +1,108 @@
+use std::{sync::Arc, time::Duration};
+
 use gtk4::prelude::*;
 use relm4::prelude::*;
+use tokio::sync::Mutex;
 
 use crate::{
-    brightness::BRIGHTNESS_STATE,
+    brightness::{self, BRIGHTNESS_STATE},
+    commands::{AppCommand, GlobalCommandExecutor, Prior},
+    settings,
     utils::icons::{BRIGHTNESS_ICON_NAMES, percentage_to_icon_from_list},
-    widgets::progress_tile::{ProgressTile, ProgressTileInit, ProgressTileMsg},
+    widgets::{
+        level_osd::LevelOsd,
+        progress_tile::{ProgressTile, ProgressTileInit, ProgressTileMsg},
+    },
 };
 
+/// How long after the last scroll a brightness change counts as finished,
+/// and is recorded so it can be undone.
+const SCROLL_SETTLE: Duration = Duration::from_millis(500);
+
+/// How long the brightness OSD stays up after the last scroll.
+const OSD_DURATION: Duration = Duration::from_millis(1200);
+
 #[derive(Debug)]
 pub struct BrightnessTile {
     progress_tile: Controller<ProgressTile>,
+    monitor: gdk4::Monitor,
+    commands: Arc<Mutex<GlobalCommandExecutor>>,
+    osd: Option<LevelOsd>,
+    /// The brightness before scrolling started and where it's got to, while
+    /// scrolling.
+    scroll: Option<(f64, f64)>,
+    /// Counts scroll events, so only the last one's timeout settles the
+    /// scroll.
+    scroll_count: u32,
+}
+
+pub struct BrightnessTileInit {
+    pub monitor: gdk4::Monitor,
+    pub commands: Arc<Mutex<GlobalCommandExecutor>>,
+    pub orientation: gtk::Orientation,
+}
+
+#[derive(Debug)]
+pub enum BrightnessTileMsg {
+    /// The tile was scrolled by this many steps; negative is up.
+    Scrolled(f64),
+    /// Scrolling has stopped for a moment, if the scroll event with this
+    /// count was the last one.
+    ScrollSettled(u32),
+    Update,
 }
 
 impl SimpleComponent for BrightnessTile {
-    type Init = ();
-    type Input = ();
+    type Init = BrightnessTileInit;
+    type Input = BrightnessTileMsg;
     type Output = ();
     type Root = gtk::Box;
     type Widgets = Self::Root;
 
     fn init(
-        _init: Self::Init,
+        BrightnessTileInit {
+            monitor,
+            commands,
+            orientation,
+        }: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        BRIGHTNESS_STATE.subscribe(sender.input_sender(), |_| ());
+        BRIGHTNESS_STATE.subscribe(sender.input_sender(), |_| BrightnessTileMsg::Update);
 
         // initialize the progress tile component
         let progress_tile = ProgressTile::builder()
             .launch(ProgressTileInit {
                 attention: super::Attention::Dim,
+                orientation,
                 ..Default::default()
             })
             .detach();
 
+        let scroll = gtk::EventControllerScroll::new(gtk::EventControllerScrollFlags::VERTICAL);
+        {
+            let sender = sender.clone();
+            scroll.connect_scroll(move |_, _, dy| {
+                sender.input(BrightnessTileMsg::Scrolled(dy));
+                glib::Propagation::Stop
+            });
+        }
+        progress_tile.widget().add_controller(scroll);
+
         root.append(progress_tile.widget());
 
-        let model = BrightnessTile { progress_tile };
+        let model = BrightnessTile {
+            progress_tile,
+            monitor,
+            commands,
+            osd: None,
+            scroll: None,
+            scroll_count: 0,
+        };
 
         // inits the tile in case it missed the initialization from the
         // BrightnessService
-        sender.input(());
+        sender.input(BrightnessTileMsg::Update);
 
         ComponentParts {
             model,
@@ -48,7 +110,63 @@ impl SimpleComponent for BrightnessTile {
         }
     }
 
-    fn update(&mut self, _msg: Self::Input, _sender: ComponentSender<Self>) {}
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            BrightnessTileMsg::Scrolled(steps) => {
+                let Some(current) = *BRIGHTNESS_STATE.read() else {
+                    return;
+                };
+                let config = settings::get_config().brightness;
+
+                // keep going from where scrolling got to, since the backlight
+                // may not have caught up yet
+                let (start, level) = self.scroll.unwrap_or((current, current));
+                let target = (level - steps * config.scroll_step).clamp(config.min_level, 1.0);
+                self.scroll = Some((start, target));
+
+                // writes are coalesced by the brightness service
+                brightness::set_brightness(target);
+
+                self.osd
+                    .get_or_insert_with(|| LevelOsd::new(&self.monitor))
+                    .show(
+                        percentage_to_icon_from_list(target, BRIGHTNESS_ICON_NAMES),
+                        target * 100.0,
+                        OSD_DURATION,
+                    );
+
+                // earlier timeouts are left to fire, and ignored
+                self.scroll_count += 1;
+                let count = self.scroll_count;
+                let sender = sender.clone();
+                glib::timeout_add_local_once(SCROLL_SETTLE, move || {
+                    sender.input(BrightnessTileMsg::ScrollSettled(count))
+                });
+            }
+            BrightnessTileMsg::ScrollSettled(count) => {
+                if count != self.scroll_count {
+                    return;
+                }
+                let Some((start, level)) = self.scroll.take() else {
+                    return;
+                };
+
+                // record the whole scroll as one change, so undoing it goes
+                // back to where scrolling started
+                let commands = self.commands.clone();
+                sender.oneshot_command(async move {
+                    let command = AppCommand::SetBrightness {
+                        level,
+                        previous: Prior::known(start),
+                    };
+                    if let Err(e) = commands.lock().await.execute(command) {
+                        log::error!("couldn't record brightness change: {e}");
+                    }
+                });
+            }
+            BrightnessTileMsg::Update => {}
+        }
+    }
 
     fn update_view(&self, root: &mut Self::Widgets, _sender: ComponentSender<Self>) {
         if let Some(p) = *BRIGHTNESS_STATE.read() {
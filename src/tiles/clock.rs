@@ -1,74 +1,101 @@
-use std::time::Duration;
-
 use chrono::{DateTime, Local};
 use gtk4::prelude::*;
 use relm4::prelude::*;
-use tokio::time::interval;
 
 use crate::{
+    calendar,
+    clock::{self, NOW},
     icon_names,
+    settings::{self, CONFIG},
+    tiles::connect_click_action,
     widgets::tile::{Tile, TileInit, TileMsg},
 };
 
 #[derive(Debug)]
 pub struct ClockTile {
-    _tile: Controller<Tile>,
+    tile: Controller<Tile>,
+    calendar_popover: gtk::Popover,
+    time_pattern: &'static str,
+}
+
+#[derive(Debug)]
+pub enum ClockTileMsg {
+    Tick(DateTime<Local>),
+    SetTimePattern(&'static str),
 }
 
 impl SimpleComponent for ClockTile {
-    type Init = ();
-    type Input = ();
+    type Init = gtk::Orientation;
+    type Input = ClockTileMsg;
     type Output = ();
     type Root = gtk::Box;
     type Widgets = ();
 
     fn init(
-        _: Self::Init,
+        orientation: Self::Init,
         root: Self::Root,
-        _sender: ComponentSender<Self>,
+        sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         let current_time = Local::now();
+        let time_pattern = clock::bar_time_pattern(&settings::get_config().clock);
 
         // initialize the Tile component
         let tile = Tile::builder()
             .launch(TileInit {
                 icon_name: Some(icon_names::CLOCK.to_string()),
-                primary: Some(format_time(&current_time)),
-                secondary: Some(format_date(&current_time)),
+                primary: Some(clock::format_local(&current_time, time_pattern)),
+                secondary: Some(clock::bar_date(&current_time)),
+                orientation,
                 ..Default::default()
             })
             .detach();
 
         root.append(tile.widget());
 
-        // start time updates
-        let tile_sender_clone = tile.sender().clone();
-        relm4::spawn(async move {
-            let mut interval = interval(Duration::from_secs(1));
-            loop {
-                interval.tick().await;
-                let now = Local::now();
-                tile_sender_clone.emit(TileMsg::SetPrimary(Some(format_time(&now))));
-                tile_sender_clone.emit(TileMsg::SetSecondary(Some(format_date(&now))));
-            }
+        let calendar_popover = calendar::calendar_popover();
+        calendar_popover.set_parent(tile.widget());
+        connect_click_action(
+            tile.widget(),
+            "clock",
+            |widgets| &widgets.clock,
+            Some(calendar_popover.clone()),
+        );
+
+        NOW.subscribe(sender.input_sender(), |now| ClockTileMsg::Tick(*now));
+        CONFIG.subscribe(sender.input_sender(), |config| {
+            ClockTileMsg::SetTimePattern(clock::bar_time_pattern(&config.clock))
         });
 
-        let model = ClockTile { _tile: tile };
+        let model = ClockTile {
+            tile,
+            calendar_popover,
+            time_pattern,
+        };
 
         ComponentParts { model, widgets: () }
     }
 
-    fn update(&mut self, _msg: Self::Input, _sender: ComponentSender<Self>) {}
+    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+        match msg {
+            ClockTileMsg::Tick(now) => {
+                self.tile.emit(TileMsg::SetPrimary(Some(clock::format_local(
+                    &now,
+                    self.time_pattern,
+                ))));
+                self.tile
+                    .emit(TileMsg::SetSecondary(Some(clock::bar_date(&now))));
+            }
+            ClockTileMsg::SetTimePattern(pattern) => {
+                self.time_pattern = pattern;
+            }
+        }
+    }
 
     fn init_root() -> Self::Root {
         gtk::Box::new(gtk::Orientation::Horizontal, 0)
     }
-}
 
-fn format_time(time: &DateTime<Local>) -> String {
-    time.format("%-I:%M %P").to_string()
-}
-
-fn format_date(time: &DateTime<Local>) -> String {
-    time.format("%a, %b %-d").to_string()
+    fn shutdown(&mut self, _widgets: &mut Self::Widgets, _output: relm4::Sender<Self::Output>) {
+        self.calendar_popover.unparent();
+    }
 }
@@ -0,0 +1,122 @@
+use gtk4::prelude::*;
+use relm4::prelude::*;
+
+use crate::{
+    pulseaudio::{self, MIC_STATE, MicData},
+    tiles::set_state_classes,
+    utils::icons::{MIC, MIC_MUTED},
+    widgets::progress_tile::{ProgressTile, ProgressTileInit, ProgressTileMsg, ProgressTileOutput},
+};
+
+/// How much one scroll step changes the input gain, in percent.
+const SCROLL_STEP: f64 = 5.0;
+
+/// Shows the default microphone's gain, turning red while it's muted.
+/// Clicking toggles mute and scrolling adjusts the gain.
+#[derive(Debug)]
+pub struct MicrophoneTile {
+    progress_tile: Controller<ProgressTile>,
+}
+
+#[derive(Debug)]
+pub enum MicrophoneTileMsg {
+    TileClicked,
+    /// The tile was scrolled by this many steps; negative is up.
+    Scrolled(f64),
+    Update,
+}
+
+impl SimpleComponent for MicrophoneTile {
+    type Init = gtk::Orientation;
+    type Input = MicrophoneTileMsg;
+    type Output = ();
+    type Root = gtk::Box;
+    type Widgets = Self::Root;
+
+    fn init(
+        orientation: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        MIC_STATE.subscribe(sender.input_sender(), |_| MicrophoneTileMsg::Update);
+
+        let progress_tile = ProgressTile::builder()
+            .launch(ProgressTileInit {
+                icon_name: Some(MIC.to_string()),
+                progress: 0.0,
+                attention: super::Attention::Dim,
+                orientation,
+                ..Default::default()
+            })
+            .forward(sender.input_sender(), |output| match output {
+                ProgressTileOutput::Clicked => MicrophoneTileMsg::TileClicked,
+            });
+
+        let scroll = gtk::EventControllerScroll::new(
+            gtk::EventControllerScrollFlags::VERTICAL | gtk::EventControllerScrollFlags::DISCRETE,
+        );
+        {
+            let sender = sender.clone();
+            scroll.connect_scroll(move |_, _, dy| {
+                sender.input(MicrophoneTileMsg::Scrolled(dy));
+                glib::Propagation::Stop
+            });
+        }
+        progress_tile.widget().add_controller(scroll);
+
+        root.append(progress_tile.widget());
+
+        let model = MicrophoneTile { progress_tile };
+
+        // initialize this tile
+        sender.input(MicrophoneTileMsg::Update);
+
+        ComponentParts {
+            model,
+            widgets: root,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+        match msg {
+            MicrophoneTileMsg::TileClicked => {
+                pulseaudio::set_mic_muted(!MIC_STATE.read().muted);
+            }
+            MicrophoneTileMsg::Scrolled(steps) => {
+                let volume = MIC_STATE.read().volume;
+                pulseaudio::set_mic_volume((volume - steps * SCROLL_STEP).clamp(0.0, 100.0));
+            }
+            MicrophoneTileMsg::Update => {}
+        }
+    }
+
+    fn update_view(&self, root: &mut Self::Widgets, _sender: ComponentSender<Self>) {
+        let mic_data = MIC_STATE.read().clone();
+        self.update_tile_data(&mic_data);
+        root.set_visible(mic_data.default_source_name.is_some());
+    }
+
+    fn init_root() -> Self::Root {
+        gtk::Box::builder().visible(false).build()
+    }
+}
+
+impl MicrophoneTile {
+    fn update_tile_data(&self, mic_data: &MicData) {
+        let icon = if mic_data.muted { MIC_MUTED } else { MIC };
+        self.progress_tile
+            .emit(ProgressTileMsg::SetIcon(Some(icon.to_string())));
+
+        self.progress_tile
+            .emit(ProgressTileMsg::SetProgress(if mic_data.muted {
+                0.0
+            } else {
+                (mic_data.volume / 100.0).clamp(0.0, 1.0)
+            }));
+
+        set_state_classes(
+            self.progress_tile.widget(),
+            &[("mic-muted", mic_data.muted)],
+        );
+    }
+}
@@ -1,72 +1,185 @@
+use std::path::PathBuf;
+
 use gtk4::prelude::*;
 use relm4::prelude::*;
 
 use crate::{
     icon_names::{MEDIA_PLAYBACK_PAUSE, MUSIC_NOTE_SINGLE},
-    mpris::{MPRIS_STATE, MprisState},
-    widgets::tile::{Tile, TileMsg},
+    mpris::{self, MPRIS_STATE, MprisState},
+    settings::{self, CONFIG},
+    widgets::marquee::Marquee,
 };
 
 #[derive(Debug)]
 pub struct MprisTile {
     state: Option<MprisState>,
+    /// The current track's album art, once found.
+    art: Option<PathBuf>,
+    marquee_enabled: bool,
+    /// On a vertical bar there's no room for the track, so it's left to the
+    /// tooltip.
+    vertical: bool,
 }
 
 #[derive(Debug)]
 pub struct MprisWidgets {
     root: <MprisTile as Component>::Root,
-    tile: Controller<Tile>,
+    icon: gtk::Image,
+    art: gtk::Image,
+    marquee: Marquee,
+}
+
+#[derive(Debug)]
+pub enum MprisMsg {
+    State(Option<MprisState>),
+    /// Album art for the art URL given was found.
+    ArtFound(String, PathBuf),
+    PlayPause,
+    ConfigChanged,
 }
 
 impl SimpleComponent for MprisTile {
-    type Init = ();
-    type Input = Option<MprisState>;
+    type Init = gtk::Orientation;
+    type Input = MprisMsg;
     type Output = ();
     type Root = gtk::Box;
     type Widgets = MprisWidgets;
 
     fn init(
-        _init: Self::Init,
+        orientation: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        MPRIS_STATE.subscribe(sender.input_sender(), |data| data.clone());
-        sender.input(MPRIS_STATE.read().clone());
+        MPRIS_STATE.subscribe(sender.input_sender(), |data| MprisMsg::State(data.clone()));
+        CONFIG.subscribe(sender.input_sender(), |_| MprisMsg::ConfigChanged);
+        sender.input(MprisMsg::State(MPRIS_STATE.read().clone()));
+
+        let icon = gtk::Image::builder()
+            .css_classes(["icon"])
+            .pixel_size(16)
+            .width_request(16)
+            .build();
+
+        let art = gtk::Image::builder()
+            .css_classes(["album-art"])
+            .pixel_size(20)
+            .visible(false)
+            .build();
 
-        // initialize the tile component
-        let tile = Tile::builder().launch(Default::default()).detach();
+        let marquee = Marquee::new(settings::get_config().tiles.max_text_width);
+        marquee.widget().add_css_class("primary");
 
-        root.append(tile.widget());
+        let container = gtk::Box::new(orientation, 8);
+        container.append(&icon);
+        container.append(&art);
+        container.append(marquee.widget());
 
-        let model = MprisTile { state: None };
+        let button = gtk::Button::builder()
+            .css_classes(["tile"])
+            .child(&container)
+            .build();
+        let input = sender.input_sender().clone();
+        button.connect_clicked(move |_| input.emit(MprisMsg::PlayPause));
+        root.append(&button);
+
+        let model = MprisTile {
+            state: None,
+            art: None,
+            marquee_enabled: marquee_enabled(),
+            vertical: orientation == gtk::Orientation::Vertical,
+        };
 
         ComponentParts {
             model,
-            widgets: MprisWidgets { root, tile },
+            widgets: MprisWidgets {
+                root,
+                icon,
+                art,
+                marquee,
+            },
         }
     }
 
-    fn update(&mut self, state: Self::Input, _sender: ComponentSender<Self>) {
-        self.state = state
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            MprisMsg::State(state) => {
+                let old_art_url = self.state.as_ref().and_then(|s| s.art_url.clone());
+                let new_art_url = state.as_ref().and_then(|s| s.art_url.clone());
+                self.state = state;
+
+                if new_art_url != old_art_url {
+                    self.art = None;
+                    if let Some(art_url) = new_art_url {
+                        let input = sender.input_sender().clone();
+                        sender.oneshot_command(async move {
+                            match mpris::album_art(&art_url).await {
+                                Ok(path) => input.emit(MprisMsg::ArtFound(art_url, path)),
+                                Err(e) => log::warn!("couldn't get album art: {e}"),
+                            }
+                        });
+                    }
+                }
+            }
+            MprisMsg::ArtFound(art_url, path) => {
+                // the track may have changed while the art was downloading
+                let current_art_url = self.state.as_ref().and_then(|s| s.art_url.as_deref());
+                if current_art_url == Some(art_url.as_str()) {
+                    self.art = Some(path);
+                }
+            }
+            MprisMsg::PlayPause => {
+                if let Some(state) = &self.state {
+                    let bus_name = state.bus_name.clone();
+                    sender.oneshot_command(mpris::play_pause(bus_name));
+                }
+            }
+            MprisMsg::ConfigChanged => self.marquee_enabled = marquee_enabled(),
+        }
     }
 
     fn update_view(&self, widgets: &mut Self::Widgets, _sender: ComponentSender<Self>) {
+        widgets
+            .marquee
+            .set_enabled(self.marquee_enabled && !self.vertical);
+        widgets.marquee.widget().set_visible(!self.vertical);
+
         match &self.state {
-            None => widgets.root.set_visible(false),
+            None => {
+                widgets.marquee.set_running(false);
+                widgets.root.set_visible(false);
+            }
             Some(MprisState {
                 title,
                 artist,
                 status,
+                ..
             }) => {
-                let icon = match status {
-                    mpris::PlaybackStatus::Playing => MUSIC_NOTE_SINGLE,
-                    _ => MEDIA_PLAYBACK_PAUSE,
-                }
-                .to_string();
+                let playing = *status == ::mpris::PlaybackStatus::Playing;
 
-                widgets.tile.emit(TileMsg::SetIcon(Some(icon)));
-                widgets.tile.emit(TileMsg::SetPrimary(title.clone()));
-                widgets.tile.emit(TileMsg::SetSecondary(artist.clone()));
+                // album art stands in for the note while playing
+                match (&self.art, playing) {
+                    (Some(art), true) => {
+                        widgets.art.set_from_file(Some(art));
+                        widgets.art.set_visible(true);
+                        widgets.icon.set_visible(false);
+                    }
+                    (_, playing) => {
+                        let icon = if playing {
+                            MUSIC_NOTE_SINGLE
+                        } else {
+                            MEDIA_PLAYBACK_PAUSE
+                        };
+                        widgets.icon.set_icon_name(Some(icon));
+                        widgets.icon.set_visible(true);
+                        widgets.art.set_visible(false);
+                    }
+                }
+                let text = track_text(title.as_deref(), artist.as_deref());
+                widgets.marquee.set_text(&text);
+                widgets.marquee.set_running(playing && !self.vertical);
+                widgets
+                    .root
+                    .set_tooltip_text(self.vertical.then_some(text.as_str()));
                 widgets.root.set_visible(true);
             }
         }
@@ -76,3 +189,18 @@ impl SimpleComponent for MprisTile {
         gtk::Box::builder().visible(false).build()
     }
 }
+
+/// Whether long titles should scroll, per the current settings.
+fn marquee_enabled() -> bool {
+    let config = CONFIG.read();
+    config.tiles.media_marquee && !config.ui.reduce_motion
+}
+
+/// Joins the title and artist into the single line shown by the tile.
+fn track_text(title: Option<&str>, artist: Option<&str>) -> String {
+    match (title, artist) {
+        (Some(title), Some(artist)) => format!("{title} · {artist}"),
+        (Some(text), None) | (None, Some(text)) => text.to_string(),
+        (None, None) => String::new(),
+    }
+}
@@ -2,10 +2,12 @@ use gtk4::prelude::*;
 use relm4::prelude::*;
 
 use crate::{
-    network::{NETWORK_STATE, NetworkInfo, SpecificNetworkInfo, get_icon, types::State},
-    network_menu::NetworkMenu,
-    tiles::Attention,
-    widgets::tile::{Tile, TileInit, TileMsg, TileOutput},
+    network::{
+        NETWORK_STATE, NetworkInfo, SpecificNetworkInfo, get_icon, get_overlay_icon,
+        types::{ConnectivityState, State},
+    },
+    tiles::{Attention, set_state_classes},
+    widgets::tile::{Tile, TileInit, TileMsg},
 };
 
 #[derive(Debug)]
@@ -18,21 +20,27 @@ pub enum NetworkTileMsg {
     Update(NetworkInfo),
 }
 
+#[derive(Debug)]
+pub enum NetworkTileOutput {
+    /// Asks the bar to open or close the network menu, pointing at the given
+    /// tile.
+    ToggleMenu(gtk::Widget),
+}
+
 #[derive(Debug)]
 pub struct NetworkTileWidgets {
     tile: Controller<Tile>,
-    _popover: gtk::Popover,
 }
 
 impl SimpleComponent for NetworkTile {
-    type Init = ();
+    type Init = gtk::Orientation;
     type Input = NetworkTileMsg;
-    type Output = TileOutput;
+    type Output = NetworkTileOutput;
     type Root = gtk::Box;
     type Widgets = NetworkTileWidgets;
 
     fn init(
-        _init: Self::Init,
+        orientation: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
@@ -45,42 +53,24 @@ impl SimpleComponent for NetworkTile {
         let tile = Tile::builder()
             .launch(TileInit {
                 icon_name: Some(get_icon(&current_state).to_string()),
+                overlay_icon_name: get_overlay_icon(&current_state).map(str::to_string),
                 secondary: get_secondary_text(&current_state),
                 tooltip: Some(get_tooltip_text(&current_state)),
+                orientation,
                 ..Default::default()
             })
             .detach();
 
-        // initialize the network menu component
-        let network_menu = NetworkMenu::builder().launch(()).detach();
-
-        // create the popover
-        let popover = gtk::Popover::builder()
-            .child(network_menu.widget())
-            .width_request(384)
-            .height_request(256)
-            .autohide(true)
-            .build();
-        popover.set_parent(tile.widget());
-
-        // connect click handler to show popover
-        let popover_clone = popover.clone();
-        tile.widget().connect_clicked(move |_| {
-            if popover_clone.is_visible() {
-                popover_clone.popdown();
-            } else {
-                popover_clone.popup();
-            }
+        // the bar owns the menu; just ask it to toggle
+        tile.widget().connect_clicked(move |button| {
+            let _ = sender.output(NetworkTileOutput::ToggleMenu(button.clone().upcast()));
         });
 
         root.append(tile.widget());
 
         ComponentParts {
             model: NetworkTile { current_state },
-            widgets: NetworkTileWidgets {
-                tile,
-                _popover: popover,
-            },
+            widgets: NetworkTileWidgets { tile },
         }
     }
 
@@ -94,6 +84,9 @@ impl SimpleComponent for NetworkTile {
         let icon = get_icon(&self.current_state);
 
         widgets.tile.emit(TileMsg::SetIcon(Some(icon.to_string())));
+        widgets.tile.emit(TileMsg::SetOverlayIcon(
+            get_overlay_icon(&self.current_state).map(str::to_string),
+        ));
         widgets.tile.emit(TileMsg::SetPrimary(None));
         widgets.tile.emit(TileMsg::SetSecondary(get_secondary_text(
             &self.current_state,
@@ -103,7 +96,9 @@ impl SimpleComponent for NetworkTile {
         ))));
         widgets
             .tile
-            .emit(TileMsg::SetAttention(get_attention(&self.current_state)))
+            .emit(TileMsg::SetAttention(get_attention(&self.current_state)));
+
+        set_state_classes(widgets.tile.widget(), &state_classes(&self.current_state));
     }
 
     fn init_root() -> Self::Root {
@@ -123,12 +118,30 @@ fn get_tooltip_text(info: &NetworkInfo) -> String {
     let state_text = info.connection_state.to_string();
 
     // add specific network info if available
-    match &info.specific_info {
+    let text = match &info.specific_info {
         Some(SpecificNetworkInfo::WiFi { wifi_ssid, .. }) => {
             format!("{}\n{}", state_text, wifi_ssid)
         }
-        Some(SpecificNetworkInfo::Wired) => format!("{}\nWired connection", state_text),
+        Some(SpecificNetworkInfo::Wired { carrier: false, .. }) => {
+            format!("{}\nCable unplugged", state_text)
+        }
+        Some(SpecificNetworkInfo::Wired {
+            wired_speed: Some(speed),
+            ..
+        }) => format!("{}\nWired connection, {} Mb/s", state_text, speed),
+        Some(SpecificNetworkInfo::Wired { .. }) => format!("{}\nWired connection", state_text),
         None => state_text,
+    };
+
+    let text = match &info.vpn_active {
+        Some(vpn) => format!("{text}\nVPN: {vpn}"),
+        None => text,
+    };
+
+    if info.metered {
+        format!("{text}\nMetered connection")
+    } else {
+        text
     }
 }
 
@@ -142,3 +155,97 @@ fn get_attention(info: &NetworkInfo) -> Attention {
         Attention::Normal
     }
 }
+
+fn state_classes(info: &NetworkInfo) -> [(&'static str, bool); 4] {
+    let connected = matches!(
+        info.connection_state,
+        State::ConnectedLocal | State::ConnectedSite | State::ConnectedGlobal
+    );
+
+    [
+        ("net-connecting", info.connection_state == State::Connecting),
+        (
+            "net-limited",
+            connected
+                && (info.connection_state != State::ConnectedGlobal
+                    || matches!(
+                        info.connectivity,
+                        ConnectivityState::Portal | ConnectivityState::Limited
+                    )),
+        ),
+        ("net-vpn", info.vpn_active.is_some()),
+        ("net-metered", connected && info.metered),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_classes() {
+        let mut info = NetworkInfo {
+            connection_state: State::Connecting,
+            ..Default::default()
+        };
+        assert_eq!(
+            state_classes(&info),
+            [
+                ("net-connecting", true),
+                ("net-limited", false),
+                ("net-vpn", false),
+                ("net-metered", false)
+            ]
+        );
+
+        info.connection_state = State::ConnectedGlobal;
+        info.connectivity = ConnectivityState::Full;
+        assert_eq!(
+            state_classes(&info),
+            [
+                ("net-connecting", false),
+                ("net-limited", false),
+                ("net-vpn", false),
+                ("net-metered", false)
+            ]
+        );
+
+        // behind a captive portal
+        info.connectivity = ConnectivityState::Portal;
+        assert_eq!(
+            state_classes(&info),
+            [
+                ("net-connecting", false),
+                ("net-limited", true),
+                ("net-vpn", false),
+                ("net-metered", false)
+            ]
+        );
+
+        // tunnelled through a vpn
+        info.connectivity = ConnectivityState::Full;
+        info.vpn_active = Some("work".to_string());
+        assert_eq!(
+            state_classes(&info),
+            [
+                ("net-connecting", false),
+                ("net-limited", false),
+                ("net-vpn", true),
+                ("net-metered", false)
+            ]
+        );
+
+        // on a phone hotspot
+        info.vpn_active = None;
+        info.metered = true;
+        assert_eq!(
+            state_classes(&info),
+            [
+                ("net-connecting", false),
+                ("net-limited", false),
+                ("net-vpn", false),
+                ("net-metered", true)
+            ]
+        );
+    }
+}
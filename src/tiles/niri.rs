@@ -1,20 +1,38 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, sync::Arc, time::Duration};
 
 use gdk4::Monitor;
 use gtk4::prelude::*;
 use niri_ipc::Workspace;
 use relm4::prelude::*;
 
-use crate::{niri::NIRI_STATE, settings::BarConfig};
+use crate::{
+    compositor::CompositorService,
+    niri::NIRI_STATE,
+    settings::{self, BarConfig, CONFIG, WorkspaceLabel},
+    tiles::set_state_classes,
+    widgets::workspace_osd::WorkspaceOsd,
+};
 
 pub struct NiriInit {
     pub bar_config: BarConfig,
+    /// Which way the bar runs. Workspaces are listed along it.
+    pub orientation: gtk::Orientation,
     pub monitor: Monitor,
+    /// Used to switch workspaces when an indicator is clicked.
+    pub compositor: Option<Arc<dyn CompositorService>>,
 }
 
 #[derive(Debug)]
 pub struct NiriTile {
     pub monitor_connector_name: Option<String>,
+    monitor: Monitor,
+    /// The workspace showing on this tile's monitor, to tell when it changes.
+    active_workspace: Option<u64>,
+    /// Created the first time a workspace switch needs it.
+    osd: Option<WorkspaceOsd>,
+    compositor: Option<Arc<dyn CompositorService>>,
+    /// Off on a vertical bar, which has no room for it.
+    show_window_title: bool,
 
     workspaces: FactoryVecDeque<NiriWorkspaceIndicator>,
 }
@@ -28,6 +46,7 @@ pub struct NiriTileWidgets {
 #[derive(Debug)]
 pub enum NiriMsg {
     Update,
+    FocusWorkspace(u64),
 }
 
 impl SimpleComponent for NiriTile {
@@ -43,17 +62,18 @@ impl SimpleComponent for NiriTile {
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         NIRI_STATE.subscribe(sender.input_sender(), |_| NiriMsg::Update);
+        // re-render workspace labels when the mapping is reloaded
+        CONFIG.subscribe(sender.input_sender(), |_| NiriMsg::Update);
 
         root.set_spacing(init.bar_config.tile_spacing);
 
         // create workspaces container for visual dots/pill
-        let workspaces_container = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        let workspaces_container = gtk::Box::new(init.orientation, 8);
 
         // create window title label
         let window_title_label = gtk::Label::builder()
             .css_classes(["dim"])
             .margin_start(16)
-            .max_width_chars(30)
             .ellipsize(pango::EllipsizeMode::End)
             .build();
 
@@ -62,9 +82,16 @@ impl SimpleComponent for NiriTile {
 
         let model = NiriTile {
             monitor_connector_name: init.monitor.connector().map(String::from),
+            monitor: init.monitor,
+            active_workspace: None,
+            osd: None,
+            compositor: init.compositor,
+            show_window_title: init.orientation == gtk::Orientation::Horizontal,
             workspaces: FactoryVecDeque::builder()
                 .launch(workspaces_container)
-                .detach(),
+                .forward(sender.input_sender(), |NiriWorkspaceOutput::Clicked(id)| {
+                    NiriMsg::FocusWorkspace(id)
+                }),
         };
 
         // init
@@ -79,21 +106,52 @@ impl SimpleComponent for NiriTile {
         }
     }
 
-    fn update(&mut self, _msg: Self::Input, _sender: ComponentSender<Self>) {
+    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+        if let NiriMsg::FocusWorkspace(id) = msg {
+            if let Some(compositor) = self.compositor.clone() {
+                relm4::spawn(async move {
+                    if let Err(e) = compositor.focus_workspace(id).await {
+                        log::error!("couldn't switch workspaces: {e}");
+                    }
+                });
+            }
+            return;
+        }
+
         let Some(state) = NIRI_STATE.read().clone() else {
             log::debug!("no niri state, not updating niri tile");
             return;
         };
 
+        let workspace_config = settings::get_config().workspaces;
+
         // collects only the workspaces for this tile's monitor
         let monitor_workspaces: Vec<&Workspace> = state
             .workspaces
             .iter()
             .filter(|w| w.output == self.monitor_connector_name)
+            .filter(|w| !workspace_config.hide_empty || w.is_active || w.active_window_id.is_some())
             .collect();
 
         let new_ids: HashSet<u64> = monitor_workspaces.iter().map(|w| w.id).collect();
 
+        let label_for = |ws: &Workspace| workspace_config.label_for(ws.name.as_deref(), ws.idx);
+
+        // briefly show which workspace this monitor switched to
+        let active = monitor_workspaces.iter().find(|ws| ws.is_active);
+        if let Some(ws) = active
+            && self.active_workspace.is_some_and(|id| id != ws.id)
+            && let Some(duration) = workspace_config.osd_duration
+        {
+            self.osd
+                .get_or_insert_with(|| WorkspaceOsd::new(&self.monitor))
+                .show(
+                    &workspace_config.label_or_name(ws.name.as_deref(), ws.idx),
+                    Duration::from_millis(duration),
+                );
+        }
+        self.active_workspace = active.map(|ws| ws.id);
+
         // first, remove stale workspaces
         {
             let mut guard = self.workspaces.guard();
@@ -125,7 +183,10 @@ impl SimpleComponent for NiriTile {
                         guard.iter().position(|existing| existing.inner.id == ws.id)
                     {
                         // update existing workspaces and filter them out
-                        guard.send(index, NiriWorkspaceMsg::Update((*ws).to_owned()));
+                        guard.send(
+                            index,
+                            NiriWorkspaceMsg::Update((*ws).to_owned(), label_for(*ws)),
+                        );
                         false
                     } else {
                         // this is a new workspace; add it
@@ -139,11 +200,12 @@ impl SimpleComponent for NiriTile {
         let mut guard = self.workspaces.guard();
         for new_ws in new_workspaces.into_iter() {
             let maybe_index = guard.iter().position(|ws| new_ws.idx == ws.inner.idx - 1);
+            let init = (new_ws.clone(), label_for(new_ws));
             if let Some(index) = maybe_index {
                 // idk why we have to subtract 1 here but bleh
-                guard.insert(index.saturating_sub(1), new_ws.clone());
+                guard.insert(index.saturating_sub(1), init);
             } else {
-                guard.push_back(new_ws.clone());
+                guard.push_back(init);
             }
         }
     }
@@ -156,12 +218,21 @@ impl SimpleComponent for NiriTile {
 
         widgets.root.set_visible(true);
 
-        // update window title
-        if self.monitor_connector_name == Some(state.focused_output) {
-            widgets.window_title_label.set_visible(true);
-            widgets
-                .window_title_label
-                .set_text(&state.focused_window_title);
+        // update window title, falling back to the app for untitled windows
+        if self.show_window_title && self.monitor_connector_name == Some(state.focused_output) {
+            let title = if state.focused_window_title.is_empty() {
+                &state.focused_window_app_id
+            } else {
+                strip_app_prefix(&state.focused_window_title, &state.focused_window_app_id)
+            };
+            let label = &widgets.window_title_label;
+            label.set_visible(!title.is_empty());
+            label.set_text(title);
+            label.set_max_width_chars(settings::get_config().tiles.window_title_width);
+            // the full title, in case it's cut off
+            label.set_tooltip_text(
+                Some(state.focused_window_title.as_str()).filter(|title| !title.is_empty()),
+            );
         } else {
             widgets.window_title_label.set_visible(false);
         };
@@ -176,35 +247,80 @@ impl SimpleComponent for NiriTile {
     }
 }
 
+/// Drops the app's name from the start of a window title that repeats it,
+/// e.g. "kitty: ~/src" from kitty becomes "~/src".
+fn strip_app_prefix<'a>(title: &'a str, app_id: &str) -> &'a str {
+    // app ids can be reverse-DNS, e.g. `org.gnome.Nautilus`
+    let app_name = app_id.rsplit('.').next().unwrap_or_default();
+    if app_name.is_empty() {
+        return title;
+    }
+
+    let Some(prefix) = title.get(..app_name.len()) else {
+        return title;
+    };
+    if !prefix.eq_ignore_ascii_case(app_name) {
+        return title;
+    }
+
+    let rest = &title[app_name.len()..];
+    let stripped = rest.trim_start_matches([' ', ':', '-', '|', '–', '—', '·']);
+    // only strip a whole word followed by a separator, and never everything
+    if stripped.is_empty() || stripped.len() == rest.len() {
+        title
+    } else {
+        stripped
+    }
+}
+
 /// A small widget for a single niri workspace indicator.
 #[derive(Debug)]
 pub struct NiriWorkspaceIndicator {
     inner: niri_ipc::Workspace,
+    /// Shown instead of the plain dot when workspace labels are configured.
+    label: Option<WorkspaceLabel>,
+}
+
+#[derive(Debug)]
+pub struct NiriWorkspaceWidgets {
+    root: gtk::Box,
+    label: gtk::Label,
+    icon: gtk::Image,
 }
 
 #[derive(Debug)]
 pub enum NiriWorkspaceMsg {
-    Update(niri_ipc::Workspace),
+    Update(niri_ipc::Workspace, Option<WorkspaceLabel>),
+}
+
+#[derive(Debug)]
+pub enum NiriWorkspaceOutput {
+    Clicked(u64),
 }
 
 impl FactoryComponent for NiriWorkspaceIndicator {
     type CommandOutput = ();
     type Index = DynamicIndex;
-    type Init = niri_ipc::Workspace;
+    type Init = (niri_ipc::Workspace, Option<WorkspaceLabel>);
     type Input = NiriWorkspaceMsg;
-    type Output = ();
+    type Output = NiriWorkspaceOutput;
     type ParentWidget = gtk::Box;
     type Root = gtk::Box;
-    type Widgets = gtk::Box;
+    type Widgets = NiriWorkspaceWidgets;
 
-    fn init_model(inner: Self::Init, _index: &Self::Index, _sender: FactorySender<Self>) -> Self {
-        Self { inner }
+    fn init_model(
+        (inner, label): Self::Init,
+        _index: &Self::Index,
+        _sender: FactorySender<Self>,
+    ) -> Self {
+        Self { inner, label }
     }
 
     fn init_root(&self) -> Self::Root {
         gtk::Box::builder()
             .css_classes(["workspace"])
             .height_request(8)
+            .halign(gtk4::Align::Center)
             .valign(gtk4::Align::Center)
             .vexpand(false)
             .build()
@@ -215,27 +331,82 @@ impl FactoryComponent for NiriWorkspaceIndicator {
         _index: &Self::Index,
         root: Self::Root,
         _returned_widget: &<Self::ParentWidget as relm4::factory::FactoryView>::ReturnedWidget,
-        _sender: FactorySender<Self>,
+        sender: FactorySender<Self>,
     ) -> Self::Widgets {
-        if self.inner.is_active {
-            root.add_css_class("active");
-        }
-        root
+        let label = gtk::Label::builder().visible(false).build();
+        let icon = gtk::Image::builder().visible(false).build();
+        root.append(&label);
+        root.append(&icon);
+
+        let click = gtk::GestureClick::new();
+        click.connect_released({
+            let sender = sender.clone();
+            let id = self.inner.id;
+            move |_, _, _, _| {
+                let _ = sender.output(NiriWorkspaceOutput::Clicked(id));
+            }
+        });
+        root.add_controller(click);
+
+        let mut widgets = NiriWorkspaceWidgets { root, label, icon };
+        self.update_view(&mut widgets, sender);
+        widgets
     }
 
     fn update(&mut self, msg: Self::Input, _sender: FactorySender<Self>) {
         match msg {
-            NiriWorkspaceMsg::Update(ws) => {
+            NiriWorkspaceMsg::Update(ws, label) => {
                 self.inner = ws;
+                self.label = label;
             }
         }
     }
 
     fn update_view(&self, widgets: &mut Self::Widgets, _sender: FactorySender<Self>) {
-        if self.inner.is_active {
-            widgets.add_css_class("active");
-        } else {
-            widgets.remove_css_class("active");
+        set_state_classes(
+            &widgets.root,
+            &[
+                ("active", self.inner.is_active),
+                ("labeled", self.label.is_some()),
+            ],
+        );
+
+        match &self.label {
+            Some(WorkspaceLabel::Text(text)) => {
+                widgets.label.set_text(text);
+                widgets.label.set_visible(true);
+                widgets.icon.set_visible(false);
+            }
+            Some(WorkspaceLabel::Icon(icon_name)) => {
+                widgets.icon.set_icon_name(Some(icon_name));
+                widgets.icon.set_visible(true);
+                widgets.label.set_visible(false);
+            }
+            None => {
+                widgets.label.set_visible(false);
+                widgets.icon.set_visible(false);
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_app_prefix() {
+        assert_eq!(strip_app_prefix("kitty: ~/src", "kitty"), "~/src");
+        assert_eq!(strip_app_prefix("Slack | general", "Slack"), "general");
+        assert_eq!(
+            strip_app_prefix("Nautilus — Downloads", "org.gnome.Nautilus"),
+            "Downloads"
+        );
+
+        // left alone unless the app's whole name leads and something follows
+        assert_eq!(strip_app_prefix("kitty", "kitty"), "kitty");
+        assert_eq!(strip_app_prefix("kittycat", "kitty"), "kittycat");
+        assert_eq!(strip_app_prefix("~/src - kitty", "kitty"), "~/src - kitty");
+        assert_eq!(strip_app_prefix("Untitled", ""), "Untitled");
+    }
+}
@@ -4,7 +4,7 @@ use relm4::prelude::*;
 use crate::{
     icon_names::BELL,
     notifications::{
-        NOTIFICATIONS_STATE, NotificationEvent, NotificationsState,
+        self, NOTIFICATIONS_STATE, NotificationEvent, NotificationsState,
         fresh::{FreshNotifications, FreshNotificationsMsg, FreshNotificationsOutput},
         subscribe_events,
     },
@@ -18,6 +18,13 @@ pub struct NotificationsTile {
     fresh_panel: Controller<FreshNotifications>,
 }
 
+#[derive(Debug)]
+pub struct NotificationsTileInit {
+    /// The monitor of the bar this tile is on; its popups appear there.
+    pub monitor: gdk4::Monitor,
+    pub orientation: gtk::Orientation,
+}
+
 #[derive(Debug)]
 pub enum NotificationsTileMsg {
     TileClicked,
@@ -38,14 +45,17 @@ pub struct NotificationsTileWidgets {
 }
 
 impl SimpleComponent for NotificationsTile {
-    type Init = ();
+    type Init = NotificationsTileInit;
     type Input = NotificationsTileMsg;
     type Output = NotificationsTileOutput;
     type Root = gtk::Box;
     type Widgets = NotificationsTileWidgets;
 
     fn init(
-        _init: Self::Init,
+        NotificationsTileInit {
+            monitor,
+            orientation,
+        }: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
@@ -79,28 +89,23 @@ impl SimpleComponent for NotificationsTile {
             }
         });
 
-        // create popups for first monitor only
-        let display = gdk4::Display::default().expect("could not get default display");
-        let monitor = display
-            .monitors()
-            .iter()
-            .next()
-            .expect("no monitor available for notifications")
-            .expect("couldn't get available monitor for notifications");
-
-        let fresh_panel =
-            FreshNotifications::builder()
-                .launch(monitor)
-                .forward(sender.input_sender(), |msg| match msg {
-                    FreshNotificationsOutput::NotificationDismissed(id) => {
-                        crate::notifications::dismiss(id);
-                        NotificationsTileMsg::Nothing
-                    }
-                    FreshNotificationsOutput::NotificationActionTriggered(id, action) => {
-                        crate::notifications::invoke_action(id, action);
-                        NotificationsTileMsg::Nothing
-                    }
-                });
+        let fresh_panel = FreshNotifications::builder().launch(monitor).forward(
+            sender.input_sender(),
+            move |msg| match msg {
+                FreshNotificationsOutput::NotificationDismissed(id) => {
+                    notifications::dismiss(id);
+                    NotificationsTileMsg::Nothing
+                }
+                FreshNotificationsOutput::NotificationSnoozed(id) => {
+                    notifications::snooze(id);
+                    NotificationsTileMsg::Nothing
+                }
+                FreshNotificationsOutput::NotificationActionTriggered(id, action) => {
+                    notifications::invoke_action(id, action);
+                    NotificationsTileMsg::Nothing
+                }
+            },
+        );
 
         let notification_count = NOTIFICATIONS_STATE.read().notifications.len();
 
@@ -109,6 +114,7 @@ impl SimpleComponent for NotificationsTile {
             tile: Tile::builder()
                 .launch(TileInit {
                     icon_name: Some(BELL.to_string()),
+                    orientation,
                     ..Default::default()
                 })
                 .forward(sender.input_sender(), |msg| match msg {
@@ -152,10 +158,11 @@ impl SimpleComponent for NotificationsTile {
                         .emit(FreshNotificationsMsg::RemoveNotification(id));
                 }
                 NotificationEvent::AllCleared => {
-                    // fresh panel will drain as each close event arrives via
-                    // the state update; no extra action needed here
+                    log::debug!("all notifications cleared");
+                    self.fresh_panel.emit(FreshNotificationsMsg::RemoveAll);
                 }
-                NotificationEvent::ActionInvoked { .. } => {}
+                NotificationEvent::ActionInvoked { .. }
+                | NotificationEvent::DoNotDisturbChanged(_) => {}
             },
             NotificationsTileMsg::Nothing => {}
         }
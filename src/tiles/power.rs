@@ -0,0 +1,67 @@
+use gtk4::prelude::*;
+use relm4::prelude::*;
+
+use crate::{
+    power_menu::PowerPopover,
+    widgets::tile::{Tile, TileInit},
+};
+
+/// A bar button that opens the power menu.
+#[derive(Debug)]
+pub struct PowerTile {
+    popover: PowerPopover,
+    _tile: Controller<Tile>,
+}
+
+#[derive(Debug)]
+pub enum PowerTileMsg {
+    Toggle,
+    Show,
+}
+
+impl SimpleComponent for PowerTile {
+    type Init = gtk::Orientation;
+    type Input = PowerTileMsg;
+    type Output = ();
+    type Root = gtk::Box;
+    type Widgets = ();
+
+    fn init(
+        orientation: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let tile = Tile::builder()
+            .launch(TileInit {
+                icon_name: Some("system-shutdown-symbolic".to_string()),
+                tooltip: Some("Power".to_string()),
+                orientation,
+                ..Default::default()
+            })
+            .detach();
+        root.append(tile.widget());
+
+        let popover = PowerPopover::new(tile.widget());
+        tile.widget()
+            .connect_clicked(move |_| sender.input(PowerTileMsg::Toggle));
+
+        ComponentParts {
+            model: PowerTile {
+                popover,
+                _tile: tile,
+            },
+            widgets: (),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+        match msg {
+            PowerTileMsg::Toggle => self.popover.toggle(),
+            PowerTileMsg::Show => self.popover.popup(),
+        }
+    }
+
+    fn init_root() -> Self::Root {
+        gtk::Box::default()
+    }
+}
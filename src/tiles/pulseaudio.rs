@@ -1,32 +1,81 @@
+use std::time::{Duration, Instant};
+
 use gtk4::prelude::*;
 use relm4::prelude::*;
 
 use crate::{
-    pulseaudio::{PulseAudioData, VOLUME_STATE},
+    pulseaudio::{self, PulseAudioData, VOLUME_STATE},
+    settings,
+    tiles::set_state_classes,
     utils::icons::{VOLUME_ICONS, VOLUME_MUTED, VOLUME_ZERO, percentage_to_icon_from_list},
-    widgets::progress_tile::{ProgressTile, ProgressTileInit, ProgressTileMsg, ProgressTileOutput},
+    widgets::{
+        level_osd::LevelOsd,
+        progress_tile::{ProgressTile, ProgressTileInit, ProgressTileMsg, ProgressTileOutput},
+    },
 };
 
+/// How long scrolling gathers up before the volume is set, so a flick of the
+/// wheel makes one change instead of dozens.
+const SCROLL_DEBOUNCE: Duration = Duration::from_millis(60);
+
+/// How long to wait for pulseaudio to report a volume scrolling sent before
+/// scrolling goes on from the reported volume again.
+const REPORT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How close, in percent, the reported volume has to be to the one sent to
+/// count as caught up. Pulseaudio's volumes don't map back to percentages
+/// exactly.
+const REPORT_TOLERANCE: f64 = 0.5;
+
+/// How long the volume OSD stays up after the last scroll.
+const OSD_DURATION: Duration = Duration::from_millis(1200);
+
 #[derive(Debug)]
 pub struct PulseAudioTile {
     progress_tile: Controller<ProgressTile>,
+    monitor: gdk4::Monitor,
+    osd: Option<LevelOsd>,
+    /// The volume scrolling is heading to, until it's been sent.
+    scroll_target: Option<f64>,
+    scroll_timeout: Option<glib::SourceId>,
+    /// The last volume scrolling sent and when, until pulseaudio reports it.
+    sent_target: Option<(f64, Instant)>,
+}
+
+pub struct PulseAudioTileInit {
+    pub monitor: gdk4::Monitor,
+    pub orientation: gtk::Orientation,
 }
 
 #[derive(Debug)]
 pub enum PulseAudioTileMsg {
     TileClicked,
+    /// The tile was scrolled by this many steps; negative is up.
+    Scrolled(f64),
+    /// Scrolling has settled; sends the volume it got to.
+    ApplyScroll,
     Update,
 }
 
+#[derive(Debug)]
+pub enum PulseAudioTileOutput {
+    /// Asks the bar to open or close the volume menu, pointing at the given
+    /// tile.
+    ToggleMenu(gtk::Widget),
+}
+
 impl SimpleComponent for PulseAudioTile {
-    type Init = ();
+    type Init = PulseAudioTileInit;
     type Input = PulseAudioTileMsg;
-    type Output = ();
+    type Output = PulseAudioTileOutput;
     type Root = gtk::Box;
     type Widgets = Self::Root;
 
     fn init(
-        _init: Self::Init,
+        PulseAudioTileInit {
+            monitor,
+            orientation,
+        }: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
@@ -37,15 +86,33 @@ impl SimpleComponent for PulseAudioTile {
                 icon_name: None,
                 progress: 0.0,
                 attention: super::Attention::Dim,
+                orientation,
                 ..Default::default()
             })
             .forward(sender.input_sender(), |output| match output {
                 ProgressTileOutput::Clicked => PulseAudioTileMsg::TileClicked,
             });
 
+        let scroll = gtk::EventControllerScroll::new(gtk::EventControllerScrollFlags::VERTICAL);
+        {
+            let sender = sender.clone();
+            scroll.connect_scroll(move |_, _, dy| {
+                sender.input(PulseAudioTileMsg::Scrolled(dy));
+                glib::Propagation::Stop
+            });
+        }
+        progress_tile.widget().add_controller(scroll);
+
         root.append(progress_tile.widget());
 
-        let model = PulseAudioTile { progress_tile };
+        let model = PulseAudioTile {
+            progress_tile,
+            monitor,
+            osd: None,
+            scroll_target: None,
+            scroll_timeout: None,
+            sent_target: None,
+        };
 
         // initialize this tile
         sender.input(PulseAudioTileMsg::Update);
@@ -56,7 +123,59 @@ impl SimpleComponent for PulseAudioTile {
         }
     }
 
-    fn update(&mut self, _msg: Self::Input, _sender: ComponentSender<Self>) {}
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            PulseAudioTileMsg::TileClicked => {
+                // the bar owns the menu; just ask it to toggle
+                let anchor = self.progress_tile.widget().clone().upcast();
+                let _ = sender.output(PulseAudioTileOutput::ToggleMenu(anchor));
+            }
+            PulseAudioTileMsg::Scrolled(steps) => {
+                let audio = settings::get_config().audio;
+                let volume_data = VOLUME_STATE.read().clone();
+
+                // keep going from where scrolling got to, since pulseaudio
+                // may not have caught up yet
+                let sent = self
+                    .sent_target
+                    .filter(|(_, sent_at)| sent_at.elapsed() < REPORT_TIMEOUT)
+                    .map(|(target, _)| target);
+                let current = self.scroll_target.or(sent).unwrap_or(volume_data.volume);
+                let target = scrolled_volume(current, steps, audio.scroll_step, audio.max_volume);
+                self.scroll_target = Some(target);
+
+                let icon = get_icon(&PulseAudioData {
+                    volume: target,
+                    ..volume_data
+                });
+                self.osd
+                    .get_or_insert_with(|| LevelOsd::new(&self.monitor))
+                    .show(icon, target, OSD_DURATION);
+
+                if self.scroll_timeout.is_none() {
+                    let sender = sender.clone();
+                    self.scroll_timeout =
+                        Some(glib::timeout_add_local_once(SCROLL_DEBOUNCE, move || {
+                            sender.input(PulseAudioTileMsg::ApplyScroll)
+                        }));
+                }
+            }
+            PulseAudioTileMsg::ApplyScroll => {
+                self.scroll_timeout = None;
+                if let Some(target) = self.scroll_target.take() {
+                    pulseaudio::set_volume(target);
+                    self.sent_target = Some((target, Instant::now()));
+                }
+            }
+            PulseAudioTileMsg::Update => {
+                if let Some((target, _)) = self.sent_target
+                    && (VOLUME_STATE.read().volume - target).abs() < REPORT_TOLERANCE
+                {
+                    self.sent_target = None;
+                }
+            }
+        }
+    }
 
     fn update_view(&self, root: &mut Self::Widgets, _sender: ComponentSender<Self>) {
         let volume_data = VOLUME_STATE.read().clone();
@@ -69,7 +188,19 @@ impl SimpleComponent for PulseAudioTile {
     }
 }
 
-fn get_icon(volume_data: &PulseAudioData) -> &str {
+/// The volume `steps` scroll steps away from `current`, where negative steps
+/// are up. Scrolling never pushes the volume past `max`, but won't pull it
+/// down to `max` either if it's already above.
+fn scrolled_volume(current: f64, steps: f64, step: f64, max: f64) -> f64 {
+    let target = current - steps * step;
+    if steps < 0.0 {
+        target.min(max.max(current))
+    } else {
+        target.max(0.0)
+    }
+}
+
+pub fn get_icon(volume_data: &PulseAudioData) -> &'static str {
     if volume_data.default_sink_name.is_none() || volume_data.muted {
         VOLUME_MUTED
     } else if volume_data.volume == 0.0 {
@@ -91,5 +222,30 @@ impl PulseAudioTile {
             } else {
                 (volume_data.volume / 100.0).clamp(0.0, 1.0)
             }));
+
+        set_state_classes(
+            self.progress_tile.widget(),
+            &[
+                ("muted", volume_data.muted),
+                ("boosted", !volume_data.muted && volume_data.volume > 100.0),
+            ],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrolled_volume() {
+        assert_eq!(scrolled_volume(50.0, -1.0, 5.0, 100.0), 55.0);
+        assert_eq!(scrolled_volume(50.0, 2.0, 5.0, 100.0), 40.0);
+        assert_eq!(scrolled_volume(98.0, -1.0, 5.0, 100.0), 100.0);
+        assert_eq!(scrolled_volume(3.0, 1.0, 5.0, 100.0), 0.0);
+        assert_eq!(scrolled_volume(100.0, -1.0, 5.0, 150.0), 105.0);
+        // already above the cap, e.g. set from somewhere else
+        assert_eq!(scrolled_volume(120.0, -1.0, 5.0, 100.0), 120.0);
+        assert_eq!(scrolled_volume(120.0, 1.0, 5.0, 100.0), 115.0);
     }
 }
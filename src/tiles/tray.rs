@@ -2,19 +2,41 @@ use gtk4::prelude::*;
 use relm4::prelude::*;
 use system_tray::{data::BaseMap, item::StatusNotifierItem};
 
-use crate::widgets::tray_item::{TrayEvent, TrayItem, TrayItemInput, TrayItemOutput};
+use crate::{
+    settings::CONFIG,
+    tray_backend::TRAY_AVAILABLE,
+    widgets::tray_item::{TrayEvent, TrayItem, TrayItemInput, TrayItemOutput},
+};
 
 #[derive(Debug)]
 pub struct TrayWidget {
     items: FactoryVecDeque<TrayItem>,
-    visible: bool,
+    /// Whether a tray client is connected. The tray is also hidden while none
+    /// of its items are shown.
+    available: bool,
     expanded: bool,
+    /// Items are stacked, and reveal upwards, on a vertical bar.
+    vertical: bool,
+}
+
+pub struct TrayInit {
+    pub items: BaseMap,
+    /// Which way the bar runs.
+    pub orientation: gtk::Orientation,
 }
 
 #[derive(Debug)]
 pub enum TrayMsg {
     ToggleExpanded,
     TrayEvent(TrayEvent),
+    /// Replaces every item.
+    Reset(BaseMap),
+    /// Pops up the menu of the item at this address.
+    ShowMenu(String),
+    /// Whether a tray client is connected.
+    SetAvailable(bool),
+    /// The settings changed, which may show or hide passive items.
+    SettingsChanged,
 }
 
 impl TrayWidget {
@@ -32,23 +54,27 @@ impl TrayWidget {
 
 #[relm4::component(pub)]
 impl SimpleComponent for TrayWidget {
-    type Init = BaseMap;
+    type Init = TrayInit;
     type Input = TrayMsg;
     type Output = TrayItemOutput;
 
     view! {
         #[root]
         gtk::Box {
-            set_orientation: gtk::Orientation::Horizontal,
+            set_orientation: if model.vertical { gtk::Orientation::Vertical } else { gtk::Orientation::Horizontal },
             set_spacing: 0,
             #[watch]
-            set_visible: model.visible,
+            set_visible: model.available && model.items.iter().any(TrayItem::is_shown),
 
             #[name(revealer)]
             gtk::Revealer {
                 #[watch]
                 set_reveal_child: model.expanded,
-                set_transition_type: gtk::RevealerTransitionType::SlideLeft,
+                set_transition_type: if model.vertical {
+                    gtk::RevealerTransitionType::SlideUp
+                } else {
+                    gtk::RevealerTransitionType::SlideLeft
+                },
                 set_transition_duration: 200,
             },
 
@@ -66,7 +92,12 @@ impl SimpleComponent for TrayWidget {
 
                     gtk::Label {
                         #[watch]
-                        set_label: if model.expanded { "󰅂" } else { "󰅁" }, // Arrow icons
+                        set_label: match (model.vertical, model.expanded) {
+                            (false, true) => "󰅂",
+                            (false, false) => "󰅁",
+                            (true, true) => "󰅀",
+                            (true, false) => "󰅃",
+                        }, // Arrow icons
                         add_css_class: "tile-icon",
                     },
                 }
@@ -75,16 +106,20 @@ impl SimpleComponent for TrayWidget {
     }
 
     fn init(
-        current_tray_items: Self::Init,
+        TrayInit {
+            items: current_tray_items,
+            orientation,
+        }: Self::Init,
         _root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         let mut model = TrayWidget {
             items: FactoryVecDeque::builder()
-                .launch(gtk::Box::default())
+                .launch(gtk::Box::new(orientation, 0))
                 .forward(sender.output_sender(), |output| output),
-            visible: true,
+            available: *TRAY_AVAILABLE.read(),
             expanded: false,
+            vertical: orientation == gtk::Orientation::Vertical,
         };
 
         for (address, (item, menu)) in current_tray_items.iter() {
@@ -94,6 +129,11 @@ impl SimpleComponent for TrayWidget {
                 .push_back((address.clone(), item.clone(), menu.clone()));
         }
 
+        TRAY_AVAILABLE.subscribe(sender.input_sender(), |available| {
+            TrayMsg::SetAvailable(*available)
+        });
+        CONFIG.subscribe(sender.input_sender(), |_| TrayMsg::SettingsChanged);
+
         let widgets = view_output!();
         widgets.revealer.set_child(Some(model.items.widget()));
 
@@ -103,6 +143,34 @@ impl SimpleComponent for TrayWidget {
     fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
         match msg {
             TrayMsg::ToggleExpanded => self.expanded = !self.expanded,
+            TrayMsg::Reset(items) => {
+                let mut guard = self.items.guard();
+                guard.clear();
+                for (address, (item, menu)) in items {
+                    guard.push_back((address, item, menu));
+                }
+            }
+            TrayMsg::SetAvailable(available) => self.available = available,
+            TrayMsg::SettingsChanged => {
+                for index in 0..self.items.len() {
+                    self.items.send(index, TrayItemInput::SettingsChanged);
+                }
+            }
+            TrayMsg::ShowMenu(address) => {
+                let index_opt = self
+                    .items
+                    .iter()
+                    .find(|item| *item.address() == address)
+                    .map(|item| item.index().current_index());
+
+                if let Some(index) = index_opt {
+                    // the item has to be on screen for its menu to point at it
+                    self.expanded = true;
+                    self.items.send(index, TrayItemInput::ShowMenu);
+                } else {
+                    log::warn!("couldn't find tray item {address} to show its menu");
+                }
+            }
             TrayMsg::TrayEvent(event) => match event {
                 TrayEvent::Add(address, status_notifier_item) => {
                     let already_exists = self.items.iter().any(|i| *i.address() == address);
@@ -116,15 +184,11 @@ impl SimpleComponent for TrayWidget {
                     }
                 }
                 TrayEvent::Update(address, update_event) => {
-                    let index_opt = self
-                        .items
-                        .iter()
-                        .find(|item| *item.address() == address)
-                        .map(|item| item.index().current_index());
-
-                    if let Some(index_to_update) = index_opt {
-                        self.items
-                            .send(index_to_update, TrayItemInput::DataUpdate(update_event));
+                    // applied here rather than sent, so whether the tray has
+                    // anything to show is known right away
+                    let mut guard = self.items.guard();
+                    if let Some(item) = guard.iter_mut().find(|item| *item.address() == address) {
+                        item.apply_update(update_event);
                     } else {
                         log::warn!("couldn't find tray item {} to send update", address);
                     }
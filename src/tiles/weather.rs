@@ -1,55 +1,151 @@
+use chrono::{Duration, Local, NaiveDate};
 use gtk4::prelude::*;
 use relm4::prelude::*;
 
 use crate::{
-    weather::WEATHER_STATE,
-    widgets::tile::{Tile, TileMsg},
+    clock,
+    icon_names::RAINDROPS,
+    settings::{self, CONFIG, CadenzaShellConfig, TemperatureShown, WeatherConfig},
+    weather::{
+        RefreshRequest, WEATHER_STATE, format_temp, format_wind_speed, request_refresh,
+        types::{DayForecast, WeatherState},
+    },
+    widgets::tile::{Tile, TileInit, TileMsg},
 };
 
+/// Shows the current weather on the bar. Clicking it opens the forecast,
+/// straight from the last report, and fetches a new one in the background.
+#[derive(Debug, Default)]
+pub struct WeatherTile {
+    /// Whether a refresh asked for by clicking is still underway.
+    refreshing: bool,
+}
+
 #[derive(Debug)]
-pub struct WeatherTile;
+pub enum WeatherTileMsg {
+    Update,
+    Refresh,
+    ConfigChanged,
+}
 
 #[derive(Debug)]
 pub struct WeatherWidgets {
     root: <WeatherTile as Component>::Root,
     tile: Controller<Tile>,
+    spinner: gtk::Spinner,
+    popover: gtk::Popover,
+    forecast_grid: gtk::Grid,
+    updated_label: gtk::Label,
 }
 
 impl SimpleComponent for WeatherTile {
-    type Init = ();
-    type Input = ();
+    type Init = gtk::Orientation;
+    type Input = WeatherTileMsg;
     type Output = ();
     type Root = gtk::Box;
     type Widgets = WeatherWidgets;
 
     fn init(
-        _init: Self::Init,
+        orientation: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         // subscribe to the global weather state
-        WEATHER_STATE.subscribe(sender.input_sender(), |_| ());
+        WEATHER_STATE.subscribe(sender.input_sender(), |_| WeatherTileMsg::Update);
+        CONFIG.subscribe(sender.input_sender(), |_| WeatherTileMsg::ConfigChanged);
 
         // Initialize the Tile component
-        let tile = Tile::builder().launch(Default::default()).detach();
+        let tile = Tile::builder()
+            .launch(TileInit {
+                orientation,
+                ..Default::default()
+            })
+            .detach();
+
+        let forecast_grid = gtk::Grid::builder()
+            .column_spacing(16)
+            .row_spacing(8)
+            .build();
+        let updated_label = gtk::Label::builder()
+            .css_classes(["dim"])
+            .halign(gtk::Align::Start)
+            .build();
+        let forecast_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(12)
+            .css_classes(["weather-forecast"])
+            .build();
+        forecast_box.append(&forecast_grid);
+        forecast_box.append(&updated_label);
+
+        let popover = gtk::Popover::builder().child(&forecast_box).build();
+        popover.set_parent(tile.widget());
+        tile.widget().connect_clicked({
+            let popover = popover.clone();
+            move |_| {
+                if popover.is_visible() {
+                    popover.popdown();
+                } else {
+                    popover.popup();
+                    sender.input(WeatherTileMsg::Refresh);
+                }
+            }
+        });
+
+        let spinner = gtk::Spinner::builder().visible(false).build();
 
         root.append(tile.widget());
+        root.append(&spinner);
 
         ComponentParts {
-            model: Self,
-            widgets: WeatherWidgets { root, tile },
+            model: Self::default(),
+            widgets: WeatherWidgets {
+                root,
+                tile,
+                spinner,
+                popover,
+                forecast_grid,
+                updated_label,
+            },
         }
     }
 
-    fn update(&mut self, _msg: Self::Input, _sender: ComponentSender<Self>) {}
+    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+        match msg {
+            WeatherTileMsg::Update => {
+                self.refreshing = false;
+            }
+            WeatherTileMsg::Refresh if self.refreshing => {}
+            WeatherTileMsg::Refresh => {
+                // the report on hand is recent enough if it's too soon
+                self.refreshing = request_refresh() == RefreshRequest::Started;
+            }
+            // the view reads the new settings itself
+            WeatherTileMsg::ConfigChanged => {}
+        }
+    }
 
     fn update_view(&self, widgets: &mut Self::Widgets, _sender: ComponentSender<Self>) {
-        if let Some(data) = WEATHER_STATE.read().clone() {
+        widgets.spinner.set_visible(self.refreshing);
+        widgets.spinner.set_spinning(self.refreshing);
+
+        let config = settings::get_config();
+        let data = WEATHER_STATE.read().clone();
+        fill_forecast(widgets, data.as_ref(), &config);
+
+        if let Some(data) = data {
+            let config = config.weather;
+
             // Update the tile with new data
-            widgets.tile.emit(TileMsg::SetIcon(Some(data.icon)));
             widgets
                 .tile
-                .emit(TileMsg::SetPrimary(Some(format!("{}°", data.temperature))));
+                .emit(TileMsg::SetTooltip(Some(detailed_weather(&data, &config))));
+            widgets.tile.emit(TileMsg::SetIcon(Some(data.icon.clone())));
+            widgets
+                .tile
+                .emit(TileMsg::SetPrimary(Some(compact_temperature(
+                    &data, &config,
+                ))));
             widgets
                 .tile
                 .emit(TileMsg::SetSecondary(Some(data.condition)));
@@ -62,4 +158,133 @@ impl SimpleComponent for WeatherTile {
     fn init_root() -> Self::Root {
         gtk::Box::builder().visible(false).build()
     }
+
+    fn shutdown(&mut self, widgets: &mut Self::Widgets, _output: relm4::Sender<Self::Output>) {
+        widgets.popover.unparent();
+    }
+}
+
+/// Lists each day's forecast in the popover, or says there isn't one.
+fn fill_forecast(
+    widgets: &WeatherWidgets,
+    data: Option<&WeatherState>,
+    config: &CadenzaShellConfig,
+) {
+    let grid = &widgets.forecast_grid;
+    while let Some(child) = grid.first_child() {
+        grid.remove(&child);
+    }
+
+    let forecast = data
+        .map(|data| data.forecast.as_slice())
+        .unwrap_or_default();
+    if forecast.is_empty() {
+        grid.attach(&gtk::Label::new(Some("Forecast unavailable")), 0, 0, 1, 1);
+    }
+
+    let today = Local::now().date_naive();
+    let unit = config.weather.temperature_unit;
+    for (row, day) in (0..).zip(forecast) {
+        let day_label = gtk::Label::builder()
+            .label(day_name(day.date, today))
+            .halign(gtk::Align::Start)
+            .css_classes(["bold"])
+            .build();
+        let icon = gtk::Image::builder()
+            .icon_name(day.icon.as_str())
+            .tooltip_text(day.condition.as_str())
+            .build();
+        let temperatures = gtk::Label::builder()
+            .label(format!(
+                "{} / {}",
+                format_temp(day.high, unit, 0, false),
+                format_temp(day.low, unit, 0, false)
+            ))
+            .halign(gtk::Align::End)
+            .hexpand(true)
+            .build();
+
+        grid.attach(&day_label, 0, row, 1, 1);
+        grid.attach(&icon, 1, row, 1, 1);
+        grid.attach(&temperatures, 2, row, 1, 1);
+        grid.attach(&precipitation(day), 3, row, 1, 1);
+    }
+
+    match data {
+        Some(data) => {
+            let fetched_at = &data.fetched_at;
+            let time = clock::format_local(fetched_at, clock::bar_time_pattern(&config.clock));
+            let updated = if fetched_at.date_naive() == Local::now().date_naive() {
+                format!("Updated {time}")
+            } else {
+                format!("Updated {} at {time}", clock::bar_date(fetched_at))
+            };
+            widgets.updated_label.set_label(&updated);
+            widgets.updated_label.set_visible(true);
+        }
+        None => widgets.updated_label.set_visible(false),
+    }
+}
+
+fn precipitation(day: &DayForecast) -> gtk::Box {
+    let precipitation = gtk::Box::builder()
+        .spacing(4)
+        .css_classes(["dim"])
+        .tooltip_text("Chance of rain or snow")
+        .build();
+    precipitation.append(&gtk::Image::from_icon_name(RAINDROPS));
+    precipitation.append(&gtk::Label::new(Some(&format!(
+        "{}%",
+        day.precipitation_chance
+    ))));
+    precipitation
+}
+
+/// "Today", "Tomorrow", or the day of the week.
+fn day_name(date: NaiveDate, today: NaiveDate) -> String {
+    if date == today {
+        "Today".to_string()
+    } else if date == today + Duration::days(1) {
+        "Tomorrow".to_string()
+    } else {
+        date.format("%A").to_string()
+    }
+}
+
+/// The temperature as configured for the bar.
+fn compact_temperature(data: &WeatherState, config: &WeatherConfig) -> String {
+    let format = |temp| {
+        format_temp(
+            temp,
+            config.temperature_unit,
+            config.decimals,
+            config.show_unit,
+        )
+    };
+
+    match config.show {
+        TemperatureShown::Actual => format(data.temperature),
+        TemperatureShown::FeelsLike => format(data.feels_like),
+        TemperatureShown::Both => {
+            format!("{} ({})", format(data.temperature), format(data.feels_like))
+        }
+    }
+}
+
+/// Both temperatures at full precision, whatever the bar shows, and the wind
+/// if the report had it.
+fn detailed_weather(data: &WeatherState, config: &WeatherConfig) -> String {
+    let unit = config.temperature_unit;
+    let mut details = format!(
+        "{}, feels like {}",
+        format_temp(data.temperature, unit, 1, true),
+        format_temp(data.feels_like, unit, 1, true),
+    );
+    if let Some(wind_speed) = data.wind_speed {
+        details.push_str(&format!(
+            "\nWind {}",
+            format_wind_speed(wind_speed, config.wind_speed_unit)
+        ));
+    }
+    details
 }
@@ -0,0 +1,804 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_lite::StreamExt;
+use relm4::SharedState;
+use system_tray::{client::ActivateRequest, data::BaseMap};
+use tokio::sync::{
+    OnceCell,
+    broadcast::{self, error::RecvError},
+};
+
+use crate::widgets::tray_item::{TrayClient, TrayEvent};
+
+/// The parts of a StatusNotifierItem host that the shell uses, so the tray can
+/// be driven without a live D-Bus session in tests.
+pub(crate) trait TrayBackend: Send + Sync {
+    /// The items currently in the tray, keyed by bus address.
+    fn items(&self) -> Arc<Mutex<BaseMap>>;
+
+    /// Subscribes to item additions, updates and removals.
+    fn subscribe(&self) -> broadcast::Receiver<TrayEvent>;
+
+    /// Forwards a click or menu activation to the item's owner.
+    fn activate(
+        &self,
+        request: ActivateRequest,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + '_>>;
+
+    /// Forwards a scroll over the item's icon to its owner, in whole steps.
+    fn scroll(
+        &self,
+        address: String,
+        delta: i32,
+        orientation: gtk4::Orientation,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + '_>>;
+
+    /// Resolves once the host can't go on, e.g. because the
+    /// StatusNotifierWatcher left the bus, so it has to be set up again.
+    fn closed(&self) -> Pin<Box<dyn Future<Output = ()> + Send + '_>>;
+}
+
+impl TrayBackend for TrayClient {
+    fn items(&self) -> Arc<Mutex<BaseMap>> {
+        TrayClient::items(self)
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<TrayEvent> {
+        TrayClient::subscribe(self)
+    }
+
+    fn activate(
+        &self,
+        request: ActivateRequest,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + '_>> {
+        Box::pin(async move { Ok(TrayClient::activate(self, request).await?) })
+    }
+
+    fn scroll(
+        &self,
+        address: String,
+        delta: i32,
+        orientation: gtk4::Orientation,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + '_>> {
+        // the client has no way to send Scroll, so it's called directly
+        Box::pin(async move {
+            let (destination, path) = item_destination(&address);
+            let orientation = match orientation {
+                gtk4::Orientation::Horizontal => "horizontal",
+                _ => "vertical",
+            };
+
+            SESSION
+                .get_or_try_init(zbus::Connection::session)
+                .await?
+                .call_method(
+                    Some(destination),
+                    path,
+                    Some(ITEM_INTERFACE),
+                    "Scroll",
+                    &(delta, orientation),
+                )
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn closed(&self) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        // the client keeps its event channel open after the watcher is gone,
+        // so the watcher's name is watched instead
+        Box::pin(async move {
+            if let Err(e) = watcher_gone().await {
+                // without a way to tell, the client is assumed to be fine
+                log::warn!("couldn't watch the StatusNotifierWatcher: {e}");
+                std::future::pending::<()>().await;
+            }
+        })
+    }
+}
+
+/// Waits for the StatusNotifierWatcher to leave the session bus, or for the
+/// bus connection to end.
+async fn watcher_gone() -> anyhow::Result<()> {
+    let connection = SESSION.get_or_try_init(zbus::Connection::session).await?;
+    let dbus = zbus::fdo::DBusProxy::new(connection).await?;
+    let mut changes = dbus
+        .receive_name_owner_changed_with_args(&[(0, WATCHER_NAME)])
+        .await?;
+
+    // it may have gone before the match rule was added
+    if !dbus.name_has_owner(WATCHER_NAME.try_into()?).await? {
+        return Ok(());
+    }
+
+    while let Some(change) = changes.next().await {
+        if change.args()?.new_owner().is_none() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The session bus connection used for requests the tray client can't make.
+static SESSION: OnceCell<zbus::Connection> = OnceCell::const_new();
+
+const ITEM_INTERFACE: &str = "org.kde.StatusNotifierItem";
+
+const WATCHER_NAME: &str = "org.kde.StatusNotifierWatcher";
+
+/// Where items live when they don't register an object path of their own.
+const DEFAULT_ITEM_PATH: &str = "/StatusNotifierItem";
+
+/// Splits an item's address into its bus name and object path. Addresses may
+/// carry the path after the bus name; without one, the spec's default path is
+/// used.
+fn item_destination(address: &str) -> (&str, &str) {
+    match address.find('/') {
+        Some(slash) => address.split_at(slash),
+        None => (address, DEFAULT_ITEM_PATH),
+    }
+}
+
+/// Whether a tray client is connected, so bars can leave the tray out while
+/// there's no StatusNotifierWatcher to host it.
+pub static TRAY_AVAILABLE: SharedState<bool> = SharedState::new();
+
+/// The longest wait between attempts to connect the tray client.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// What the tray host has to tell the app.
+pub(crate) enum TrayHostEvent {
+    /// A client is connected. Its items replace whatever the bars showed,
+    /// which also happens when events were missed.
+    Ready(Arc<dyn TrayBackend>),
+    Event(TrayEvent),
+    /// The client stopped; another one is on its way.
+    Lost,
+}
+
+impl fmt::Debug for TrayHostEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ready(_) => f.write_str("Ready"),
+            Self::Event(event) => f.debug_tuple("Event").field(event).finish(),
+            Self::Lost => f.write_str("Lost"),
+        }
+    }
+}
+
+/// Keeps a tray client connected, passing its events to `on_event`.
+///
+/// If the client can't be set up, or the watcher it's connected to goes away,
+/// it's set up again after a delay that doubles with each failed attempt.
+pub(crate) async fn run_tray_host(on_event: impl Fn(TrayHostEvent)) {
+    host_tray(
+        || async { Ok(Arc::new(TrayClient::new().await?) as Arc<dyn TrayBackend>) },
+        on_event,
+    )
+    .await
+}
+
+async fn host_tray<F, Fut>(mut connect: F, on_event: impl Fn(TrayHostEvent))
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<Arc<dyn TrayBackend>>>,
+{
+    let mut delay: Option<Duration> = None;
+    loop {
+        match connect().await {
+            Ok(client) => {
+                delay = None;
+                serve_tray(client, &on_event).await;
+            }
+            Err(e) => log::warn!("couldn't set up tray client: {e}"),
+        }
+
+        let wait = next_reconnect_delay(delay);
+        delay = Some(wait);
+        log::debug!("reconnecting tray client in {wait:?}");
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Passes a connected client's events on until it's closed. The client is
+/// dropped afterwards, so a new one can take its place.
+async fn serve_tray(client: Arc<dyn TrayBackend>, on_event: &impl Fn(TrayHostEvent)) {
+    let mut rx = client.subscribe();
+    let mut closed = client.closed();
+
+    *TRAY_AVAILABLE.write() = true;
+    on_event(TrayHostEvent::Ready(Arc::clone(&client)));
+
+    loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Ok(event) => on_event(TrayHostEvent::Event(event)),
+                Err(RecvError::Lagged(missed)) => {
+                    // the item map is still right, so start over from it
+                    log::warn!("missed {missed} tray events, reloading the tray");
+                    on_event(TrayHostEvent::Ready(Arc::clone(&client)));
+                }
+                Err(RecvError::Closed) => {
+                    log::warn!("tray client stopped");
+                    break;
+                }
+            },
+            () = &mut closed => {
+                log::warn!("the tray's StatusNotifierWatcher went away");
+                break;
+            }
+        }
+    }
+
+    *TRAY_AVAILABLE.write() = false;
+    on_event(TrayHostEvent::Lost);
+}
+
+fn next_reconnect_delay(previous: Option<Duration>) -> Duration {
+    previous.map_or(Duration::from_secs(1), |previous| {
+        (previous * 2).min(MAX_RECONNECT_DELAY)
+    })
+}
+
+/// The D-Bus error returned when the item's owner is no longer on the bus,
+/// e.g. because the app crashed without unregistering its item.
+const SERVICE_UNKNOWN: &str = "org.freedesktop.DBus.Error.ServiceUnknown";
+
+/// D-Bus errors returned by items that don't implement the request, like
+/// menu-only items asked to `Activate`.
+const UNSUPPORTED: &[&str] = &[
+    "org.freedesktop.DBus.Error.UnknownMethod",
+    "org.freedesktop.DBus.Error.NotSupported",
+];
+
+/// What came of forwarding an activation to a tray item.
+#[derive(Debug, PartialEq, Eq)]
+enum ActivateOutcome {
+    Activated,
+    /// The item's owner has left the bus; the item should be dropped.
+    ItemGone(String),
+    /// The item doesn't support the request. Items without a default action
+    /// answer left clicks this way, and want their menu shown instead.
+    Unsupported(String),
+    /// The item is still around but didn't accept the request, even after a
+    /// retry.
+    Failed(String),
+}
+
+/// Sends an activate request from a tray item to its owner.
+///
+/// Transient errors are retried once. An item whose owner has vanished from
+/// the bus, or that doesn't support the request, is reported straight away,
+/// since retrying can't help.
+async fn activate_item(tray: &dyn TrayBackend, request: ActivateRequest) -> ActivateOutcome {
+    let address = request_address(&request).to_string();
+
+    let mut retried = false;
+    loop {
+        let Err(e) = tray.activate(request.clone()).await else {
+            return ActivateOutcome::Activated;
+        };
+
+        if is_service_unknown(&e) {
+            log::warn!("tray item {address} is no longer on the bus: {e}");
+            return ActivateOutcome::ItemGone(address);
+        }
+
+        if is_unsupported(&e) {
+            log::debug!("tray item {address} doesn't support the request: {e}");
+            return ActivateOutcome::Unsupported(address);
+        }
+
+        if retried {
+            log::error!("error sending activate request to tray item {address}: {e}");
+            return ActivateOutcome::Failed(address);
+        }
+
+        log::debug!("activating tray item {address} failed, retrying: {e}");
+        retried = true;
+    }
+}
+
+/// What the bars should be told about a change to the tray.
+#[derive(Debug)]
+pub(crate) enum TrayUpdate {
+    /// Start over from these items.
+    Reset(BaseMap),
+    Event(TrayEvent),
+}
+
+/// What the app should do after a tray item was clicked.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ActivateResponse {
+    Activated,
+    /// Show the item's menu on the bar it was clicked on.
+    ShowMenu(String),
+    /// The item's owner is gone, and its icon was dropped from the tray. The
+    /// bars should drop it too. `name` is the item's title, if it had one.
+    Removed {
+        address: String,
+        name: Option<String>,
+    },
+    /// The item didn't respond.
+    Unresponsive,
+}
+
+/// The app's handling of tray host events and item clicks, kept apart from
+/// the bars so it can be driven by a [`mock::MockTray`] in tests.
+#[derive(Debug, Default)]
+pub(crate) struct TrayHandler {
+    /// Tray items that answered a left click by saying they can't be
+    /// activated. They get their menu shown instead, without asking again.
+    menu_only_items: HashSet<String>,
+}
+
+impl TrayHandler {
+    /// Takes in an event from the tray host, keeping `tray` set to the
+    /// connected client, and returns what the bars should be told.
+    pub fn host_event(
+        &mut self,
+        event: TrayHostEvent,
+        tray: &mut Option<Arc<dyn TrayBackend>>,
+    ) -> Option<TrayUpdate> {
+        match event {
+            TrayHostEvent::Ready(client) => {
+                let items = match client.items().lock() {
+                    Ok(items) => items.clone(),
+                    Err(e) => {
+                        log::error!("couldn't lock tray items mutex: {}", e);
+                        return None;
+                    }
+                };
+                *tray = Some(client);
+                Some(TrayUpdate::Reset(items))
+            }
+            TrayHostEvent::Event(event) => {
+                if let TrayEvent::Remove(address) = &event {
+                    self.menu_only_items.remove(address);
+                }
+                Some(TrayUpdate::Event(event))
+            }
+            TrayHostEvent::Lost => {
+                // the items' owners may well be gone too, so start afresh
+                *tray = None;
+                self.menu_only_items.clear();
+                Some(TrayUpdate::Reset(BaseMap::default()))
+            }
+        }
+    }
+
+    /// Forwards a click on a tray item to its owner, unless the item is known
+    /// to only have a menu.
+    pub async fn activate(
+        &mut self,
+        tray: &dyn TrayBackend,
+        request: ActivateRequest,
+    ) -> ActivateResponse {
+        if let ActivateRequest::Default { address, .. } = &request
+            && self.menu_only_items.contains(address)
+        {
+            return ActivateResponse::ShowMenu(address.clone());
+        }
+        let is_default = matches!(request, ActivateRequest::Default { .. });
+
+        match activate_item(tray, request).await {
+            ActivateOutcome::Activated => ActivateResponse::Activated,
+            ActivateOutcome::Unsupported(address) if is_default => {
+                // the item only has a menu, so show that instead
+                self.menu_only_items.insert(address.clone());
+                ActivateResponse::ShowMenu(address)
+            }
+            ActivateOutcome::ItemGone(address) => {
+                // the owner crashed without unregistering its item, so drop
+                // the icon ourselves instead of leaving a dead one
+                let removed = tray
+                    .items()
+                    .lock()
+                    .ok()
+                    .and_then(|mut items| items.remove(&address));
+                ActivateResponse::Removed {
+                    address,
+                    name: removed.and_then(|(item, _)| item.title),
+                }
+            }
+            ActivateOutcome::Unsupported(_) | ActivateOutcome::Failed(_) => {
+                ActivateResponse::Unresponsive
+            }
+        }
+    }
+}
+
+fn request_address(request: &ActivateRequest) -> &str {
+    match request {
+        ActivateRequest::MenuItem { address, .. }
+        | ActivateRequest::Default { address, .. }
+        | ActivateRequest::Secondary { address, .. } => address,
+    }
+}
+
+/// Whether an activation failed because the item's owner has left the bus.
+fn is_service_unknown(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .any(|cause| match cause.downcast_ref::<zbus::Error>() {
+            Some(zbus::Error::MethodError(name, ..)) => name.as_str() == SERVICE_UNKNOWN,
+            Some(zbus::Error::FDO(e)) => matches!(**e, zbus::fdo::Error::ServiceUnknown(_)),
+            // the tray client doesn't always keep the zbus error in the source
+            // chain, so fall back to the error name in the message
+            _ => cause.to_string().contains(SERVICE_UNKNOWN),
+        })
+}
+
+/// Whether an activation failed because the item doesn't implement it.
+fn is_unsupported(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .any(|cause| match cause.downcast_ref::<zbus::Error>() {
+            Some(zbus::Error::MethodError(name, ..)) => UNSUPPORTED.contains(&name.as_str()),
+            Some(zbus::Error::FDO(e)) => matches!(
+                **e,
+                zbus::fdo::Error::UnknownMethod(_) | zbus::fdo::Error::NotSupported(_)
+            ),
+            _ => {
+                let message = cause.to_string();
+                UNSUPPORTED.iter().any(|name| message.contains(name))
+            }
+        })
+}
+
+/// An in-memory tray that replays scripted events and records activations.
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+
+    pub(crate) struct MockTray {
+        items: Arc<Mutex<BaseMap>>,
+        tx: broadcast::Sender<TrayEvent>,
+        pub activated: Mutex<Vec<ActivateRequest>>,
+        /// Errors returned by the next calls to `activate`, in order.
+        pub activate_errors: Mutex<Vec<anyhow::Error>>,
+        pub scrolled: Mutex<Vec<(String, i32, gtk4::Orientation)>>,
+        lost: tokio::sync::Notify,
+    }
+
+    impl MockTray {
+        pub fn new() -> Self {
+            Self {
+                items: Arc::default(),
+                tx: broadcast::channel(16).0,
+                activated: Mutex::default(),
+                activate_errors: Mutex::default(),
+                scrolled: Mutex::default(),
+                lost: tokio::sync::Notify::new(),
+            }
+        }
+
+        /// Makes the tray close, as if its watcher left the bus.
+        pub fn lose(&self) {
+            self.lost.notify_one();
+        }
+
+        /// Applies an event to the item map, like the real client does, and
+        /// broadcasts it to subscribers.
+        pub fn emit(&self, event: TrayEvent) {
+            {
+                let mut items = self.items.lock().unwrap();
+                match &event {
+                    TrayEvent::Add(address, item) => {
+                        items.insert(address.clone(), ((**item).clone(), None));
+                    }
+                    TrayEvent::Remove(address) => {
+                        items.remove(address);
+                    }
+                    TrayEvent::Update(..) => {}
+                }
+            }
+            let _ = self.tx.send(event);
+        }
+    }
+
+    impl TrayBackend for MockTray {
+        fn items(&self) -> Arc<Mutex<BaseMap>> {
+            Arc::clone(&self.items)
+        }
+
+        fn subscribe(&self) -> broadcast::Receiver<TrayEvent> {
+            self.tx.subscribe()
+        }
+
+        fn activate(
+            &self,
+            request: ActivateRequest,
+        ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + '_>> {
+            Box::pin(async move {
+                let mut errors = self.activate_errors.lock().unwrap();
+                if !errors.is_empty() {
+                    return Err(errors.remove(0));
+                }
+                self.activated.lock().unwrap().push(request);
+                Ok(())
+            })
+        }
+
+        fn scroll(
+            &self,
+            address: String,
+            delta: i32,
+            orientation: gtk4::Orientation,
+        ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + '_>> {
+            Box::pin(async move {
+                self.scrolled
+                    .lock()
+                    .unwrap()
+                    .push((address, delta, orientation));
+                Ok(())
+            })
+        }
+
+        fn closed(&self) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+            Box::pin(self.lost.notified())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use system_tray::item::StatusNotifierItem;
+
+    use super::{mock::MockTray, *};
+
+    fn default_request(address: &str) -> ActivateRequest {
+        ActivateRequest::Default {
+            address: address.to_string(),
+            x: 0,
+            y: 0,
+        }
+    }
+
+    #[test]
+    fn test_mock_tracks_added_and_removed_items() {
+        let tray = MockTray::new();
+        let mut rx = tray.subscribe();
+
+        tray.emit(TrayEvent::Add(
+            ":1.42".to_string(),
+            Box::new(StatusNotifierItem::default()),
+        ));
+        assert!(tray.items().lock().unwrap().contains_key(":1.42"));
+        assert!(matches!(rx.try_recv(), Ok(TrayEvent::Add(address, _)) if address == ":1.42"));
+
+        tray.emit(TrayEvent::Remove(":1.42".to_string()));
+        assert!(tray.items().lock().unwrap().is_empty());
+        assert!(matches!(rx.try_recv(), Ok(TrayEvent::Remove(address)) if address == ":1.42"));
+    }
+
+    #[tokio::test]
+    async fn test_activate_item_forwards_request() {
+        let tray = MockTray::new();
+
+        assert_eq!(
+            activate_item(&tray, default_request(":1.42")).await,
+            ActivateOutcome::Activated
+        );
+
+        let activated = tray.activated.lock().unwrap();
+        assert!(matches!(
+            activated.as_slice(),
+            [ActivateRequest::Default { address, .. }] if address == ":1.42"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_activate_item_retries_transient_errors_once() {
+        let tray = MockTray::new();
+        tray.activate_errors
+            .lock()
+            .unwrap()
+            .push(anyhow::anyhow!("timed out"));
+
+        assert_eq!(
+            activate_item(&tray, default_request(":1.42")).await,
+            ActivateOutcome::Activated
+        );
+        assert_eq!(tray.activated.lock().unwrap().len(), 1);
+
+        tray.activate_errors
+            .lock()
+            .unwrap()
+            .extend([anyhow::anyhow!("timed out"), anyhow::anyhow!("timed out")]);
+
+        assert_eq!(
+            activate_item(&tray, default_request(":1.42")).await,
+            ActivateOutcome::Failed(":1.42".to_string())
+        );
+        assert_eq!(tray.activated.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_activate_item_does_not_retry_vanished_items() {
+        let tray = MockTray::new();
+        tray.activate_errors.lock().unwrap().extend([
+            anyhow::anyhow!(
+                "{SERVICE_UNKNOWN}: The name :1.42 was not provided by any .service files"
+            ),
+            anyhow::anyhow!("timed out"),
+        ]);
+
+        assert_eq!(
+            activate_item(&tray, default_request(":1.42")).await,
+            ActivateOutcome::ItemGone(":1.42".to_string())
+        );
+
+        // the second scripted error was never consumed
+        assert_eq!(tray.activate_errors.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_host_reconnects_when_tray_is_lost() {
+        let first = Arc::new(MockTray::new());
+        let second = Arc::new(MockTray::new());
+        let mut trays = vec![Arc::clone(&second), Arc::clone(&first)];
+        let connect = move || {
+            let tray = trays.pop();
+            async move {
+                match tray {
+                    Some(tray) => Ok(tray as Arc<dyn TrayBackend>),
+                    None => Err(anyhow::anyhow!("no watcher")),
+                }
+            }
+        };
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let host = host_tray(connect, move |event| {
+            let _ = tx.send(match event {
+                TrayHostEvent::Ready(_) => "ready",
+                TrayHostEvent::Event(_) => "event",
+                TrayHostEvent::Lost => "lost",
+            });
+        });
+
+        let script = async {
+            assert_eq!(rx.recv().await, Some("ready"));
+            first.lose();
+            assert_eq!(rx.recv().await, Some("lost"));
+
+            // the second tray is connected after a delay
+            assert_eq!(rx.recv().await, Some("ready"));
+            second.emit(TrayEvent::Remove(":1.42".to_string()));
+            assert_eq!(rx.recv().await, Some("event"));
+        };
+
+        tokio::select! {
+            () = host => unreachable!("the host runs forever"),
+            () = script => {}
+        }
+    }
+
+    #[test]
+    fn test_next_reconnect_delay() {
+        let delays: Vec<_> = std::iter::successors(Some(next_reconnect_delay(None)), |&delay| {
+            Some(next_reconnect_delay(Some(delay)))
+        })
+        .take(8)
+        .map(|delay| delay.as_secs())
+        .collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60]);
+    }
+
+    #[test]
+    fn test_item_destination() {
+        assert_eq!(item_destination(":1.42"), (":1.42", "/StatusNotifierItem"));
+        assert_eq!(
+            item_destination(":1.42/org/ayatana/NotificationItem/nm_applet"),
+            (":1.42", "/org/ayatana/NotificationItem/nm_applet")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_activate_item_reports_unsupported_requests() {
+        let tray = MockTray::new();
+        tray.activate_errors.lock().unwrap().extend([
+            anyhow::anyhow!("org.freedesktop.DBus.Error.UnknownMethod: No such method 'Activate'"),
+            anyhow::anyhow!("timed out"),
+        ]);
+
+        assert_eq!(
+            activate_item(&tray, default_request(":1.42")).await,
+            ActivateOutcome::Unsupported(":1.42".to_string())
+        );
+        assert_eq!(tray.activate_errors.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_handler_tracks_added_and_removed_items() {
+        let tray = Arc::new(MockTray::new());
+        tray.emit(TrayEvent::Add(
+            ":1.42".to_string(),
+            Box::new(StatusNotifierItem::default()),
+        ));
+
+        let mut handler = TrayHandler::default();
+        let mut connected = None;
+
+        // bars start from the items already in the tray
+        let update = handler.host_event(TrayHostEvent::Ready(tray.clone()), &mut connected);
+        assert!(matches!(update, Some(TrayUpdate::Reset(items)) if items.contains_key(":1.42")));
+        assert!(connected.is_some());
+
+        let update = handler.host_event(
+            TrayHostEvent::Event(TrayEvent::Remove(":1.42".to_string())),
+            &mut connected,
+        );
+        assert!(matches!(
+            update,
+            Some(TrayUpdate::Event(TrayEvent::Remove(address))) if address == ":1.42"
+        ));
+
+        // losing the tray empties the bars'
+        let update = handler.host_event(TrayHostEvent::Lost, &mut connected);
+        assert!(matches!(update, Some(TrayUpdate::Reset(items)) if items.is_empty()));
+        assert!(connected.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_handler_shows_menus_of_menu_only_items() {
+        let tray = MockTray::new();
+        tray.activate_errors.lock().unwrap().push(anyhow::anyhow!(
+            "org.freedesktop.DBus.Error.UnknownMethod: No such method 'Activate'"
+        ));
+        let mut handler = TrayHandler::default();
+
+        assert_eq!(
+            handler.activate(&tray, default_request(":1.42")).await,
+            ActivateResponse::ShowMenu(":1.42".to_string())
+        );
+
+        // the item isn't asked again
+        assert_eq!(
+            handler.activate(&tray, default_request(":1.42")).await,
+            ActivateResponse::ShowMenu(":1.42".to_string())
+        );
+        assert!(tray.activated.lock().unwrap().is_empty());
+
+        // until it's been removed and added back
+        handler.host_event(
+            TrayHostEvent::Event(TrayEvent::Remove(":1.42".to_string())),
+            &mut None,
+        );
+        assert_eq!(
+            handler.activate(&tray, default_request(":1.42")).await,
+            ActivateResponse::Activated
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handler_removes_vanished_items() {
+        let tray = MockTray::new();
+        tray.emit(TrayEvent::Add(
+            ":1.42".to_string(),
+            Box::new(StatusNotifierItem {
+                title: Some("Discord".to_string()),
+                ..Default::default()
+            }),
+        ));
+        tray.activate_errors.lock().unwrap().push(anyhow::anyhow!(
+            "{SERVICE_UNKNOWN}: The name :1.42 has no owner"
+        ));
+        let mut handler = TrayHandler::default();
+
+        assert_eq!(
+            handler.activate(&tray, default_request(":1.42")).await,
+            ActivateResponse::Removed {
+                address: ":1.42".to_string(),
+                name: Some("Discord".to_string()),
+            }
+        );
+        assert!(tray.items().lock().unwrap().is_empty());
+    }
+}
@@ -1 +1,3 @@
+pub mod coalesce;
 pub mod icons;
+pub mod state;
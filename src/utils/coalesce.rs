@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant};
+
+/// Collapses bursts of updates into at most one per interval, keeping only the
+/// latest. The first update of a burst starts the interval, and whatever was
+/// pushed last is delivered once it ends (on the trailing edge).
+///
+/// Time is passed in rather than read, so callers drive it from their event
+/// loop (see [`sleep_until`]) and tests can simulate bursts.
+#[derive(Debug)]
+pub struct Coalescer<T> {
+    interval: Duration,
+    pending: Option<(T, Instant)>,
+}
+
+impl<T> Coalescer<T> {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            pending: None,
+        }
+    }
+
+    /// Records an update made at `now`, replacing any pending one. Doesn't
+    /// move the deadline if a burst is already underway.
+    pub fn push(&mut self, value: T, now: Instant) {
+        let due = match self.pending.take() {
+            Some((_, due)) => due,
+            None => now + self.interval,
+        };
+        self.pending = Some((value, due));
+    }
+
+    /// When the pending update should be delivered, if there is one.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.as_ref().map(|(_, due)| *due)
+    }
+
+    /// Takes the pending update if it's due by `now`.
+    pub fn poll(&mut self, now: Instant) -> Option<T> {
+        match self.pending.take() {
+            Some((value, due)) if due <= now => Some(value),
+            pending => {
+                self.pending = pending;
+                None
+            }
+        }
+    }
+}
+
+/// Sleeps until the earliest of `deadlines`, or forever if there are none, for
+/// use as a `tokio::select!` branch next to the source of updates.
+pub async fn sleep_until(deadlines: impl IntoIterator<Item = Option<Instant>>) {
+    match deadlines.into_iter().flatten().min() {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_is_delivered_once_on_trailing_edge() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut coalescer = Coalescer::new(Duration::from_millis(100));
+
+        // a burst of updates within one interval
+        for (ms, value) in [(0, 1), (10, 2), (40, 3), (95, 4)] {
+            coalescer.push(value, at(ms));
+            assert_eq!(coalescer.poll(at(ms)), None);
+        }
+        assert_eq!(coalescer.deadline(), Some(at(100)));
+
+        // only the latest value comes out, and only once
+        assert_eq!(coalescer.poll(at(100)), Some(4));
+        assert_eq!(coalescer.poll(at(150)), None);
+        assert_eq!(coalescer.deadline(), None);
+
+        // the next burst gets its own interval
+        coalescer.push(5, at(160));
+        coalescer.push(6, at(230));
+        assert_eq!(coalescer.poll(at(259)), None);
+        assert_eq!(coalescer.poll(at(260)), Some(6));
+    }
+}
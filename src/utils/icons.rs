@@ -9,6 +9,9 @@ pub const BRIGHTNESS_ICON_NAMES: &[&str] = &[DISPLAY_BRIGHTNESS];
 pub const VOLUME_ICONS: &[&str] = &[SPEAKER_MIN, SPEAKER_MID, SPEAKER_MAX];
 pub const VOLUME_MUTED: &str = SPEAKER_CROSS;
 pub const VOLUME_ZERO: &str = SPEAKER_CROSS;
+// from the system icon theme
+pub const MIC: &str = "audio-input-microphone-symbolic";
+pub const MIC_MUTED: &str = "microphone-disabled-symbolic";
 
 pub const BATTERY_ICON_NAMES: &[&str] = &[
     BATTERY_EMPTY,
@@ -46,6 +49,11 @@ pub const NETWORK_WIFI_DISCONNECTED: &str = RADIOWAVES_X;
 pub const NETWORK_WIRED_DISABLED: &str = RADIOWAVES_NO;
 pub const NETWORK_WIRED_CONNECTED: &str = LAN;
 pub const NETWORK_WIRED_UNREACHABLE: &str = LAN_QUESTION;
+// from the system icon theme
+pub const NETWORK_WIRED_UNPLUGGED: &str = "network-wired-disconnected-symbolic";
+// from the system icon theme, layered over the other network icons
+pub const NETWORK_VPN: &str = "network-vpn-symbolic";
+pub const NETWORK_METERED: &str = "network-cellular-connected-symbolic";
 
 /// Get an icon from a list based on a percentage value from 0.0 to 1.0.
 pub fn percentage_to_icon_from_list<'a>(percentage: f64, icons: &'a [&'a str]) -> &'a str {
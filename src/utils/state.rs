@@ -0,0 +1,14 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+
+/// The directory the shell keeps what it learns between runs in, created if
+/// it doesn't exist yet.
+pub fn get_state_directory() -> Result<PathBuf> {
+    let state_dir = dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .context("couldn't find state directory")?;
+    let cadenza_state = state_dir.join("cadenza-shell");
+    fs::create_dir_all(&cadenza_state).context("couldn't create state directory")?;
+    Ok(cadenza_state)
+}
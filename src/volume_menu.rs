@@ -0,0 +1,272 @@
+use gtk4::prelude::*;
+use relm4::{factory::FactoryVecDeque, prelude::*};
+
+use crate::{
+    pulseaudio::{self, AppStream, PulseAudioData, VOLUME_STATE},
+    tiles::pulseaudio::get_icon,
+    utils::icons::{VOLUME_ICONS, VOLUME_MUTED},
+};
+
+/// Shown for apps that don't have an icon.
+const FALLBACK_APP_ICON: &str = "application-x-executable-symbolic";
+
+#[derive(Debug)]
+pub struct VolumeMenu {
+    volume_state: PulseAudioData,
+    streams: FactoryVecDeque<AppStreamRow>,
+}
+
+#[derive(Debug)]
+pub enum VolumeMenuMsg {
+    UpdateState(PulseAudioData),
+}
+
+#[derive(Debug)]
+pub struct VolumeMenuWidgets {
+    icon: gtk::Image,
+    status_label: gtk::Label,
+    empty_label: gtk::Label,
+}
+
+impl SimpleComponent for VolumeMenu {
+    type Init = ();
+    type Input = VolumeMenuMsg;
+    type Output = ();
+    type Root = gtk::Box;
+    type Widgets = VolumeMenuWidgets;
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let streams = FactoryVecDeque::builder()
+            .launch(
+                gtk::Box::builder()
+                    .orientation(gtk::Orientation::Vertical)
+                    .spacing(12)
+                    .build(),
+            )
+            .detach();
+
+        let mut model = VolumeMenu {
+            volume_state: PulseAudioData::default(),
+            streams,
+        };
+        model.sync_streams(VOLUME_STATE.read().clone());
+
+        // create header box
+        let header_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(20)
+            .hexpand(true)
+            .css_classes(["content-title"])
+            .build();
+
+        let icon = gtk::Image::builder()
+            .icon_size(gtk::IconSize::Large)
+            .icon_name(get_icon(&model.volume_state))
+            .build();
+
+        let status_label = gtk::Label::builder()
+            .halign(gtk::Align::End)
+            .hexpand(true)
+            .label(get_status_text(&model.volume_state))
+            .build();
+
+        header_box.append(&icon);
+        header_box.append(&status_label);
+        root.append(&header_box);
+
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .vscrollbar_policy(gtk::PolicyType::Automatic)
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .vexpand(true)
+            .build();
+
+        let content_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(16)
+            .build();
+
+        let empty_label = gtk::Label::builder()
+            .css_classes(["dim"])
+            .label("No apps are playing sound")
+            .visible(model.volume_state.streams.is_empty())
+            .build();
+
+        content_box.append(&empty_label);
+        content_box.append(model.streams.widget());
+        scrolled_window.set_child(Some(&content_box));
+        root.append(&scrolled_window);
+
+        VOLUME_STATE.subscribe(sender.input_sender(), |state| {
+            VolumeMenuMsg::UpdateState(state.clone())
+        });
+
+        let widgets = VolumeMenuWidgets {
+            icon,
+            status_label,
+            empty_label,
+        };
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+        match msg {
+            VolumeMenuMsg::UpdateState(state) => self.sync_streams(state),
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, _sender: ComponentSender<Self>) {
+        widgets
+            .icon
+            .set_icon_name(Some(get_icon(&self.volume_state)));
+        widgets
+            .status_label
+            .set_label(&get_status_text(&self.volume_state));
+        widgets
+            .empty_label
+            .set_visible(self.volume_state.streams.is_empty());
+    }
+
+    fn init_root() -> Self::Root {
+        gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(16)
+            .vexpand(true)
+            .height_request(384)
+            .build()
+    }
+}
+
+impl VolumeMenu {
+    /// Brings the rows in line with `state`, updating rows in place so a
+    /// slider being dragged isn't torn down under the pointer.
+    fn sync_streams(&mut self, state: PulseAudioData) {
+        {
+            let mut guard = self.streams.guard();
+
+            let mut i = 0;
+            while let Some(row) = guard.get(i) {
+                if state.streams.iter().any(|s| s.index == row.stream.index) {
+                    i += 1;
+                } else {
+                    guard.remove(i);
+                }
+            }
+
+            for stream in &state.streams {
+                match guard
+                    .iter()
+                    .position(|row| row.stream.index == stream.index)
+                {
+                    Some(i) => {
+                        if let Some(row) = guard.get_mut(i) {
+                            row.stream = stream.clone();
+                        }
+                    }
+                    None => {
+                        guard.push_back(stream.clone());
+                    }
+                }
+            }
+        }
+
+        self.volume_state = state;
+    }
+}
+
+fn get_status_text(state: &PulseAudioData) -> String {
+    if state.default_sink_name.is_none() {
+        "No output device".to_string()
+    } else if state.muted {
+        "Muted".to_string()
+    } else {
+        format!("{:.0}%", state.volume)
+    }
+}
+
+/// A row with one app's stream and its volume.
+#[derive(Debug)]
+struct AppStreamRow {
+    stream: AppStream,
+}
+
+#[relm4::factory]
+impl FactoryComponent for AppStreamRow {
+    type CommandOutput = ();
+    type Init = AppStream;
+    type Input = ();
+    type Output = ();
+    type ParentWidget = gtk::Box;
+
+    view! {
+        #[root]
+        gtk::Box {
+            add_css_class: "app-stream",
+            set_spacing: 12,
+
+            gtk::Image {
+                set_pixel_size: 24,
+                set_valign: gtk::Align::Center,
+                #[watch]
+                set_icon_name: Some(self.stream.icon_name.as_deref().unwrap_or(FALLBACK_APP_ICON)),
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_hexpand: true,
+
+                gtk::Label {
+                    set_halign: gtk::Align::Start,
+                    set_ellipsize: gtk::pango::EllipsizeMode::End,
+                    #[watch]
+                    set_label: &self.stream.name,
+                },
+
+                gtk::Label {
+                    add_css_class: "dim",
+                    set_halign: gtk::Align::Start,
+                    set_ellipsize: gtk::pango::EllipsizeMode::End,
+                    #[watch]
+                    set_label: self.stream.title.as_deref().unwrap_or_default(),
+                    #[watch]
+                    set_visible: self.stream.title.is_some(),
+                },
+
+                gtk::Scale {
+                    set_range: (0.0, 100.0),
+                    set_increments: (1.0, 5.0),
+                    #[watch]
+                    set_sensitive: !self.stream.muted,
+                    #[watch]
+                    #[block_signal(volume_handler)]
+                    set_value: self.stream.volume,
+                    connect_value_changed[index = self.stream.index] => move |scale| {
+                        pulseaudio::set_stream_volume(index, scale.value());
+                    } @volume_handler,
+                },
+            },
+
+            gtk::ToggleButton {
+                add_css_class: "flat",
+                set_valign: gtk::Align::Center,
+                set_tooltip_text: Some("Mute"),
+                #[watch]
+                set_icon_name: if self.stream.muted { VOLUME_MUTED } else { VOLUME_ICONS[VOLUME_ICONS.len() - 1] },
+                #[watch]
+                #[block_signal(mute_handler)]
+                set_active: self.stream.muted,
+                connect_toggled[index = self.stream.index] => move |button| {
+                    pulseaudio::set_stream_muted(index, button.is_active());
+                } @mute_handler,
+            },
+        }
+    }
+
+    fn init_model(stream: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
+        Self { stream }
+    }
+}
@@ -1,35 +1,171 @@
 pub mod types;
 
-use std::time::Duration;
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
+use anyhow::Context;
 use chrono::{Local, Timelike};
 use relm4::SharedState;
-use tokio::time::sleep;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Notify, time::sleep_until};
 
 use crate::{
     icon_names::{
         DISPLAY_BRIGHTNESS, FEW_CLOUDS, FOG, MOON, MOON_CLOUD, RAIN, RAINDROPS, ROUND_CLOUD, SNOW,
         SNOWFLAKE, STORM,
     },
-    weather::types::{WeatherState, WttrReport},
+    settings::{self, CONFIG, TemperatureUnit, WeatherLocation, WindSpeedUnit},
+    utils::state::get_state_directory,
+    weather::types::{DayForecast, WeatherState, WttrDay, WttrReport},
 };
 
 /// The global weather state that can be subscribed to.
 pub static WEATHER_STATE: SharedState<Option<WeatherState>> = SharedState::new();
 
+/// Refreshes asked for by hand are ignored if the last fetch started less than
+/// this long ago, so clicking repeatedly doesn't spam wttr.in.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Wakes the poller early for a refresh.
+static REFRESH: Notify = Notify::const_new();
+
+/// When the latest fetch started (or was asked for).
+static LAST_FETCH: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// The coordinates found for the configured place name, so later polls ask
+/// for the same spot. Saved to disk, so the name is only looked up once.
+static GEOCODED_PLACE: Mutex<Option<GeocodedPlace>> = Mutex::new(None);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct GeocodedPlace {
+    /// The place name as it was configured.
+    place: String,
+    /// Where wttr.in found it, as `"lat,long"`.
+    coordinates: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshRequest {
+    /// A fetch is on its way; `WEATHER_STATE` updates when it finishes.
+    Started,
+    /// The weather was fetched moments ago, so nothing was done.
+    TooSoon,
+}
+
 pub async fn start_weather_polling() {
+    // watch the location, so a new one is polled right away
+    let (location_tx, location_rx) = relm4::channel();
+    CONFIG.subscribe(&location_tx, |config| config.weather.location.clone());
+    let mut location = settings::get_config().weather.location;
+
+    match read_geocoded_place() {
+        Ok(place) => {
+            if let Ok(mut geocoded) = GEOCODED_PLACE.lock() {
+                *geocoded = place;
+            }
+        }
+        Err(e) => log::warn!("couldn't read the saved weather location: {e}"),
+    }
+
     let mut backoff: Option<u64> = None; // None => 600s normal cadence
     loop {
-        let result = fetch_wttr().await;
-        if result.is_ok() {
-            backoff = None;
-        } else {
-            backoff = Some(next_backoff(backoff));
+        mark_fetched();
+        match fetch_wttr(location.as_ref()).await {
+            Ok(state) => {
+                backoff = None;
+                *WEATHER_STATE.write() = Some(state);
+            }
+            Err(e) => {
+                // the last report stays up, so a brief outage doesn't hide
+                // the widget
+                log::warn!("couldn't fetch the weather: {e}");
+                backoff = Some(next_backoff(backoff));
+            }
         }
-        *WEATHER_STATE.write() = result.ok();
 
         let seconds_to_wait = backoff.unwrap_or(600);
-        sleep(Duration::from_secs(seconds_to_wait)).await;
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(seconds_to_wait);
+        loop {
+            tokio::select! {
+                _ = sleep_until(deadline) => break,
+                _ = REFRESH.notified() => {
+                    log::debug!("refreshing weather early");
+                    break;
+                }
+                Some(new_location) = location_rx.recv() => {
+                    if new_location != location {
+                        log::debug!("weather location changed; refreshing");
+                        location = new_location;
+                        // the last report is for somewhere else now
+                        *WEATHER_STATE.write() = None;
+                        backoff = None;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Asks the poller to fetch the weather now instead of at its next poll,
+/// unless it fetched within the last minute.
+pub fn request_refresh() -> RefreshRequest {
+    let Ok(mut last_fetch) = LAST_FETCH.lock() else {
+        return RefreshRequest::TooSoon;
+    };
+
+    if last_fetch.is_some_and(|at| at.elapsed() < MIN_REFRESH_INTERVAL) {
+        return RefreshRequest::TooSoon;
+    }
+
+    // claim the slot now so that clicks before the fetch starts are ignored
+    *last_fetch = Some(Instant::now());
+    REFRESH.notify_one();
+    RefreshRequest::Started
+}
+
+fn mark_fetched() {
+    if let Ok(mut last_fetch) = LAST_FETCH.lock() {
+        *last_fetch = Some(Instant::now());
+    }
+}
+
+/// Formats a temperature given in degrees Fahrenheit in `unit`, with
+/// `decimals` places (at most 1), optionally followed by the unit.
+pub fn format_temp(temp: f64, unit: TemperatureUnit, decimals: u8, show_unit: bool) -> String {
+    let decimals = decimals.min(1) as usize;
+    let celsius = (temp - 32.0) * 5.0 / 9.0;
+    let (temp, unit) = match (unit, show_unit) {
+        (TemperatureUnit::Fahrenheit, true) => (temp, "°F"),
+        (TemperatureUnit::Fahrenheit, false) => (temp, "°"),
+        (TemperatureUnit::Celsius, true) => (celsius, "°C"),
+        (TemperatureUnit::Celsius, false) => (celsius, "°"),
+        (TemperatureUnit::Kelvin, true) => (celsius + 273.15, " K"),
+        (TemperatureUnit::Kelvin, false) => (celsius + 273.15, ""),
+    };
+
+    // don't show "-0°" for temperatures that round to zero
+    let scale = 10_f64.powi(decimals as i32);
+    let temp = if (temp * scale).round() == 0.0 {
+        0.0
+    } else {
+        temp
+    };
+
+    format!("{temp:.decimals$}{unit}")
+}
+
+/// Formats a wind speed given in kilometers per hour in `unit`.
+pub fn format_wind_speed(kmph: f64, unit: WindSpeedUnit) -> String {
+    match unit {
+        WindSpeedUnit::KilometersPerHour => format!("{kmph:.0} km/h"),
+        WindSpeedUnit::MilesPerHour => format!("{:.0} mph", kmph / 1.609_344),
+        WindSpeedUnit::MetersPerSecond => format!("{:.1} m/s", kmph / 3.6),
+        WindSpeedUnit::Knots => format!("{:.0} kn", kmph / 1.852),
     }
 }
 
@@ -82,11 +218,62 @@ fn map_icon(code: &str, dark: bool) -> &'static str {
     }
 }
 
-async fn fetch_wttr() -> anyhow::Result<WeatherState> {
-    let body = reqwest::get("https://v2.wttr.in/?format=j1")
-        .await?
-        .text()
-        .await?;
+/// How to ask wttr.in for `location`, which is by IP address if it's unset.
+fn wttr_location(location: Option<&WeatherLocation>) -> String {
+    match location {
+        None => String::new(),
+        Some(WeatherLocation::Coordinates {
+            latitude,
+            longitude,
+        }) => format!("{latitude},{longitude}"),
+        Some(WeatherLocation::Place(place)) => {
+            let geocoded = GEOCODED_PLACE
+                .lock()
+                .ok()
+                .and_then(|geocoded| geocoded.clone())
+                .filter(|geocoded| &geocoded.place == place);
+            match geocoded {
+                Some(geocoded) => geocoded.coordinates,
+                None => place.trim().replace(' ', "+"),
+            }
+        }
+    }
+}
+
+/// The report URL for `location`, with the location percent-encoded so names
+/// with `/`, `?` or `#` in them stay in the path.
+fn wttr_url(location: Option<&WeatherLocation>) -> reqwest::Url {
+    let mut url = reqwest::Url::parse("https://v2.wttr.in/?format=j1")
+        .expect("wttr.in base URL should parse");
+    url.path_segments_mut()
+        .expect("wttr.in base URL should have a path")
+        .pop_if_empty()
+        .push(&wttr_location(location));
+    url
+}
+
+fn geocoded_place_path() -> anyhow::Result<PathBuf> {
+    Ok(get_state_directory()?.join("weather_location.json"))
+}
+
+/// The place name looked up last, saved by [`write_geocoded_place`].
+fn read_geocoded_place() -> anyhow::Result<Option<GeocodedPlace>> {
+    let json = match fs::read_to_string(geocoded_place_path()?) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("couldn't read weather location"),
+    };
+    Ok(serde_json::from_str(&json)?)
+}
+
+fn write_geocoded_place(place: &GeocodedPlace) -> anyhow::Result<()> {
+    fs::write(geocoded_place_path()?, serde_json::to_string(place)?)
+        .context("couldn't write weather location")
+}
+
+async fn fetch_wttr(location: Option<&WeatherLocation>) -> anyhow::Result<WeatherState> {
+    let url = wttr_url(location);
+    let body = reqwest::get(url).await?.text().await?;
     let parsed: WttrReport = serde_json::from_str(&body)?;
     let current = parsed
         .current_condition
@@ -100,7 +287,9 @@ async fn fetch_wttr() -> anyhow::Result<WeatherState> {
         .astronomy
         .first()
         .ok_or_else(|| anyhow::anyhow!("missing astronomy[0]"))?;
-    let temp_f = current.temp_f.parse::<i32>().unwrap_or(0);
+    let temp_f = current.temp_f.parse::<f64>().unwrap_or(0.0);
+    let feels_like_f = current.feels_like_f.parse::<f64>().unwrap_or(temp_f);
+    let wind_speed = current.wind_speed_kmph.parse::<f64>().ok();
     let desc = current
         .weather_desc
         .first()
@@ -112,10 +301,72 @@ async fn fetch_wttr() -> anyhow::Result<WeatherState> {
     )
     .to_string();
 
+    // look a place name up just once; wttr.in's match for it can drift
+    if let Some(WeatherLocation::Place(place)) = location
+        && let Some(area) = parsed.nearest_area.first()
+        && let Ok(mut geocoded) = GEOCODED_PLACE.lock()
+        && geocoded.as_ref().is_none_or(|saved| &saved.place != place)
+    {
+        log::info!(
+            "found weather location {place} at {}, {}",
+            area.latitude,
+            area.longitude
+        );
+        let found = GeocodedPlace {
+            place: place.clone(),
+            coordinates: format!("{},{}", area.latitude, area.longitude),
+        };
+        if let Err(e) = write_geocoded_place(&found) {
+            log::warn!("couldn't save the weather location: {e}");
+        }
+        *geocoded = Some(found);
+    }
+
     Ok(WeatherState {
         temperature: temp_f,
+        feels_like: feels_like_f,
+        wind_speed,
         condition: desc,
         icon,
+        forecast: parsed.weather.iter().map_while(day_forecast).collect(),
+        fetched_at: Local::now(),
+    })
+}
+
+/// Reads one day of the forecast, or `None` if it's missing anything, so a
+/// broken report doesn't show up as a day of zeros.
+fn day_forecast(day: &WttrDay) -> Option<DayForecast> {
+    let date = chrono::NaiveDate::parse_from_str(&day.date, "%Y-%m-%d").ok()?;
+    let high = day.max_temp_f.parse().ok()?;
+    let low = day.min_temp_f.parse().ok()?;
+
+    // the conditions around noon stand for the whole day
+    let midday = day
+        .hourly
+        .iter()
+        .find(|hour| hour.time == "1200")
+        .or_else(|| day.hourly.get(day.hourly.len() / 2))?;
+    let condition = midday
+        .weather_desc
+        .first()
+        .map(|desc| desc.value.trim().to_string())
+        .unwrap_or_else(|| "Unknown".into());
+
+    let precipitation_chance = day
+        .hourly
+        .iter()
+        .flat_map(|hour| [&hour.chanceofrain, &hour.chanceofsnow])
+        .filter_map(|chance| chance.parse::<u8>().ok())
+        .max()
+        .unwrap_or(0);
+
+    Some(DayForecast {
+        date,
+        high,
+        low,
+        condition,
+        icon: map_icon(&midday.weather_code, false).to_string(),
+        precipitation_chance,
     })
 }
 
@@ -136,3 +387,118 @@ fn is_dark_now(sunrise: &str, sunset: &str) -> bool {
     let after_sunset = h > eh || (h == eh && m > em);
     before_sunrise || after_sunset
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day_forecast() {
+        let day: WttrDay = serde_json::from_str(
+            r#"{
+                "astronomy": [],
+                "date": "2026-10-16",
+                "maxtempF": "64",
+                "mintempF": "48",
+                "hourly": [
+                    {"time": "900", "weatherCode": "113", "weatherDesc": [{"value": "Sunny"}],
+                     "chanceofrain": "10", "chanceofsnow": "0"},
+                    {"time": "1200", "weatherCode": "296", "weatherDesc": [{"value": "Light rain "}],
+                     "chanceofrain": "70", "chanceofsnow": "0"},
+                    {"time": "1500", "weatherCode": "296", "weatherDesc": [{"value": "Light rain"}],
+                     "chanceofrain": "85", "chanceofsnow": ""}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let forecast = day_forecast(&day).unwrap();
+        assert_eq!(
+            forecast.date,
+            chrono::NaiveDate::from_ymd_opt(2026, 10, 16).unwrap()
+        );
+        assert_eq!((forecast.high, forecast.low), (64.0, 48.0));
+        assert_eq!(forecast.condition, "Light rain");
+        assert_eq!(forecast.precipitation_chance, 85);
+    }
+
+    #[test]
+    fn test_condition_without_feels_like_or_wind() {
+        let condition: types::WttrCondition = serde_json::from_str(
+            r#"{"temp_F": "61", "weatherCode": "113", "weatherDesc": [{"value": "Sunny"}]}"#,
+        )
+        .unwrap();
+        assert!(condition.feels_like_f.is_empty());
+        assert!(condition.wind_speed_kmph.is_empty());
+    }
+
+    #[test]
+    fn test_day_forecast_without_temperatures() {
+        let day: WttrDay =
+            serde_json::from_str(r#"{"astronomy": [], "date": "2026-10-16"}"#).unwrap();
+        assert!(day_forecast(&day).is_none());
+    }
+
+    #[test]
+    fn test_format_temp() {
+        let f = TemperatureUnit::Fahrenheit;
+        assert_eq!(format_temp(72.0, f, 0, false), "72°");
+        assert_eq!(format_temp(72.0, f, 1, true), "72.0°F");
+        assert_eq!(format_temp(71.66, f, 0, true), "72°F");
+        assert_eq!(format_temp(71.66, f, 1, false), "71.7°");
+        // more than one decimal place isn't supported
+        assert_eq!(format_temp(71.66, f, 3, false), "71.7°");
+        assert_eq!(format_temp(-0.4, f, 0, false), "0°");
+        assert_eq!(format_temp(-0.4, f, 1, false), "-0.4°");
+    }
+
+    #[test]
+    fn test_format_temp_units() {
+        assert_eq!(
+            format_temp(212.0, TemperatureUnit::Celsius, 0, true),
+            "100°C"
+        );
+        assert_eq!(format_temp(32.5, TemperatureUnit::Celsius, 0, false), "0°");
+        assert_eq!(format_temp(32.0, TemperatureUnit::Kelvin, 0, true), "273 K");
+        assert_eq!(format_temp(32.0, TemperatureUnit::Kelvin, 0, false), "273");
+    }
+
+    #[test]
+    fn test_format_wind_speed() {
+        assert_eq!(
+            format_wind_speed(16.0, WindSpeedUnit::KilometersPerHour),
+            "16 km/h"
+        );
+        assert_eq!(
+            format_wind_speed(16.0, WindSpeedUnit::MilesPerHour),
+            "10 mph"
+        );
+        assert_eq!(
+            format_wind_speed(18.0, WindSpeedUnit::MetersPerSecond),
+            "5.0 m/s"
+        );
+        assert_eq!(format_wind_speed(18.52, WindSpeedUnit::Knots), "10 kn");
+    }
+
+    #[test]
+    fn test_wttr_location() {
+        assert_eq!(wttr_location(None), "");
+        let coordinates = WeatherLocation::Coordinates {
+            latitude: 52.52,
+            longitude: 13.405,
+        };
+        assert_eq!(wttr_location(Some(&coordinates)), "52.52,13.405");
+        let place = WeatherLocation::Place(" San Francisco ".to_string());
+        assert_eq!(wttr_location(Some(&place)), "San+Francisco");
+    }
+
+    #[test]
+    fn test_wttr_url() {
+        assert_eq!(wttr_url(None).as_str(), "https://v2.wttr.in/?format=j1");
+        let place = WeatherLocation::Place("Ny Ålesund/Svalbard?#".to_string());
+        assert_eq!(
+            wttr_url(Some(&place)).as_str(),
+            "https://v2.wttr.in/Ny+%C3%85lesund%2FSvalbard%3F%23?format=j1"
+        );
+    }
+}
@@ -1,10 +1,35 @@
+use chrono::{DateTime, Local, NaiveDate};
 use serde::Deserialize;
 
 #[derive(Debug, Default, Clone)]
 pub struct WeatherState {
-    pub temperature: i32,
+    /// In degrees Fahrenheit.
+    pub temperature: f64,
+    /// The apparent temperature, in degrees Fahrenheit.
+    pub feels_like: f64,
+    /// In kilometers per hour. `None` if the report left it out.
+    pub wind_speed: Option<f64>,
     pub condition: String,
     pub icon: String,
+    /// Today and the next few days. Empty if the report had no usable
+    /// forecast.
+    pub forecast: Vec<DayForecast>,
+    /// When this report was fetched.
+    pub fetched_at: DateTime<Local>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayForecast {
+    pub date: NaiveDate,
+    /// In degrees Fahrenheit.
+    pub high: f64,
+    /// In degrees Fahrenheit.
+    pub low: f64,
+    /// Around midday.
+    pub condition: String,
+    pub icon: String,
+    /// The highest chance of rain or snow through the day, in percent.
+    pub precipitation_chance: u8,
 }
 
 #[derive(Deserialize, Debug)]
@@ -16,6 +41,10 @@ pub struct WttrDesc {
 pub struct WttrCondition {
     #[serde(rename = "temp_F")]
     pub temp_f: String,
+    #[serde(rename = "FeelsLikeF", default)]
+    pub feels_like_f: String,
+    #[serde(rename = "windspeedKmph", default)]
+    pub wind_speed_kmph: String,
     #[serde(rename = "weatherCode")]
     pub weather_code: String,
     #[serde(rename = "weatherDesc")]
@@ -28,13 +57,46 @@ pub struct WttrAstronomy {
     pub sunset: String,
 }
 
+/// Three hours of a day's forecast.
+#[derive(Deserialize, Debug)]
+pub struct WttrHour {
+    /// e.g. "1200" for the three hours around noon.
+    #[serde(default)]
+    pub time: String,
+    #[serde(rename = "weatherCode", default)]
+    pub weather_code: String,
+    #[serde(rename = "weatherDesc", default)]
+    pub weather_desc: Vec<WttrDesc>,
+    #[serde(default)]
+    pub chanceofrain: String,
+    #[serde(default)]
+    pub chanceofsnow: String,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct WttrDay {
     pub astronomy: Vec<WttrAstronomy>,
+    #[serde(default)]
+    pub date: String,
+    #[serde(rename = "maxtempF", default)]
+    pub max_temp_f: String,
+    #[serde(rename = "mintempF", default)]
+    pub min_temp_f: String,
+    #[serde(default)]
+    pub hourly: Vec<WttrHour>,
+}
+
+/// The place wttr.in found for the location asked for.
+#[derive(Deserialize, Debug)]
+pub struct WttrArea {
+    pub latitude: String,
+    pub longitude: String,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct WttrReport {
     pub current_condition: Vec<WttrCondition>,
     pub weather: Vec<WttrDay>,
+    #[serde(default)]
+    pub nearest_area: Vec<WttrArea>,
 }
@@ -1,4 +1,9 @@
 pub mod bar;
+pub mod identify_overlay;
+pub mod level_osd;
+pub mod marquee;
+pub mod osd;
 pub mod progress_tile;
 pub mod tile;
 pub mod tray_item;
+pub mod workspace_osd;
@@ -1,28 +1,72 @@
-mod center;
-mod left;
-mod right;
+mod group;
+mod menu_host;
 
-use std::sync::{Arc, Mutex};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use gdk4::Monitor;
 use gtk4::prelude::*;
 use gtk4_layer_shell::{Edge, Layer, LayerShell};
 use relm4::prelude::*;
 use system_tray::data::BaseMap;
+use tokio::sync::broadcast::error::RecvError;
 
+pub use self::menu_host::BarMenu;
 use crate::{
-    notifications::panel::ActionPanel,
-    settings,
+    notifications::{
+        self, NOTIFICATIONS_STATE, NotificationEvent,
+        panel::{ActionPanel, ActionPanelInit},
+        types::NotificationUrgency,
+    },
+    services::Services,
+    settings::{self, BarConfig, BarPosition, MonitorBarConfig},
+    shortcuts,
     widgets::{
         bar::{
-            center::CenterGroup,
-            left::{LeftGroup, LeftGroupInit},
-            right::{RightGroup, RightGroupInit, RightGroupMsg, RightGroupOutput},
+            group::{TileGroup, TileGroupInit, TileGroupMsg, TileGroupOutput},
+            menu_host::MenuHost,
         },
         tray_item::{TrayEvent, TrayItemOutput},
     },
 };
 
+/// How long a compact bar stays expanded after a notification arrives.
+const PEEK_DURATION: Duration = Duration::from_secs(4);
+
+/// The ids settings use to refer to tiles on the bar.
+pub const TILE_IDS: &[&str] = &[
+    "workspaces",
+    "clock",
+    "weather",
+    "media",
+    "brightness",
+    "volume",
+    "microphone",
+    "bluetooth",
+    "network",
+    "battery",
+    "tray",
+    "notifications",
+    "power",
+];
+
+/// Wraps tiles that a compact bar hides until it expands. The transition is
+/// skipped when `reduce_motion` is set.
+fn collapsible(bar_config: &BarConfig, transition: gtk::RevealerTransitionType) -> gtk::Revealer {
+    let transition = if settings::get_config().ui.reduce_motion {
+        gtk::RevealerTransitionType::None
+    } else {
+        transition
+    };
+
+    gtk::Revealer::builder()
+        .transition_type(transition)
+        .reveal_child(!bar_config.compact)
+        .build()
+}
+
 #[derive(Debug)]
 pub struct Bar {
     monitor: Monitor,
@@ -31,29 +75,97 @@ pub struct Bar {
     /// compositor from migrating the layer surface to another output.
     window: gtk::Window,
 
-    // save Controllers so they aren't dropped
-    left: Controller<LeftGroup>,
-    center: Controller<CenterGroup>,
-    right: Controller<RightGroup>,
+    /// The left, center and right parts of the bar. Saved so they aren't
+    /// dropped.
+    groups: [Controller<TileGroup>; 3],
+    /// The ids of the tiles this bar shows.
+    shown_tiles: Vec<String>,
+    /// The bar's contents, for menus whose tile isn't shown to point at.
+    content: gtk::Widget,
 
     notification_center: Controller<ActionPanel>,
+
+    /// Shows the network and bluetooth menus.
+    menus: MenuHost,
+
+    bar_config: BarConfig,
+    /// The edge the bar is on, which may differ from `bar_config` on this
+    /// monitor.
+    position: BarPosition,
+    /// Whether all tiles are showing. Always true unless the bar is compact.
+    expanded: bool,
+    hovered: bool,
+    /// Set while a compact bar is expanded for a new notification. Counts up
+    /// so only the latest peek's timeout collapses the bar.
+    peek: Option<u32>,
+    peek_count: u32,
+    /// Holds the bar's contents if it autohides, leaving a thin strip along
+    /// the edge to hover over while they're hidden.
+    autohide_revealer: Option<gtk::Revealer>,
+    /// Set while counting down to hiding an autohiding bar. Counts up like
+    /// `peek`, so a countdown that was called off doesn't hide the bar.
+    hide_timeout: Option<u32>,
+    hide_count: u32,
+    /// Whether there's a critical notification, which keeps an autohiding bar
+    /// up.
+    critical: bool,
 }
 
 #[derive(Debug)]
 pub struct BarInit {
     pub monitor: Monitor,
     pub tray_items: Option<Arc<Mutex<BaseMap>>>,
+    pub services: Services,
+    /// The settings for this monitor's bar in particular.
+    pub monitor_bar: MonitorBarConfig,
 }
 
 #[derive(Debug)]
 pub enum BarMsg {
     TrayEvent(TrayEvent),
+    /// Replaces the tray's items, after the tray client (re)connects or
+    /// goes away.
+    ResetTray(BaseMap),
+    /// Pops up the menu of the tray item at this address.
+    ShowTrayMenu(String),
     ToggleNotificationCenter,
+    ShowPowerMenu,
+    /// Opens or closes a menu, pointing at the given widget.
+    ToggleMenu(BarMenu, gtk::Widget),
+    /// Opens or closes a menu, pointing at the tile it belongs to. The bar
+    /// asks the app to do so like the tile was clicked, so menus on other
+    /// bars close.
+    ToggleTileMenu(BarMenu),
+    /// Like [`BarMsg::ToggleTileMenu`], but leaves the menu open if it already
+    /// is.
+    ShowTileMenu(BarMenu),
+    /// Closes the menu open on this bar, if any.
+    CloseMenus,
+    /// The menu was closed, possibly by clicking elsewhere.
+    MenuClosed,
+    PointerEntered,
+    PointerLeft,
+    /// A notification arrived; a compact bar expands for a moment.
+    Peek,
+    /// The peek with this count is over.
+    PeekEnded(u32),
+    /// Whether there's a critical notification open.
+    SetCritical(bool),
+    /// An autohiding bar has gone long enough without being needed, if the
+    /// countdown with this count is still on.
+    HideTimeout(u32),
 }
 
 #[derive(Debug)]
 pub enum BarOutput {
     ToggleNotificationCenter,
+    /// The network tile was clicked. Carries the tile to point the menu at.
+    ToggleNetworkMenu(gtk::Widget),
+    /// The bluetooth tile was clicked. Carries the tile to point the menu at.
+    #[cfg(feature = "bluetooth")]
+    ToggleBluetoothMenu(gtk::Widget),
+    /// The volume tile was clicked. Carries the tile to point the menu at.
+    ToggleVolumeMenu(gtk::Widget),
     TrayItemOutput(TrayItemOutput),
     /// Emitted when the bar's monitor becomes invalid so the app can remove
     /// and drop the bar. Carries the connector name used as the map key.
@@ -79,53 +191,143 @@ impl SimpleAsyncComponent for Bar {
         BarInit {
             monitor,
             tray_items,
+            services,
+            monitor_bar,
         }: Self::Init,
         window: Self::Root,
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
         let config = settings::get_config();
 
+        for tile in &monitor_bar.hidden_tiles {
+            if !TILE_IDS.contains(&tile.as_str()) {
+                log::warn!("ignoring unknown tile {tile:?} in hidden_tiles");
+            }
+        }
+
+        shortcuts::attach(&window, services.commands.clone());
+
         // create notification center for this bar/monitor
-        let notification_center = ActionPanel::builder().launch(monitor.clone()).detach();
+        let notification_center = ActionPanel::builder()
+            .launch(ActionPanelInit {
+                monitor: monitor.clone(),
+            })
+            .detach();
 
-        let model = Bar {
-            left: LeftGroup::builder()
-                .launch(LeftGroupInit {
-                    bar_config: config.bar,
+        let position = monitor_bar.position.unwrap_or(config.bar.position);
+        let orientation = if position.is_vertical() {
+            gtk::Orientation::Vertical
+        } else {
+            gtk::Orientation::Horizontal
+        };
+
+        let layout = &config.bar.layout;
+        let groups = [&layout.left, &layout.center, &layout.right].map(|tiles| {
+            TileGroup::builder()
+                .launch(TileGroupInit {
+                    tiles: tiles
+                        .iter()
+                        .filter(|tile| monitor_bar.shows(tile))
+                        .cloned()
+                        .collect(),
+                    bar_config: config.bar.clone(),
+                    orientation,
                     monitor: monitor.clone(),
-                })
-                .detach(),
-            center: CenterGroup::builder().launch(config.bar).detach(),
-            right: RightGroup::builder()
-                .launch(RightGroupInit {
-                    bar_config: config.bar,
-                    tray_items,
+                    tray_items: tray_items.clone(),
+                    services: services.clone(),
                 })
                 .forward(sender.output_sender(), |output| match output {
-                    RightGroupOutput::ToggleNotificationCenter => {
+                    TileGroupOutput::ToggleNotificationCenter => {
                         BarOutput::ToggleNotificationCenter
                     }
-                    RightGroupOutput::TrayItemOutput(tray_item_output) => {
+                    TileGroupOutput::ToggleNetworkMenu(anchor) => {
+                        BarOutput::ToggleNetworkMenu(anchor)
+                    }
+                    #[cfg(feature = "bluetooth")]
+                    TileGroupOutput::ToggleBluetoothMenu(anchor) => {
+                        BarOutput::ToggleBluetoothMenu(anchor)
+                    }
+                    TileGroupOutput::ToggleVolumeMenu(anchor) => {
+                        BarOutput::ToggleVolumeMenu(anchor)
+                    }
+                    TileGroupOutput::TrayItemOutput(tray_item_output) => {
                         BarOutput::TrayItemOutput(tray_item_output)
                     }
-                }),
+                })
+        });
+        let shown_tiles = [&layout.left, &layout.center, &layout.right]
+            .into_iter()
+            .flatten()
+            .filter(|tile| monitor_bar.shows(tile))
+            .cloned()
+            .collect();
+
+        let [left, center, right] = &groups;
+        let bar = gtk::CenterBox::builder()
+            .css_classes(["bar"])
+            .orientation(orientation)
+            .shrink_center_last(true)
+            .start_widget(left.widget())
+            .center_widget(center.widget())
+            .end_widget(right.widget())
+            .build();
+        if position.is_vertical() {
+            bar.add_css_class("vertical");
+            bar.set_width_request(config.bar.height);
+            bar.set_vexpand(true);
+        } else {
+            bar.set_height_request(config.bar.height);
+            bar.set_hexpand(true);
+        }
+
+        let autohide = &config.bar.autohide;
+        let autohide_revealer = autohide.enabled.then(|| {
+            let transition = match position {
+                BarPosition::Top => gtk::RevealerTransitionType::SlideDown,
+                BarPosition::Bottom => gtk::RevealerTransitionType::SlideUp,
+                BarPosition::Left => gtk::RevealerTransitionType::SlideRight,
+                BarPosition::Right => gtk::RevealerTransitionType::SlideLeft,
+            };
+            let duration = if config.ui.reduce_motion {
+                0
+            } else {
+                autohide.animation_duration
+            };
+
+            gtk::Revealer::builder()
+                .transition_type(transition)
+                .transition_duration(duration)
+                .reveal_child(false)
+                .child(&bar)
+                .build()
+        });
+
+        let model = Bar {
+            groups,
+            shown_tiles,
+            content: bar.clone().upcast(),
 
             notification_center,
+            menus: MenuHost::new(&window, &bar, {
+                let sender = sender.input_sender().clone();
+                move || sender.emit(BarMsg::MenuClosed)
+            }),
+
+            bar_config: config.bar.clone(),
+            position,
+            expanded: !config.bar.compact,
+            hovered: false,
+            peek: None,
+            peek_count: 0,
+            autohide_revealer,
+            hide_timeout: None,
+            hide_count: 0,
+            critical: false,
 
             window: window.clone(),
             monitor,
         };
 
-        let bar = gtk::CenterBox::builder()
-            .css_classes(["bar"])
-            .height_request(config.bar.height)
-            .hexpand(true)
-            .shrink_center_last(true)
-            .start_widget(model.left.widget())
-            .center_widget(model.center.widget())
-            .end_widget(model.right.widget())
-            .build();
-
         // init layer shell
         if !window.is_layer_window() {
             window.init_layer_shell();
@@ -134,11 +336,80 @@ impl SimpleAsyncComponent for Bar {
         window.set_monitor(Some(&model.monitor));
         window.set_namespace(Some("bar"));
         window.set_layer(Layer::Top);
-        window.set_exclusive_zone(config.bar.height);
-        window.set_anchor(Edge::Top, true);
-        window.set_anchor(Edge::Left, true);
-        window.set_anchor(Edge::Right, true);
-        window.set_child(Some(&bar));
+        let (edge, span) = edges(position);
+        window.set_anchor(edge, true);
+        for edge in span {
+            window.set_anchor(edge, model.expanded);
+        }
+
+        if let Some(revealer) = &model.autohide_revealer {
+            // windows aren't kept out from under a bar that's usually hidden
+            window.set_exclusive_zone(0);
+
+            // the strip that's left when the bar is hidden, to catch the pointer
+            let hotspot = gtk::Box::new(orientation, 0);
+            if position.is_vertical() {
+                hotspot.set_width_request(1);
+            } else {
+                hotspot.set_height_request(1);
+            }
+            hotspot.append(revealer);
+            window.set_child(Some(&hotspot));
+
+            // let the window shrink back down to the strip
+            let window = window.clone();
+            revealer.connect_child_revealed_notify(move |revealer| {
+                if !revealer.is_child_revealed() {
+                    window.set_default_size(1, 1);
+                }
+            });
+
+            NOTIFICATIONS_STATE.subscribe(sender.input_sender(), |state| {
+                BarMsg::SetCritical(
+                    state
+                        .notifications
+                        .values()
+                        .any(|n| n.urgency == NotificationUrgency::Critical),
+                )
+            });
+        } else {
+            // this is the bar's width when it's vertical
+            window.set_exclusive_zone(config.bar.height);
+            window.set_child(Some(&bar));
+        }
+
+        if config.bar.compact || config.bar.autohide.enabled {
+            let hover = gtk::EventControllerMotion::new();
+            hover.connect_enter({
+                let sender = sender.clone();
+                move |_, _, _| sender.input(BarMsg::PointerEntered)
+            });
+            hover.connect_leave({
+                let sender = sender.clone();
+                move |_| sender.input(BarMsg::PointerLeft)
+            });
+            window.add_controller(hover);
+        }
+
+        if config.bar.compact {
+            let mut events = notifications::subscribe_events();
+            let input = sender.input_sender().clone();
+            relm4::spawn_local(async move {
+                loop {
+                    match events.recv().await {
+                        Ok(NotificationEvent::Received(notification)) => {
+                            if notifications::should_pop_up(&notification)
+                                && input.send(BarMsg::Peek).is_err()
+                            {
+                                break;
+                            }
+                        }
+                        Ok(_) | Err(RecvError::Lagged(_)) => {}
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+        }
 
         // listen for the monitor becoming invalid (e.g. display unplugged); GDK
         // emits this signal before the compositor destroys the layer-shell
@@ -166,15 +437,88 @@ impl SimpleAsyncComponent for Bar {
         AsyncComponentParts { model, widgets: () }
     }
 
-    async fn update(&mut self, msg: Self::Input, _sender: AsyncComponentSender<Self>) {
+    async fn update(&mut self, msg: Self::Input, sender: AsyncComponentSender<Self>) {
         match msg {
-            // propagate tray update to the right group
-            BarMsg::TrayEvent(event) => self.right.emit(RightGroupMsg::TrayEvent(event)),
+            // propagate tray update to whichever group has the tray
+            BarMsg::TrayEvent(event) => {
+                for group in &self.groups {
+                    group.emit(TileGroupMsg::TrayEvent(event.clone()));
+                }
+            }
+            BarMsg::ResetTray(items) => {
+                for group in &self.groups {
+                    group.emit(TileGroupMsg::ResetTray(items.clone()));
+                }
+            }
+            BarMsg::ShowTrayMenu(address) => {
+                for group in &self.groups {
+                    group.emit(TileGroupMsg::ShowTrayMenu(address.clone()));
+                }
+            }
             BarMsg::ToggleNotificationCenter => {
                 use crate::notifications::panel::ActionPanelMsg;
                 self.notification_center.emit(ActionPanelMsg::Toggle);
             }
+            BarMsg::ShowPowerMenu => {
+                for group in &self.groups {
+                    group.emit(TileGroupMsg::ShowPowerMenu);
+                }
+            }
+            BarMsg::ToggleMenu(menu, anchor) => self.menus.toggle(menu, &anchor),
+            BarMsg::ToggleTileMenu(menu) => {
+                if self.shown_tiles.iter().any(|tile| tile == menu.tile_id()) {
+                    for group in &self.groups {
+                        group.emit(TileGroupMsg::ToggleMenu(menu));
+                    }
+                } else {
+                    // the layout left the tile out, so point at the bar
+                    let anchor = self.content.clone();
+                    let _ = sender.output(match menu {
+                        BarMenu::Network => BarOutput::ToggleNetworkMenu(anchor),
+                        #[cfg(feature = "bluetooth")]
+                        BarMenu::Bluetooth => BarOutput::ToggleBluetoothMenu(anchor),
+                        BarMenu::Volume => BarOutput::ToggleVolumeMenu(anchor),
+                    });
+                }
+            }
+            BarMsg::ShowTileMenu(menu) => {
+                if !self.menus.is_showing(menu) {
+                    sender.input(BarMsg::ToggleTileMenu(menu));
+                }
+            }
+            BarMsg::CloseMenus => self.menus.close(),
+            // the bar may collapse or hide again now, which is checked below
+            BarMsg::MenuClosed => {}
+            BarMsg::PointerEntered => self.hovered = true,
+            BarMsg::PointerLeft => self.hovered = false,
+            BarMsg::Peek => {
+                self.peek_count += 1;
+                self.peek = Some(self.peek_count);
+
+                let peek = self.peek_count;
+                let sender = sender.clone();
+                glib::timeout_add_local_once(PEEK_DURATION, move || {
+                    sender.input(BarMsg::PeekEnded(peek))
+                });
+            }
+            BarMsg::PeekEnded(peek) => {
+                if self.peek == Some(peek) {
+                    self.peek = None;
+                }
+            }
+            BarMsg::SetCritical(critical) => self.critical = critical,
+            BarMsg::HideTimeout(count) => {
+                if self.hide_timeout == Some(count) {
+                    self.hide_timeout = None;
+                    if let Some(revealer) = &self.autohide_revealer {
+                        revealer.set_reveal_child(false);
+                    }
+                }
+            }
         }
+
+        self.update_expansion();
+        self.update_autohide(&sender);
     }
 
     fn update_view(&self, _widgets: &mut Self::Widgets, _sender: AsyncComponentSender<Self>) {}
@@ -191,3 +535,70 @@ impl SimpleAsyncComponent for Bar {
         self.window.close();
     }
 }
+
+/// The edge a bar at `position` is anchored to, and the two it stretches
+/// between while expanded.
+fn edges(position: BarPosition) -> (Edge, [Edge; 2]) {
+    match position {
+        BarPosition::Top => (Edge::Top, [Edge::Left, Edge::Right]),
+        BarPosition::Bottom => (Edge::Bottom, [Edge::Left, Edge::Right]),
+        BarPosition::Left => (Edge::Left, [Edge::Top, Edge::Bottom]),
+        BarPosition::Right => (Edge::Right, [Edge::Top, Edge::Bottom]),
+    }
+}
+
+impl Bar {
+    /// Shows or hides an autohiding bar. It comes up right away while hovered,
+    /// showing a menu, or while there's a critical notification, and hides
+    /// once none of those have been true for `hide_delay`.
+    fn update_autohide(&mut self, sender: &AsyncComponentSender<Self>) {
+        let Some(revealer) = &self.autohide_revealer else {
+            return;
+        };
+
+        if self.hovered || self.critical || self.menus.is_open() {
+            // the countdown may already have fired, so it's called off rather
+            // than removed
+            self.hide_timeout = None;
+            revealer.set_reveal_child(true);
+        } else if revealer.reveals_child() && self.hide_timeout.is_none() {
+            self.hide_count += 1;
+            self.hide_timeout = Some(self.hide_count);
+
+            let count = self.hide_count;
+            let delay = Duration::from_millis(self.bar_config.autohide.hide_delay);
+            let sender = sender.clone();
+            glib::timeout_add_local_once(delay, move || sender.input(BarMsg::HideTimeout(count)));
+        }
+    }
+
+    /// Expands or collapses a compact bar. It stays expanded while hovered,
+    /// showing a menu, or peeking at a new notification.
+    fn update_expansion(&mut self) {
+        let expanded =
+            !self.bar_config.compact || self.hovered || self.peek.is_some() || self.menus.is_open();
+        if expanded == self.expanded {
+            return;
+        }
+        self.expanded = expanded;
+
+        // the exclusive zone is left alone, so an expanding bar spreads over
+        // windows instead of pushing them around
+        let (_, span) = edges(self.position);
+        for edge in span {
+            self.window.set_anchor(edge, expanded);
+        }
+        if !expanded {
+            // let the window shrink back around the clock and battery
+            if self.position.is_vertical() {
+                self.window.set_default_size(self.bar_config.height, 1);
+            } else {
+                self.window.set_default_size(1, self.bar_config.height);
+            }
+        }
+
+        for group in &self.groups {
+            group.emit(TileGroupMsg::SetExpanded(expanded));
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use std::sync::{Arc, Mutex};
+
+use gtk4::prelude::{BoxExt, Cast, OrientableExt, WidgetExt};
+use relm4::prelude::*;
+use system_tray::{client::Event as TrayEvent, data::BaseMap};
+
+#[cfg(feature = "bluetooth")]
+use crate::tiles::bluetooth::{BluetoothTile, BluetoothTileOutput};
+#[cfg(feature = "niri")]
+use crate::tiles::niri::{NiriInit, NiriTile};
+use crate::{
+    services::Services,
+    settings::BarConfig,
+    tiles::{
+        battery::BatteryTile,
+        brightness::{BrightnessTile, BrightnessTileInit},
+        clock::ClockTile,
+        microphone::MicrophoneTile,
+        mpris::MprisTile,
+        network::{NetworkTile, NetworkTileOutput},
+        notifications::{NotificationsTile, NotificationsTileInit, NotificationsTileOutput},
+        power::{PowerTile, PowerTileMsg},
+        pulseaudio::{PulseAudioTile, PulseAudioTileInit, PulseAudioTileOutput},
+        tray::{TrayInit, TrayMsg, TrayWidget},
+        weather::WeatherTile,
+    },
+    widgets::{
+        bar::{BarMenu, TILE_IDS, collapsible},
+        tray_item::TrayItemOutput,
+    },
+};
+
+/// Tiles a compact bar keeps showing while it's collapsed.
+const PINNED_TILES: &[&str] = &["clock", "battery"];
+
+/// One part of the bar (left, center or right), holding whichever tiles the
+/// layout puts there.
+#[derive(Debug)]
+pub struct TileGroup {
+    tray: Option<Controller<TrayWidget>>,
+    power: Option<Controller<PowerTile>>,
+    /// The tiles that menus point at when opened without clicking them, if
+    /// they're in this group.
+    network_anchor: Option<gtk::Widget>,
+    #[cfg(feature = "bluetooth")]
+    bluetooth_anchor: Option<gtk::Widget>,
+    volume_anchor: Option<gtk::Widget>,
+    /// The runs of tiles between pinned ones, hidden while a compact bar is
+    /// collapsed.
+    extras: Vec<gtk::Revealer>,
+}
+
+#[derive(Debug, Default)]
+pub struct TileGroupWidgets {
+    #[cfg(feature = "niri")]
+    _workspaces: Option<Controller<NiriTile>>,
+    _clock: Option<Controller<ClockTile>>,
+    _weather: Option<Controller<WeatherTile>>,
+    _media: Option<Controller<MprisTile>>,
+    _brightness: Option<Controller<BrightnessTile>>,
+    _volume: Option<Controller<PulseAudioTile>>,
+    _microphone: Option<Controller<MicrophoneTile>>,
+    #[cfg(feature = "bluetooth")]
+    _bluetooth: Option<Controller<BluetoothTile>>,
+    _network: Option<Controller<NetworkTile>>,
+    _battery: Option<Controller<BatteryTile>>,
+    _notifications: Option<Controller<NotificationsTile>>,
+}
+
+pub struct TileGroupInit {
+    /// Ids of the tiles to show, in order.
+    pub tiles: Vec<String>,
+    pub bar_config: BarConfig,
+    /// Which way the bar runs; tiles are laid out along it.
+    pub orientation: gtk::Orientation,
+    pub monitor: gdk4::Monitor,
+    pub tray_items: Option<Arc<Mutex<BaseMap>>>,
+    pub services: Services,
+}
+
+#[derive(Debug)]
+pub enum TileGroupMsg {
+    TrayEvent(TrayEvent),
+    /// Replaces the tray's items. Ignored if the tray isn't in this group.
+    ResetTray(BaseMap),
+    /// Pops up the menu of the tray item at this address. Ignored if the tray
+    /// isn't in this group.
+    ShowTrayMenu(String),
+    ShowPowerMenu,
+    /// Asks for a menu to be toggled, as if its tile was clicked. Ignored if
+    /// the tile isn't in this group.
+    ToggleMenu(BarMenu),
+    SetExpanded(bool),
+}
+
+#[derive(Debug)]
+pub enum TileGroupOutput {
+    ToggleNotificationCenter,
+    ToggleNetworkMenu(gtk::Widget),
+    #[cfg(feature = "bluetooth")]
+    ToggleBluetoothMenu(gtk::Widget),
+    ToggleVolumeMenu(gtk::Widget),
+    TrayItemOutput(TrayItemOutput),
+}
+
+impl SimpleComponent for TileGroup {
+    type Init = TileGroupInit;
+    type Input = TileGroupMsg;
+    type Output = TileGroupOutput;
+    type Root = gtk::Box;
+    type Widgets = TileGroupWidgets;
+
+    fn init_root() -> Self::Root {
+        gtk::Box::new(gtk::Orientation::Horizontal, 0)
+    }
+
+    fn init(
+        TileGroupInit {
+            tiles,
+            bar_config,
+            orientation,
+            monitor,
+            tray_items,
+            services,
+        }: Self::Init,
+        root: Self::Root,
+        sender: relm4::ComponentSender<Self>,
+    ) -> relm4::ComponentParts<Self> {
+        root.set_orientation(orientation);
+        root.set_spacing(bar_config.tile_spacing);
+        if orientation == gtk::Orientation::Vertical {
+            root.set_margin_top(bar_config.edge_padding);
+            root.set_margin_bottom(bar_config.edge_padding);
+        } else {
+            root.set_margin_horizontal(bar_config.edge_padding);
+        }
+
+        let mut model = TileGroup {
+            tray: None,
+            power: None,
+            network_anchor: None,
+            #[cfg(feature = "bluetooth")]
+            bluetooth_anchor: None,
+            volume_anchor: None,
+            extras: Vec::new(),
+        };
+        let mut widgets = TileGroupWidgets::default();
+
+        let mut placed: Vec<(&str, gtk::Box)> = Vec::new();
+        for id in &tiles {
+            if placed.iter().any(|(placed_id, _)| placed_id == id) {
+                log::warn!("tile {id:?} is in the bar layout more than once; showing it once");
+                continue;
+            }
+
+            let tile: gtk::Box = match id.as_str() {
+                #[cfg(feature = "niri")]
+                "workspaces" => widgets
+                    ._workspaces
+                    .insert(
+                        NiriTile::builder()
+                            .launch(NiriInit {
+                                bar_config: bar_config.clone(),
+                                orientation,
+                                monitor: monitor.clone(),
+                                compositor: services.compositor.clone(),
+                            })
+                            .detach(),
+                    )
+                    .widget()
+                    .clone(),
+                "clock" => widgets
+                    ._clock
+                    .insert(ClockTile::builder().launch(orientation).detach())
+                    .widget()
+                    .clone(),
+                "weather" => widgets
+                    ._weather
+                    .insert(WeatherTile::builder().launch(orientation).detach())
+                    .widget()
+                    .clone(),
+                "media" => widgets
+                    ._media
+                    .insert(MprisTile::builder().launch(orientation).detach())
+                    .widget()
+                    .clone(),
+                "brightness" => widgets
+                    ._brightness
+                    .insert(
+                        BrightnessTile::builder()
+                            .launch(BrightnessTileInit {
+                                monitor: monitor.clone(),
+                                commands: services.commands.clone(),
+                                orientation,
+                            })
+                            .detach(),
+                    )
+                    .widget()
+                    .clone(),
+                "volume" => {
+                    let volume = widgets._volume.insert(
+                        PulseAudioTile::builder()
+                            .launch(PulseAudioTileInit {
+                                monitor: monitor.clone(),
+                                orientation,
+                            })
+                            .forward(sender.output_sender(), |msg| match msg {
+                                PulseAudioTileOutput::ToggleMenu(anchor) => {
+                                    TileGroupOutput::ToggleVolumeMenu(anchor)
+                                }
+                            }),
+                    );
+                    model.volume_anchor = Some(volume.widget().clone().upcast());
+                    volume.widget().clone()
+                }
+                "microphone" => widgets
+                    ._microphone
+                    .insert(MicrophoneTile::builder().launch(orientation).detach())
+                    .widget()
+                    .clone(),
+                #[cfg(feature = "bluetooth")]
+                "bluetooth" => {
+                    let bluetooth = widgets._bluetooth.insert(
+                        BluetoothTile::builder().launch(orientation).forward(
+                            sender.output_sender(),
+                            |msg| match msg {
+                                BluetoothTileOutput::ToggleMenu(anchor) => {
+                                    TileGroupOutput::ToggleBluetoothMenu(anchor)
+                                }
+                            },
+                        ),
+                    );
+                    model.bluetooth_anchor = Some(bluetooth.widget().clone().upcast());
+                    bluetooth.widget().clone()
+                }
+                "network" => {
+                    let network = widgets._network.insert(
+                        NetworkTile::builder().launch(orientation).forward(
+                            sender.output_sender(),
+                            |msg| match msg {
+                                NetworkTileOutput::ToggleMenu(anchor) => {
+                                    TileGroupOutput::ToggleNetworkMenu(anchor)
+                                }
+                            },
+                        ),
+                    );
+                    model.network_anchor = Some(network.widget().clone().upcast());
+                    network.widget().clone()
+                }
+                "battery" => widgets
+                    ._battery
+                    .insert(BatteryTile::builder().launch(orientation).detach())
+                    .widget()
+                    .clone(),
+                "tray" => {
+                    // the tray starts out empty until the tray client connects,
+                    // and hides itself while there's none
+                    let items = match tray_items.as_ref().map(|items| items.lock()) {
+                        Some(Ok(items)) => items.clone(),
+                        Some(Err(e)) => {
+                            log::error!("couldn't lock tray items mutex: {}", e);
+                            continue;
+                        }
+                        None => BaseMap::new(),
+                    };
+                    model
+                        .tray
+                        .insert(
+                            TrayWidget::builder()
+                                .launch(TrayInit { items, orientation })
+                                .forward(sender.output_sender(), TileGroupOutput::TrayItemOutput),
+                        )
+                        .widget()
+                        .clone()
+                }
+                "notifications" => widgets
+                    ._notifications
+                    .insert(
+                        NotificationsTile::builder()
+                            .launch(NotificationsTileInit {
+                                monitor: monitor.clone(),
+                                orientation,
+                            })
+                            .forward(sender.output_sender(), |msg| match msg {
+                                NotificationsTileOutput::ToggleNotificationCenter => {
+                                    TileGroupOutput::ToggleNotificationCenter
+                                }
+                            }),
+                    )
+                    .widget()
+                    .clone(),
+                "power" => model
+                    .power
+                    .insert(PowerTile::builder().launch(orientation).detach())
+                    .widget()
+                    .clone(),
+                // known, but not built into this binary
+                id if TILE_IDS.contains(&id) => {
+                    log::debug!("skipping tile {id:?}, which isn't available in this build");
+                    continue;
+                }
+                id => {
+                    log::warn!("skipping unknown tile {id:?} in the bar layout");
+                    continue;
+                }
+            };
+            // anything beside a tile, like the weather tile's spinner, follows
+            // the bar around
+            tile.set_orientation(orientation);
+            placed.push((id.as_str(), tile));
+        }
+
+        // tiles before the first pinned one slide away towards it, and the rest
+        // slide the other way
+        let (towards_start, towards_end) = match orientation {
+            gtk::Orientation::Vertical => (
+                gtk::RevealerTransitionType::SlideUp,
+                gtk::RevealerTransitionType::SlideDown,
+            ),
+            _ => (
+                gtk::RevealerTransitionType::SlideLeft,
+                gtk::RevealerTransitionType::SlideRight,
+            ),
+        };
+        let first_pinned = placed
+            .iter()
+            .position(|(id, _)| PINNED_TILES.contains(id))
+            .unwrap_or(placed.len());
+        let mut run: Option<gtk::Box> = None;
+        for (i, (id, tile)) in placed.iter().enumerate() {
+            if PINNED_TILES.contains(id) {
+                run = None;
+                root.append(tile);
+                continue;
+            }
+
+            let run = run.get_or_insert_with(|| {
+                let transition = if i < first_pinned {
+                    towards_start
+                } else {
+                    towards_end
+                };
+                let run_box = gtk::Box::new(orientation, bar_config.tile_spacing);
+                let revealer = collapsible(&bar_config, transition);
+                revealer.set_child(Some(&run_box));
+                root.append(&revealer);
+                model.extras.push(revealer);
+                run_box
+            });
+            run.append(tile);
+        }
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        match message {
+            TileGroupMsg::TrayEvent(event) => {
+                if let Some(ref tray) = self.tray {
+                    tray.emit(TrayMsg::TrayEvent(event));
+                }
+            }
+            TileGroupMsg::ResetTray(items) => {
+                if let Some(ref tray) = self.tray {
+                    tray.emit(TrayMsg::Reset(items));
+                }
+            }
+            TileGroupMsg::ShowTrayMenu(address) => {
+                if let Some(ref tray) = self.tray {
+                    tray.emit(TrayMsg::ShowMenu(address));
+                }
+            }
+            TileGroupMsg::ShowPowerMenu => {
+                if let Some(ref power) = self.power {
+                    power.emit(PowerTileMsg::Show);
+                }
+            }
+            TileGroupMsg::ToggleMenu(menu) => {
+                let output = match menu {
+                    BarMenu::Network => self
+                        .network_anchor
+                        .clone()
+                        .map(TileGroupOutput::ToggleNetworkMenu),
+                    #[cfg(feature = "bluetooth")]
+                    BarMenu::Bluetooth => self
+                        .bluetooth_anchor
+                        .clone()
+                        .map(TileGroupOutput::ToggleBluetoothMenu),
+                    BarMenu::Volume => self
+                        .volume_anchor
+                        .clone()
+                        .map(TileGroupOutput::ToggleVolumeMenu),
+                };
+                if let Some(output) = output {
+                    let _ = sender.output(output);
+                }
+            }
+            TileGroupMsg::SetExpanded(expanded) => {
+                for revealer in &self.extras {
+                    revealer.set_reveal_child(expanded);
+                }
+            }
+        }
+    }
+}
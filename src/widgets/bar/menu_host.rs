@@ -0,0 +1,163 @@
+use std::{cell::Cell, rc::Rc};
+
+use gdk4::Rectangle;
+use gtk4::prelude::*;
+use gtk4_layer_shell::{KeyboardMode, LayerShell};
+use relm4::prelude::*;
+
+#[cfg(feature = "bluetooth")]
+use crate::bluetooth_menu::{BluetoothMenu, BluetoothMenuMsg};
+use crate::{network_menu::NetworkMenu, volume_menu::VolumeMenu};
+
+/// The menus a bar can show from its tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarMenu {
+    Network,
+    #[cfg(feature = "bluetooth")]
+    Bluetooth,
+    Volume,
+}
+
+impl BarMenu {
+    /// The id of the tile the menu belongs to.
+    pub fn tile_id(self) -> &'static str {
+        match self {
+            BarMenu::Network => "network",
+            #[cfg(feature = "bluetooth")]
+            BarMenu::Bluetooth => "bluetooth",
+            BarMenu::Volume => "volume",
+        }
+    }
+
+    /// Whether the menu has text inputs (like the WiFi password or Bluetooth
+    /// PIN entries), so the bar needs keyboard focus while it's open.
+    fn wants_keyboard(self) -> bool {
+        match self {
+            BarMenu::Network => true,
+            #[cfg(feature = "bluetooth")]
+            BarMenu::Bluetooth => true,
+            BarMenu::Volume => false,
+        }
+    }
+}
+
+/// The one popover a bar shows its menus in. Opening a menu swaps it into the
+/// popover and points the popover at the tile that asked, closing whichever
+/// menu was open before.
+#[derive(Debug)]
+pub struct MenuHost {
+    window: gtk::Window,
+    popover: gtk::Popover,
+    /// The menu currently shown, if the popover is open.
+    current: Rc<Cell<Option<BarMenu>>>,
+
+    network: Controller<NetworkMenu>,
+    #[cfg(feature = "bluetooth")]
+    bluetooth: Controller<BluetoothMenu>,
+    volume: Controller<VolumeMenu>,
+}
+
+impl MenuHost {
+    /// Creates the host on `parent`, the widget filling `window`, which is the
+    /// bar's layer-shell window. `on_closed` is called whenever a menu closes,
+    /// including when it's dismissed by clicking elsewhere.
+    pub fn new(
+        window: &gtk::Window,
+        parent: &impl IsA<gtk::Widget>,
+        on_closed: impl Fn() + 'static,
+    ) -> Self {
+        let popover = gtk::Popover::builder()
+            .width_request(384)
+            .height_request(256)
+            .autohide(true)
+            .build();
+        popover.set_parent(parent);
+
+        #[cfg(feature = "bluetooth")]
+        let bluetooth = BluetoothMenu::builder().launch(()).detach();
+
+        let current: Rc<Cell<Option<BarMenu>>> = Rc::default();
+        {
+            let window = window.clone();
+            let current = current.clone();
+            #[cfg(feature = "bluetooth")]
+            let bluetooth_sender = bluetooth.sender().clone();
+            popover.connect_closed(move |_| {
+                current.set(None);
+                // give the keyboard back to whatever had it before
+                window.set_keyboard_mode(KeyboardMode::None);
+                // scanning for devices drains power, so don't leave it running
+                #[cfg(feature = "bluetooth")]
+                bluetooth_sender.emit(BluetoothMenuMsg::Closed);
+                on_closed();
+            });
+        }
+
+        Self {
+            window: window.clone(),
+            popover,
+            current,
+            network: NetworkMenu::builder().launch(()).detach(),
+            #[cfg(feature = "bluetooth")]
+            bluetooth,
+            volume: VolumeMenu::builder().launch(()).detach(),
+        }
+    }
+
+    /// Opens `menu` pointing at `anchor`, or closes it if it's already open.
+    pub fn toggle(&self, menu: BarMenu, anchor: &gtk::Widget) {
+        if self.current.get() == Some(menu) {
+            self.popover.popdown();
+            return;
+        }
+
+        // close the previous menu first; this also releases the keyboard
+        self.popover.popdown();
+
+        let child: &gtk::Widget = match menu {
+            BarMenu::Network => self.network.widget().upcast_ref(),
+            #[cfg(feature = "bluetooth")]
+            BarMenu::Bluetooth => self.bluetooth.widget().upcast_ref(),
+            BarMenu::Volume => self.volume.widget().upcast_ref(),
+        };
+        self.popover.set_child(Some(child));
+
+        // the popover hangs off the bar itself, so aim it at the tile
+        if let Some(parent) = self.popover.parent()
+            && let Some(bounds) = anchor.compute_bounds(&parent)
+        {
+            self.popover.set_pointing_to(Some(&Rectangle::new(
+                bounds.x() as i32,
+                bounds.y() as i32,
+                bounds.width() as i32,
+                bounds.height() as i32,
+            )));
+        }
+
+        if menu.wants_keyboard() {
+            self.window.set_keyboard_mode(KeyboardMode::OnDemand);
+        }
+
+        self.current.set(Some(menu));
+        self.popover.popup();
+    }
+
+    /// Whether `menu` is the one open.
+    pub fn is_showing(&self, menu: BarMenu) -> bool {
+        self.current.get() == Some(menu)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.current.get().is_some()
+    }
+
+    pub fn close(&self) {
+        self.popover.popdown();
+    }
+}
+
+impl Drop for MenuHost {
+    fn drop(&mut self) {
+        self.popover.unparent();
+    }
+}
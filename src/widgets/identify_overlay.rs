@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use gtk4::prelude::*;
+use gtk4_layer_shell::{Layer, LayerShell};
+
+use crate::monitors::MonitorInfo;
+
+/// How long the overlays stay up before closing themselves.
+const OVERLAY_DURATION: Duration = Duration::from_secs(4);
+
+/// Shows a large label with the monitor's connector and make/model in the
+/// middle of the given monitor, like display settings dialogs do. The window
+/// closes itself after a few seconds.
+pub fn show(monitor: &gdk4::Monitor, info: &MonitorInfo) {
+    let content = gtk::Box::builder()
+        .css_classes(["identify-overlay", "background"])
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(8)
+        .build();
+
+    content.append(
+        &gtk::Label::builder()
+            .css_classes(["identify-connector"])
+            .label(info.connector.as_str())
+            .build(),
+    );
+
+    if let Some(name) = info.display_name() {
+        content.append(
+            &gtk::Label::builder()
+                .css_classes(["identify-model"])
+                .label(name)
+                .build(),
+        );
+    }
+
+    let window = gtk::Window::builder()
+        .title("cadenza identify display")
+        .child(&content)
+        .build();
+
+    window.init_layer_shell();
+    window.set_monitor(Some(monitor));
+    window.set_namespace(Some("identify-display"));
+    window.set_layer(Layer::Overlay);
+    window.present();
+
+    glib::timeout_add_local_once(OVERLAY_DURATION, move || window.close());
+}
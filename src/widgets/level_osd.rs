@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use gtk4::prelude::*;
+use gtk4_layer_shell::{Edge, LayerShell};
+
+use crate::widgets::osd::Osd;
+
+/// Space between the OSD and the bottom of the monitor.
+const BOTTOM_MARGIN: i32 = 96;
+
+/// A level, like the volume or brightness, shown near the bottom of one
+/// monitor while it's being changed from the bar.
+///
+/// Showing it again while it's up just updates the level and restarts the
+/// timer, like [`WorkspaceOsd`](super::workspace_osd::WorkspaceOsd).
+#[derive(Debug)]
+pub struct LevelOsd {
+    osd: Osd,
+    icon: gtk::Image,
+    level: gtk::ProgressBar,
+    label: gtk::Label,
+}
+
+impl LevelOsd {
+    pub fn new(monitor: &gdk4::Monitor) -> Self {
+        let icon = gtk::Image::builder().pixel_size(24).build();
+        let level = gtk::ProgressBar::builder()
+            .valign(gtk::Align::Center)
+            .hexpand(true)
+            .build();
+        let label = gtk::Label::builder()
+            .css_classes(["level-osd-label"])
+            .width_chars(4)
+            .xalign(1.0)
+            .build();
+
+        let content = gtk::Box::builder()
+            .css_classes(["level-osd", "background"])
+            .spacing(16)
+            .width_request(256)
+            .build();
+        content.append(&icon);
+        content.append(&level);
+        content.append(&label);
+
+        let osd = Osd::new(monitor, "level-osd", &content);
+        osd.window().set_anchor(Edge::Bottom, true);
+        osd.window().set_margin(Edge::Bottom, BOTTOM_MARGIN);
+
+        Self {
+            osd,
+            icon,
+            level,
+            label,
+        }
+    }
+
+    /// Shows `percent` with `icon` for `duration`, replacing whatever is
+    /// showing. The bar tops out at 100%, but the label shows the real value.
+    pub fn show(&self, icon: &str, percent: f64, duration: Duration) {
+        self.icon.set_icon_name(Some(icon));
+        self.level.set_fraction((percent / 100.0).clamp(0.0, 1.0));
+        self.label.set_label(&format!("{percent:.0}%"));
+
+        self.osd.show(duration);
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+
+use gtk4::{glib, pango, prelude::*};
+
+/// How fast overflowing text scrolls, in pixels per second.
+const SCROLL_SPEED: f64 = 30.0;
+
+/// How long the text rests at either end before moving again.
+const END_PAUSE: Duration = Duration::from_millis(1500);
+
+/// A single-line label that scrolls its text when it doesn't fit, pausing while
+/// hovered. When scrolling is disabled, or the text fits, it behaves like a
+/// plain ellipsized label.
+#[derive(Clone)]
+pub struct Marquee {
+    root: gtk::ScrolledWindow,
+    label: gtk::Label,
+    state: Rc<MarqueeState>,
+}
+
+#[derive(Default)]
+struct MarqueeState {
+    enabled: Cell<bool>,
+    running: Cell<bool>,
+    hovered: Cell<bool>,
+    tick: RefCell<Option<gtk::TickCallbackId>>,
+    /// Frame time of the previous tick, in microseconds.
+    last_frame: Cell<Option<i64>>,
+    /// Frame time until which the text stays put.
+    paused_until: Cell<i64>,
+}
+
+impl std::fmt::Debug for Marquee {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Marquee")
+            .field("text", &self.label.text())
+            .field("enabled", &self.state.enabled.get())
+            .field("running", &self.state.running.get())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Marquee {
+    /// Creates a marquee that is at most `width_chars` characters wide.
+    pub fn new(width_chars: i32) -> Self {
+        let label = gtk::Label::builder()
+            .single_line_mode(true)
+            .xalign(0.0)
+            .ellipsize(pango::EllipsizeMode::End)
+            .build();
+
+        let root = gtk::ScrolledWindow::builder()
+            .css_classes(["marquee"])
+            .hscrollbar_policy(gtk::PolicyType::External)
+            .vscrollbar_policy(gtk::PolicyType::Never)
+            .propagate_natural_width(true)
+            .child(&label)
+            .build();
+
+        let char_width = label
+            .pango_context()
+            .metrics(None, None)
+            .approximate_char_width()
+            / pango::SCALE;
+        root.set_max_content_width(char_width * width_chars);
+
+        let state: Rc<MarqueeState> = Rc::default();
+
+        let hover = gtk::EventControllerMotion::new();
+        {
+            let state = state.clone();
+            hover.connect_enter(move |_, _, _| state.hovered.set(true));
+        }
+        {
+            let state = state.clone();
+            hover.connect_leave(move |_| state.hovered.set(false));
+        }
+        root.add_controller(hover);
+
+        // only tick while there is something on screen to animate
+        {
+            let label = label.clone();
+            let state = state.clone();
+            root.connect_map(move |root| update_ticking(root, &label, &state, true));
+        }
+        {
+            let label = label.clone();
+            let state = state.clone();
+            root.connect_unmap(move |root| update_ticking(root, &label, &state, false));
+        }
+
+        Self { root, label, state }
+    }
+
+    pub fn widget(&self) -> &gtk::ScrolledWindow {
+        &self.root
+    }
+
+    /// Sets the text, scrolling back to its start.
+    pub fn set_text(&self, text: &str) {
+        if self.label.text() == text {
+            return;
+        }
+
+        self.label.set_text(text);
+        self.root.hadjustment().set_value(0.0);
+        self.state.last_frame.set(None);
+    }
+
+    /// Whether the text may scroll at all. When disabled, long text is
+    /// ellipsized instead.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.state.enabled.set(enabled);
+        self.label.set_ellipsize(if enabled {
+            pango::EllipsizeMode::None
+        } else {
+            pango::EllipsizeMode::End
+        });
+        update_ticking(&self.root, &self.label, &self.state, self.root.is_mapped());
+    }
+
+    /// Starts or stops scrolling, e.g. as playback starts and stops.
+    pub fn set_running(&self, running: bool) {
+        self.state.running.set(running);
+        update_ticking(&self.root, &self.label, &self.state, self.root.is_mapped());
+    }
+}
+
+/// Adds or removes the tick callback so that it only runs while the marquee is
+/// enabled, running and mapped.
+fn update_ticking(
+    root: &gtk::ScrolledWindow,
+    label: &gtk::Label,
+    state: &Rc<MarqueeState>,
+    mapped: bool,
+) {
+    let should_tick = mapped && state.enabled.get() && state.running.get();
+    let mut tick = state.tick.borrow_mut();
+
+    if should_tick && tick.is_none() {
+        let label = label.clone();
+        let tick_state = state.clone();
+        *tick = Some(root.add_tick_callback(move |root, clock| {
+            step(root, &label, &tick_state, clock.frame_time());
+            glib::ControlFlow::Continue
+        }));
+    } else if !should_tick && let Some(id) = tick.take() {
+        id.remove();
+        state.last_frame.set(None);
+        root.hadjustment().set_value(0.0);
+    }
+}
+
+/// Advances the scroll position for one frame.
+fn step(root: &gtk::ScrolledWindow, label: &gtk::Label, state: &MarqueeState, frame_time: i64) {
+    let pause = END_PAUSE.as_micros() as i64;
+
+    let Some(last_frame) = state.last_frame.replace(Some(frame_time)) else {
+        // first frame since (re)starting; rest at the beginning
+        state.paused_until.set(frame_time + pause);
+        return;
+    };
+
+    if state.hovered.get() || frame_time < state.paused_until.get() {
+        return;
+    }
+
+    let adjustment = root.hadjustment();
+    // the label is allocated its full width while scrolling is enabled, so any
+    // excess over the viewport is how far it overflows
+    if label.width() <= root.width() {
+        adjustment.set_value(0.0);
+        return;
+    }
+
+    let end = adjustment.upper() - adjustment.page_size();
+    if adjustment.value() >= end {
+        // rested at the end; jump back to the start and rest there
+        adjustment.set_value(0.0);
+        state.paused_until.set(frame_time + pause);
+        return;
+    }
+
+    let elapsed = (frame_time - last_frame) as f64 / 1_000_000.0;
+    let next = (adjustment.value() + SCROLL_SPEED * elapsed).min(end);
+    adjustment.set_value(next);
+
+    if next >= end {
+        state.paused_until.set(frame_time + pause);
+    }
+}
@@ -0,0 +1,97 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use gtk4::prelude::*;
+use gtk4_layer_shell::{Layer, LayerShell};
+
+use crate::settings;
+
+/// How long an OSD takes to fade out, unless `reduce_motion` is set.
+const FADE_DURATION: Duration = Duration::from_millis(200);
+
+/// The window an OSD's content is shown in: an overlay on one monitor that
+/// fades in, and fades back out a moment later.
+///
+/// Showing it again while it's up just restarts the timer, so content that
+/// changes in quick succession doesn't make it blink.
+#[derive(Debug)]
+pub struct Osd {
+    window: gtk::Window,
+    revealer: gtk::Revealer,
+    hide_timeout: Rc<RefCell<Option<glib::SourceId>>>,
+}
+
+impl Osd {
+    /// Puts `content` in an OSD on `monitor`. `namespace` is its layer shell
+    /// namespace, e.g. "workspace-osd".
+    pub fn new(monitor: &gdk4::Monitor, namespace: &str, content: &impl IsA<gtk::Widget>) -> Self {
+        let revealer = gtk::Revealer::builder()
+            .transition_type(gtk::RevealerTransitionType::Crossfade)
+            .child(content)
+            .build();
+
+        let window = gtk::Window::builder()
+            .title(format!("cadenza {}", namespace.replace('-', " ")))
+            .child(&revealer)
+            .can_target(false)
+            .build();
+
+        window.init_layer_shell();
+        window.set_monitor(Some(monitor));
+        window.set_namespace(Some(namespace));
+        window.set_layer(Layer::Overlay);
+
+        // unmap once faded out, so the surface doesn't linger
+        let window_clone = window.clone();
+        revealer.connect_child_revealed_notify(move |revealer| {
+            if !revealer.is_child_revealed() {
+                window_clone.set_visible(false);
+            }
+        });
+
+        Self {
+            window,
+            revealer,
+            hide_timeout: Rc::default(),
+        }
+    }
+
+    /// The layer shell window, for placing the OSD on its monitor.
+    pub fn window(&self) -> &gtk::Window {
+        &self.window
+    }
+
+    /// Shows the OSD for `duration`, or for `duration` from now if it's
+    /// already up.
+    pub fn show(&self, duration: Duration) {
+        let fade = if settings::get_config().ui.reduce_motion {
+            Duration::ZERO
+        } else {
+            FADE_DURATION
+        };
+        self.revealer
+            .set_transition_duration(fade.as_millis() as u32);
+
+        self.window.present();
+        self.revealer.set_reveal_child(true);
+
+        if let Some(previous) = self.hide_timeout.take() {
+            previous.remove();
+        }
+        let revealer = self.revealer.clone();
+        let hide_timeout = Rc::clone(&self.hide_timeout);
+        let source = glib::timeout_add_local_once(duration, move || {
+            hide_timeout.take();
+            revealer.set_reveal_child(false);
+        });
+        self.hide_timeout.replace(Some(source));
+    }
+}
+
+impl Drop for Osd {
+    fn drop(&mut self) {
+        if let Some(source) = self.hide_timeout.take() {
+            source.remove();
+        }
+        self.window.destroy();
+    }
+}
@@ -43,6 +43,9 @@ pub struct ProgressTileInit {
     pub progress: f64,
     pub attention: Attention,
     pub extra_classes: Vec<String>,
+    /// Which way the bar runs. On a vertical bar the icon sits over the bar,
+    /// which fills upwards.
+    pub orientation: gtk::Orientation,
 }
 
 impl Default for ProgressTileInit {
@@ -52,6 +55,7 @@ impl Default for ProgressTileInit {
             progress: 0.0,
             attention: Attention::Normal,
             extra_classes: Vec::new(),
+            orientation: gtk::Orientation::Horizontal,
         }
     }
 }
@@ -66,7 +70,7 @@ impl SimpleComponent for ProgressTile {
     fn init(
         init: Self::Init,
         root: Self::Root,
-        _sender: ComponentSender<Self>,
+        sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         let model = ProgressTile {
             icon: init.icon_name,
@@ -77,7 +81,7 @@ impl SimpleComponent for ProgressTile {
         };
 
         // create container
-        let container = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        let container = gtk::Box::new(init.orientation, 8);
 
         // create icon
         let icon = gtk::Image::builder()
@@ -87,10 +91,22 @@ impl SimpleComponent for ProgressTile {
             .build();
 
         // create progress bar
+        let vertical = init.orientation == gtk::Orientation::Vertical;
         let progress_bar = gtk::ProgressBar::builder()
             .css_classes(vec![model.attention.css_class()])
             .fraction(model.progress)
-            .valign(gtk::Align::Center)
+            .orientation(init.orientation)
+            .inverted(vertical)
+            .halign(if vertical {
+                gtk::Align::Center
+            } else {
+                gtk::Align::Fill
+            })
+            .valign(if vertical {
+                gtk::Align::Fill
+            } else {
+                gtk::Align::Center
+            })
             .build();
 
         // add widgets to container
@@ -98,6 +114,9 @@ impl SimpleComponent for ProgressTile {
         container.append(&progress_bar);
 
         root.set_child(Some(&container));
+        root.connect_clicked(move |_| {
+            let _ = sender.output(ProgressTileOutput::Clicked);
+        });
 
         // set initial values
         if let Some(icon_name) = &model.icon {
@@ -9,16 +9,22 @@ use crate::tiles::Attention;
 #[derive(Clone, Debug)]
 pub struct Tile {
     icon: Option<String>,
+    overlay_icon: Option<String>,
     primary: Option<String>,
     secondary: Option<String>,
     attention: Attention,
     tooltip: Option<String>,
+    /// Whether the tile is on a vertical bar, where it stacks its icon over
+    /// the primary text and leaves out the secondary text.
+    vertical: bool,
 }
 
 #[derive(Debug)]
 pub enum TileMsg {
     Click,
     SetIcon(Option<String>),
+    /// Sets a small glyph drawn over the corner of the icon.
+    SetOverlayIcon(Option<String>),
     SetPrimary(Option<String>),
     SetSecondary(Option<String>),
     SetAttention(Attention),
@@ -61,27 +67,34 @@ pub enum TileOutput {
 #[derive(Debug)]
 pub struct TileWidgets {
     root: gtk::Button,
+    container: gtk::Box,
     icon: gtk::Image,
+    overlay_icon: gtk::Image,
     primary_label: gtk::Label,
     secondary_label: gtk::Label,
 }
 
 pub struct TileInit {
     pub icon_name: Option<String>,
+    pub overlay_icon_name: Option<String>,
     pub primary: Option<String>,
     pub secondary: Option<String>,
     pub attention: Attention,
     pub tooltip: Option<String>,
+    /// Which way the bar runs.
+    pub orientation: gtk::Orientation,
 }
 
 impl Default for TileInit {
     fn default() -> Self {
         Self {
             icon_name: None,
+            overlay_icon_name: None,
             primary: None,
             secondary: None,
             attention: Attention::Normal,
             tooltip: None,
+            orientation: gtk::Orientation::Horizontal,
         }
     }
 }
@@ -100,10 +113,12 @@ impl SimpleComponent for Tile {
     ) -> ComponentParts<Self> {
         let model = Tile {
             icon: init.icon_name,
+            overlay_icon: init.overlay_icon_name,
             primary: init.primary,
             secondary: init.secondary,
             attention: init.attention,
             tooltip: init.tooltip,
+            vertical: init.orientation == gtk::Orientation::Vertical,
         };
 
         // create container
@@ -116,6 +131,16 @@ impl SimpleComponent for Tile {
             .width_request(16)
             .build();
 
+        let overlay_icon = gtk::Image::builder()
+            .css_classes(["overlay-icon"])
+            .pixel_size(8)
+            .halign(gtk::Align::End)
+            .valign(gtk::Align::End)
+            .build();
+
+        let icon_overlay = gtk::Overlay::builder().child(&icon).build();
+        icon_overlay.add_overlay(&overlay_icon);
+
         let primary_label = gtk::Label::builder()
             .css_classes(vec!["primary", model.attention.css_class()])
             .ellipsize(gtk::pango::EllipsizeMode::End)
@@ -129,7 +154,7 @@ impl SimpleComponent for Tile {
             .build();
 
         // add all widgets to container
-        container.append(&icon);
+        container.append(&icon_overlay);
         container.append(&primary_label);
         container.append(&secondary_label);
 
@@ -144,7 +169,9 @@ impl SimpleComponent for Tile {
 
         let mut widgets = TileWidgets {
             root: root.clone(),
+            container,
             icon,
+            overlay_icon,
             primary_label,
             secondary_label,
         };
@@ -163,6 +190,9 @@ impl SimpleComponent for Tile {
             TileMsg::SetIcon(icon) => {
                 self.icon = icon;
             }
+            TileMsg::SetOverlayIcon(overlay_icon) => {
+                self.overlay_icon = overlay_icon;
+            }
             TileMsg::SetPrimary(primary) => {
                 self.primary = primary;
             }
@@ -196,6 +226,12 @@ impl SimpleComponent for Tile {
         } else {
             widgets.icon.set_visible(false);
         }
+        widgets
+            .overlay_icon
+            .set_visible(self.icon.is_some() && self.overlay_icon.is_some());
+        widgets
+            .overlay_icon
+            .set_icon_name(self.overlay_icon.as_deref());
 
         // update primary label
         if let Some(primary_text) = &self.primary {
@@ -206,13 +242,21 @@ impl SimpleComponent for Tile {
         }
 
         // update secondary label
-        if let Some(secondary_text) = &self.secondary {
+        if let Some(secondary_text) = &self.secondary
+            && !self.vertical
+        {
             widgets.secondary_label.set_label(secondary_text);
             widgets.secondary_label.set_visible(true);
         } else {
             widgets.secondary_label.set_visible(false);
         }
 
+        widgets.container.set_orientation(if self.vertical {
+            gtk::Orientation::Vertical
+        } else {
+            gtk::Orientation::Horizontal
+        });
+
         // update tooltip
         widgets.root.set_tooltip_text(self.tooltip.as_deref());
     }
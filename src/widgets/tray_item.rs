@@ -1,3 +1,5 @@
+use std::{cell::RefCell, rc::Rc};
+
 use gdk4::prelude::*;
 use gtk4::prelude::*;
 use relm4::{factory::FactoryView, prelude::*};
@@ -9,6 +11,8 @@ use system_tray::{
     menu::{MenuItem, MenuType, TrayMenu},
 };
 
+use crate::{settings, tiles::set_state_classes};
+
 #[derive(Debug)]
 pub struct TrayItem {
     inner: StatusNotifierItem,
@@ -19,8 +23,24 @@ pub struct TrayItem {
 }
 
 pub struct TrayItemWidgets {
+    root: gtk::Button,
+    icon: gtk::Image,
+    /// Drawn small in the icon's bottom-right corner.
+    overlay_icon: gtk::Image,
+    /// Shown instead of the icon when the item doesn't have one.
+    fallback_label: gtk::Label,
     popover: gtk::PopoverMenu,
     action_group: gio::SimpleActionGroup,
+    /// Read whenever the tooltip is about to show.
+    tooltip: Rc<RefCell<TrayTooltip>>,
+}
+
+/// What the item's tooltip shows.
+#[derive(Default)]
+struct TrayTooltip {
+    icon_name: Option<String>,
+    icon_texture: Option<gdk4::Texture>,
+    markup: String,
 }
 
 impl TrayItem {
@@ -35,16 +55,153 @@ impl TrayItem {
     pub fn replace_inner(&mut self, status_notifier_item: StatusNotifierItem) {
         self.inner = status_notifier_item;
     }
+
+    /// Applies a change the item announced. The view catches up when the
+    /// factory guard holding the item is dropped.
+    pub fn apply_update(&mut self, update_event: UpdateEvent) {
+        match update_event {
+            UpdateEvent::AttentionIcon(attention_icon_name) => {
+                self.inner.attention_icon_name = attention_icon_name
+            }
+            UpdateEvent::Icon {
+                icon_name,
+                icon_pixmap,
+            } => {
+                self.inner.icon_name = icon_name;
+                self.inner.icon_pixmap = icon_pixmap;
+            }
+            UpdateEvent::OverlayIcon(name) => self.inner.overlay_icon_name = name,
+            UpdateEvent::Status(status) => self.inner.status = status,
+            UpdateEvent::Title(title) => self.inner.title = title,
+            UpdateEvent::Tooltip(tooltip) => self.inner.tool_tip = tooltip,
+            UpdateEvent::Menu(tray_menu) => self.menu = Some(tray_menu),
+            UpdateEvent::MenuDiff(menu_diffs) => {
+                if let Some(menu) = self.menu.as_mut() {
+                    for _diff in &menu_diffs {
+                        apply_menu_diffs(menu, &menu_diffs);
+                    }
+                }
+            }
+            UpdateEvent::MenuConnect(menu_path) => {
+                self.inner.menu = Some(menu_path.clone());
+            }
+        }
+    }
+
+    /// Whether the item is on the bar: passive items are hidden unless
+    /// passive items are shown.
+    pub fn is_shown(&self) -> bool {
+        !matches!(self.inner.status, Status::Passive)
+            || settings::get_config().tiles.show_passive_tray_items
+    }
+
+    /// Status changes and settings changes go through here, so an item that
+    /// becomes active shows up again.
+    fn update_visibility(&self, widgets: &TrayItemWidgets) {
+        widgets.root.set_visible(self.is_shown());
+    }
+
+    /// Shows the item's icon, or its attention icon while it needs attention,
+    /// with its overlay icon on top. Items without any icon show the start of
+    /// their id instead.
+    fn update_icon(&self, widgets: &TrayItemWidgets) {
+        let needs_attention = matches!(self.inner.status, Status::NeedsAttention);
+        let attention_icon = self
+            .inner
+            .attention_icon_name
+            .as_deref()
+            .filter(|name| needs_attention && !name.is_empty());
+        let icon_name = self
+            .inner
+            .icon_name
+            .as_deref()
+            .filter(|name| !name.is_empty());
+
+        let has_icon = if let Some(name) = attention_icon.or(icon_name) {
+            widgets.icon.set_icon_name(Some(name));
+            true
+        } else if let Some(texture) = self
+            .inner
+            .icon_pixmap
+            .as_deref()
+            .and_then(pixmap_to_texture)
+        {
+            widgets.icon.set_paintable(Some(&texture));
+            true
+        } else {
+            false
+        };
+        widgets.icon.set_visible(has_icon);
+        widgets.fallback_label.set_visible(!has_icon);
+
+        let overlay_icon = self
+            .inner
+            .overlay_icon_name
+            .as_deref()
+            .filter(|name| !name.is_empty());
+        widgets.overlay_icon.set_icon_name(overlay_icon);
+        widgets.overlay_icon.set_visible(overlay_icon.is_some());
+
+        set_state_classes(
+            &widgets.root,
+            &[
+                ("tray-active", matches!(self.inner.status, Status::Active)),
+                ("tray-needs-attention", needs_attention),
+                (
+                    "tray-attention-pulse",
+                    needs_attention && !settings::get_config().ui.reduce_motion,
+                ),
+            ],
+        );
+    }
+
+    /// Takes the tooltip's title, text and icon from the item's `ToolTip`,
+    /// falling back to the item's title or id for the title.
+    fn update_tooltip(&self, widgets: &TrayItemWidgets) {
+        let tool_tip = self.inner.tool_tip.as_ref();
+        let title = [
+            tool_tip.map(|tool_tip| tool_tip.title.as_str()),
+            self.inner.title.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .find(|title| !title.is_empty())
+        .unwrap_or(&self.inner.id);
+        let description = tool_tip.map_or("", |tool_tip| tool_tip.description.as_str());
+
+        let icon_name = tool_tip
+            .map(|tool_tip| tool_tip.icon_name.clone())
+            .filter(|name| !name.is_empty());
+        let icon_texture = tool_tip
+            .filter(|_| icon_name.is_none())
+            .and_then(|tool_tip| pixmap_to_texture(&tool_tip.icon_data));
+
+        *widgets.tooltip.borrow_mut() = TrayTooltip {
+            icon_name,
+            icon_texture,
+            markup: tooltip_markup(title, description),
+        };
+    }
 }
 
 #[derive(Debug)]
 pub enum TrayItemInput {
-    DataUpdate(UpdateEvent),
+    /// Pops up the item's menu, for items that can't be activated.
+    ShowMenu,
+    /// The settings changed, so whether the item is shown may have too.
+    SettingsChanged,
 }
 
 #[derive(Debug)]
 pub enum TrayItemOutput {
     Activate(ActivateRequest),
+    /// The item's icon was scrolled by `delta` whole steps along
+    /// `orientation`; negative is up or left.
+    Scroll {
+        address: String,
+        delta: i32,
+        orientation: gtk::Orientation,
+    },
 }
 
 impl FactoryComponent for TrayItem {
@@ -74,35 +231,16 @@ impl FactoryComponent for TrayItem {
         }
     }
 
-    fn update(&mut self, message: Self::Input, _sender: FactorySender<Self>) {
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: FactorySender<Self>,
+    ) {
+        // neither changes the item, so there's nothing to rebuild
         match message {
-            TrayItemInput::DataUpdate(update_event) => match update_event {
-                UpdateEvent::AttentionIcon(attention_icon_name) => {
-                    self.inner.attention_icon_name = attention_icon_name
-                }
-                UpdateEvent::Icon {
-                    icon_name,
-                    icon_pixmap,
-                } => {
-                    self.inner.icon_name = icon_name;
-                    self.inner.icon_pixmap = icon_pixmap;
-                }
-                UpdateEvent::OverlayIcon(name) => self.inner.overlay_icon_name = name,
-                UpdateEvent::Status(status) => self.inner.status = status,
-                UpdateEvent::Title(title) => self.inner.title = title,
-                UpdateEvent::Tooltip(tooltip) => self.inner.tool_tip = tooltip,
-                UpdateEvent::Menu(tray_menu) => self.menu = Some(tray_menu),
-                UpdateEvent::MenuDiff(menu_diffs) => {
-                    if let Some(menu) = self.menu.as_mut() {
-                        for _diff in &menu_diffs {
-                            apply_menu_diffs(menu, &menu_diffs);
-                        }
-                    }
-                }
-                UpdateEvent::MenuConnect(menu_path) => {
-                    self.inner.menu = Some(menu_path.clone());
-                }
-            },
+            TrayItemInput::ShowMenu => widgets.popover.popup(),
+            TrayItemInput::SettingsChanged => self.update_visibility(widgets),
         }
     }
 
@@ -137,57 +275,75 @@ impl FactoryComponent for TrayItem {
         root.set_width_request(24);
         root.set_height_request(24);
 
-        // add status-specific CSS classes
-        match self.inner.status {
-            Status::Active => root.add_css_class("tray-active"),
-            Status::NeedsAttention => root.add_css_class("tray-needs-attention"),
-            _ => {} // default styling
-        }
-
-        let mut tooltip_text = if let Some(title) = &self.inner.title
-            && !title.is_empty()
-        {
-            String::from(title)
-        } else {
-            String::new()
-        };
-
-        // Create enhanced tooltip with more information
-        if let Some(tooltip) = &self.inner.tool_tip {
-            tooltip_text.push_str(&format!("\n{}", tooltip.description));
-        };
-        root.set_tooltip_text(Some(tooltip_text.trim_start()));
-
-        // Create image or label for the button
-        if let Some(icon_name) = &self.inner.icon_name
-            && !icon_name.is_empty()
+        // the tooltip is built when it's shown, since it has an icon
+        let tooltip: Rc<RefCell<TrayTooltip>> = Rc::default();
+        root.set_has_tooltip(true);
         {
-            let image = gtk::Image::from_icon_name(icon_name);
-            image.set_pixel_size(16);
-            image.set_halign(gtk::Align::Center);
-            image.set_valign(gtk::Align::Center);
-            root.set_child(Some(&image));
-        } else if let Some(pixmaps) = &self.inner.icon_pixmap {
-            if let Some(texture) = pixmap_to_texture(pixmaps) {
-                let image = gtk::Image::from_paintable(Some(&texture));
-                image.set_pixel_size(16);
-                image.set_halign(gtk::Align::Center);
-                image.set_valign(gtk::Align::Center);
-                root.set_child(Some(&image));
-            } else {
-                let label =
-                    gtk::Label::new(Some(&self.inner.id.chars().take(2).collect::<String>()));
-                root.set_child(Some(&label));
-            }
-        } else {
-            let label = gtk::Label::new(Some(&self.inner.id.chars().take(2).collect::<String>()));
-            root.set_child(Some(&label));
+            let tooltip = Rc::clone(&tooltip);
+            root.connect_query_tooltip(move |_, _, _, _, gtk_tooltip| {
+                let tooltip = tooltip.borrow();
+                let content = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+                let icon = if let Some(name) = &tooltip.icon_name {
+                    Some(gtk::Image::from_icon_name(name))
+                } else {
+                    tooltip
+                        .icon_texture
+                        .as_ref()
+                        .map(|texture| gtk::Image::from_paintable(Some(texture)))
+                };
+                if let Some(icon) = icon {
+                    icon.set_pixel_size(32);
+                    content.append(&icon);
+                }
+                content.append(
+                    &gtk::Label::builder()
+                        .use_markup(true)
+                        .label(&tooltip.markup)
+                        .xalign(0.0)
+                        .build(),
+                );
+                gtk_tooltip.set_custom(Some(&content));
+                true
+            });
         }
 
-        // TODO: Left click - activate
+        // the icon, or the label when there's no icon, with the overlay
+        // icon in the bottom-right corner
+        let icon = gtk::Image::builder()
+            .pixel_size(16)
+            .halign(gtk::Align::Center)
+            .valign(gtk::Align::Center)
+            .build();
+        let fallback_label =
+            gtk::Label::new(Some(&self.inner.id.chars().take(2).collect::<String>()));
+        let overlay_icon = gtk::Image::builder()
+            .pixel_size(8)
+            .halign(gtk::Align::End)
+            .valign(gtk::Align::End)
+            .css_classes(["tray-overlay-icon"])
+            .can_target(false)
+            .build();
+
+        let base = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        base.append(&icon);
+        base.append(&fallback_label);
+        let overlay = gtk::Overlay::builder().child(&base).build();
+        overlay.add_overlay(&overlay_icon);
+        root.set_child(Some(&overlay));
+
+        // left click activates the item, or shows its menu if the item says
+        // it's only a menu. items that turn out not to support activation
+        // are sent a ShowMenu by the app instead
         let address_clone = self.address.clone();
         let sender_clone = sender.clone();
+        let item_is_menu = self.inner.item_is_menu;
+        let popover_clone = popover.clone();
         root.connect_clicked(move |_| {
+            if item_is_menu {
+                popover_clone.popup();
+                return;
+            }
+
             sender_clone
                 .output(TrayItemOutput::Activate(ActivateRequest::Default {
                     address: address_clone.clone(),
@@ -217,13 +373,48 @@ impl FactoryComponent for TrayItem {
         });
         root.add_controller(middle_click_gesture);
 
-        TrayItemWidgets {
+        // scrolling is forwarded for items like volume applets. discrete
+        // deltas are whole steps, with touchpad scrolls added up into them
+        let scroll = gtk::EventControllerScroll::new(
+            gtk::EventControllerScrollFlags::BOTH_AXES | gtk::EventControllerScrollFlags::DISCRETE,
+        );
+        let address_clone = self.address.clone();
+        let sender_clone = sender.clone();
+        scroll.connect_scroll(move |_, dx, dy| {
+            if let Some((delta, orientation)) = scroll_steps(dx, dy) {
+                sender_clone
+                    .output(TrayItemOutput::Scroll {
+                        address: address_clone.clone(),
+                        delta,
+                        orientation,
+                    })
+                    .unwrap_or_else(|_| log::error!("couldn't scroll tray item {}", address_clone));
+            }
+            glib::Propagation::Stop
+        });
+        root.add_controller(scroll);
+
+        let widgets = TrayItemWidgets {
+            root,
+            icon,
+            overlay_icon,
+            fallback_label,
             popover,
             action_group,
-        }
+            tooltip,
+        };
+        self.update_visibility(&widgets);
+        self.update_icon(&widgets);
+        self.update_tooltip(&widgets);
+
+        widgets
     }
 
     fn update_view(&self, widgets: &mut Self::Widgets, sender: FactorySender<Self>) {
+        self.update_visibility(widgets);
+        self.update_icon(widgets);
+        self.update_tooltip(widgets);
+
         if let Some(ref menu) = self.menu
             && let Some(ref menu_path) = self.inner.menu
         {
@@ -262,6 +453,37 @@ impl AsMenuWithActions for TrayMenu {
     }
 }
 
+/// Builds the tooltip's text: the title in bold, then the description. Items
+/// may put markup in the description, which is kept if Pango can read it.
+fn tooltip_markup(title: &str, description: &str) -> String {
+    let mut markup = format!("<b>{}</b>", glib::markup_escape_text(title.trim()));
+
+    let description = description.trim();
+    if !description.is_empty() {
+        markup.push('\n');
+        if gtk::pango::parse_markup(description, '\0').is_ok() {
+            markup.push_str(description);
+        } else {
+            markup.push_str(&glib::markup_escape_text(description));
+        }
+    }
+
+    markup
+}
+
+/// Turns a scroll into the whole steps StatusNotifierItem expects, along
+/// whichever axis moved the most.
+fn scroll_steps(dx: f64, dy: f64) -> Option<(i32, gtk::Orientation)> {
+    let (delta, orientation) = if dy.abs() >= dx.abs() {
+        (dy, gtk::Orientation::Vertical)
+    } else {
+        (dx, gtk::Orientation::Horizontal)
+    };
+
+    let delta = delta.round() as i32;
+    (delta != 0).then_some((delta, orientation))
+}
+
 fn clean_menu_label(label: &str) -> String {
     // handle underscore escaping: "__" becomes "_", single "_" are accelerator
     // markers
@@ -389,3 +611,34 @@ fn pixmap_to_texture(pixmaps: &[IconPixmap]) -> Option<gdk4::Texture> {
 
     Some(texture.upcast())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scroll_steps() {
+        assert_eq!(
+            scroll_steps(0.0, -1.0),
+            Some((-1, gtk::Orientation::Vertical))
+        );
+        assert_eq!(
+            scroll_steps(2.0, 1.0),
+            Some((2, gtk::Orientation::Horizontal))
+        );
+        assert_eq!(scroll_steps(0.2, 0.1), None);
+    }
+
+    #[test]
+    fn test_tooltip_markup() {
+        assert_eq!(tooltip_markup("Network", ""), "<b>Network</b>");
+        assert_eq!(
+            tooltip_markup("Tom & Jerry", "Connected to <i>home</i>"),
+            "<b>Tom &amp; Jerry</b>\nConnected to <i>home</i>"
+        );
+        assert_eq!(
+            tooltip_markup("Updates", "3 < 4 <br>"),
+            "<b>Updates</b>\n3 &lt; 4 &lt;br&gt;"
+        );
+    }
+}
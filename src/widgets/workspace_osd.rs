@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use gtk4::prelude::*;
+
+use crate::{settings::WorkspaceLabel, widgets::osd::Osd};
+
+/// A large workspace label shown in the middle of one monitor for a moment
+/// after switching workspaces there.
+///
+/// Switching again while it's up just changes the label and restarts the
+/// timer, so flicking through workspaces doesn't make it blink.
+#[derive(Debug)]
+pub struct WorkspaceOsd {
+    osd: Osd,
+    label: gtk::Label,
+    icon: gtk::Image,
+}
+
+impl WorkspaceOsd {
+    pub fn new(monitor: &gdk4::Monitor) -> Self {
+        let label = gtk::Label::builder()
+            .css_classes(["workspace-osd-label"])
+            .build();
+        let icon = gtk::Image::builder()
+            .css_classes(["workspace-osd-icon"])
+            .pixel_size(64)
+            .build();
+
+        let content = gtk::Box::builder()
+            .css_classes(["workspace-osd", "background"])
+            .build();
+        content.append(&label);
+        content.append(&icon);
+
+        Self {
+            osd: Osd::new(monitor, "workspace-osd", &content),
+            label,
+            icon,
+        }
+    }
+
+    /// Shows `label` for `duration`, replacing whatever is showing.
+    pub fn show(&self, label: &WorkspaceLabel, duration: Duration) {
+        match label {
+            WorkspaceLabel::Text(text) => {
+                self.label.set_label(text);
+                self.label.set_visible(true);
+                self.icon.set_visible(false);
+            }
+            WorkspaceLabel::Icon(icon) => {
+                self.icon.set_icon_name(Some(icon));
+                self.icon.set_visible(true);
+                self.label.set_visible(false);
+            }
+        }
+
+        self.osd.show(duration);
+    }
+}